@@ -4,9 +4,9 @@ use crate::cli::error::{
 };
 use crate::cli::util::{
     cluster_identifiers_from, convert_row_to_nu_value, duration_to_golang_string,
-    get_active_cluster,
+    get_active_cluster, QueryResults,
 };
-use crate::client::{AnalyticsQueryRequest, HttpResponse};
+use crate::client::{AnalyticsQueryRequest, HttpResponse, HttpStreamResponse};
 use crate::state::State;
 use crate::RemoteCluster;
 use log::debug;
@@ -14,7 +14,8 @@ use nu_engine::CallExt;
 use nu_protocol::ast::Call;
 use nu_protocol::engine::{Command, EngineState, Stack};
 use nu_protocol::{
-    Category, IntoPipelineData, PipelineData, ShellError, Signature, Span, SyntaxShape, Value,
+    Category, IntoPipelineData, ListStream, PipelineData, ShellError, Signature, Span, SyntaxShape,
+    Value,
 };
 use std::ops::Add;
 use std::sync::atomic::AtomicBool;
@@ -97,7 +98,7 @@ fn run(
 
     debug!("Running analytics query {}", &statement);
 
-    let mut results: Vec<Value> = vec![];
+    let mut results: Vec<Box<dyn Iterator<Item = Value> + Send>> = vec![];
     for identifier in cluster_identifiers {
         let active_cluster = get_active_cluster(identifier.clone(), &guard, span)?;
         let bucket = call
@@ -105,23 +106,60 @@ fn run(
             .or_else(|| active_cluster.active_bucket());
         let maybe_scope = bucket.and_then(|b| scope.clone().map(|s| (b, s)));
 
-        results.extend(do_analytics_query(
-            identifier.clone(),
-            active_cluster,
-            maybe_scope,
-            &statement,
-            ctrl_c.clone(),
-            span,
-            with_meta,
-            true,
-        )?);
+        // The metadata is only complete once the whole response has been read, so that is
+        // returned all at once.
+        if with_meta {
+            results.push(Box::new(
+                do_analytics_query(
+                    identifier.clone(),
+                    active_cluster,
+                    maybe_scope,
+                    &statement,
+                    ctrl_c.clone(),
+                    span,
+                    with_meta,
+                    true,
+                )?
+                .into_iter(),
+            ));
+        } else {
+            let response = send_analytics_query_stream(
+                active_cluster,
+                maybe_scope,
+                &statement,
+                ctrl_c.clone(),
+                span,
+            )?;
+            results.push(Box::new(QueryResults::new(
+                response,
+                identifier.clone(),
+                analytics_errors,
+                span,
+            )));
+        }
     }
+    drop(guard);
 
-    Ok(Value::List {
-        vals: results,
-        internal_span: span,
+    let mut rows = results.into_iter().flatten().peekable();
+
+    // Queries which fail outright do so before returning any rows, so are errors of the command
+    // itself.
+    if let Some(Value::Error { error, .. }) = rows.next_if(|v| matches!(v, Value::Error { .. })) {
+        return Err(*error);
+    }
+
+    if rows.peek().is_none() {
+        return Ok(Value::List {
+            vals: vec![],
+            internal_span: span,
+        }
+        .into_pipeline_data());
     }
-    .into_pipeline_data())
+
+    Ok(PipelineData::ListStream(
+        ListStream::new(rows, span, Some(ctrl_c)),
+        None,
+    ))
 }
 
 pub fn send_analytics_query(
@@ -131,10 +169,22 @@ pub fn send_analytics_query(
     ctrl_c: Arc<AtomicBool>,
     span: Span,
 ) -> Result<HttpResponse, ShellError> {
+    send_analytics_query_stream(active_cluster, scope, statement, ctrl_c, span)?
+        .into_response()
+        .map_err(|e| client_error_to_shell_error(e, span))
+}
+
+pub fn send_analytics_query_stream(
+    active_cluster: &RemoteCluster,
+    scope: impl Into<Option<(String, String)>>,
+    statement: impl Into<String>,
+    ctrl_c: Arc<AtomicBool>,
+    span: Span,
+) -> Result<HttpStreamResponse, ShellError> {
     let response = active_cluster
         .cluster()
         .http_client()
-        .analytics_query_request(
+        .analytics_query_request_stream(
             AnalyticsQueryRequest::Execute {
                 statement: statement.into(),
                 scope: scope.into(),
@@ -146,6 +196,9 @@ pub fn send_analytics_query(
         .map_err(|e| client_error_to_shell_error(e, span))?;
 
     if response.status() != 200 {
+        let response = response
+            .into_response()
+            .map_err(|e| client_error_to_shell_error(e, span))?;
         return Err(unexpected_status_code_error(
             response.status(),
             response.content(),
@@ -179,49 +232,7 @@ pub fn do_analytics_query(
     }
 
     if let Some(content_errors) = content.get("errors") {
-        return if let Some(arr) = content_errors.as_array() {
-            if arr.len() == 1 {
-                let e = match arr.first() {
-                    Some(e) => e,
-                    None => {
-                        return Err(malformed_response_error(
-                            "analytics errors present but empty",
-                            content_errors.to_string(),
-                            span,
-                        ))
-                    }
-                };
-                let code = e.get("code").map(|c| c.as_i64().unwrap_or_default());
-                let reason = match code {
-                    Some(c) => AnalyticsErrorReason::from(c),
-                    None => AnalyticsErrorReason::UnknownError,
-                };
-                let msg = match e.get("msg") {
-                    Some(msg) => msg.to_string(),
-                    None => "".to_string(),
-                };
-                Err(analytics_error(reason, code, msg, span))
-            } else {
-                let messages = arr
-                    .iter()
-                    .map(|e| e.to_string())
-                    .collect::<Vec<String>>()
-                    .join(",");
-
-                Err(analytics_error(
-                    AnalyticsErrorReason::MultiErrors,
-                    None,
-                    messages,
-                    span,
-                ))
-            }
-        } else {
-            Err(malformed_response_error(
-                "analytics errors not an array",
-                content_errors.to_string(),
-                span,
-            ))
-        };
+        return Err(analytics_errors(content_errors, span));
     } else if let Some(content_results) = content.get("results") {
         if let Some(arr) = content_results.as_array() {
             for result in arr {
@@ -248,3 +259,45 @@ pub fn do_analytics_query(
 
     Ok(results)
 }
+
+// analytics_errors converts the errors reported in an analytics response into a shell error.
+fn analytics_errors(content_errors: &serde_json::Value, span: Span) -> ShellError {
+    if let Some(arr) = content_errors.as_array() {
+        if arr.len() == 1 {
+            let e = match arr.first() {
+                Some(e) => e,
+                None => {
+                    return malformed_response_error(
+                        "analytics errors present but empty",
+                        content_errors.to_string(),
+                        span,
+                    )
+                }
+            };
+            let code = e.get("code").map(|c| c.as_i64().unwrap_or_default());
+            let reason = match code {
+                Some(c) => AnalyticsErrorReason::from(c),
+                None => AnalyticsErrorReason::UnknownError,
+            };
+            let msg = match e.get("msg") {
+                Some(msg) => msg.to_string(),
+                None => "".to_string(),
+            };
+            analytics_error(reason, code, msg, span)
+        } else {
+            let messages = arr
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+
+            analytics_error(AnalyticsErrorReason::MultiErrors, None, messages, span)
+        }
+    } else {
+        malformed_response_error(
+            "analytics errors not an array",
+            content_errors.to_string(),
+            span,
+        )
+    }
+}
@@ -4,12 +4,16 @@ use crate::cli::error::{
 };
 use crate::cli::util::convert_json_value_to_nu_value;
 use crate::client::ManagementRequest;
+use crate::remote_cluster::RemoteCluster;
 use crate::state::State;
 use nu_engine::CallExt;
 use nu_protocol::ast::Call;
 use nu_protocol::engine::{Command, EngineState, Stack};
-use nu_protocol::{Category, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape};
+use nu_protocol::{
+    Category, IntoPipelineData, PipelineData, ShellError, Signature, Span, SyntaxShape,
+};
 use std::ops::Add;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 use tokio::time::Instant;
 
@@ -70,14 +74,48 @@ fn buckets(
         }
     };
 
-    let response = active_cluster
+    let deadline = Instant::now().add(active_cluster.timeouts().management_timeout());
+    let mut content = get_json(
+        active_cluster,
+        ManagementRequest::GetBucket { name: name.clone() },
+        deadline,
+        ctrl_c.clone(),
+        span,
+    )?;
+
+    // The full bucket config does not carry the revision, so it is taken from the terse config
+    // which the SDKs follow topology changes with.
+    let terse = get_json(
+        active_cluster,
+        ManagementRequest::GetBucketConfig { name },
+        deadline,
+        ctrl_c,
+        span,
+    )?;
+    if let Some(config) = content.as_object_mut() {
+        for field in ["rev", "revEpoch"] {
+            if let Some(value) = terse.get(field) {
+                config.insert(field.to_string(), value.clone());
+            }
+        }
+    }
+
+    let converted = convert_json_value_to_nu_value(&content, span)?;
+
+    Ok(converted.into_pipeline_data())
+}
+
+fn get_json(
+    cluster: &RemoteCluster,
+    request: ManagementRequest,
+    deadline: Instant,
+    ctrl_c: Arc<AtomicBool>,
+    span: Span,
+) -> Result<serde_json::Value, ShellError> {
+    let response = cluster
         .cluster()
         .http_client()
-        .management_request(
-            ManagementRequest::GetBucket { name },
-            Instant::now().add(active_cluster.timeouts().management_timeout()),
-            ctrl_c,
-        )
+        .management_request(request, deadline, ctrl_c)
         .map_err(|e| client_error_to_shell_error(e, span))?;
 
     match response.status() {
@@ -91,9 +129,5 @@ fn buckets(
         }
     }
 
-    let content = serde_json::from_str(response.content())
-        .map_err(|e| deserialize_error(e.to_string(), span))?;
-    let converted = convert_json_value_to_nu_value(&content, span)?;
-
-    Ok(converted.into_pipeline_data())
+    serde_json::from_str(response.content()).map_err(|e| deserialize_error(e.to_string(), span))
 }
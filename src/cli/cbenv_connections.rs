@@ -0,0 +1,134 @@
+use crate::cli::util::NuValueMap;
+use crate::state::State;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
+
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+
+#[derive(Clone)]
+pub struct CbEnvConnections {
+    state: Arc<Mutex<State>>,
+}
+
+impl CbEnvConnections {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for CbEnvConnections {
+    fn name(&self) -> &str {
+        "cb-env connections"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("cb-env connections")
+            .named(
+                "cluster",
+                SyntaxShape::String,
+                "only the connections to the cluster with this identifier",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "only the connections to this bucket",
+                None,
+            )
+            .switch(
+                "drop",
+                "close the connections, they are reopened by the next command which needs them",
+                None,
+            )
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Lists the key-value connections kept open between commands"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        connections(self.state.clone(), engine_state, stack, call, input)
+    }
+}
+
+fn connections(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    _input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let span = call.head;
+
+    let cluster: Option<String> = call.get_flag(engine_state, stack, "cluster")?;
+    let bucket: Option<String> = call.get_flag(engine_state, stack, "bucket")?;
+    let drop = call.has_flag(engine_state, stack, "drop")?;
+
+    let guard = state.lock().unwrap();
+    let rt = Runtime::new().unwrap();
+    let connections = rt.block_on(
+        guard
+            .kv_pool()
+            .connections(cluster.as_deref(), bucket.as_deref()),
+    );
+    if drop {
+        guard
+            .kv_pool()
+            .drop_connections(cluster.as_deref(), bucket.as_deref());
+    }
+
+    let results = connections
+        .into_iter()
+        .map(|c| {
+            let mut collected = NuValueMap::default();
+            collected.add_string("cluster", c.cluster(), span);
+            collected.add_string("bucket", c.bucket(), span);
+            collected.add(
+                "nodes",
+                Value::List {
+                    vals: c
+                        .nodes()
+                        .iter()
+                        .map(|n| Value::string(n.clone(), span))
+                        .collect(),
+                    internal_span: span,
+                },
+            );
+            collected.add_i64("uses", c.uses() as i64, span);
+            collected.add(
+                "idle",
+                Value::Duration {
+                    val: c.idle().as_nanos() as i64,
+                    internal_span: span,
+                },
+            );
+            collected.add(
+                "age",
+                Value::Duration {
+                    val: c.age().as_nanos() as i64,
+                    internal_span: span,
+                },
+            );
+            collected.add_bool("dropped", drop, span);
+            collected.into_value(span)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: span,
+    }
+    .into_pipeline_data())
+}
@@ -90,6 +90,18 @@ impl Command for CbEnvRegister {
                 "the path to the certificate to use with tls",
                 None,
             )
+            .named(
+                "tls-client-cert-path",
+                SyntaxShape::String,
+                "the path to the client certificate to authenticate with instead of a username and password",
+                None,
+            )
+            .named(
+                "tls-client-key-path",
+                SyntaxShape::String,
+                "the path to the private key of the client certificate",
+                None,
+            )
             .named(
                 "tls-accept-all-certs",
                 SyntaxShape::String,
@@ -151,6 +163,9 @@ fn clusters_register(
         .get_flag(engine_state, stack, "tls-accept-all-certs")?
         .unwrap_or(true);
     let cert_path = call.get_flag(engine_state, stack, "tls-cert-path")?;
+    let client_cert_path: Option<String> =
+        call.get_flag(engine_state, stack, "tls-client-cert-path")?;
+    let client_key_path = call.get_flag(engine_state, stack, "tls-client-key-path")?;
     let save = call.get_flag(engine_state, stack, "save")?.unwrap_or(false);
     let capella = call.get_flag(engine_state, stack, "capella-organization")?;
     let project = call.get_flag(engine_state, stack, "project")?;
@@ -162,8 +177,13 @@ fn clusters_register(
         .collect::<Vec<String>>();
     let tls_config = if tls_enabled {
         Some(
-            RustTlsConfig::new(tls_accept_all_certs, cert_path)
-                .map_err(|e| generic_error(e.message(), e.expanded_message(), None))?,
+            RustTlsConfig::new(
+                tls_accept_all_certs,
+                cert_path,
+                client_cert_path.clone(),
+                client_key_path,
+            )
+            .map_err(|e| generic_error(e.message(), e.expanded_message(), None))?,
         )
     } else {
         None
@@ -172,7 +192,15 @@ fn clusters_register(
     let username_flag = call.get_flag(engine_state, stack, "username")?;
     let password_flag = call.get_flag(engine_state, stack, "password")?;
 
-    let (username, password) = get_username_and_password(username_flag, password_flag)?;
+    // The client certificate identifies the user, so credentials are only needed without one.
+    let (username, password) = if tls_enabled && client_cert_path.is_some() {
+        (
+            username_flag.unwrap_or_default(),
+            password_flag.unwrap_or_default(),
+        )
+    } else {
+        get_username_and_password(username_flag, password_flag)?
+    };
 
     let cluster = RemoteCluster::new(
         RemoteClusterResources {
@@ -189,6 +217,8 @@ fn clusters_register(
         capella,
         project,
         DEFAULT_KV_BATCH_SIZE,
+        None,
+        None,
         RemoteClusterType::from(hostnames),
     );
 
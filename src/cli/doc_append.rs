@@ -0,0 +1,144 @@
+//! The `doc append` command performs a KV append operation.
+
+use crate::cli::doc_common::run_kv_concat_ops;
+use crate::client::{Durability, KeyValueRequest};
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct DocAppend {
+    state: Arc<Mutex<State>>,
+}
+
+impl DocAppend {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for DocAppend {
+    fn name(&self) -> &str {
+        "doc append"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("doc append")
+            .optional("id", SyntaxShape::String, "the document id")
+            .optional("content", SyntaxShape::Any, "the content to add to the document")
+            .named(
+                "id-column",
+                SyntaxShape::String,
+                "the name of the id column if used with an input stream",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "the name of the bucket",
+                None,
+            )
+            .named(
+                "content-column",
+                SyntaxShape::String,
+                "the name of the content column if used with an input stream",
+                None,
+            )
+            .named(
+                "cas",
+                SyntaxShape::Int,
+                "the cas value the document must have, overridden by a cas column if used with an input stream",
+                None,
+            )
+            .named("scope", SyntaxShape::String, "the name of the scope", None)
+            .named(
+                "collection",
+                SyntaxShape::String,
+                "the name of the collection",
+                None,
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters which should be contacted",
+                None,
+            )
+            .named(
+                "durability",
+                SyntaxShape::String,
+                "the durability level required for the mutation: none, majority, majority-and-persist-active or persist-to-majority",
+                None,
+            )
+            .named(
+                "durability-timeout",
+                SyntaxShape::Int,
+                "the timeout for meeting the durability level (in ms)",
+                None,
+            )
+            .named(
+                "batch-size",
+                SyntaxShape::Number,
+                "the maximum number of items to batch send at a time",
+                None,
+            )
+            .switch("halt-on-error", "halt on any errors", Some('e'))
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Appends raw content to a document through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_append(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![Example {
+            description: "Appends to the end of a document",
+            example: "doc append my_doc_id ',world'",
+            result: None,
+        }]
+    }
+}
+
+fn build_req(
+    key: String,
+    value: Vec<u8>,
+    _expiry: u32,
+    cas: u64,
+    durability: Durability,
+) -> KeyValueRequest {
+    KeyValueRequest::Append {
+        key,
+        value,
+        cas,
+        durability,
+    }
+}
+
+fn run_append(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let results = run_kv_concat_ops(state, engine_state, stack, call, input, build_req)?;
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
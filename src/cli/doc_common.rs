@@ -1,9 +1,14 @@
+use crate::cli::doc_get::ids_from_input;
+use crate::cli::throughput::{Throughput, ThroughputSummary};
 use crate::cli::util::{
-    cluster_identifiers_from, convert_nu_value_to_json_value, get_active_cluster,
-    namespace_from_args, NuValueMap,
+    cluster_identifiers_from, convert_json_value_to_nu_value, convert_nu_value_to_json_value,
+    get_active_cluster, namespace_from_args, NuValueMap,
+};
+use crate::cli::{client_error_to_shell_error, generic_error, serialize_error};
+use crate::client::{
+    ClientError, DocumentFormat, Durability, DurabilityLevel, KeyValueRequest, KvClient,
+    KvResponse, MutationState,
 };
-use crate::cli::{client_error_to_shell_error, serialize_error};
-use crate::client::{ClientError, KeyValueRequest, KvClient, KvResponse};
 use crate::remote_cluster::RemoteCluster;
 use crate::state::State;
 use futures::stream::FuturesUnordered;
@@ -14,10 +19,12 @@ use nu_protocol::ast::Call;
 use nu_protocol::engine::{EngineState, Stack};
 use nu_protocol::{PipelineData, ShellError, Span, Value};
 use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::future::Future;
 use std::ops::Add;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 use tokio::runtime::Runtime;
 use tokio::time::Instant;
 
@@ -27,7 +34,7 @@ pub(crate) fn run_kv_store_ops(
     stack: &mut Stack,
     call: &Call,
     input: PipelineData,
-    req_builder: fn(String, Vec<u8>, u32) -> KeyValueRequest,
+    req_builder: fn(String, Vec<u8>, u32, u32, u64, Durability) -> KeyValueRequest,
 ) -> Result<Vec<Value>, ShellError> {
     let span = call.head;
 
@@ -39,10 +46,13 @@ pub(crate) fn run_kv_store_ops(
         .get_flag(engine_state, stack, "content-column")?
         .unwrap_or_else(|| String::from("content"));
 
+    let cas_flag: Option<i64> = call.get_flag(engine_state, stack, "cas")?;
+    let format = format_from_args(engine_state, stack, call)?.unwrap_or_default();
+
     let input_args = if let Some(id) = call.opt::<String>(engine_state, stack, 0)? {
         if let Some(v) = call.opt::<Value>(engine_state, stack, 1)? {
-            let content = convert_nu_value_to_json_value(&v, span)?;
-            vec![(id, content)]
+            let content = encode_content(format, &v, span)?;
+            vec![(id, content, None)]
         } else {
             vec![]
         }
@@ -57,17 +67,21 @@ pub(crate) fn run_kv_store_ops(
         if let Value::Record { val, .. } = i {
             let mut id = None;
             let mut content = None;
+            let mut cas = None;
             for (k, v) in val.iter() {
                 if k.clone() == id_column {
                     id = id_from_value(v, span);
                 }
                 if k.clone() == content_column {
-                    content = convert_nu_value_to_json_value(v, span).ok();
+                    content = encode_content(format, v, span).ok();
+                }
+                if k == "cas" {
+                    cas = cas_from_value(v);
                 }
             }
 
             if let Some(c) = content {
-                return Some((id.unwrap_or("".into()), c));
+                return Some((id.unwrap_or("".into()), c, cas));
             }
         }
         None
@@ -75,10 +89,145 @@ pub(crate) fn run_kv_store_ops(
 
     let mut all_items = vec![];
     for item in filtered.chain(input_args) {
-        let value =
-            serde_json::to_vec(&item.1).map_err(|e| serialize_error(e.to_string(), span))?;
+        // A cas carried by the row takes precedence over the cas flag.
+        let cas = item.2.or(cas_flag.map(|c| c as u64)).unwrap_or_default();
 
-        all_items.push((item.0, value));
+        all_items.push((item.0, item.1, cas));
+    }
+
+    let flags = format.flags();
+    run_kv_mutations(
+        state,
+        engine_state,
+        stack,
+        call,
+        span,
+        all_items,
+        |key, value, expiry, cas, durability| {
+            req_builder(key, value, flags, expiry, cas, durability)
+        },
+        false,
+    )
+}
+
+pub(crate) fn format_from_args(
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+) -> Result<Option<DocumentFormat>, ShellError> {
+    match call.get_flag::<String>(engine_state, stack, "format")? {
+        Some(f) => DocumentFormat::try_from(f.as_str()).map(Some).map_err(|e| {
+            generic_error(
+                e,
+                "Format must be one of json, string or binary".to_string(),
+                call.head,
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+// encode_content converts the content into the bytes stored for the given format.
+fn encode_content(
+    format: DocumentFormat,
+    content: &Value,
+    span: Span,
+) -> Result<Vec<u8>, ShellError> {
+    match (format, content) {
+        (DocumentFormat::Json, _) => {
+            let json = convert_nu_value_to_json_value(content, span)?;
+            serde_json::to_vec(&json).map_err(|e| serialize_error(e.to_string(), span))
+        }
+        (DocumentFormat::String, Value::String { val, .. }) => Ok(val.clone().into_bytes()),
+        (DocumentFormat::Binary, Value::Binary { val, .. }) => Ok(val.clone()),
+        (DocumentFormat::Binary, Value::String { val, .. }) => Ok(val.clone().into_bytes()),
+        (DocumentFormat::String, _) => Err(generic_error(
+            "Content must be a string when using the string format",
+            None,
+            span,
+        )),
+        (DocumentFormat::Binary, _) => Err(generic_error(
+            "Content must be binary or a string when using the binary format",
+            None,
+            span,
+        )),
+    }
+}
+
+// run_kv_concat_ops is used by append and prepend, which take the content as raw bytes rather than
+// serializing it to JSON.
+pub(crate) fn run_kv_concat_ops(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+    req_builder: fn(String, Vec<u8>, u32, u64, Durability) -> KeyValueRequest,
+) -> Result<Vec<Value>, ShellError> {
+    let span = call.head;
+
+    let id_column = call
+        .get_flag(engine_state, stack, "id-column")?
+        .unwrap_or_else(|| String::from("id"));
+
+    let content_column = call
+        .get_flag(engine_state, stack, "content-column")?
+        .unwrap_or_else(|| String::from("content"));
+
+    let cas_flag: Option<i64> = call.get_flag(engine_state, stack, "cas")?;
+
+    let input_args = if let Some(id) = call.opt::<String>(engine_state, stack, 0)? {
+        if let Some(v) = call.opt::<Value>(engine_state, stack, 1)? {
+            vec![(id, Some(v), None)]
+        } else {
+            vec![]
+        }
+    } else {
+        vec![]
+    };
+
+    let filtered = input.into_iter().filter_map(move |i| {
+        if let Value::Record { val, .. } = i {
+            let mut id = None;
+            let mut content = None;
+            let mut cas = None;
+            for (k, v) in val.iter() {
+                if k.clone() == id_column {
+                    id = id_from_value(v, span);
+                }
+                if k.clone() == content_column {
+                    content = Some(v.clone());
+                }
+                if k == "cas" {
+                    cas = cas_from_value(v);
+                }
+            }
+
+            if content.is_some() {
+                return Some((id.unwrap_or_default(), content, cas));
+            }
+        }
+        None
+    });
+
+    let mut all_items = vec![];
+    for (id, content, cas) in filtered.chain(input_args) {
+        let value = match content {
+            Some(Value::String { val, .. }) => val.into_bytes(),
+            Some(Value::Binary { val, .. }) => val,
+            _ => {
+                return Err(generic_error(
+                    "Content must be a string or binary",
+                    "Append and prepend add the content to the document as raw bytes".to_string(),
+                    span,
+                ))
+            }
+        };
+
+        // A cas carried by the row takes precedence over the cas flag.
+        let cas = cas.or(cas_flag.map(|c| c as u64)).unwrap_or_default();
+
+        all_items.push((id, value, cas));
     }
 
     run_kv_mutations(
@@ -89,6 +238,55 @@ pub(crate) fn run_kv_store_ops(
         span,
         all_items,
         req_builder,
+        false,
+    )
+}
+
+pub(crate) fn run_kv_counter_ops(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+    req_builder: fn(String, u64, Option<u64>, u32, Durability) -> KeyValueRequest,
+) -> Result<Vec<Value>, ShellError> {
+    let span = call.head;
+
+    let id_column = call
+        .get_flag(engine_state, stack, "id-column")?
+        .unwrap_or_else(|| String::from("id"));
+    let delta: i64 = call.get_flag(engine_state, stack, "delta")?.unwrap_or(1);
+    let initial: Option<i64> = call.get_flag(engine_state, stack, "initial")?;
+    if delta < 0 || initial.map(|i| i < 0).unwrap_or_default() {
+        return Err(generic_error(
+            "Delta and initial must not be negative",
+            "Use doc decrement to reduce the value of a counter".to_string(),
+            span,
+        ));
+    }
+
+    let all_items = ids_from_input(input, id_column, call.positional_nth(0))?
+        .into_iter()
+        .map(|id| (id, vec![], 0))
+        .collect();
+
+    run_kv_mutations(
+        state,
+        engine_state,
+        stack,
+        call,
+        span,
+        all_items,
+        |key, _, expiry, _, durability| {
+            req_builder(
+                key,
+                delta as u64,
+                initial.map(|i| i as u64),
+                expiry,
+                durability,
+            )
+        },
+        true,
     )
 }
 
@@ -106,19 +304,34 @@ pub fn id_from_value(v: &Value, span: Span) -> Option<String> {
     }
 }
 
+// cas_from_value extracts a cas from an input row, ignoring the zero value rows carry when a
+// document could not be fetched.
+pub(crate) fn cas_from_value(v: &Value) -> Option<u64> {
+    match v {
+        Value::Int { val, .. } if *val != 0 => Some(*val as u64),
+        _ => None,
+    }
+}
+
 pub fn run_kv_mutations(
     state: Arc<Mutex<State>>,
     engine_state: &EngineState,
     stack: &mut Stack,
     call: &Call,
     span: Span,
-    all_items: Vec<(String, Vec<u8>)>,
-    req_builder: fn(String, Vec<u8>, u32) -> KeyValueRequest,
+    all_items: Vec<(String, Vec<u8>, u64)>,
+    req_builder: impl Fn(String, Vec<u8>, u32, u64, Durability) -> KeyValueRequest,
+    results_per_key: bool,
 ) -> Result<Vec<Value>, ShellError> {
     let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
 
+    let id_column = call
+        .get_flag(engine_state, stack, "id-column")?
+        .unwrap_or_else(|| String::from("id"));
+
     let expiry: i64 = call.get_flag(engine_state, stack, "expiry")?.unwrap_or(0);
     let batch_size: Option<i64> = call.get_flag(engine_state, stack, "batch-size")?;
+    let durability = durability_from_args(engine_state, stack, call)?;
 
     let bucket_flag = call.get_flag(engine_state, stack, "bucket")?;
     let scope_flag = call.get_flag(engine_state, stack, "scope")?;
@@ -127,6 +340,12 @@ pub fn run_kv_mutations(
     let halt_on_error = call.has_flag(engine_state, stack, "halt-on-error")?;
 
     let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+    let throughput = Throughput::from_args(
+        engine_state,
+        stack,
+        call,
+        Some((all_items.len() * cluster_identifiers.len()) as u64),
+    )?;
 
     let guard = state.lock().unwrap();
 
@@ -154,6 +373,15 @@ pub fn run_kv_mutations(
                     return Err(e);
                 }
 
+                if results_per_key {
+                    let collected = MutationKeyResult::new(identifier.clone())
+                        .id_column(&id_column)
+                        .error(e.to_string())
+                        .into_value(span);
+                    results.push(collected);
+                    continue;
+                }
+
                 let mut failures = HashSet::new();
                 failures.insert(e.to_string());
                 let collected = MutationResult::new(identifier.clone())
@@ -172,25 +400,31 @@ pub fn run_kv_mutations(
         let mut success = 0;
         let mut failed = 0;
         let mut fail_reasons: HashSet<String> = HashSet::new();
+        let mut durability_failures = vec![];
         for items in all_values.clone() {
             for item in items.clone() {
-                let deadline = Instant::now().add(active_cluster.timeouts().data_timeout());
+                let timeout = active_cluster.timeouts().data_timeout();
 
                 let ctrl_c = ctrl_c.clone();
 
                 let client = client.clone();
+                let throughput = throughput.clone();
+                let identifier = identifier.clone();
 
                 if !item.0.is_empty() {
+                    let request = req_builder(item.0, item.1, expiry as u32, item.2, durability);
                     workers.push(async move {
-                        client
-                            .request(
-                                req_builder(item.0, item.1, expiry as u32),
-                                cid,
-                                deadline,
-                                ctrl_c,
-                            )
+                        throughput
+                            .request(&identifier, &client, request, cid, timeout, ctrl_c)
                             .await
                     });
+                } else if results_per_key {
+                    results.push(
+                        MutationKeyResult::new(identifier.clone())
+                            .id_column(&id_column)
+                            .error("Missing doc id".to_string())
+                            .into_value(span),
+                    );
                 } else {
                     failed += 1;
                     let mut missing_reason = HashSet::new();
@@ -198,6 +432,22 @@ pub fn run_kv_mutations(
                     fail_reasons.extend(missing_reason);
                 }
             }
+
+            if results_per_key {
+                let (values, mutations) = process_kv_workers_per_key(
+                    workers,
+                    &rt,
+                    halt_on_error,
+                    &identifier,
+                    &id_column,
+                    span,
+                )?;
+                results.extend(values);
+                guard.record_mutations(&identifier, mutations);
+                workers = FuturesUnordered::new();
+                continue;
+            }
+
             // process_kv_workers will handle creating an error for us if halt_on_error is set so we
             // can just bubble it.
             let worked = process_kv_workers(workers, &rt, halt_on_error, span)?;
@@ -205,24 +455,177 @@ pub fn run_kv_mutations(
             success += worked.success;
             failed += worked.failed;
             fail_reasons.extend(worked.fail_reasons);
+            durability_failures.extend(worked.durability_failures);
+            guard.record_mutations(&identifier, worked.mutations);
+            workers = FuturesUnordered::new()
+        }
+
+        if results_per_key {
+            continue;
+        }
+
+        let collected = MutationResult::new(identifier.clone())
+            .success(success)
+            .failed(failed)
+            .fail_reasons(fail_reasons)
+            .durability_failures(durability_failures)
+            .throughput(throughput.summary(&identifier));
+
+        results.push(collected.into_value(span));
+    }
+    throughput.finish();
+
+    Ok(results)
+}
+
+// run_kv_key_ops runs an operation which only requires a key, and optionally a cas, against each
+// item and summarises the results in the same way as mutations.
+pub(crate) fn run_kv_key_ops<T: Clone>(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    items: Vec<T>,
+    req_builder: impl Fn(T) -> KeyValueRequest,
+) -> Result<Vec<Value>, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let batch_size: Option<i64> = call.get_flag(engine_state, stack, "batch-size")?;
+    let mut all_items: Vec<Vec<T>> = vec![];
+    if let Some(size) = batch_size {
+        all_items = build_batched_kv_items(size as u32, items.clone());
+    }
+
+    let bucket_flag = call.get_flag(engine_state, stack, "bucket")?;
+    let scope_flag = call.get_flag(engine_state, stack, "scope")?;
+    let collection_flag = call.get_flag(engine_state, stack, "collection")?;
+    let halt_on_error = call.has_flag(engine_state, stack, "halt-on-error")?;
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+    let throughput = Throughput::from_args(
+        engine_state,
+        stack,
+        call,
+        Some((items.len() * cluster_identifiers.len()) as u64),
+    )?;
+
+    let guard = state.lock().unwrap();
+
+    let mut results = vec![];
+    for identifier in cluster_identifiers {
+        let rt = Runtime::new().unwrap();
+        let (active_cluster, client, cid) = match get_active_cluster_client_cid(
+            &rt,
+            identifier.clone(),
+            &guard,
+            bucket_flag.clone(),
+            scope_flag.clone(),
+            collection_flag.clone(),
+            ctrl_c.clone(),
+            span,
+        ) {
+            Ok(c) => c,
+            Err(e) => {
+                if halt_on_error {
+                    return Err(e);
+                }
+
+                let mut failures = HashSet::new();
+                failures.insert(e.to_string());
+                let collected = MutationResult::new(identifier.clone())
+                    .fail_reasons(failures)
+                    .into_value(span);
+                results.push(collected);
+                continue;
+            }
+        };
+
+        if all_items.is_empty() {
+            all_items = build_batched_kv_items(active_cluster.kv_batch_size(), items.clone());
+        }
+
+        let mut workers = FuturesUnordered::new();
+        let mut success = 0;
+        let mut failed = 0;
+        let mut fail_reasons: HashSet<String> = HashSet::new();
+        let mut durability_failures = vec![];
+        for batch in all_items.clone() {
+            for item in batch {
+                let timeout = active_cluster.timeouts().data_timeout();
+                let ctrl_c = ctrl_c.clone();
+                let client = client.clone();
+                let throughput = throughput.clone();
+                let identifier = identifier.clone();
+                let request = req_builder(item);
+
+                workers.push(async move {
+                    throughput
+                        .request(&identifier, &client, request, cid, timeout, ctrl_c)
+                        .await
+                });
+            }
+
+            let worked = process_kv_workers(workers, &rt, halt_on_error, span)?;
+
+            success += worked.success;
+            failed += worked.failed;
+            fail_reasons.extend(worked.fail_reasons);
+            durability_failures.extend(worked.durability_failures);
+            guard.record_mutations(&identifier, worked.mutations);
             workers = FuturesUnordered::new()
         }
 
         let collected = MutationResult::new(identifier.clone())
             .success(success)
             .failed(failed)
-            .fail_reasons(fail_reasons);
+            .fail_reasons(fail_reasons)
+            .durability_failures(durability_failures)
+            .throughput(throughput.summary(&identifier));
 
         results.push(collected.into_value(span));
     }
+    throughput.finish();
 
     Ok(results)
 }
 
+pub(crate) fn durability_from_args(
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+) -> Result<Durability, ShellError> {
+    let level = match call.get_flag::<String>(engine_state, stack, "durability")? {
+        Some(l) => DurabilityLevel::try_from(l.as_str()).map_err(|e| {
+            generic_error(
+                e,
+                "Durability must be one of none, majority, majority-and-persist-active or persist-to-majority".to_string(),
+                call.head,
+            )
+        })?,
+        None => DurabilityLevel::None,
+    };
+    let timeout = match call.get_flag::<i64>(engine_state, stack, "durability-timeout")? {
+        Some(t) if t < 0 => {
+            return Err(generic_error(
+                format!("Durability timeout cannot be negative, got {}", t),
+                None,
+                call.head,
+            ));
+        }
+        Some(t) => Some(Duration::from_millis(t as u64)),
+        None => None,
+    };
+
+    Durability::new(level, timeout).map_err(|e| generic_error(e, None, call.head))
+}
+
 pub(crate) struct WorkerResponse {
     pub(crate) success: i32,
     pub(crate) failed: i32,
     pub(crate) fail_reasons: HashSet<String>,
+    pub(crate) durability_failures: Vec<(String, String)>,
+    pub(crate) mutations: MutationState,
 }
 
 pub(crate) fn process_kv_workers(
@@ -231,29 +634,98 @@ pub(crate) fn process_kv_workers(
     halt_on_error: bool,
     span: Span,
 ) -> Result<WorkerResponse, ShellError> {
-    let (success, failed, fail_reasons) = rt.block_on(async {
+    let (success, failed, fail_reasons, durability_failures, mutations) = rt.block_on(async {
         let mut success = 0;
         let mut failed = 0;
         let mut fail_reasons: HashSet<String> = HashSet::new();
+        let mut durability_failures = vec![];
+        let mut mutations = MutationState::default();
         while let Some(result) = workers.next().await {
             match result {
-                Ok(_) => success += 1,
+                Ok(res) => {
+                    success += 1;
+                    if let Some(token) = res.mutation_token() {
+                        mutations.add(token);
+                    }
+                }
                 Err(e) => {
                     if halt_on_error {
                         return Err(client_error_to_shell_error(e, span));
                     }
                     failed += 1;
+                    // Durability failures are reported per document as an ambiguous write may
+                    // or may not have been applied.
+                    if let ClientError::DurabilityImpossible { key }
+                    | ClientError::SyncWriteAmbiguous { key } = &e
+                    {
+                        durability_failures.push((key.clone(), e.to_string()));
+                    }
                     fail_reasons.insert(e.to_string());
                 }
             }
         }
-        Ok((success, failed, fail_reasons))
+        Ok((
+            success,
+            failed,
+            fail_reasons,
+            durability_failures,
+            mutations,
+        ))
     })?;
 
     Ok(WorkerResponse {
         success,
         failed,
         fail_reasons,
+        durability_failures,
+        mutations,
+    })
+}
+
+// process_kv_workers_per_key produces a row for each response rather than a summary, including
+// any value returned, such as the new value of a counter, along with the writes made.
+pub(crate) fn process_kv_workers_per_key(
+    mut workers: FuturesUnordered<impl Future<Output = Result<KvResponse, ClientError>>>,
+    rt: &Runtime,
+    halt_on_error: bool,
+    cluster: &str,
+    id_column: &str,
+    span: Span,
+) -> Result<(Vec<Value>, MutationState), ShellError> {
+    rt.block_on(async {
+        let mut results = vec![];
+        let mut mutations = MutationState::default();
+        while let Some(result) = workers.next().await {
+            match result {
+                Ok(mut res) => {
+                    if let Some(token) = res.mutation_token() {
+                        mutations.add(token);
+                    }
+                    let mut collected = MutationKeyResult::new(cluster)
+                        .id_column(id_column)
+                        .key(res.key())
+                        .cas(res.cas() as i64);
+                    if let Some(content) = res.content() {
+                        collected =
+                            collected.value(convert_json_value_to_nu_value(&content, span)?);
+                    }
+                    results.push(collected.into_value(span));
+                }
+                Err(e) => {
+                    if halt_on_error {
+                        return Err(client_error_to_shell_error(e, span));
+                    }
+                    results.push(
+                        MutationKeyResult::new(cluster)
+                            .id_column(id_column)
+                            .key(e.key().unwrap_or_default())
+                            .error(e.to_string())
+                            .into_value(span),
+                    );
+                }
+            }
+        }
+        Ok((results, mutations))
     })
 }
 
@@ -290,14 +762,16 @@ pub(crate) fn get_active_cluster_client_cid<'a>(
     ctrl_c: Arc<AtomicBool>,
     span: Span,
 ) -> Result<(&'a RemoteCluster, Arc<KvClient>, u32), ShellError> {
-    let active_cluster = get_active_cluster(cluster, guard, span)?;
+    let active_cluster = get_active_cluster(cluster.clone(), guard, span)?;
 
     let (bucket, scope, collection) =
         namespace_from_args(bucket, scope, collection, active_cluster, span)?;
 
     let deadline = Instant::now().add(active_cluster.timeouts().data_timeout());
     let client = rt
-        .block_on(active_cluster.cluster().key_value_client(
+        .block_on(guard.kv_pool().client(
+            &cluster,
+            active_cluster.cluster(),
             bucket.clone(),
             deadline,
             ctrl_c.clone(),
@@ -313,7 +787,7 @@ pub(crate) fn get_active_cluster_client_cid<'a>(
         ))
         .map_err(|e| client_error_to_shell_error(e, span))?;
 
-    Ok((active_cluster, Arc::new(client), cid))
+    Ok((active_cluster, client, cid))
 }
 
 #[derive(Debug)]
@@ -321,6 +795,8 @@ pub struct MutationResult {
     success: i32,
     failed: i32,
     fail_reasons: HashSet<String>,
+    durability_failures: Vec<(String, String)>,
+    throughput: Option<ThroughputSummary>,
     cluster: String,
 }
 
@@ -330,6 +806,8 @@ impl MutationResult {
             success: 0,
             failed: 0,
             fail_reasons: Default::default(),
+            durability_failures: vec![],
+            throughput: None,
             cluster,
         }
     }
@@ -348,6 +826,16 @@ impl MutationResult {
         self
     }
 
+    pub fn durability_failures(mut self, durability_failures: Vec<(String, String)>) -> Self {
+        self.durability_failures = durability_failures;
+        self
+    }
+
+    pub(crate) fn throughput(mut self, throughput: Option<ThroughputSummary>) -> Self {
+        self.throughput = throughput;
+        self
+    }
+
     pub fn into_value(self, span: Span) -> Value {
         let mut collected = NuValueMap::default();
         collected.add_i64("processed", (self.success + self.failed) as i64, span);
@@ -360,6 +848,90 @@ impl MutationResult {
             .collect::<Vec<String>>()
             .join(", ");
         collected.add_string("failures", reasons, span);
+        if !self.durability_failures.is_empty() {
+            let failures = self
+                .durability_failures
+                .into_iter()
+                .map(|(key, reason)| {
+                    let mut failure = NuValueMap::default();
+                    failure.add_string("id", key, span);
+                    failure.add_string("reason", reason, span);
+                    failure.into_value(span)
+                })
+                .collect();
+            collected.add(
+                "durability_failures",
+                Value::List {
+                    vals: failures,
+                    internal_span: span,
+                },
+            );
+        }
+        if let Some(throughput) = self.throughput {
+            throughput.add_to(&mut collected, span);
+        }
+        collected.add_string("cluster", self.cluster, span);
+        collected.into_value(span)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MutationKeyResult {
+    error: Option<String>,
+    value: Option<Value>,
+    key: Option<String>,
+    cluster: String,
+    cas: Option<i64>,
+    id_column: Option<String>,
+}
+
+impl MutationKeyResult {
+    pub fn new(cluster: impl Into<String>) -> Self {
+        Self {
+            error: None,
+            value: None,
+            key: None,
+            cluster: cluster.into(),
+            cas: None,
+            id_column: None,
+        }
+    }
+
+    pub fn id_column(mut self, id_column: impl Into<String>) -> Self {
+        self.id_column = Some(id_column.into());
+        self
+    }
+
+    pub fn value(mut self, value: Value) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    pub fn key(mut self, key: String) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub fn cas(mut self, cas: i64) -> Self {
+        self.cas = Some(cas);
+        self
+    }
+
+    pub fn error(mut self, err: String) -> Self {
+        self.error = Some(err);
+        self
+    }
+
+    pub fn into_value(self, span: Span) -> Value {
+        let mut collected = NuValueMap::default();
+        collected.add_string(
+            self.id_column.unwrap_or_else(|| "id".to_string()),
+            self.key.unwrap_or_default(),
+            span,
+        );
+        collected.add("value", self.value.unwrap_or_default());
+        collected.add_i64("cas", self.cas.unwrap_or_default(), span);
+        collected.add_string("error", self.error.unwrap_or_default(), span);
         collected.add_string("cluster", self.cluster, span);
         collected.into_value(span)
     }
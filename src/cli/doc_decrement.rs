@@ -0,0 +1,157 @@
+//! The `doc decrement` command performs a KV decrement operation.
+
+use crate::cli::doc_common::run_kv_counter_ops;
+use crate::client::{Durability, KeyValueRequest};
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct DocDecrement {
+    state: Arc<Mutex<State>>,
+}
+
+impl DocDecrement {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for DocDecrement {
+    fn name(&self) -> &str {
+        "doc decrement"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("doc decrement")
+            .optional("id", SyntaxShape::String, "the document id")
+            .named(
+                "id-column",
+                SyntaxShape::String,
+                "the name of the id column if used with an input stream",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "the name of the bucket",
+                None,
+            )
+            .named(
+                "delta",
+                SyntaxShape::Int,
+                "the amount to change the counter by, defaults to 1",
+                None,
+            )
+            .named(
+                "initial",
+                SyntaxShape::Int,
+                "the value to create the counter with if it does not exist, the operation fails for missing counters if not set",
+                None,
+            )
+            .named(
+                "expiry",
+                SyntaxShape::Number,
+                "the expiry for the counters in seconds, or absolute",
+                None,
+            )
+            .named("scope", SyntaxShape::String, "the name of the scope", None)
+            .named(
+                "collection",
+                SyntaxShape::String,
+                "the name of the collection",
+                None,
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters which should be contacted",
+                None,
+            )
+            .named(
+                "durability",
+                SyntaxShape::String,
+                "the durability level required for the mutation: none, majority, majority-and-persist-active or persist-to-majority",
+                None,
+            )
+            .named(
+                "durability-timeout",
+                SyntaxShape::Int,
+                "the timeout for meeting the durability level (in ms)",
+                None,
+            )
+            .named(
+                "batch-size",
+                SyntaxShape::Number,
+                "the maximum number of items to batch send at a time",
+                None,
+            )
+            .switch("halt-on-error", "halt on any errors", Some('e'))
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Decrements a counter document through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_decrement(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Decrements a counter by 1",
+                example: "doc decrement my_counter",
+                result: None,
+            },
+            Example {
+                description: "Decrements multiple counters by 5 with IDs from the previous command",
+                example: "echo [[id]; [counter_1] [counter_2]] | doc decrement --delta 5",
+                result: None,
+            },
+        ]
+    }
+}
+
+fn build_req(
+    key: String,
+    delta: u64,
+    initial: Option<u64>,
+    expiry: u32,
+    durability: Durability,
+) -> KeyValueRequest {
+    KeyValueRequest::Decrement {
+        key,
+        delta,
+        initial,
+        expiry,
+        durability,
+    }
+}
+
+fn run_decrement(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let results = run_kv_counter_ops(state, engine_state, stack, call, input, build_req)?;
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
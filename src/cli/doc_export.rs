@@ -0,0 +1,772 @@
+//! The `doc export` command writes the documents of a collection to a file.
+
+use crate::cli::doc_common::get_active_cluster_client_cid;
+use crate::cli::error::{client_error_to_shell_error, generic_error, unexpected_status_code_error};
+use crate::cli::parquet::{ColumnType, ParquetWriter};
+use crate::cli::query::{query_errors, send_query_stream};
+use crate::cli::throughput::ProgressLine;
+use crate::cli::util::{
+    cluster_identifiers_from, get_active_cluster, namespace_from_args, NuValueMap,
+};
+use crate::client::{ClientError, JsonResultsStream, QueryOptions, RangeScan, ScanType};
+use crate::state::State;
+use serde_json::{Map, Value as JsonValue};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::runtime::Runtime;
+
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, Span, SyntaxShape,
+    Value,
+};
+
+static PARQUET_ROW_GROUP_SIZE: usize = 10_000;
+
+#[derive(Clone)]
+pub struct DocExport {
+    state: Arc<Mutex<State>>,
+}
+
+impl DocExport {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for DocExport {
+    fn name(&self) -> &str {
+        "doc export"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("doc export")
+            .required(
+                "filename",
+                SyntaxShape::String,
+                "the path of the file to write the documents to",
+            )
+            .named(
+                "format",
+                SyntaxShape::String,
+                "the format to write: jsonl, json, csv or parquet, defaults to the file extension or jsonl",
+                None,
+            )
+            .named(
+                "id-field",
+                SyntaxShape::String,
+                "the name of the field to add the document id to each document as",
+                None,
+            )
+            .switch(
+                "include-meta",
+                "include the cas and expiry of each document",
+                None,
+            )
+            .named(
+                "meta-field",
+                SyntaxShape::String,
+                "the name of the field to add the cas and expiry to with --include-meta, defaults to meta",
+                None,
+            )
+            .named(
+                "concurrency",
+                SyntaxShape::Int,
+                "the number of partitions to scan at the same time, defaults to 1",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "the name of the bucket",
+                None,
+            )
+            .named("scope", SyntaxShape::String, "the name of the scope", None)
+            .named(
+                "collection",
+                SyntaxShape::String,
+                "the name of the collection",
+                None,
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters which should be contacted",
+                None,
+            )
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Export the documents of a collection to a file"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_export(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description:
+                    "Exports the active collection as JSON lines, keeping the ids in an id field",
+                example: "doc export airlines.jsonl --id-field id",
+                result: None,
+            },
+            Example {
+                description:
+                    "Exports a collection as CSV, with nested fields flattened into columns",
+                example: "doc export routes.csv --collection route",
+                result: None,
+            },
+            Example {
+                description:
+                    "Exports a collection as Parquet, keeping the cas and expiry in a _meta field",
+                example:
+                    "doc export hotels.parquet --collection hotel --include-meta --meta-field _meta",
+                result: None,
+            },
+        ]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum ExportFormat {
+    JsonLines,
+    JsonArray,
+    Csv,
+    Parquet,
+}
+
+impl TryFrom<&str> for ExportFormat {
+    type Error = String;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        match input.to_lowercase().as_str() {
+            "jsonl" | "ndjson" => Ok(Self::JsonLines),
+            "json" => Ok(Self::JsonArray),
+            "csv" => Ok(Self::Csv),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(format!("unknown export format {}", input)),
+        }
+    }
+}
+
+impl ExportFormat {
+    fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| Self::try_from(e).ok())
+            .unwrap_or(Self::JsonLines)
+    }
+}
+
+struct ExportArgs {
+    id_field: Option<String>,
+    meta_field: Option<String>,
+    concurrency: usize,
+    bucket: Option<String>,
+    scope: Option<String>,
+    collection: Option<String>,
+}
+
+fn run_export(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    _input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let filename: String = call.req(engine_state, stack, 0)?;
+    let path = nu_path::expand_path_with(filename, engine_state.cwd(Some(stack))?, true);
+    let format = match call.get_flag::<String>(engine_state, stack, "format")? {
+        Some(f) => ExportFormat::try_from(f.as_str()).map_err(|e| {
+            generic_error(
+                e,
+                "Format must be one of jsonl, json, csv or parquet".to_string(),
+                span,
+            )
+        })?,
+        None => ExportFormat::from_path(&path),
+    };
+
+    let concurrency: Option<i64> = call.get_flag(engine_state, stack, "concurrency")?;
+    if concurrency.map(|c| c < 1).unwrap_or_default() {
+        return Err(generic_error("Concurrency must be at least 1", None, span));
+    }
+    let meta_field = if call.has_flag(engine_state, stack, "include-meta")? {
+        Some(
+            call.get_flag(engine_state, stack, "meta-field")?
+                .unwrap_or_else(|| "meta".to_string()),
+        )
+    } else {
+        None
+    };
+    let args = ExportArgs {
+        id_field: call.get_flag(engine_state, stack, "id-field")?,
+        meta_field,
+        concurrency: concurrency.unwrap_or(1) as usize,
+        bucket: call.get_flag(engine_state, stack, "bucket")?,
+        scope: call.get_flag(engine_state, stack, "scope")?,
+        collection: call.get_flag(engine_state, stack, "collection")?,
+    };
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+    // Each cluster gets its own file when exporting from more than one.
+    let file_per_cluster = cluster_identifiers.len() > 1;
+
+    let guard = state.lock().unwrap();
+
+    let mut results = vec![];
+    for identifier in cluster_identifiers {
+        let path = if file_per_cluster {
+            path_for_cluster(&path, &identifier)
+        } else {
+            path.clone()
+        };
+
+        let writer = ExportWriter::create(&path, format).map_err(|e| {
+            generic_error(
+                format!("Failed to create {}", path.display()),
+                e.to_string(),
+                span,
+            )
+        })?;
+        let mut exporter = Exporter::new(identifier, writer, &args);
+
+        let exported = match export_with_scan(&mut exporter, &args, &guard, ctrl_c.clone(), span) {
+            Ok(false) => export_with_query(&mut exporter, &args, &guard, ctrl_c.clone(), span),
+            exported => exported.map(|_| ()),
+        };
+        if let Err(e) = exported {
+            exporter.fail(e.to_string());
+        }
+        if ctrl_c.load(Ordering::SeqCst) {
+            exporter.fail("Export was interrupted before it completed".to_string());
+        }
+
+        results.push(exporter.finish(&path, span)?);
+    }
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: span,
+    }
+    .into_pipeline_data())
+}
+
+// path_for_cluster adds the cluster identifier to the file name, before the extension.
+fn path_for_cluster(path: &Path, cluster: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, cluster, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, cluster),
+    };
+    path.with_file_name(name)
+}
+
+// export_with_scan reads the documents with range scans, returning false without exporting
+// anything if the cluster does not support them.
+fn export_with_scan(
+    exporter: &mut Exporter,
+    args: &ExportArgs,
+    guard: &MutexGuard<State>,
+    ctrl_c: Arc<AtomicBool>,
+    span: Span,
+) -> Result<bool, ShellError> {
+    let rt = Runtime::new().unwrap();
+    let (active_cluster, client, cid) = get_active_cluster_client_cid(
+        &rt,
+        exporter.cluster.clone(),
+        guard,
+        args.bucket.clone(),
+        args.scope.clone(),
+        args.collection.clone(),
+        ctrl_c.clone(),
+        span,
+    )?;
+
+    let scan = RangeScan::new(ScanType::Range {
+        from: None,
+        to: None,
+    })
+    .concurrency(args.concurrency);
+    let mut items = client.range_scan(scan, cid, active_cluster.timeouts().data_timeout(), ctrl_c);
+
+    while let Some(item) = items.blocking_recv() {
+        let mut item = match item {
+            Ok(item) => item,
+            // Every partition fails the same way, so nothing has been scanned yet.
+            Err(ClientError::RangeScanUnsupported) if exporter.processed() == 0 => {
+                return Ok(false)
+            }
+            Err(e) => {
+                exporter.fail(e.to_string());
+                continue;
+            }
+        };
+
+        let expiry = item.meta().map(|m| m.expiry()).unwrap_or_default();
+        match item.body().map(|body| serde_json::from_slice(&body)) {
+            Some(Ok(content)) => {
+                exporter.document(item.key(), content, item.cas(), expiry, span)?
+            }
+            _ => exporter.fail("Document is not JSON".to_string()),
+        }
+    }
+
+    Ok(true)
+}
+
+fn export_with_query(
+    exporter: &mut Exporter,
+    args: &ExportArgs,
+    guard: &MutexGuard<State>,
+    ctrl_c: Arc<AtomicBool>,
+    span: Span,
+) -> Result<(), ShellError> {
+    let active_cluster = get_active_cluster(exporter.cluster.clone(), guard, span)?;
+    let (bucket, scope, collection) = namespace_from_args(
+        args.bucket.clone(),
+        args.scope.clone(),
+        args.collection.clone(),
+        active_cluster,
+        span,
+    )?;
+    let or_default = |name: String| {
+        if name.is_empty() {
+            "_default".to_string()
+        } else {
+            name
+        }
+    };
+
+    let statement = format!(
+        "SELECT META(d).id AS id, META(d).cas AS cas, META(d).expiration AS expiry, d AS doc FROM `{}`.`{}`.`{}` AS d",
+        bucket,
+        or_default(scope),
+        or_default(collection)
+    );
+
+    let response = send_query_stream(
+        active_cluster,
+        statement,
+        None,
+        None,
+        ctrl_c,
+        None,
+        span,
+        None,
+        QueryOptions::default(),
+    )?;
+    if response.status() != 200 {
+        let response = response
+            .into_response()
+            .map_err(|e| client_error_to_shell_error(e, span))?;
+        return Err(unexpected_status_code_error(
+            response.status(),
+            response.content(),
+            span,
+        ));
+    }
+
+    let mut rows = JsonResultsStream::new(response);
+    for row in rows.by_ref() {
+        let mut row = row.map_err(|e| client_error_to_shell_error(e, span))?;
+        let key = row["id"].as_str().unwrap_or_default().to_string();
+        let cas = row["cas"].as_u64().unwrap_or_default();
+        let expiry = row["expiry"].as_u64().unwrap_or_default() as u32;
+        let content = row.get_mut("doc").map(JsonValue::take).unwrap_or_default();
+        exporter.document(key, content, cas, expiry, span)?;
+    }
+
+    if let Some(errors) = rows.fields().get("errors") {
+        return Err(query_errors(errors, span));
+    }
+
+    Ok(())
+}
+
+// Exporter writes the documents of a single cluster, keeping count of what has been written.
+struct Exporter {
+    cluster: String,
+    writer: ExportWriter,
+    id_field: Option<String>,
+    meta_field: Option<String>,
+    exported: i64,
+    failed: i64,
+    fail_reasons: HashSet<String>,
+    progress: ProgressLine,
+}
+
+impl Exporter {
+    fn new(cluster: String, writer: ExportWriter, args: &ExportArgs) -> Self {
+        Self {
+            cluster,
+            writer,
+            id_field: args.id_field.clone(),
+            meta_field: args.meta_field.clone(),
+            exported: 0,
+            failed: 0,
+            fail_reasons: HashSet::new(),
+            progress: ProgressLine::new(),
+        }
+    }
+
+    fn document(
+        &mut self,
+        key: String,
+        content: JsonValue,
+        cas: u64,
+        expiry: u32,
+        span: Span,
+    ) -> Result<(), ShellError> {
+        let mut content = match content {
+            JsonValue::Object(content) => content,
+            _ => {
+                self.fail("Document is not a JSON object".to_string());
+                return Ok(());
+            }
+        };
+
+        if let Some(field) = &self.id_field {
+            content.insert(field.clone(), JsonValue::String(key));
+        }
+        if let Some(field) = &self.meta_field {
+            if content.contains_key(field) {
+                self.fail(format!(
+                    "Document already has a {} field, name another with --meta-field",
+                    field
+                ));
+                return Ok(());
+            }
+            let mut meta = Map::new();
+            meta.insert("cas".to_string(), cas.into());
+            meta.insert("expiry".to_string(), expiry.into());
+            content.insert(field.clone(), JsonValue::Object(meta));
+        }
+
+        self.writer
+            .write(content)
+            .map_err(|e| generic_error("Failed to write document", e.to_string(), span))?;
+        self.exported += 1;
+        self.progress
+            .update(|| progress_line(&self.cluster, self.exported, self.failed));
+
+        Ok(())
+    }
+
+    fn processed(&self) -> i64 {
+        self.exported + self.failed
+    }
+
+    fn fail(&mut self, reason: String) {
+        self.failed += 1;
+        self.fail_reasons.insert(reason);
+    }
+
+    fn finish(self, path: &Path, span: Span) -> Result<Value, ShellError> {
+        if self.progress.is_live() {
+            self.progress
+                .finish(&progress_line(&self.cluster, self.exported, self.failed));
+        }
+        self.writer
+            .finish()
+            .map_err(|e| generic_error("Failed to write export", e.to_string(), span))?;
+
+        let mut collected = NuValueMap::default();
+        collected.add_i64("processed", self.exported + self.failed, span);
+        collected.add_i64("exported", self.exported, span);
+        collected.add_i64("failed", self.failed, span);
+        collected.add_string(
+            "failures",
+            self.fail_reasons
+                .into_iter()
+                .collect::<Vec<String>>()
+                .join(", "),
+            span,
+        );
+        collected.add_string("file", path.display().to_string(), span);
+        collected.add_string("cluster", self.cluster, span);
+        Ok(collected.into_value(span))
+    }
+}
+
+fn progress_line(cluster: &str, exported: i64, failed: i64) -> String {
+    format!(
+        "{}: exported {} documents, {} failed",
+        cluster, exported, failed
+    )
+}
+
+// ExportWriter writes documents to the file in the chosen format. CSV and Parquet need every
+// column to be known before the header or schema can be written, so the rows are spooled to a
+// temporary file first.
+struct ExportWriter {
+    format: ExportFormat,
+    out: BufWriter<File>,
+    written: u64,
+    spool: Option<(PathBuf, BufWriter<File>)>,
+    columns: Vec<String>,
+    column_types: HashMap<String, ColumnType>,
+}
+
+impl ExportWriter {
+    fn create(path: &Path, format: ExportFormat) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let spool = match format {
+            ExportFormat::Csv | ExportFormat::Parquet => {
+                let spool_path = std::env::temp_dir()
+                    .join(format!("cbsh-export-{}.jsonl", uuid::Uuid::new_v4()));
+                let spool = BufWriter::new(File::create(&spool_path)?);
+                Some((spool_path, spool))
+            }
+            ExportFormat::JsonArray => {
+                out.write_all(b"[")?;
+                None
+            }
+            ExportFormat::JsonLines => None,
+        };
+
+        Ok(Self {
+            format,
+            out,
+            written: 0,
+            spool,
+            columns: vec![],
+            column_types: HashMap::new(),
+        })
+    }
+
+    fn write(&mut self, content: Map<String, JsonValue>) -> io::Result<()> {
+        match self.format {
+            ExportFormat::JsonLines => {
+                serde_json::to_writer(&mut self.out, &content)?;
+                self.out.write_all(b"\n")?;
+            }
+            ExportFormat::JsonArray => {
+                self.out
+                    .write_all(if self.written == 0 { b"\n" } else { b",\n" })?;
+                serde_json::to_writer(&mut self.out, &content)?;
+            }
+            ExportFormat::Csv | ExportFormat::Parquet => {
+                let mut row = Map::new();
+                flatten("", JsonValue::Object(content), &mut row);
+                for (column, value) in &row {
+                    let value_type = ColumnType::of(value);
+                    match self.column_types.get_mut(column) {
+                        Some(column_type) => {
+                            if let Some(value_type) = value_type {
+                                *column_type = column_type.widen(value_type);
+                            }
+                        }
+                        None => {
+                            self.columns.push(column.clone());
+                            self.column_types
+                                .insert(column.clone(), value_type.unwrap_or(ColumnType::String));
+                        }
+                    }
+                }
+                if let Some((_, spool)) = &mut self.spool {
+                    serde_json::to_writer(&mut *spool, &row)?;
+                    spool.write_all(b"\n")?;
+                }
+            }
+        }
+        self.written += 1;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        match self.format {
+            ExportFormat::JsonLines => {}
+            ExportFormat::JsonArray => self.out.write_all(b"\n]\n")?,
+            ExportFormat::Csv | ExportFormat::Parquet => {
+                if let Some((spool_path, spool)) = self.spool.take() {
+                    drop(spool.into_inner()?);
+                    let written = match self.format {
+                        ExportFormat::Parquet => self.write_parquet(&spool_path),
+                        _ => self.write_csv(&spool_path),
+                    };
+                    fs::remove_file(&spool_path)?;
+                    written?;
+                }
+            }
+        }
+
+        self.out.flush()
+    }
+
+    fn write_csv(&mut self, spool_path: &Path) -> io::Result<()> {
+        let header = self
+            .columns
+            .iter()
+            .map(|c| csv_field(c))
+            .collect::<Vec<String>>()
+            .join(",");
+        writeln!(self.out, "{}", header)?;
+
+        for line in BufReader::new(File::open(spool_path)?).lines() {
+            let row: Map<String, JsonValue> = serde_json::from_str(&line?)?;
+            let fields = self
+                .columns
+                .iter()
+                .map(|c| match row.get(c) {
+                    Some(JsonValue::String(s)) => csv_field(s),
+                    Some(JsonValue::Null) | None => String::new(),
+                    Some(v) => csv_field(&v.to_string()),
+                })
+                .collect::<Vec<String>>()
+                .join(",");
+            writeln!(self.out, "{}", fields)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_parquet(&mut self, spool_path: &Path) -> io::Result<()> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| (c.clone(), self.column_types[c]))
+            .collect();
+        let mut writer = ParquetWriter::new(&mut self.out, columns)?;
+
+        let mut rows = Vec::with_capacity(PARQUET_ROW_GROUP_SIZE);
+        for line in BufReader::new(File::open(spool_path)?).lines() {
+            rows.push(serde_json::from_str(&line?)?);
+            if rows.len() == PARQUET_ROW_GROUP_SIZE {
+                writer.write_row_group(&rows)?;
+                rows.clear();
+            }
+        }
+        writer.write_row_group(&rows)?;
+        writer.finish()?;
+
+        Ok(())
+    }
+}
+
+// flatten turns nested objects into columns named by the path to each value, such as
+// address.city. Arrays are kept whole and written as JSON.
+fn flatten(prefix: &str, value: JsonValue, out: &mut Map<String, JsonValue>) {
+    match value {
+        JsonValue::Object(fields) if !fields.is_empty() => {
+            for (name, value) in fields {
+                let path = if prefix.is_empty() {
+                    name
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                flatten(&path, value, out);
+            }
+        }
+        value => {
+            out.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn flattens_nested_fields() {
+        let mut row = Map::new();
+        flatten(
+            "",
+            json!({"name": "a", "address": {"city": "b", "geo": {"lat": 1}}, "tags": [1, 2], "empty": {}}),
+            &mut row,
+        );
+
+        assert_eq!(
+            json!({"name": "a", "address.city": "b", "address.geo.lat": 1, "tags": [1, 2], "empty": {}}),
+            JsonValue::Object(row)
+        );
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!("plain", csv_field("plain"));
+        assert_eq!("\"a,b\"", csv_field("a,b"));
+        assert_eq!("\"say \"\"hi\"\"\"", csv_field("say \"hi\""));
+        assert_eq!("\"two\nlines\"", csv_field("two\nlines"));
+    }
+
+    #[test]
+    fn infers_parquet_column_types() {
+        let path = std::env::temp_dir().join(format!("cbsh-test-{}.parquet", uuid::Uuid::new_v4()));
+        let mut writer = ExportWriter::create(&path, ExportFormat::Parquet).unwrap();
+        for doc in [
+            json!({"id": 1, "geo": {"lat": 1}, "ok": true, "name": null}),
+            json!({"id": 2, "geo": {"lat": 1.5}, "ok": "yes", "name": "a"}),
+        ]
+        .iter()
+        {
+            writer.write(doc.as_object().unwrap().clone()).unwrap();
+        }
+
+        let types = writer
+            .columns
+            .iter()
+            .map(|c| (c.as_str(), writer.column_types[c]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("id", ColumnType::Int64),
+                ("geo.lat", ColumnType::Double),
+                ("ok", ColumnType::String),
+                ("name", ColumnType::String),
+            ],
+            types
+        );
+
+        writer.finish().unwrap();
+        let written = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(b"PAR1", &written[..4]);
+        assert_eq!(b"PAR1", &written[written.len() - 4..]);
+    }
+
+    #[test]
+    fn adds_the_cluster_to_file_names() {
+        assert_eq!(
+            PathBuf::from("/tmp/export-local.csv"),
+            path_for_cluster(Path::new("/tmp/export.csv"), "local")
+        );
+        assert_eq!(
+            PathBuf::from("/tmp/export-local"),
+            path_for_cluster(Path::new("/tmp/export"), "local")
+        );
+    }
+}
@@ -3,24 +3,32 @@
 use super::util::convert_json_value_to_nu_value;
 use crate::state::State;
 
-use crate::cli::doc_common::{build_batched_kv_items, get_active_cluster_client_cid};
+use crate::cli::doc_common::{
+    build_batched_kv_items, cas_from_value, format_from_args, get_active_cluster_client_cid,
+};
+use crate::cli::throughput::Throughput;
 use crate::cli::util::{cluster_identifiers_from, NuValueMap};
-use crate::client::KeyValueRequest;
+use crate::client::{DocumentFormat, DocumentMeta, KeyValueRequest, KvClient, KvResponse};
+use bytes::Bytes;
+use chrono::DateTime;
+use futures::future::select_ok;
 use futures::stream::FuturesUnordered;
-use futures::StreamExt;
+use futures::{Future, FutureExt, StreamExt};
 use log::debug;
-use std::ops::Add;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::runtime::Runtime;
-use tokio::time::Instant;
 
-use crate::cli::error::generic_error;
+use crate::cli::error::{deserialize_error, generic_error};
 use nu_engine::CallExt;
 use nu_protocol::ast::Call;
 use nu_protocol::engine::{Command, EngineState, Stack};
 use nu_protocol::{
-    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, Span, SyntaxShape,
-    Value,
+    Category, Example, IntoPipelineData, ListStream, PipelineData, ShellError, Signature, Span,
+    SyntaxShape, Value,
 };
 
 #[derive(Clone)]
@@ -67,12 +75,46 @@ impl Command for DocGet {
                 "the clusters which should be contacted",
                 None,
             )
+            .switch(
+                "with-meta",
+                "include the expiry, flags, datatype, seqno and deleted state of the documents, and how often each get was retried",
+                None,
+            )
+            .named(
+                "format",
+                SyntaxShape::String,
+                "the format to read the documents as: json, string or binary, defaults to the format recorded in the document flags",
+                None,
+            )
+            .named(
+                "replica",
+                SyntaxShape::String,
+                "read from replicas: any for the first copy to respond, all for every copy or the index of a replica",
+                None,
+            )
             .named(
                 "batch-size",
                 SyntaxShape::Number,
                 "the maximum number of items to batch send at a time",
                 None,
             )
+            .named(
+                "rate-limit",
+                SyntaxShape::Int,
+                "the maximum number of operations to send per second",
+                None,
+            )
+            .named(
+                "max-in-flight",
+                SyntaxShape::Int,
+                "the maximum number of operations waiting on a response at once",
+                None,
+            )
+            .switch(
+                "progress",
+                "report progress while running and include throughput and latencies in the results",
+                None,
+            )
             .switch("halt-on-error", "halt on any errors", Some('e'))
             .category(Category::Custom("couchbase".to_string()))
     }
@@ -103,6 +145,21 @@ impl Command for DocGet {
                 example: "echo [[id]; [airline_10] [airline_11]] | doc get",
                 result: None,
             },
+            Example {
+                description: "Fetches a document along with its metadata, such as expiry",
+                example: "doc get my_doc_id --with-meta",
+                result: None,
+            },
+            Example {
+                description: "Fetches a document from whichever copy responds first",
+                example: "doc get my_doc_id --replica any",
+                result: None,
+            },
+            Example {
+                description: "Fetches every copy of a document",
+                example: "doc get my_doc_id --replica all",
+                result: None,
+            },
         ]
     }
 }
@@ -114,6 +171,54 @@ fn run_get(
     call: &Call,
     input: PipelineData,
 ) -> Result<PipelineData, ShellError> {
+    let id_column: String = call
+        .get_flag(engine_state, stack, "id-column")?
+        .unwrap_or_else(|| "id".to_string());
+    let ids = ids_from_input(input, id_column, call.positional_nth(0))?;
+
+    let replica: Option<String> = call.get_flag(engine_state, stack, "replica")?;
+    let results = match replica {
+        Some(r) => {
+            let mode = ReplicaMode::try_from(r.as_str()).map_err(|e| {
+                generic_error(
+                    e,
+                    "Replica must be any, all or the index of a replica".to_string(),
+                    call.head,
+                )
+            })?;
+            run_replica_get(state, engine_state, stack, call, ids, mode)?
+        }
+        None => {
+            return Ok(
+                run_kv_get_ops(state, engine_state, stack, call, ids, |key| {
+                    KeyValueRequest::Get { key }
+                })?
+                .into_pipeline_data(),
+            )
+        }
+    };
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
+
+// run_kv_get_ops runs an operation which returns the document, such as get or get-and-lock, for
+// each id and produces a row per document. The clusters are connected to upfront, but each batch
+// of ids is only fetched once the rows of the previous one have been read.
+pub(crate) fn run_kv_get_ops<F>(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    ids: Vec<String>,
+    req_builder: F,
+) -> Result<KvGetResults<F>, ShellError>
+where
+    F: Fn(String) -> KeyValueRequest + Send + 'static,
+{
     let span = call.head;
     let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
 
@@ -122,9 +227,7 @@ fn run_get(
     let id_column: String = call
         .get_flag(engine_state, stack, "id-column")?
         .unwrap_or_else(|| "id".to_string());
-    let ids = ids_from_input(input, id_column.clone(), call.positional_nth(0))?;
 
-    let mut workers = FuturesUnordered::new();
     let guard = state.lock().unwrap();
 
     let mut all_ids: Vec<Vec<String>> = vec![];
@@ -136,10 +239,21 @@ fn run_get(
     let scope_flag = call.get_flag(engine_state, stack, "scope")?;
     let collection_flag = call.get_flag(engine_state, stack, "collection")?;
     let halt_on_error = call.has_flag(engine_state, stack, "halt-on-error")?;
+    let with_meta = call.has_flag(engine_state, stack, "with-meta")?;
+    let format = format_from_args(engine_state, stack, call)?;
+    // Fetching the metadata takes a second operation for each document.
+    let ops_per_id = if with_meta { 2 } else { 1 };
+    let throughput = Throughput::from_args(
+        engine_state,
+        stack,
+        call,
+        Some((ids.len() * cluster_identifiers.len() * ops_per_id) as u64),
+    )?;
 
-    let mut results = vec![];
+    let rt = Runtime::new().unwrap();
+    let mut pending = VecDeque::new();
+    let mut clusters = VecDeque::new();
     for identifier in cluster_identifiers {
-        let rt = Runtime::new().unwrap();
         let (active_cluster, client, cid) = match get_active_cluster_client_cid(
             &rt,
             identifier.clone(),
@@ -160,7 +274,7 @@ fn run_get(
                     .id_column(&id_column)
                     .error(e.to_string())
                     .into_value(call.head);
-                results.push(collected);
+                pending.push_back(collected);
                 continue;
             }
         };
@@ -171,32 +285,408 @@ fn run_get(
 
         debug!("Running kv get for docs {:?}", &ids);
 
+        clusters.push_back(ClusterGets {
+            identifier,
+            client,
+            cid,
+            timeout: active_cluster.timeouts().data_timeout(),
+            batches: all_ids.clone().into(),
+        });
+    }
+
+    Ok(KvGetResults {
+        pending,
+        clusters,
+        req_builder,
+        id_column,
+        with_meta,
+        format,
+        halt_on_error,
+        throughput,
+        ctrl_c,
+        rt,
+        span,
+    })
+}
+
+// The ids still to be fetched from a cluster.
+struct ClusterGets {
+    identifier: String,
+    client: Arc<KvClient>,
+    cid: u32,
+    timeout: Duration,
+    batches: VecDeque<Vec<String>>,
+}
+
+// KvGetResults fetches the documents a batch at a time as the pipeline reads them, so that
+// reading only the first few documents does not fetch the rest.
+pub(crate) struct KvGetResults<F> {
+    pending: VecDeque<Value>,
+    clusters: VecDeque<ClusterGets>,
+    req_builder: F,
+    id_column: String,
+    with_meta: bool,
+    format: Option<DocumentFormat>,
+    halt_on_error: bool,
+    throughput: Arc<Throughput>,
+    ctrl_c: Arc<AtomicBool>,
+    rt: Runtime,
+    span: Span,
+}
+
+impl<F> KvGetResults<F>
+where
+    F: Fn(String) -> KeyValueRequest + Send + 'static,
+{
+    // collect_all fetches every document, failing if any row is an error.
+    pub(crate) fn collect_all(self) -> Result<Vec<Value>, ShellError> {
+        self.map(|v| match v {
+            Value::Error { error, .. } => Err(*error),
+            v => Ok(v),
+        })
+        .collect()
+    }
+
+    pub(crate) fn into_pipeline_data(self) -> PipelineData {
+        let span = self.span;
+        let ctrl_c = self.ctrl_c.clone();
+        PipelineData::ListStream(ListStream::new(self, span, Some(ctrl_c)), None)
+    }
+
+    fn get_batch(&self, gets: &ClusterGets, ids: Vec<String>) -> Result<Vec<Value>, ShellError> {
+        let span = self.span;
+        let with_meta = self.with_meta;
+        let halt_on_error = self.halt_on_error;
+        let identifier = &gets.identifier;
+        let id_column = &self.id_column;
+        let cid = gets.cid;
+
+        let mut workers = FuturesUnordered::new();
+        for id in ids {
+            let timeout = gets.timeout;
+
+            let ctrl_c = self.ctrl_c.clone();
+            let meta_request = if with_meta {
+                Some(KeyValueRequest::GetMeta { key: id.clone() })
+            } else {
+                None
+            };
+            let request = (self.req_builder)(id);
+
+            let client = gets.client.clone();
+            let throughput = self.throughput.clone();
+
+            workers.push(async move {
+                let meta = async {
+                    match meta_request {
+                        Some(r) => Some(
+                            throughput
+                                .request(identifier, &client, r, cid, timeout, ctrl_c.clone())
+                                .await,
+                        ),
+                        None => None,
+                    }
+                };
+                futures::join!(
+                    throughput.request(identifier, &client, request, cid, timeout, ctrl_c.clone()),
+                    meta
+                )
+            });
+        }
+
+        self.rt.block_on(async {
+            let mut results = vec![];
+            while let Some((response, meta)) = workers.next().await {
+                // The metadata is still reported when the get fails, as get meta can see
+                // deleted documents.
+                let meta = match meta {
+                    Some(Ok(mut m)) => m.document_meta(),
+                    Some(Err(e)) => {
+                        if halt_on_error {
+                            return Err(generic_error(
+                                "Failed to fetch document metadata",
+                                Some(e.to_string()),
+                                span,
+                            ));
+                        }
+                        None
+                    }
+                    None => None,
+                };
+
+                match response {
+                    Ok(mut res) => {
+                        let mut collected = GetResult::new(identifier)
+                            .id_column(id_column)
+                            .key(res.key())
+                            .cas(res.cas() as i64);
+                        if with_meta {
+                            collected = collected.meta(meta).retries(Some(res.retries()));
+                        }
+
+                        match decode_content(&mut res, self.format, span) {
+                            Ok(c) => {
+                                collected = collected.content(c);
+                            }
+                            Err(e) => {
+                                if halt_on_error {
+                                    return Err(e);
+                                }
+                                collected = collected.error(e.to_string());
+                            }
+                        }
+                        results.push(collected.into_value(span));
+                    }
+                    Err(e) => {
+                        if halt_on_error {
+                            return Err(generic_error(
+                                "Failed to fetch document",
+                                Some(e.to_string()),
+                                span,
+                            ));
+                        }
+
+                        let mut collected = GetResult::new(identifier)
+                            .id_column(id_column)
+                            .key(e.key().unwrap_or_default())
+                            .error(e.to_string());
+                        if with_meta {
+                            collected = collected.meta(meta).retries(None);
+                        }
+                        results.push(collected.into_value(span));
+                    }
+                }
+            }
+            Ok(results)
+        })
+    }
+}
+
+impl<F> Iterator for KvGetResults<F>
+where
+    F: Fn(String) -> KeyValueRequest + Send + 'static,
+{
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.pending.pop_front() {
+                return Some(value);
+            }
+
+            let gets = match self.clusters.front_mut() {
+                Some(gets) => gets,
+                None => {
+                    self.throughput.finish();
+                    return None;
+                }
+            };
+            let ids = match gets.batches.pop_front() {
+                Some(ids) => ids,
+                None => {
+                    self.clusters.pop_front();
+                    continue;
+                }
+            };
+
+            let gets = self.clusters.front()?;
+            match self.get_batch(gets, ids) {
+                Ok(values) => self.pending.extend(values),
+                Err(e) => {
+                    // Nothing more is fetched once halted.
+                    self.clusters.clear();
+                    self.throughput.finish();
+                    return Some(Value::error(e, self.span));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum ReplicaMode {
+    Any,
+    All,
+    Index(u32),
+}
+
+impl TryFrom<&str> for ReplicaMode {
+    type Error = String;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        match input {
+            "any" => Ok(Self::Any),
+            "all" => Ok(Self::All),
+            _ => input
+                .parse::<u32>()
+                .map(Self::Index)
+                .map_err(|_| format!("unknown replica mode {}", input)),
+        }
+    }
+}
+
+// run_replica_get reads documents from their replicas, as well as the active copy for the any and
+// all modes. With any the first successful response for each document is used, with all every
+// copy is returned.
+fn run_replica_get(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    ids: Vec<String>,
+    mode: ReplicaMode,
+) -> Result<Vec<Value>, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+    let batch_size: Option<i64> = call.get_flag(engine_state, stack, "batch-size")?;
+    let id_column: String = call
+        .get_flag(engine_state, stack, "id-column")?
+        .unwrap_or_else(|| "id".to_string());
+
+    let guard = state.lock().unwrap();
+
+    let mut all_ids: Vec<Vec<String>> = vec![];
+    if let Some(size) = batch_size {
+        all_ids = build_batched_kv_items(size as u32, ids.clone());
+    }
+
+    let bucket_flag = call.get_flag(engine_state, stack, "bucket")?;
+    let scope_flag = call.get_flag(engine_state, stack, "scope")?;
+    let collection_flag = call.get_flag(engine_state, stack, "collection")?;
+    let halt_on_error = call.has_flag(engine_state, stack, "halt-on-error")?;
+    let format = format_from_args(engine_state, stack, call)?;
+
+    // The clusters are connected to upfront, so that how many copies are read is known for the
+    // progress.
+    let rt = Runtime::new().unwrap();
+    let mut results = vec![];
+    let mut clusters = vec![];
+    for identifier in cluster_identifiers {
+        let (active_cluster, client, cid) = match get_active_cluster_client_cid(
+            &rt,
+            identifier.clone(),
+            &guard,
+            bucket_flag.clone(),
+            scope_flag.clone(),
+            collection_flag.clone(),
+            ctrl_c.clone(),
+            span,
+        ) {
+            Ok(c) => c,
+            Err(e) => {
+                if halt_on_error {
+                    return Err(e);
+                }
+
+                let collected = GetResult::new(identifier.clone())
+                    .id_column(&id_column)
+                    .error(e.to_string())
+                    .into_value(span);
+                results.push(collected);
+                continue;
+            }
+        };
+
+        if all_ids.is_empty() {
+            all_ids = build_batched_kv_items(active_cluster.kv_batch_size(), ids.clone());
+        }
+
+        // None represents the active copy of the document.
+        let copies: Vec<Option<u32>> = match mode {
+            ReplicaMode::Index(i) => vec![Some(i)],
+            ReplicaMode::Any | ReplicaMode::All => std::iter::once(None)
+                .chain((0..client.num_replicas()).map(Some))
+                .collect(),
+        };
+
+        clusters.push((
+            identifier,
+            client,
+            cid,
+            active_cluster.timeouts().data_timeout(),
+            copies,
+        ));
+    }
+
+    let throughput = Throughput::from_args(
+        engine_state,
+        stack,
+        call,
+        Some(
+            clusters
+                .iter()
+                .map(|(.., copies)| (ids.len() * copies.len()) as u64)
+                .sum(),
+        ),
+    )?;
+
+    for (identifier, client, cid, timeout, copies) in clusters {
+        debug!("Running kv replica get for docs {:?}", &ids);
+
         for ids in all_ids.clone() {
+            let mut workers = FuturesUnordered::new();
             for id in ids {
-                let deadline = Instant::now().add(active_cluster.timeouts().data_timeout());
-
-                let ctrl_c = ctrl_c.clone();
-                let id = id.clone();
+                let read_copy = {
+                    let id = id.clone();
+                    let identifier = identifier.clone();
+                    let ctrl_c = ctrl_c.clone();
+                    let client = client.clone();
+                    let throughput = throughput.clone();
+                    move |copy: Option<u32>| {
+                        let identifier = identifier.clone();
+                        let ctrl_c = ctrl_c.clone();
+                        let client = client.clone();
+                        let throughput = throughput.clone();
+                        let request = match copy {
+                            Some(replica_index) => KeyValueRequest::GetReplica {
+                                key: id.clone(),
+                                replica_index,
+                            },
+                            None => KeyValueRequest::Get { key: id.clone() },
+                        };
+                        async move {
+                            throughput
+                                .request(&identifier, &client, request, cid, timeout, ctrl_c)
+                                .await
+                        }
+                    }
+                };
 
-                let client = client.clone();
+                if matches!(mode, ReplicaMode::Any) {
+                    let read = read_any_copy(copies.clone(), read_copy);
+                    let id = id.clone();
+                    workers.push(
+                        async move {
+                            match read.await {
+                                Ok((copy, res)) => (id, copy, Ok(res)),
+                                Err(e) => (id, None, Err(e)),
+                            }
+                        }
+                        .boxed_local(),
+                    );
+                    continue;
+                }
 
-                workers.push(async move {
-                    client
-                        .request(KeyValueRequest::Get { key: id }, cid, deadline, ctrl_c)
-                        .await
-                });
+                for copy in copies.clone() {
+                    let read = read_copy(copy);
+                    let id = id.clone();
+                    workers.push(async move { (id, copy, read.await) }.boxed_local());
+                }
             }
-            rt.block_on(async {
-                while let Some(response) = workers.next().await {
+
+            let collected = rt.block_on(async {
+                while let Some((id, copy, response)) = workers.next().await {
                     match response {
                         Ok(mut res) => {
                             let mut collected = GetResult::new(&identifier)
                                 .id_column(&id_column)
-                                .key(res.key())
-                                .cas(res.cas() as i64);
+                                .key(id.clone())
+                                .cas(res.cas() as i64)
+                                .replica(copy);
 
-                            let content = res.content().unwrap_or_default();
-                            match convert_json_value_to_nu_value(&content, call.head) {
+                            match decode_content(&mut res, format, span) {
                                 Ok(c) => {
                                     collected = collected.content(c);
                                 }
@@ -207,36 +697,60 @@ fn run_get(
                                     collected = collected.error(e.to_string());
                                 }
                             }
-                            results.push(collected.into_value(call.head));
+                            results.push(collected.into_value(span));
                         }
                         Err(e) => {
                             if halt_on_error {
                                 return Err(generic_error(
                                     "Failed to fetch document",
                                     Some(e.to_string()),
-                                    call.head,
+                                    span,
                                 ));
                             }
 
-                            let collected = GetResult::new(&identifier)
+                            let mut collected = GetResult::new(&identifier)
                                 .id_column(&id_column)
-                                .key(e.key().unwrap_or_default())
-                                .error(e.to_string())
-                                .into_value(call.head);
-                            results.push(collected);
+                                .key(id)
+                                .error(e.to_string());
+                            // With any mode the error is only reported once no copy could be
+                            // read, so it does not belong to a single copy.
+                            if !matches!(mode, ReplicaMode::Any) {
+                                collected = collected.replica(copy);
+                            }
+                            results.push(collected.into_value(span));
                         }
                     }
                 }
                 Ok(())
-            })?;
+            });
+            if let Err(e) = collected {
+                throughput.finish();
+                return Err(e);
+            }
         }
     }
 
-    Ok(Value::List {
-        vals: results,
-        internal_span: call.head,
-    }
-    .into_pipeline_data())
+    throughput.finish();
+    Ok(results)
+}
+
+// read_any_copy reads all of the copies of a document concurrently and resolves with the first
+// successful response, dropping the outstanding reads. If every read fails the last error is
+// returned.
+async fn read_any_copy<F, Fut, T, E>(
+    copies: Vec<Option<u32>>,
+    read_copy: F,
+) -> Result<(Option<u32>, T), E>
+where
+    F: Fn(Option<u32>) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let reads = copies.into_iter().map(|copy| {
+        let read = read_copy(copy);
+        Box::pin(async move { read.await.map(|res| (copy, res)) })
+    });
+
+    select_ok(reads).await.map(|(res, _)| res)
 }
 
 pub(crate) fn ids_from_input(
@@ -272,6 +786,43 @@ pub(crate) fn ids_from_input(
     Ok(ids)
 }
 
+// ids_with_cas_from_input behaves like ids_from_input but also picks up the cas column from
+// records, such as those produced by doc get, falling back to the provided cas.
+pub(crate) fn ids_with_cas_from_input(
+    input: PipelineData,
+    id_column: String,
+    id: Option<&nu_protocol::ast::Expression>,
+    cas: u64,
+) -> Result<Vec<(String, u64)>, ShellError> {
+    let mut ids: Vec<(String, u64)> = input
+        .into_iter()
+        .filter_map(move |v| match v {
+            Value::String { val, .. } => Some((val, cas)),
+            Value::Int { val, .. } => Some((val.to_string(), cas)),
+            Value::Record { val, .. } => {
+                let row_cas = val.get("cas").and_then(cas_from_value).unwrap_or(cas);
+                if let Some(d) = val.get(id_column.clone()) {
+                    match d {
+                        Value::String { val, .. } => Some((val.clone(), row_cas)),
+                        _ => None,
+                    }
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        })
+        .collect();
+
+    if let Some(id) = id {
+        if let Some(i) = id.as_string() {
+            ids.push((i, cas));
+        }
+    }
+
+    Ok(ids)
+}
+
 #[derive(Debug)]
 pub(crate) struct GetResult {
     error: Option<String>,
@@ -280,6 +831,10 @@ pub(crate) struct GetResult {
     cluster: String,
     cas: Option<i64>,
     id_column: Option<String>,
+    replica: Option<Option<u32>>,
+    xattrs: Option<Value>,
+    meta: Option<Option<DocumentMeta>>,
+    retries: Option<Option<u32>>,
 }
 
 impl GetResult {
@@ -291,9 +846,35 @@ impl GetResult {
             cluster: cluster.into(),
             cas: None,
             id_column: None,
+            replica: None,
+            xattrs: None,
+            meta: None,
+            retries: None,
         }
     }
 
+    pub fn meta(mut self, meta: Option<DocumentMeta>) -> GetResult {
+        self.meta = Some(meta);
+        self
+    }
+
+    // retries records how often the get was retried, which is unknown if it failed.
+    pub fn retries(mut self, retries: Option<u32>) -> GetResult {
+        self.retries = Some(retries);
+        self
+    }
+
+    pub fn xattrs(mut self, xattrs: Value) -> GetResult {
+        self.xattrs = Some(xattrs);
+        self
+    }
+
+    // replica records which copy of the document was read, None being the active copy.
+    pub fn replica(mut self, replica_index: Option<u32>) -> GetResult {
+        self.replica = Some(replica_index);
+        self
+    }
+
     pub fn id_column(mut self, id_column: impl Into<String>) -> Self {
         self.id_column = Some(id_column.into());
         self
@@ -327,9 +908,175 @@ impl GetResult {
             span,
         );
         collected.add("content", self.content.unwrap_or_default());
+        if let Some(xattrs) = self.xattrs {
+            collected.add("xattrs", xattrs);
+        }
         collected.add_i64("cas", self.cas.unwrap_or_default(), span);
         collected.add_string("error", self.error.unwrap_or_default(), span);
+        if let Some(meta) = self.meta {
+            collected.add("meta", meta_into_value(meta, span));
+        }
+        if let Some(retries) = self.retries {
+            collected.add(
+                "retries",
+                match retries {
+                    Some(r) => Value::Int {
+                        val: r as i64,
+                        internal_span: span,
+                    },
+                    None => Value::Nothing {
+                        internal_span: span,
+                    },
+                },
+            );
+        }
+        if let Some(replica) = self.replica {
+            collected.add_bool("is_replica", replica.is_some(), span);
+            collected.add(
+                "replica_index",
+                match replica {
+                    Some(i) => Value::Int {
+                        val: i as i64,
+                        internal_span: span,
+                    },
+                    None => Value::Nothing {
+                        internal_span: span,
+                    },
+                },
+            );
+        }
         collected.add_string("cluster", self.cluster, span);
         collected.into_value(span)
     }
 }
+
+// decode_content converts the document into a value according to the format, which defaults to
+// the format recorded in the flags of the document.
+fn decode_content(
+    res: &mut KvResponse,
+    format: Option<DocumentFormat>,
+    span: Span,
+) -> Result<Value, ShellError> {
+    decode_body(res.body(), res.flags(), format, span)
+}
+
+pub(crate) fn decode_body(
+    body: Option<Bytes>,
+    flags: u32,
+    format: Option<DocumentFormat>,
+    span: Span,
+) -> Result<Value, ShellError> {
+    let format = format.unwrap_or_else(|| DocumentFormat::from_flags(flags));
+    let body = match body {
+        Some(b) => b,
+        None => {
+            return Ok(Value::Nothing {
+                internal_span: span,
+            })
+        }
+    };
+
+    match format {
+        DocumentFormat::Json => {
+            let content: serde_json::Value = serde_json::from_slice(body.as_ref())
+                .map_err(|e| deserialize_error(e.to_string(), span))?;
+            convert_json_value_to_nu_value(&content, span)
+        }
+        DocumentFormat::String => match String::from_utf8(body.to_vec()) {
+            Ok(val) => Ok(Value::String {
+                val,
+                internal_span: span,
+            }),
+            Err(e) => Err(deserialize_error(e.to_string(), span)),
+        },
+        DocumentFormat::Binary => Ok(Value::Binary {
+            val: body.to_vec(),
+            internal_span: span,
+        }),
+    }
+}
+
+fn meta_into_value(meta: Option<DocumentMeta>, span: Span) -> Value {
+    let meta = match meta {
+        Some(m) => m,
+        None => {
+            return Value::Nothing {
+                internal_span: span,
+            }
+        }
+    };
+
+    let mut collected = NuValueMap::default();
+    let expiry = if meta.expiry() == 0 {
+        None
+    } else {
+        DateTime::from_timestamp(meta.expiry() as i64, 0)
+    };
+    collected.add(
+        "expiry",
+        match expiry {
+            Some(e) => Value::Date {
+                val: e.fixed_offset(),
+                internal_span: span,
+            },
+            None => Value::Nothing {
+                internal_span: span,
+            },
+        },
+    );
+    collected.add_i64("flags", meta.flags() as i64, span);
+    collected.add(
+        "datatype",
+        Value::List {
+            vals: meta
+                .datatype()
+                .into_iter()
+                .map(|d| Value::String {
+                    val: d.to_string(),
+                    internal_span: span,
+                })
+                .collect(),
+            internal_span: span,
+        },
+    );
+    collected.add_i64("seqno", meta.seqno() as i64, span);
+    collected.add_bool("deleted", meta.deleted(), span);
+    collected.into_value(span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::{pending, ready, Either};
+
+    #[tokio::test]
+    async fn any_replica_returns_first_successful_copy() {
+        // The replicas never respond, so this only completes if the outstanding reads are
+        // not waited for.
+        let read = read_any_copy(vec![None, Some(0), Some(1)], |copy| match copy {
+            None => Either::Left(ready(Ok::<_, String>("active"))),
+            Some(_) => Either::Right(pending()),
+        });
+        let res = tokio::time::timeout(Duration::from_secs(5), read)
+            .await
+            .expect("any should not wait for every copy");
+
+        assert_eq!(Ok((None, "active")), res);
+    }
+
+    #[tokio::test]
+    async fn any_replica_falls_back_to_a_replica_and_reports_errors() {
+        let res = read_any_copy(vec![None, Some(0)], |copy| match copy {
+            None => ready(Err("active unavailable".to_string())),
+            Some(_) => ready(Ok("replica")),
+        })
+        .await;
+        assert_eq!(Ok((Some(0), "replica")), res);
+
+        let res = read_any_copy(vec![None, Some(0)], |_| {
+            ready(Err::<(), _>("unavailable".to_string()))
+        })
+        .await;
+        assert_eq!(Err("unavailable".to_string()), res);
+    }
+}
@@ -0,0 +1,139 @@
+//! The `doc get-and-lock` command performs a KV get and lock operation.
+
+use crate::cli::doc_get::{ids_from_input, run_kv_get_ops};
+use crate::client::KeyValueRequest;
+use crate::state::State;
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct DocGetAndLock {
+    state: Arc<Mutex<State>>,
+}
+
+impl DocGetAndLock {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for DocGetAndLock {
+    fn name(&self) -> &str {
+        "doc get-and-lock"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("doc get-and-lock")
+            .optional("id", SyntaxShape::String, "the document id")
+            .named(
+                "lock-time",
+                SyntaxShape::Number,
+                "the time to lock the documents for in seconds, the server default is used if not set",
+                None,
+            )
+            .named(
+                "id-column",
+                SyntaxShape::String,
+                "the name of the id column if used with an input stream",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "the name of the bucket",
+                None,
+            )
+            .named("scope", SyntaxShape::String, "the name of the scope", None)
+            .named(
+                "collection",
+                SyntaxShape::String,
+                "the name of the collection",
+                None,
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters which should be contacted",
+                None,
+            )
+            .named(
+                "batch-size",
+                SyntaxShape::Number,
+                "the maximum number of items to batch send at a time",
+                None,
+            )
+            .switch("halt-on-error", "halt on any errors", Some('e'))
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Fetches and locks a document through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_get_and_lock(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Fetches and locks a document for 10 seconds",
+                example: "doc get-and-lock my_doc_id --lock-time 10",
+                result: None,
+            },
+            Example {
+                description:
+                    "Locks a document, replaces it using the lock cas and so releases the lock",
+                example: "doc get-and-lock my_doc_id | update content.name bob | doc replace",
+                result: None,
+            },
+            Example {
+                description: "Locks a document and then unlocks it",
+                example: "doc get-and-lock my_doc_id | doc unlock",
+                result: None,
+            },
+        ]
+    }
+}
+
+fn run_get_and_lock(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let id_column: String = call
+        .get_flag(engine_state, stack, "id-column")?
+        .unwrap_or_else(|| "id".to_string());
+    let lock_time: i64 = call
+        .get_flag(engine_state, stack, "lock-time")?
+        .unwrap_or(0);
+    let ids = ids_from_input(input, id_column, call.positional_nth(0))?;
+
+    // The documents are locked upfront, whether or not the rows end up being read.
+    let results = run_kv_get_ops(state, engine_state, stack, call, ids, move |key| {
+        KeyValueRequest::GetAndLock {
+            key,
+            lock_time: lock_time as u32,
+        }
+    })?
+    .collect_all()?;
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
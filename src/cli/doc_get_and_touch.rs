@@ -0,0 +1,131 @@
+//! The `doc get-and-touch` command performs a KV get and touch operation.
+
+use crate::cli::doc_get::{ids_from_input, run_kv_get_ops};
+use crate::client::KeyValueRequest;
+use crate::state::State;
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct DocGetAndTouch {
+    state: Arc<Mutex<State>>,
+}
+
+impl DocGetAndTouch {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for DocGetAndTouch {
+    fn name(&self) -> &str {
+        "doc get-and-touch"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("doc get-and-touch")
+            .optional("id", SyntaxShape::String, "the document id")
+            .named(
+                "expiry",
+                SyntaxShape::Number,
+                "the new expiry for the documents in seconds, or absolute",
+                None,
+            )
+            .named(
+                "id-column",
+                SyntaxShape::String,
+                "the name of the id column if used with an input stream",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "the name of the bucket",
+                None,
+            )
+            .named("scope", SyntaxShape::String, "the name of the scope", None)
+            .named(
+                "collection",
+                SyntaxShape::String,
+                "the name of the collection",
+                None,
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters which should be contacted",
+                None,
+            )
+            .named(
+                "batch-size",
+                SyntaxShape::Number,
+                "the maximum number of items to batch send at a time",
+                None,
+            )
+            .switch("halt-on-error", "halt on any errors", Some('e'))
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Fetches a document and updates its expiry through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_get_and_touch(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Fetches a document and sets it to expire in 60 seconds",
+                example: "doc get-and-touch my_doc_id --expiry 60",
+                result: None,
+            },
+            Example {
+                description: "Fetches multiple documents with IDs from the previous command and removes their expiry",
+                example: "echo [[id]; [airline_10] [airline_11]] | doc get-and-touch",
+                result: None,
+            },
+        ]
+    }
+}
+
+fn run_get_and_touch(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let id_column: String = call
+        .get_flag(engine_state, stack, "id-column")?
+        .unwrap_or_else(|| "id".to_string());
+    let expiry: i64 = call.get_flag(engine_state, stack, "expiry")?.unwrap_or(0);
+    let ids = ids_from_input(input, id_column, call.positional_nth(0))?;
+
+    // The expiry must be updated even when nothing reads the rows, so they are all fetched here.
+    let results = run_kv_get_ops(state, engine_state, stack, call, ids, move |key| {
+        KeyValueRequest::GetAndTouch {
+            key,
+            expiry: expiry as u32,
+        }
+    })?
+    .collect_all()?;
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
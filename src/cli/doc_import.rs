@@ -1,16 +1,33 @@
-use crate::cli::doc_common::{id_from_value, run_kv_mutations};
-use crate::cli::error::serialize_error;
-use crate::cli::util::convert_nu_value_to_json_value;
-use crate::client::KeyValueRequest;
+//! The `doc import` command writes the rows of a file to documents through the data service.
+
+use crate::cli::doc_common::{durability_from_args, get_active_cluster_client_cid, MutationResult};
+use crate::cli::error::{generic_error, serialize_error};
+use crate::cli::throughput::Throughput;
+use crate::cli::util::{
+    cluster_identifiers_from, convert_nu_value_to_json_value, namespace_from_args,
+};
+use crate::client::{DocumentFormat, Durability, KeyValueRequest, KvClient, MutationState};
 use crate::state::State;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
 use nu_command::Open;
 use nu_engine::CallExt;
 use nu_protocol::ast::Call;
 use nu_protocol::engine::{Command, EngineState, Stack};
 use nu_protocol::{
-    Category, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, Span, SyntaxShape,
+    Value,
 };
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::runtime::Runtime;
 
 #[derive(Clone)]
 pub struct DocImport {
@@ -66,12 +83,64 @@ impl Command for DocImport {
                 "the clusters which should be contacted",
                 None,
             )
+            .named(
+                "durability",
+                SyntaxShape::String,
+                "the durability level required for the mutation: none, majority, majority-and-persist-active or persist-to-majority",
+                None,
+            )
+            .named(
+                "durability-timeout",
+                SyntaxShape::Int,
+                "the timeout for meeting the durability level (in ms)",
+                None,
+            )
             .named(
                 "batch-size",
                 SyntaxShape::Number,
                 "the maximum number of items to batch send at a time",
                 None,
             )
+            .named(
+                "mode",
+                SyntaxShape::String,
+                "how the documents are written: insert, upsert or replace, defaults to upsert",
+                None,
+            )
+            .named(
+                "id-template",
+                SyntaxShape::String,
+                "build the document ids from fields of each row, such as user::%id%",
+                None,
+            )
+            .switch(
+                "resume",
+                "continue an interrupted import from its last checkpoint",
+                None,
+            )
+            .named(
+                "errors-file",
+                SyntaxShape::String,
+                "the path of a file to write the rows which failed to import to",
+                None,
+            )
+            .named(
+                "rate-limit",
+                SyntaxShape::Int,
+                "the maximum number of operations to send per second",
+                None,
+            )
+            .named(
+                "max-in-flight",
+                SyntaxShape::Int,
+                "the maximum number of operations waiting on a response at once",
+                None,
+            )
+            .switch(
+                "progress",
+                "report progress while running and include throughput and latencies in the results",
+                None,
+            )
             .category(Category::Custom("couchbase".to_string()))
     }
 
@@ -79,6 +148,10 @@ impl Command for DocImport {
         "Import documents from a file through the data service"
     }
 
+    fn extra_usage(&self) -> &str {
+        "JSON lines files (.jsonl or .ndjson) are read a line at a time, files in other formats are loaded into memory as a whole. Progress is recorded in a checkpoint file alongside the imported file as each batch completes, so that an interrupted import can be continued with --resume. The checkpoint is removed once the import completes, and is not used if the imported file has changed since."
+    }
+
     fn run(
         &self,
         engine_state: &EngineState,
@@ -88,10 +161,99 @@ impl Command for DocImport {
     ) -> Result<PipelineData, ShellError> {
         run_import(self.state.clone(), engine_state, stack, call, input)
     }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Imports the documents in a file, using the id field of each as its id",
+                example: "doc import users.json",
+                result: None,
+            },
+            Example {
+                description: "Inserts users with ids built from their id field, recording any failures",
+                example: "doc import users.jsonl --mode insert --id-template user::%id% --errors-file failed.jsonl",
+                result: None,
+            },
+            Example {
+                description: "Continues an import which was interrupted",
+                example: "doc import users.jsonl --resume",
+                result: None,
+            },
+        ]
+    }
 }
 
-fn build_req(key: String, value: Vec<u8>, expiry: u32) -> KeyValueRequest {
-    KeyValueRequest::Set { key, value, expiry }
+#[derive(Debug, Copy, Clone)]
+enum ImportMode {
+    Insert,
+    Upsert,
+    Replace,
+}
+
+impl TryFrom<&str> for ImportMode {
+    type Error = String;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        match input {
+            "insert" => Ok(Self::Insert),
+            "upsert" => Ok(Self::Upsert),
+            "replace" => Ok(Self::Replace),
+            _ => Err(format!("unknown import mode {}", input)),
+        }
+    }
+}
+
+impl ImportMode {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Insert => "insert",
+            Self::Upsert => "upsert",
+            Self::Replace => "replace",
+        }
+    }
+
+    fn request(
+        &self,
+        key: String,
+        value: Vec<u8>,
+        expiry: u32,
+        durability: Durability,
+    ) -> KeyValueRequest {
+        let flags = DocumentFormat::Json.flags();
+        match self {
+            Self::Insert => KeyValueRequest::Insert {
+                key,
+                value,
+                flags,
+                expiry,
+                durability,
+            },
+            Self::Upsert => KeyValueRequest::Set {
+                key,
+                value,
+                flags,
+                expiry,
+                durability,
+            },
+            Self::Replace => KeyValueRequest::Replace {
+                key,
+                value,
+                flags,
+                expiry,
+                cas: 0,
+                durability,
+            },
+        }
+    }
+}
+
+type Row = Result<Map<String, JsonValue>, String>;
+
+struct ImportItem {
+    row: usize,
+    key: String,
+    value: Vec<u8>,
+    content: Map<String, JsonValue>,
 }
 
 fn run_import(
@@ -102,46 +264,235 @@ fn run_import(
     input: PipelineData,
 ) -> Result<PipelineData, ShellError> {
     let span = call.head;
-    let open = Open;
-    let data = open.run(engine_state, stack, call, input)?;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let filename: String = call.req(engine_state, stack, 0)?;
+    let cwd = engine_state.cwd(Some(stack))?;
+    let path = nu_path::expand_path_with(filename, &cwd, true);
 
     let id_column = call
         .get_flag(engine_state, stack, "id-column")?
         .unwrap_or_else(|| String::from("id"));
+    let id_template: Option<String> = call.get_flag(engine_state, stack, "id-template")?;
+    if let Some(template) = &id_template {
+        if !template.contains('%') || template.matches('%').count() % 2 != 0 {
+            return Err(generic_error(
+                "Invalid id template",
+                "The template must name fields between % signs, such as user::%id%".to_string(),
+                span,
+            ));
+        }
+    }
+    let mode = match call.get_flag::<String>(engine_state, stack, "mode")? {
+        Some(m) => ImportMode::try_from(m.as_str()).map_err(|e| {
+            generic_error(
+                e,
+                "Mode must be one of insert, upsert or replace".to_string(),
+                span,
+            )
+        })?,
+        None => ImportMode::Upsert,
+    };
+    let resume = call.has_flag(engine_state, stack, "resume")?;
+    let errors_path = call
+        .get_flag::<String>(engine_state, stack, "errors-file")?
+        .map(|p| nu_path::expand_path_with(p, &cwd, true));
 
-    let filtered = data
-        .into_iter()
-        .filter_map(move |i| {
-            let id_column = id_column.clone();
-
-            if let Value::Record { val, .. } = i {
-                let mut id = None;
-                let mut content = serde_json::Map::new();
-                for (k, v) in val.iter() {
-                    if k.clone() == id_column {
-                        id = id_from_value(v, span);
+    let expiry: i64 = call.get_flag(engine_state, stack, "expiry")?.unwrap_or(0);
+    let batch_size: Option<i64> = call.get_flag(engine_state, stack, "batch-size")?;
+    let durability = durability_from_args(engine_state, stack, call)?;
+    let bucket_flag = call.get_flag(engine_state, stack, "bucket")?;
+    let scope_flag = call.get_flag(engine_state, stack, "scope")?;
+    let collection_flag = call.get_flag(engine_state, stack, "collection")?;
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+
+    let rows = read_rows(&path, engine_state, stack, call, input)?;
+    // The number of rows is not known up front, so progress is reported without an ETA.
+    let throughput = Throughput::from_args(engine_state, stack, call, None)?;
+
+    let mut errors = match errors_path {
+        Some(p) => Some(ErrorsFile::open(&p, resume).map_err(|e| {
+            generic_error(
+                format!("Failed to open {}", p.display()),
+                e.to_string(),
+                span,
+            )
+        })?),
+        None => None,
+    };
+
+    let guard = state.lock().unwrap();
+    let rt = Runtime::new().unwrap();
+
+    let mut results = vec![];
+    let mut targets = vec![];
+    let mut namespaces = vec![];
+    for identifier in cluster_identifiers {
+        match get_active_cluster_client_cid(
+            &rt,
+            identifier.clone(),
+            &guard,
+            bucket_flag.clone(),
+            scope_flag.clone(),
+            collection_flag.clone(),
+            ctrl_c.clone(),
+            span,
+        ) {
+            Ok((active_cluster, client, cid)) => {
+                let (bucket, scope, collection) = namespace_from_args(
+                    bucket_flag.clone(),
+                    scope_flag.clone(),
+                    collection_flag.clone(),
+                    active_cluster,
+                    span,
+                )?;
+                namespaces.push(json!({
+                    "cluster": identifier,
+                    "bucket": bucket,
+                    "scope": scope,
+                    "collection": collection,
+                }));
+                targets.push(ImportTarget::new(
+                    identifier,
+                    client,
+                    cid,
+                    active_cluster.timeouts().data_timeout(),
+                    active_cluster.kv_batch_size(),
+                    throughput.clone(),
+                ))
+            }
+            Err(e) => {
+                let mut failures = HashSet::new();
+                failures.insert(e.to_string());
+                results.push(
+                    MutationResult::new(identifier)
+                        .fail_reasons(failures)
+                        .into_value(span),
+                );
+            }
+        }
+    }
+    drop(guard);
+
+    let batch_size = batch_size
+        .map(|s| s as usize)
+        .or_else(|| targets.first().map(|t| t.batch_size as usize))
+        .unwrap_or(1)
+        .max(1);
+
+    if targets.is_empty() {
+        return Ok(Value::List {
+            vals: results,
+            internal_span: call.head,
+        }
+        .into_pipeline_data());
+    }
+
+    // The checkpoint is only valid for the same rows going to the same documents.
+    let checkpoint = Checkpoint::for_import(
+        &path,
+        json!({
+            "targets": namespaces,
+            "mode": mode.name(),
+            "id_column": id_column,
+            "id_template": id_template,
+        }),
+    )
+    .map_err(|e| generic_error("Failed to read the imported file", e.to_string(), span))?;
+    let offset = if resume {
+        checkpoint.load().map_err(|e| {
+            generic_error("Failed to read the import checkpoint", e.to_string(), span)
+        })?
+    } else {
+        0
+    };
+
+    let mut rows = rows.enumerate().skip(offset);
+    let mut committed = offset;
+    loop {
+        let batch: Vec<(usize, Row)> = rows.by_ref().take(batch_size).collect();
+        if batch.is_empty() {
+            break;
+        }
+        let batch_len = batch.len();
+
+        let mut failures = vec![];
+        let mut items = vec![];
+        for (row, content) in batch {
+            let failure = match content {
+                Ok(content) => match key_for(&content, &id_column, id_template.as_deref()) {
+                    Some(key) => {
+                        let value = serde_json::to_vec(&content)
+                            .map_err(|e| serialize_error(e.to_string(), span))?;
+                        items.push(ImportItem {
+                            row,
+                            key,
+                            value,
+                            content,
+                        });
+                        continue;
                     }
+                    None => ("Missing doc id".to_string(), Some(content)),
+                },
+                Err(e) => (e, None),
+            };
 
-                    content.insert(k.clone(), convert_nu_value_to_json_value(v, span).ok()?);
-                }
-                return Some((id.unwrap_or("".into()), content));
+            // Rows which cannot be imported fail for every cluster.
+            for target in &mut targets {
+                target.fail(failure.0.clone());
             }
-            None
-        })
-        .collect::<Vec<(
-            String,
-            serde_json::Map<std::string::String, serde_json::Value>,
-        )>>();
+            failures.push((row, None, None, failure.0, failure.1));
+        }
+
+        for target in &mut targets {
+            for (i, error) in target.import(&rt, &items, mode, expiry as u32, durability, &ctrl_c) {
+                let item = &items[i];
+                failures.push((
+                    item.row,
+                    Some(item.key.clone()),
+                    Some(target.identifier.clone()),
+                    error,
+                    Some(item.content.clone()),
+                ));
+            }
+        }
+
+        // An interrupted batch is left uncommitted, so that resuming runs it again.
+        if ctrl_c.load(Ordering::SeqCst) {
+            break;
+        }
+
+        if let Some(errors) = &mut errors {
+            errors
+                .write(failures)
+                .map_err(|e| generic_error("Failed to write failed rows", e.to_string(), span))?;
+        }
+        committed += batch_len;
+        checkpoint.save(committed).map_err(|e| {
+            generic_error("Failed to save the import checkpoint", e.to_string(), span)
+        })?;
+    }
+
+    if !ctrl_c.load(Ordering::SeqCst) {
+        checkpoint.remove().map_err(|e| {
+            generic_error(
+                "Failed to remove the import checkpoint",
+                e.to_string(),
+                span,
+            )
+        })?;
+    }
 
-    let mut all_items = vec![];
-    for item in filtered {
-        let value =
-            serde_json::to_vec(&item.1).map_err(|e| serialize_error(e.to_string(), span))?;
+    throughput.finish();
 
-        all_items.push((item.0, value));
+    let guard = state.lock().unwrap();
+    for target in &mut targets {
+        guard.record_mutations(&target.identifier, std::mem::take(&mut target.mutations));
     }
+    drop(guard);
 
-    let results = run_kv_mutations(state, engine_state, stack, call, span, all_items, build_req)?;
+    results.extend(targets.into_iter().map(|t| t.into_value(span)));
 
     Ok(Value::List {
         vals: results,
@@ -149,3 +500,369 @@ fn run_import(
     }
     .into_pipeline_data())
 }
+
+// read_rows reads the rows of the file as they are needed. JSON lines files are read a line at a
+// time, other formats are read through open, which loads the whole file.
+fn read_rows(
+    path: &Path,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+) -> Result<Box<dyn Iterator<Item = Row>>, ShellError> {
+    let span = call.head;
+    let json_lines = path
+        .extension()
+        .map(|e| e == "jsonl" || e == "ndjson")
+        .unwrap_or_default();
+
+    if json_lines {
+        let file = File::open(path).map_err(|e| {
+            generic_error(
+                format!("Failed to open {}", path.display()),
+                e.to_string(),
+                span,
+            )
+        })?;
+
+        let rows = BufReader::new(file)
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| {
+                let line = line.map_err(|e| e.to_string())?;
+                match serde_json::from_str(&line) {
+                    Ok(JsonValue::Object(content)) => Ok(content),
+                    Ok(_) => Err("Row is not an object".to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            });
+        return Ok(Box::new(rows));
+    }
+
+    let data = Open.run(engine_state, stack, call, input)?;
+    let rows = data
+        .into_iter()
+        .map(move |v| match convert_nu_value_to_json_value(&v, span) {
+            Ok(JsonValue::Object(content)) => Ok(content),
+            Ok(_) => Err("Row is not a record".to_string()),
+            Err(e) => Err(e.to_string()),
+        });
+    Ok(Box::new(rows))
+}
+
+// key_for builds the id of the document for a row, either from the id column or by replacing
+// each %field% in the id template with the value of that field.
+fn key_for(
+    content: &Map<String, JsonValue>,
+    id_column: &str,
+    id_template: Option<&str>,
+) -> Option<String> {
+    let template = match id_template {
+        Some(t) => t,
+        None => return content.get(id_column).and_then(id_from_json),
+    };
+
+    let mut key = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('%') {
+        key.push_str(&rest[..start]);
+        let field = &rest[start + 1..];
+        let end = field.find('%')?;
+        key.push_str(&content.get(&field[..end]).and_then(id_from_json)?);
+        rest = &field[end + 1..];
+    }
+    key.push_str(rest);
+
+    Some(key)
+}
+
+fn id_from_json(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(s) => Some(s.clone()),
+        JsonValue::Number(n) if n.is_i64() || n.is_u64() => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+// ImportTarget is a cluster being imported to, along with the results so far.
+struct ImportTarget {
+    identifier: String,
+    client: Arc<KvClient>,
+    cid: u32,
+    timeout: Duration,
+    batch_size: u32,
+    throughput: Arc<Throughput>,
+    success: i32,
+    failed: i32,
+    fail_reasons: HashSet<String>,
+    mutations: MutationState,
+}
+
+impl ImportTarget {
+    fn new(
+        identifier: String,
+        client: Arc<KvClient>,
+        cid: u32,
+        timeout: Duration,
+        batch_size: u32,
+        throughput: Arc<Throughput>,
+    ) -> Self {
+        Self {
+            identifier,
+            client,
+            cid,
+            timeout,
+            batch_size,
+            throughput,
+            success: 0,
+            failed: 0,
+            fail_reasons: HashSet::new(),
+            mutations: MutationState::default(),
+        }
+    }
+
+    fn fail(&mut self, reason: String) {
+        self.failed += 1;
+        self.fail_reasons.insert(reason);
+    }
+
+    // import writes the items, returning the index and error of each which failed.
+    fn import(
+        &mut self,
+        rt: &Runtime,
+        items: &[ImportItem],
+        mode: ImportMode,
+        expiry: u32,
+        durability: Durability,
+        ctrl_c: &Arc<AtomicBool>,
+    ) -> Vec<(usize, String)> {
+        let mut workers = FuturesUnordered::new();
+        for (i, item) in items.iter().enumerate() {
+            let request = mode.request(item.key.clone(), item.value.clone(), expiry, durability);
+            let client = self.client.clone();
+            let throughput = self.throughput.clone();
+            let identifier = self.identifier.clone();
+            let cid = self.cid;
+            let timeout = self.timeout;
+            let ctrl_c = ctrl_c.clone();
+
+            workers.push(async move {
+                let result = throughput
+                    .request(&identifier, &client, request, cid, timeout, ctrl_c)
+                    .await;
+                (i, result)
+            });
+        }
+
+        rt.block_on(async {
+            let mut failures = vec![];
+            while let Some((i, result)) = workers.next().await {
+                match result {
+                    Ok(res) => {
+                        self.success += 1;
+                        if let Some(token) = res.mutation_token() {
+                            self.mutations.add(token);
+                        }
+                    }
+                    Err(e) => {
+                        self.fail(e.to_string());
+                        failures.push((i, e.to_string()));
+                    }
+                }
+            }
+            failures
+        })
+    }
+
+    fn into_value(self, span: Span) -> Value {
+        let throughput = self.throughput.summary(&self.identifier);
+        MutationResult::new(self.identifier)
+            .success(self.success)
+            .failed(self.failed)
+            .fail_reasons(self.fail_reasons)
+            .throughput(throughput)
+            .into_value(span)
+    }
+}
+
+// Checkpoint records how many rows of a file have been imported, so that an interrupted import
+// can carry on from there. It also records where the rows were imported to and how, so that an
+// import with other options does not skip rows it never wrote, and the size and modification time
+// of the file, so that rows of an edited or replaced file are not skipped either.
+struct Checkpoint {
+    path: PathBuf,
+    import: JsonValue,
+    file: JsonValue,
+}
+
+impl Checkpoint {
+    fn for_import(path: &Path, import: JsonValue) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut name = path.as_os_str().to_owned();
+        name.push(".checkpoint");
+        Ok(Self {
+            path: PathBuf::from(name),
+            import,
+            file: json!({
+                "size": metadata.len(),
+                "modified": modified.as_nanos() as u64,
+            }),
+        })
+    }
+
+    fn load(&self) -> io::Result<usize> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+
+        let checkpoint: JsonValue = serde_json::from_str(&content)?;
+        if checkpoint["file"] != self.file {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the file has changed since the checkpoint was written, import it again without --resume",
+            ));
+        }
+        if checkpoint["import"] != self.import {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the checkpoint is for an import with other targets or options: {}",
+                    checkpoint["import"]
+                ),
+            ));
+        }
+        checkpoint["offset"]
+            .as_u64()
+            .map(|o| o as usize)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "checkpoint has no offset"))
+    }
+
+    // save writes the checkpoint to a temporary file first, so that a crash part way through
+    // never leaves it half written.
+    fn save(&self, offset: usize) -> io::Result<()> {
+        let temp = self.path.with_extension("checkpoint.tmp");
+        fs::write(
+            &temp,
+            json!({ "offset": offset, "import": self.import, "file": self.file }).to_string(),
+        )?;
+        fs::rename(&temp, &self.path)
+    }
+
+    fn remove(&self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+// ErrorsFile records the rows which failed to import as JSON lines, along with why.
+struct ErrorsFile {
+    out: BufWriter<File>,
+}
+
+type Failure = (
+    usize,
+    Option<String>,
+    Option<String>,
+    String,
+    Option<Map<String, JsonValue>>,
+);
+
+impl ErrorsFile {
+    // open appends to the file when resuming, so that the failures from before are kept.
+    fn open(path: &Path, append: bool) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+
+        Ok(Self {
+            out: BufWriter::new(file),
+        })
+    }
+
+    fn write(&mut self, failures: Vec<Failure>) -> io::Result<()> {
+        for (row, id, cluster, error, content) in failures {
+            let failure = json!({
+                "row": row,
+                "id": id,
+                "cluster": cluster,
+                "error": error,
+                "document": content,
+            });
+            serde_json::to_writer(&mut self.out, &failure)?;
+            self.out.write_all(b"\n")?;
+        }
+
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_keys_from_templates() {
+        let content = json!({"id": 123, "type": "user", "name": "a"});
+        let content = content.as_object().unwrap();
+
+        assert_eq!(Some("123".to_string()), key_for(content, "id", None));
+        assert_eq!(Some("a".to_string()), key_for(content, "name", None));
+        assert_eq!(
+            Some("user::123".to_string()),
+            key_for(content, "id", Some("user::%id%"))
+        );
+        assert_eq!(
+            Some("user::123::a".to_string()),
+            key_for(content, "id", Some("%type%::%id%::%name%"))
+        );
+        assert_eq!(None, key_for(content, "id", Some("user::%missing%")));
+        assert_eq!(None, key_for(content, "missing", None));
+    }
+
+    #[test]
+    fn resumes_only_the_same_import() {
+        let path = std::env::temp_dir().join(format!("cbsh-import-{}.jsonl", uuid::Uuid::new_v4()));
+        fs::write(&path, "{\"id\": 1}\n").unwrap();
+        let import = json!({"targets": [{"cluster": "local", "bucket": "a"}], "mode": "upsert"});
+        let checkpoint = Checkpoint::for_import(&path, import.clone()).unwrap();
+        assert_eq!(0, checkpoint.load().unwrap());
+
+        checkpoint.save(20).unwrap();
+        assert_eq!(
+            20,
+            Checkpoint::for_import(&path, import.clone())
+                .unwrap()
+                .load()
+                .unwrap()
+        );
+
+        let other = json!({"targets": [{"cluster": "local", "bucket": "b"}], "mode": "upsert"});
+        let err = Checkpoint::for_import(&path, other)
+            .unwrap()
+            .load()
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        fs::write(&path, "{\"id\": 2}\n{\"id\": 3}\n").unwrap();
+        let err = Checkpoint::for_import(&path, import)
+            .unwrap()
+            .load()
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        checkpoint.remove().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+//! The `doc increment` command performs a KV increment operation.
+
+use crate::cli::doc_common::run_kv_counter_ops;
+use crate::client::{Durability, KeyValueRequest};
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct DocIncrement {
+    state: Arc<Mutex<State>>,
+}
+
+impl DocIncrement {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for DocIncrement {
+    fn name(&self) -> &str {
+        "doc increment"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("doc increment")
+            .optional("id", SyntaxShape::String, "the document id")
+            .named(
+                "id-column",
+                SyntaxShape::String,
+                "the name of the id column if used with an input stream",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "the name of the bucket",
+                None,
+            )
+            .named(
+                "delta",
+                SyntaxShape::Int,
+                "the amount to change the counter by, defaults to 1",
+                None,
+            )
+            .named(
+                "initial",
+                SyntaxShape::Int,
+                "the value to create the counter with if it does not exist, the operation fails for missing counters if not set",
+                None,
+            )
+            .named(
+                "expiry",
+                SyntaxShape::Number,
+                "the expiry for the counters in seconds, or absolute",
+                None,
+            )
+            .named("scope", SyntaxShape::String, "the name of the scope", None)
+            .named(
+                "collection",
+                SyntaxShape::String,
+                "the name of the collection",
+                None,
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters which should be contacted",
+                None,
+            )
+            .named(
+                "durability",
+                SyntaxShape::String,
+                "the durability level required for the mutation: none, majority, majority-and-persist-active or persist-to-majority",
+                None,
+            )
+            .named(
+                "durability-timeout",
+                SyntaxShape::Int,
+                "the timeout for meeting the durability level (in ms)",
+                None,
+            )
+            .named(
+                "batch-size",
+                SyntaxShape::Number,
+                "the maximum number of items to batch send at a time",
+                None,
+            )
+            .switch("halt-on-error", "halt on any errors", Some('e'))
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Increments a counter document through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_increment(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Increments a counter by 1",
+                example: "doc increment my_counter",
+                result: None,
+            },
+            Example {
+                description: "Increments a counter by 5, creating it with a value of 100 if it does not exist",
+                example: "doc increment my_counter --delta 5 --initial 100",
+                result: None,
+            },
+        ]
+    }
+}
+
+fn build_req(
+    key: String,
+    delta: u64,
+    initial: Option<u64>,
+    expiry: u32,
+    durability: Durability,
+) -> KeyValueRequest {
+    KeyValueRequest::Increment {
+        key,
+        delta,
+        initial,
+        expiry,
+        durability,
+    }
+}
+
+fn run_increment(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let results = run_kv_counter_ops(state, engine_state, stack, call, input, build_req)?;
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
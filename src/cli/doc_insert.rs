@@ -1,7 +1,7 @@
 //! The `doc insert` command performs a KV insert operation.
 
 use crate::cli::doc_common::run_kv_store_ops;
-use crate::client::KeyValueRequest;
+use crate::client::{Durability, KeyValueRequest};
 use crate::state::State;
 use std::sync::{Arc, Mutex};
 
@@ -68,12 +68,41 @@ impl Command for DocInsert {
                 "the clusters which should be contacted",
                 None,
             )
+            .named(
+                "durability",
+                SyntaxShape::String,
+                "the durability level required for the mutation: none, majority, majority-and-persist-active or persist-to-majority",
+                None,
+            )
+            .named(
+                "durability-timeout",
+                SyntaxShape::Int,
+                "the timeout for meeting the durability level (in ms)",
+                None,
+            )
             .named(
                 "batch-size",
                 SyntaxShape::Number,
                 "the maximum number of items to batch send at a time",
                 None,
             )
+            .named(
+                "rate-limit",
+                SyntaxShape::Int,
+                "the maximum number of operations to send per second",
+                None,
+            )
+            .named(
+                "max-in-flight",
+                SyntaxShape::Int,
+                "the maximum number of operations waiting on a response at once",
+                None,
+            )
+            .switch(
+                "progress",
+                "report progress while running and include throughput and latencies in the results",
+                None,
+            )
             .switch("halt-on-error", "halt on any errors", Some('e'))
             .category(Category::Custom("couchbase".to_string()))
     }
@@ -93,8 +122,21 @@ impl Command for DocInsert {
     }
 }
 
-fn build_req(key: String, value: Vec<u8>, expiry: u32) -> KeyValueRequest {
-    KeyValueRequest::Insert { key, value, expiry }
+fn build_req(
+    key: String,
+    value: Vec<u8>,
+    flags: u32,
+    expiry: u32,
+    _cas: u64,
+    durability: Durability,
+) -> KeyValueRequest {
+    KeyValueRequest::Insert {
+        key,
+        value,
+        flags,
+        expiry,
+        durability,
+    }
 }
 
 fn run_insert(
@@ -1,7 +1,7 @@
 //! The `doc replace` command performs a KV replace operation.
 
 use crate::cli::doc_common::run_kv_store_ops;
-use crate::client::KeyValueRequest;
+use crate::client::{Durability, KeyValueRequest};
 use crate::state::State;
 use std::sync::{Arc, Mutex};
 
@@ -55,6 +55,12 @@ impl Command for DocReplace {
                 "the expiry for the documents in seconds, or absolute",
                 None,
             )
+            .named(
+                "cas",
+                SyntaxShape::Int,
+                "the cas value the document must have, overridden by a cas column if used with an input stream",
+                None,
+            )
             .named("scope", SyntaxShape::String, "the name of the scope", None)
             .named(
                 "collection",
@@ -68,12 +74,41 @@ impl Command for DocReplace {
                 "the clusters which should be contacted",
                 None,
             )
+            .named(
+                "durability",
+                SyntaxShape::String,
+                "the durability level required for the mutation: none, majority, majority-and-persist-active or persist-to-majority",
+                None,
+            )
+            .named(
+                "durability-timeout",
+                SyntaxShape::Int,
+                "the timeout for meeting the durability level (in ms)",
+                None,
+            )
             .named(
                 "batch-size",
                 SyntaxShape::Number,
                 "the maximum number of items to batch send at a time",
                 None,
             )
+            .named(
+                "rate-limit",
+                SyntaxShape::Int,
+                "the maximum number of operations to send per second",
+                None,
+            )
+            .named(
+                "max-in-flight",
+                SyntaxShape::Int,
+                "the maximum number of operations waiting on a response at once",
+                None,
+            )
+            .switch(
+                "progress",
+                "report progress while running and include throughput and latencies in the results",
+                None,
+            )
             .switch("halt-on-error", "halt on any errors", Some('e'))
             .category(Category::Custom("couchbase".to_string()))
     }
@@ -93,8 +128,22 @@ impl Command for DocReplace {
     }
 }
 
-fn build_req(key: String, value: Vec<u8>, expiry: u32) -> KeyValueRequest {
-    KeyValueRequest::Replace { key, value, expiry }
+fn build_req(
+    key: String,
+    value: Vec<u8>,
+    flags: u32,
+    expiry: u32,
+    cas: u64,
+    durability: Durability,
+) -> KeyValueRequest {
+    KeyValueRequest::Replace {
+        key,
+        value,
+        flags,
+        expiry,
+        cas,
+        durability,
+    }
 }
 
 fn run_replace(
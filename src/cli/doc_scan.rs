@@ -0,0 +1,350 @@
+//! The `doc scan` command streams the documents of a collection using KV range scans.
+
+use crate::cli::doc_common::{format_from_args, get_active_cluster_client_cid};
+use crate::cli::doc_get::{decode_body, GetResult};
+use crate::cli::error::{client_error_to_shell_error, generic_error};
+use crate::cli::util::{cluster_identifiers_from, NuValueMap};
+use crate::client::{ClientError, DocumentFormat, RangeScan, ScanItem, ScanType};
+use crate::state::State;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, ListStream, PipelineData, ShellError, Signature, Span, SyntaxShape, Value,
+};
+
+#[derive(Clone)]
+pub struct DocScan {
+    state: Arc<Mutex<State>>,
+}
+
+impl DocScan {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for DocScan {
+    fn name(&self) -> &str {
+        "doc scan"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("doc scan")
+            .named(
+                "prefix",
+                SyntaxShape::String,
+                "only the documents with ids starting with this prefix",
+                None,
+            )
+            .named(
+                "from",
+                SyntaxShape::String,
+                "only the documents with ids from this one onwards",
+                None,
+            )
+            .named(
+                "to",
+                SyntaxShape::String,
+                "only the documents with ids up to and including this one",
+                None,
+            )
+            .named(
+                "sample",
+                SyntaxShape::Int,
+                "a random sample of at most this many documents",
+                None,
+            )
+            .switch("ids-only", "only return the ids of the documents", None)
+            .named(
+                "concurrency",
+                SyntaxShape::Int,
+                "the number of partitions to scan at the same time, defaults to 1",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "the name of the bucket",
+                None,
+            )
+            .named("scope", SyntaxShape::String, "the name of the scope", None)
+            .named(
+                "collection",
+                SyntaxShape::String,
+                "the name of the collection",
+                None,
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters which should be contacted",
+                None,
+            )
+            .switch(
+                "with-meta",
+                "include the expiry, flags, datatype and seqno of the documents",
+                None,
+            )
+            .named(
+                "format",
+                SyntaxShape::String,
+                "the format to read the documents as: json, string or binary, defaults to the format recorded in the document flags",
+                None,
+            )
+            .switch("halt-on-error", "halt on any errors", Some('e'))
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Lists the documents of a collection through the data service, without needing an index"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_scan(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Lists every document in the active collection",
+                example: "doc scan",
+                result: None,
+            },
+            Example {
+                description: "Lists the ids of the documents with ids starting with airline_",
+                example: "doc scan --prefix airline_ --ids-only",
+                result: None,
+            },
+            Example {
+                description: "Lists the documents with ids between airline_10 and airline_20",
+                example: "doc scan --from airline_10 --to airline_20",
+                result: None,
+            },
+            Example {
+                description: "Fetches a random sample of 10 documents",
+                example: "doc scan --sample 10",
+                result: None,
+            },
+        ]
+    }
+}
+
+fn run_scan(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    _input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let scan_type = scan_type_from_args(engine_state, stack, call)?;
+    let ids_only = call.has_flag(engine_state, stack, "ids-only")?;
+    let concurrency: Option<i64> = call.get_flag(engine_state, stack, "concurrency")?;
+    if concurrency.map(|c| c < 1).unwrap_or_default() {
+        return Err(generic_error("Concurrency must be at least 1", None, span));
+    }
+    let scan = RangeScan::new(scan_type)
+        .ids_only(ids_only)
+        .concurrency(concurrency.unwrap_or(1) as usize);
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+    let bucket_flag = call.get_flag(engine_state, stack, "bucket")?;
+    let scope_flag = call.get_flag(engine_state, stack, "scope")?;
+    let collection_flag = call.get_flag(engine_state, stack, "collection")?;
+    let halt_on_error = call.has_flag(engine_state, stack, "halt-on-error")?;
+    let with_meta = call.has_flag(engine_state, stack, "with-meta")?;
+    let format = format_from_args(engine_state, stack, call)?;
+
+    let guard = state.lock().unwrap();
+
+    let mut failed = VecDeque::new();
+    let mut scans = VecDeque::new();
+    for identifier in cluster_identifiers {
+        let rt = Runtime::new().unwrap();
+        let (active_cluster, client, cid) = match get_active_cluster_client_cid(
+            &rt,
+            identifier.clone(),
+            &guard,
+            bucket_flag.clone(),
+            scope_flag.clone(),
+            collection_flag.clone(),
+            ctrl_c.clone(),
+            span,
+        ) {
+            Ok(c) => c,
+            Err(e) => {
+                if halt_on_error {
+                    return Err(e);
+                }
+
+                failed.push_back(
+                    GetResult::new(identifier.clone())
+                        .error(e.to_string())
+                        .into_value(span),
+                );
+                continue;
+            }
+        };
+
+        // The scan carries on in the background, the items being read as the pipeline wants them.
+        let items = client.range_scan(
+            scan.clone(),
+            cid,
+            active_cluster.timeouts().data_timeout(),
+            ctrl_c.clone(),
+        );
+        scans.push_back((identifier, items));
+    }
+
+    let results = ScanResults {
+        failed,
+        scans,
+        ids_only,
+        with_meta,
+        format,
+        halt_on_error,
+        span,
+    };
+
+    Ok(PipelineData::ListStream(
+        ListStream::new(results, span, Some(ctrl_c)),
+        None,
+    ))
+}
+
+fn scan_type_from_args(
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+) -> Result<ScanType, ShellError> {
+    let prefix: Option<String> = call.get_flag(engine_state, stack, "prefix")?;
+    let from: Option<String> = call.get_flag(engine_state, stack, "from")?;
+    let to: Option<String> = call.get_flag(engine_state, stack, "to")?;
+    let sample: Option<i64> = call.get_flag(engine_state, stack, "sample")?;
+
+    let ranged = from.is_some() || to.is_some();
+    if [prefix.is_some(), ranged, sample.is_some()]
+        .iter()
+        .filter(|set| **set)
+        .count()
+        > 1
+    {
+        return Err(generic_error(
+            "Only one kind of scan can be used",
+            "Use either --prefix, --from and --to, or --sample".to_string(),
+            call.head,
+        ));
+    }
+
+    match (prefix, sample) {
+        (Some(prefix), _) => Ok(ScanType::Prefix(prefix)),
+        (_, Some(limit)) => {
+            if limit < 1 {
+                return Err(generic_error("Sample must be at least 1", None, call.head));
+            }
+            Ok(ScanType::Sample {
+                limit: limit as u64,
+                seed: rand::random(),
+            })
+        }
+        (None, None) => Ok(ScanType::Range { from, to }),
+    }
+}
+
+// ScanResults reads the items of each cluster's scan in turn, converting them into rows only once
+// the pipeline asks for them.
+struct ScanResults {
+    // Rows for the clusters which could not be scanned at all.
+    failed: VecDeque<Value>,
+    scans: VecDeque<(String, mpsc::Receiver<Result<ScanItem, ClientError>>)>,
+    ids_only: bool,
+    with_meta: bool,
+    format: Option<DocumentFormat>,
+    halt_on_error: bool,
+    span: Span,
+}
+
+impl Iterator for ScanResults {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(failed) = self.failed.pop_front() {
+            return Some(failed);
+        }
+
+        loop {
+            let (cluster, items) = self.scans.front_mut()?;
+            let item = match items.blocking_recv() {
+                Some(item) => item,
+                None => {
+                    self.scans.pop_front();
+                    continue;
+                }
+            };
+
+            let cluster = cluster.clone();
+            let value = match item {
+                Ok(item) => self.item_into_value(cluster, item),
+                Err(e) if self.halt_on_error => Err(client_error_to_shell_error(e, self.span)),
+                Err(e) => Ok(GetResult::new(cluster)
+                    .error(e.to_string())
+                    .into_value(self.span)),
+            };
+
+            return Some(match value {
+                Ok(v) => v,
+                Err(e) => {
+                    // Dropping the remaining scans stops them.
+                    self.scans.clear();
+                    Value::error(e, self.span)
+                }
+            });
+        }
+    }
+}
+
+impl ScanResults {
+    fn item_into_value(&self, cluster: String, mut item: ScanItem) -> Result<Value, ShellError> {
+        if self.ids_only {
+            let mut collected = NuValueMap::default();
+            collected.add_string("id", item.key(), self.span);
+            collected.add_string("cluster", cluster, self.span);
+            return Ok(collected.into_value(self.span));
+        }
+
+        let meta = item.meta();
+        let flags = meta.map(|m| m.flags()).unwrap_or_default();
+        let mut collected = GetResult::new(cluster)
+            .key(item.key())
+            .cas(item.cas() as i64);
+        if self.with_meta {
+            collected = collected.meta(meta);
+        }
+
+        match decode_body(item.body(), flags, self.format, self.span) {
+            Ok(content) => collected = collected.content(content),
+            Err(e) => {
+                if self.halt_on_error {
+                    return Err(e);
+                }
+                collected = collected.error(e.to_string());
+            }
+        }
+
+        Ok(collected.into_value(self.span))
+    }
+}
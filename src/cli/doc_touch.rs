@@ -0,0 +1,131 @@
+//! The `doc touch` command performs a KV touch operation.
+
+use crate::cli::doc_common::run_kv_key_ops;
+use crate::cli::doc_get::ids_from_input;
+use crate::client::KeyValueRequest;
+use crate::state::State;
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct DocTouch {
+    state: Arc<Mutex<State>>,
+}
+
+impl DocTouch {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for DocTouch {
+    fn name(&self) -> &str {
+        "doc touch"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("doc touch")
+            .optional("id", SyntaxShape::String, "the document id")
+            .named(
+                "expiry",
+                SyntaxShape::Number,
+                "the new expiry for the documents in seconds, or absolute",
+                None,
+            )
+            .named(
+                "id-column",
+                SyntaxShape::String,
+                "the name of the id column if used with an input stream",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "the name of the bucket",
+                None,
+            )
+            .named("scope", SyntaxShape::String, "the name of the scope", None)
+            .named(
+                "collection",
+                SyntaxShape::String,
+                "the name of the collection",
+                None,
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters which should be contacted",
+                None,
+            )
+            .named(
+                "batch-size",
+                SyntaxShape::Number,
+                "the maximum number of items to batch send at a time",
+                None,
+            )
+            .switch("halt-on-error", "halt on any errors", Some('e'))
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Updates the expiry of a document through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_touch(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Sets a document to expire in 60 seconds",
+                example: "doc touch my_doc_id --expiry 60",
+                result: None,
+            },
+            Example {
+                description:
+                    "Removes the expiry from multiple documents with IDs from the previous command",
+                example: "echo [[id]; [airline_10] [airline_11]] | doc touch",
+                result: None,
+            },
+        ]
+    }
+}
+
+fn run_touch(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let id_column: String = call
+        .get_flag(engine_state, stack, "id-column")?
+        .unwrap_or_else(|| "id".to_string());
+    let expiry: i64 = call.get_flag(engine_state, stack, "expiry")?.unwrap_or(0);
+    let ids = ids_from_input(input, id_column, call.positional_nth(0))?;
+
+    let results = run_kv_key_ops(state, engine_state, stack, call, ids, |key| {
+        KeyValueRequest::Touch {
+            key,
+            expiry: expiry as u32,
+        }
+    })?;
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
@@ -0,0 +1,137 @@
+//! The `doc unlock` command performs a KV unlock operation.
+
+use crate::cli::doc_common::run_kv_key_ops;
+use crate::cli::doc_get::ids_with_cas_from_input;
+use crate::client::KeyValueRequest;
+use crate::state::State;
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct DocUnlock {
+    state: Arc<Mutex<State>>,
+}
+
+impl DocUnlock {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for DocUnlock {
+    fn name(&self) -> &str {
+        "doc unlock"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("doc unlock")
+            .optional("id", SyntaxShape::String, "the document id")
+            .named(
+                "cas",
+                SyntaxShape::Int,
+                "the cas returned when the document was locked, overridden by a cas column if used with an input stream",
+                None,
+            )
+            .named(
+                "id-column",
+                SyntaxShape::String,
+                "the name of the id column if used with an input stream",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "the name of the bucket",
+                None,
+            )
+            .named("scope", SyntaxShape::String, "the name of the scope", None)
+            .named(
+                "collection",
+                SyntaxShape::String,
+                "the name of the collection",
+                None,
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters which should be contacted",
+                None,
+            )
+            .named(
+                "batch-size",
+                SyntaxShape::Number,
+                "the maximum number of items to batch send at a time",
+                None,
+            )
+            .switch("halt-on-error", "halt on any errors", Some('e'))
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Unlocks a document through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_unlock(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Unlocks a document using the cas returned when it was locked",
+                example: "doc unlock my_doc_id --cas 1712321628975398912",
+                result: None,
+            },
+            Example {
+                description: "Unlocks documents locked by the previous command",
+                example: "doc get-and-lock my_doc_id | doc unlock",
+                result: None,
+            },
+        ]
+    }
+}
+
+fn run_unlock(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let id_column: String = call
+        .get_flag(engine_state, stack, "id-column")?
+        .unwrap_or_else(|| "id".to_string());
+    let cas: Option<i64> = call.get_flag(engine_state, stack, "cas")?;
+    let ids = ids_with_cas_from_input(
+        input,
+        id_column,
+        call.positional_nth(0),
+        cas.unwrap_or_default() as u64,
+    )?;
+
+    let results = run_kv_key_ops(
+        state,
+        engine_state,
+        stack,
+        call,
+        ids,
+        |(key, cas): (String, u64)| KeyValueRequest::Unlock { key, cas },
+    )?;
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
@@ -1,7 +1,7 @@
 //! The `doc upsert` command performs a KV upsert operation.
 
 use crate::cli::doc_common::run_kv_store_ops;
-use crate::client::KeyValueRequest;
+use crate::client::{Durability, KeyValueRequest};
 use crate::state::State;
 use nu_protocol::ast::Call;
 use nu_protocol::engine::{Command, EngineState, Stack};
@@ -48,6 +48,12 @@ impl Command for DocUpsert {
                 "the name of the content column if used with an input stream",
                 None,
             )
+            .named(
+                "format",
+                SyntaxShape::String,
+                "the format to store the documents in: json, string or binary, defaults to json",
+                None,
+            )
             .named(
                 "expiry",
                 SyntaxShape::Number,
@@ -67,12 +73,41 @@ impl Command for DocUpsert {
                 "the clusters which should be contacted",
                 None,
             )
+            .named(
+                "durability",
+                SyntaxShape::String,
+                "the durability level required for the mutation: none, majority, majority-and-persist-active or persist-to-majority",
+                None,
+            )
+            .named(
+                "durability-timeout",
+                SyntaxShape::Int,
+                "the timeout for meeting the durability level (in ms)",
+                None,
+            )
             .named(
                 "batch-size",
                 SyntaxShape::Number,
                 "the maximum number of items to batch send at a time",
                 None,
             )
+            .named(
+                "rate-limit",
+                SyntaxShape::Int,
+                "the maximum number of operations to send per second",
+                None,
+            )
+            .named(
+                "max-in-flight",
+                SyntaxShape::Int,
+                "the maximum number of operations waiting on a response at once",
+                None,
+            )
+            .switch(
+                "progress",
+                "report progress while running and include throughput and latencies in the results",
+                None,
+            )
             .switch("halt-on-error", "halt on any errors", Some('e'))
             .category(Category::Custom("couchbase".to_string()))
     }
@@ -92,8 +127,21 @@ impl Command for DocUpsert {
     }
 }
 
-fn build_req(key: String, value: Vec<u8>, expiry: u32) -> KeyValueRequest {
-    KeyValueRequest::Set { key, value, expiry }
+fn build_req(
+    key: String,
+    value: Vec<u8>,
+    flags: u32,
+    expiry: u32,
+    _cas: u64,
+    durability: Durability,
+) -> KeyValueRequest {
+    KeyValueRequest::Set {
+        key,
+        value,
+        flags,
+        expiry,
+        durability,
+    }
 }
 
 fn run_upsert(
@@ -16,6 +16,7 @@ mod buckets_flush;
 mod buckets_get;
 mod buckets_sample;
 mod buckets_update;
+mod cbenv_connections;
 mod cbenv_managed;
 mod cbenv_register;
 mod cbenv_unregister;
@@ -35,11 +36,21 @@ mod columnar_query;
 mod credentials_create;
 mod ctrlc_future;
 mod doc;
+mod doc_append;
 mod doc_common;
+mod doc_decrement;
+mod doc_export;
 mod doc_get;
+mod doc_get_and_lock;
+mod doc_get_and_touch;
+mod doc_increment;
 mod doc_insert;
+mod doc_prepend;
 mod doc_remove;
 mod doc_replace;
+mod doc_scan;
+mod doc_touch;
+mod doc_unlock;
 mod doc_upsert;
 mod fake_data;
 mod health;
@@ -59,18 +70,37 @@ mod cbenv_scope;
 mod cbenv_timeouts;
 mod doc_import;
 mod error;
+mod parquet;
 mod projects;
 mod projects_create;
 mod projects_drop;
 mod query;
+mod query_active;
 mod query_advise;
+mod query_cancel;
+mod query_completed;
+mod query_execute;
+mod query_explain;
 mod query_indexes;
+mod query_prepare;
 mod query_transactions;
+mod query_vitals;
 mod scopes;
 mod scopes_create;
 mod scopes_drop;
 mod search;
+mod subdoc;
+mod subdoc_array_add_unique;
+mod subdoc_array_append;
+mod subdoc_array_prepend;
+mod subdoc_common;
+mod subdoc_counter;
 mod subdoc_get;
+mod subdoc_insert;
+mod subdoc_remove;
+mod subdoc_replace;
+mod subdoc_upsert;
+mod throughput;
 mod transactions;
 mod transactions_list_atrs;
 mod tutorial;
@@ -108,6 +138,7 @@ pub use buckets_flush::BucketsFlush;
 pub use buckets_get::BucketsGet;
 pub use buckets_sample::BucketsSample;
 pub use buckets_update::BucketsUpdate;
+pub use cbenv_connections::CbEnvConnections;
 pub use cbenv_llm::CbEnvLLM;
 pub use cbenv_managed::CBEnvManaged;
 pub use cbenv_register::CbEnvRegister;
@@ -128,11 +159,21 @@ pub use columnar_query::ColumnarQuery;
 pub use credentials_create::CredentialsCreate;
 pub use ctrlc_future::CtrlcFuture;
 pub use doc::Doc;
+pub use doc_append::DocAppend;
+pub use doc_decrement::DocDecrement;
+pub use doc_export::DocExport;
 pub use doc_get::DocGet;
+pub use doc_get_and_lock::DocGetAndLock;
+pub use doc_get_and_touch::DocGetAndTouch;
 pub use doc_import::DocImport;
+pub use doc_increment::DocIncrement;
 pub use doc_insert::DocInsert;
+pub use doc_prepend::DocPrepend;
 pub use doc_remove::DocRemove;
 pub use doc_replace::DocReplace;
+pub use doc_scan::DocScan;
+pub use doc_touch::DocTouch;
+pub use doc_unlock::DocUnlock;
 pub use doc_upsert::DocUpsert;
 pub use error::*;
 pub use fake_data::FakeData;
@@ -154,14 +195,30 @@ pub use projects::Projects;
 pub use projects_create::ProjectsCreate;
 pub use projects_drop::ProjectsDrop;
 pub use query::Query;
+pub use query_active::QueryActive;
 pub use query_advise::QueryAdvise;
+pub use query_cancel::QueryCancel;
+pub use query_completed::QueryCompleted;
+pub use query_execute::QueryExecute;
+pub use query_explain::QueryExplain;
 pub use query_indexes::QueryIndexes;
+pub use query_prepare::QueryPrepare;
 pub use query_transactions::QueryTransactions;
+pub use query_vitals::QueryVitals;
 pub use scopes::Scopes;
 pub use scopes_create::ScopesCreate;
 pub use scopes_drop::ScopesDrop;
 pub use search::Search;
+pub use subdoc::SubDoc;
+pub use subdoc_array_add_unique::SubDocArrayAddUnique;
+pub use subdoc_array_append::SubDocArrayAppend;
+pub use subdoc_array_prepend::SubDocArrayPrepend;
+pub use subdoc_counter::SubDocCounter;
 pub use subdoc_get::SubDocGet;
+pub use subdoc_insert::SubDocInsert;
+pub use subdoc_remove::SubDocRemove;
+pub use subdoc_replace::SubDocReplace;
+pub use subdoc_upsert::SubDocUpsert;
 pub use transactions::Transactions;
 pub use transactions_list_atrs::TransactionsListAtrs;
 pub use tutorial::Tutorial;
@@ -0,0 +1,564 @@
+//! A minimal Parquet writer for `doc export`. Every column is optional and written as a single
+//! uncompressed, plain encoded data page per row group, which any Parquet reader can load.
+
+use serde_json::{Map, Value as JsonValue};
+use std::io::{self, Write};
+
+static MAGIC: &[u8] = b"PAR1";
+static CREATED_BY: &str = "couchbase-shell";
+
+// Parquet physical types.
+const TYPE_BOOLEAN: i32 = 0;
+const TYPE_INT64: i32 = 2;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+
+const REPETITION_OPTIONAL: i32 = 1;
+const CONVERTED_TYPE_UTF8: i32 = 0;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_TYPE_DATA: i32 = 0;
+
+// Thrift compact protocol types.
+const COMPACT_I32: u8 = 5;
+const COMPACT_I64: u8 = 6;
+const COMPACT_BINARY: u8 = 8;
+const COMPACT_LIST: u8 = 9;
+const COMPACT_STRUCT: u8 = 12;
+
+// ColumnType is the type a column is written as, found from the values it holds.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum ColumnType {
+    Boolean,
+    Int64,
+    Double,
+    String,
+}
+
+impl ColumnType {
+    // of returns the type of the value, or None for null which fits any type.
+    pub(crate) fn of(value: &JsonValue) -> Option<Self> {
+        match value {
+            JsonValue::Null => None,
+            JsonValue::Bool(_) => Some(Self::Boolean),
+            JsonValue::Number(n) if n.is_i64() => Some(Self::Int64),
+            JsonValue::Number(_) => Some(Self::Double),
+            _ => Some(Self::String),
+        }
+    }
+
+    // widen returns the type which can hold the values of both types. Integers and doubles are
+    // written as doubles, any other mix is written as JSON text.
+    pub(crate) fn widen(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Self::Int64, Self::Double) | (Self::Double, Self::Int64) => Self::Double,
+            _ => Self::String,
+        }
+    }
+
+    fn physical(self) -> i32 {
+        match self {
+            Self::Boolean => TYPE_BOOLEAN,
+            Self::Int64 => TYPE_INT64,
+            Self::Double => TYPE_DOUBLE,
+            Self::String => TYPE_BYTE_ARRAY,
+        }
+    }
+}
+
+struct ChunkMeta {
+    column_type: ColumnType,
+    name: String,
+    offset: i64,
+    size: i64,
+    num_values: i64,
+}
+
+struct RowGroupMeta {
+    chunks: Vec<ChunkMeta>,
+    num_rows: i64,
+}
+
+// ParquetWriter writes rows of flattened documents, a row group at a time, with the footer
+// written by finish.
+pub(crate) struct ParquetWriter<W: Write> {
+    out: W,
+    offset: i64,
+    columns: Vec<(String, ColumnType)>,
+    row_groups: Vec<RowGroupMeta>,
+}
+
+impl<W: Write> ParquetWriter<W> {
+    pub(crate) fn new(mut out: W, columns: Vec<(String, ColumnType)>) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        Ok(Self {
+            out,
+            offset: MAGIC.len() as i64,
+            columns,
+            row_groups: vec![],
+        })
+    }
+
+    pub(crate) fn write_row_group(&mut self, rows: &[Map<String, JsonValue>]) -> io::Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let mut chunks = vec![];
+        for (name, column_type) in &self.columns {
+            let page = encode_page(rows.iter().map(|r| r.get(name)), *column_type);
+
+            let mut header = Compact::new();
+            header.i32(1, PAGE_TYPE_DATA);
+            header.i32(2, page.len() as i32);
+            header.i32(3, page.len() as i32);
+            header.begin_struct(5);
+            header.i32(1, rows.len() as i32);
+            header.i32(2, ENCODING_PLAIN);
+            header.i32(3, ENCODING_RLE);
+            header.i32(4, ENCODING_RLE);
+            header.end_struct();
+            let header = header.finish();
+
+            self.out.write_all(&header)?;
+            self.out.write_all(&page)?;
+            let size = (header.len() + page.len()) as i64;
+            chunks.push(ChunkMeta {
+                column_type: *column_type,
+                name: name.clone(),
+                offset: self.offset,
+                size,
+                num_values: rows.len() as i64,
+            });
+            self.offset += size;
+        }
+
+        self.row_groups.push(RowGroupMeta {
+            chunks,
+            num_rows: rows.len() as i64,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        let mut meta = Compact::new();
+        meta.i32(1, 1);
+
+        meta.begin_list(2, COMPACT_STRUCT, self.columns.len() + 1);
+        meta.begin_element();
+        meta.binary(4, b"schema");
+        meta.i32(5, self.columns.len() as i32);
+        meta.end_struct();
+        for (name, column_type) in &self.columns {
+            meta.begin_element();
+            meta.i32(1, column_type.physical());
+            meta.i32(3, REPETITION_OPTIONAL);
+            meta.binary(4, name.as_bytes());
+            if *column_type == ColumnType::String {
+                meta.i32(6, CONVERTED_TYPE_UTF8);
+            }
+            meta.end_struct();
+        }
+
+        meta.i64(3, self.row_groups.iter().map(|g| g.num_rows).sum());
+
+        meta.begin_list(4, COMPACT_STRUCT, self.row_groups.len());
+        for group in &self.row_groups {
+            meta.begin_element();
+            meta.begin_list(1, COMPACT_STRUCT, group.chunks.len());
+            for chunk in &group.chunks {
+                meta.begin_element();
+                meta.i64(2, chunk.offset);
+                meta.begin_struct(3);
+                meta.i32(1, chunk.column_type.physical());
+                meta.begin_list(2, COMPACT_I32, 2);
+                meta.list_i32(ENCODING_PLAIN);
+                meta.list_i32(ENCODING_RLE);
+                meta.begin_list(3, COMPACT_BINARY, 1);
+                meta.list_binary(chunk.name.as_bytes());
+                meta.i32(4, CODEC_UNCOMPRESSED);
+                meta.i64(5, chunk.num_values);
+                meta.i64(6, chunk.size);
+                meta.i64(7, chunk.size);
+                meta.i64(9, chunk.offset);
+                meta.end_struct();
+                meta.end_struct();
+            }
+            meta.i64(2, group.chunks.iter().map(|c| c.size).sum());
+            meta.i64(3, group.num_rows);
+            meta.end_struct();
+        }
+
+        meta.binary(6, CREATED_BY.as_bytes());
+        let meta = meta.finish();
+
+        self.out.write_all(&meta)?;
+        self.out.write_all(&(meta.len() as u32).to_le_bytes())?;
+        self.out.write_all(MAGIC)?;
+        Ok(self.out)
+    }
+}
+
+// encode_page encodes the definition levels and the non null values of a column. Values which do
+// not fit the column type are written as null.
+fn encode_page<'a>(
+    values: impl Iterator<Item = Option<&'a JsonValue>>,
+    column_type: ColumnType,
+) -> Vec<u8> {
+    let mut defined = vec![];
+    let mut data = vec![];
+    let mut bits = 0;
+    for value in values {
+        let written = match (column_type, value) {
+            (_, None) | (_, Some(JsonValue::Null)) => false,
+            (ColumnType::Boolean, Some(JsonValue::Bool(b))) => {
+                if bits % 8 == 0 {
+                    data.push(0);
+                }
+                if *b {
+                    *data.last_mut().unwrap() |= 1 << (bits % 8);
+                }
+                bits += 1;
+                true
+            }
+            (ColumnType::Int64, Some(v)) => match v.as_i64() {
+                Some(i) => {
+                    data.extend_from_slice(&i.to_le_bytes());
+                    true
+                }
+                None => false,
+            },
+            (ColumnType::Double, Some(v)) => match v.as_f64() {
+                Some(f) => {
+                    data.extend_from_slice(&f.to_le_bytes());
+                    true
+                }
+                None => false,
+            },
+            (ColumnType::String, Some(v)) => {
+                let text = match v {
+                    JsonValue::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                data.extend_from_slice(text.as_bytes());
+                true
+            }
+            _ => false,
+        };
+        defined.push(written);
+    }
+
+    let levels = encode_levels(&defined);
+    let mut page = Vec::with_capacity(4 + levels.len() + data.len());
+    page.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+    page.extend_from_slice(&levels);
+    page.extend_from_slice(&data);
+    page
+}
+
+// encode_levels writes the definition levels as runs of the RLE hybrid encoding, with a bit
+// width of one as the levels are either 0 for null or 1 for a value.
+fn encode_levels(defined: &[bool]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < defined.len() {
+        let run = defined[i..]
+            .iter()
+            .take_while(|d| **d == defined[i])
+            .count();
+        write_varint(&mut out, (run as u64) << 1);
+        out.push(defined[i] as u8);
+        i += run;
+    }
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+// Compact writes structs with the Thrift compact protocol, which is how Parquet encodes its page
+// headers and file metadata. It keeps the last field id of each open struct, as field ids are
+// written as deltas from it.
+struct Compact {
+    buf: Vec<u8>,
+    last_field: Vec<i16>,
+}
+
+impl Compact {
+    fn new() -> Self {
+        Self {
+            buf: vec![],
+            last_field: vec![0],
+        }
+    }
+
+    fn field(&mut self, id: i16, field_type: u8) {
+        let last = self.last_field.last_mut().unwrap();
+        let delta = id - *last;
+        if delta > 0 && delta <= 15 {
+            self.buf.push(((delta as u8) << 4) | field_type);
+        } else {
+            self.buf.push(field_type);
+            write_varint(&mut self.buf, zigzag(id as i64));
+        }
+        *last = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, COMPACT_I32);
+        write_varint(&mut self.buf, zigzag(value as i64));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, COMPACT_I64);
+        write_varint(&mut self.buf, zigzag(value));
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, COMPACT_BINARY);
+        self.list_binary(value);
+    }
+
+    fn begin_struct(&mut self, id: i16) {
+        self.field(id, COMPACT_STRUCT);
+        self.begin_element();
+    }
+
+    fn end_struct(&mut self) {
+        self.buf.push(0);
+        self.last_field.pop();
+    }
+
+    fn begin_list(&mut self, id: i16, element_type: u8, len: usize) {
+        self.field(id, COMPACT_LIST);
+        if len < 15 {
+            self.buf.push(((len as u8) << 4) | element_type);
+        } else {
+            self.buf.push(0xf0 | element_type);
+            write_varint(&mut self.buf, len as u64);
+        }
+    }
+
+    // begin_element starts a struct within a list, which has no field header.
+    fn begin_element(&mut self) {
+        self.last_field.push(0);
+    }
+
+    fn list_i32(&mut self, value: i32) {
+        write_varint(&mut self.buf, zigzag(value as i64));
+    }
+
+    fn list_binary(&mut self, value: &[u8]) {
+        write_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.end_struct();
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::basic::{ConvertedType, Repetition, Type as PhysicalType};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::Field;
+    use serde_json::json;
+
+    #[test]
+    fn widens_column_types() {
+        assert_eq!(
+            ColumnType::Int64,
+            ColumnType::Int64.widen(ColumnType::Int64)
+        );
+        assert_eq!(
+            ColumnType::Double,
+            ColumnType::Int64.widen(ColumnType::Double)
+        );
+        assert_eq!(
+            ColumnType::String,
+            ColumnType::Boolean.widen(ColumnType::Int64)
+        );
+        assert_eq!(None, ColumnType::of(&JsonValue::Null));
+        assert_eq!(Some(ColumnType::String), ColumnType::of(&json!([1, 2])));
+    }
+
+    #[test]
+    fn encodes_definition_levels_as_runs() {
+        assert_eq!(
+            vec![0x06, 0x01, 0x02, 0x00, 0x02, 0x01],
+            encode_levels(&[true, true, true, false, true])
+        );
+    }
+
+    #[test]
+    fn encodes_optional_values_plainly() {
+        let values = [json!(true), JsonValue::Null, json!(false), json!(true)];
+        assert_eq!(
+            vec![0x06, 0, 0, 0, 0x02, 0x01, 0x02, 0x00, 0x04, 0x01, 0b101],
+            encode_page(values.iter().map(Some), ColumnType::Boolean)
+        );
+
+        let values = [json!("ab"), json!({"a": 1})];
+        let mut expected = vec![0x02, 0, 0, 0, 0x04, 0x01, 2, 0, 0, 0];
+        expected.extend_from_slice(b"ab");
+        expected.extend_from_slice(&[7, 0, 0, 0]);
+        expected.extend_from_slice(b"{\"a\":1}");
+        assert_eq!(
+            expected,
+            encode_page(values.iter().map(Some), ColumnType::String)
+        );
+    }
+
+    #[test]
+    fn writes_compact_field_headers() {
+        let mut compact = Compact::new();
+        compact.i32(1, 1);
+        compact.i64(3, -1);
+        compact.i32(20, 2);
+        compact.begin_struct(21);
+        compact.i32(1, 3);
+        compact.end_struct();
+        assert_eq!(
+            vec![0x15, 0x02, 0x26, 0x01, 0x05, 0x28, 0x04, 0x1c, 0x15, 0x06, 0x00, 0x00],
+            compact.finish()
+        );
+    }
+
+    fn object(value: JsonValue) -> Map<String, JsonValue> {
+        match value {
+            JsonValue::Object(o) => o,
+            _ => unreachable!(),
+        }
+    }
+
+    // The file is read back with the parquet crate, so that the layout is checked by a real reader
+    // rather than only against our own idea of it.
+    #[test]
+    fn reads_back_with_a_parquet_reader() {
+        let columns = vec![
+            ("name".to_string(), ColumnType::String),
+            ("age".to_string(), ColumnType::Int64),
+            ("score".to_string(), ColumnType::Double),
+            ("active".to_string(), ColumnType::Boolean),
+        ];
+        let mut writer = ParquetWriter::new(vec![], columns.clone()).unwrap();
+        let first = vec![
+            object(json!({"name": "a", "age": 1, "score": 1.5, "active": true})),
+            object(json!({"name": "b", "score": 2, "active": null})),
+        ];
+        let second = (0..20)
+            .map(|i| {
+                let mut row = object(json!({"name": [i], "active": i % 2 == 0}));
+                if i % 3 != 0 {
+                    row.insert("age".to_string(), json!(i));
+                }
+                row
+            })
+            .collect::<Vec<_>>();
+        writer.write_row_group(&first).unwrap();
+        writer.write_row_group(&[]).unwrap();
+        writer.write_row_group(&second).unwrap();
+        let file = writer.finish().unwrap();
+
+        let reader = SerializedFileReader::new(bytes::Bytes::from(file)).unwrap();
+        let meta = reader.metadata();
+        assert_eq!(22, meta.file_metadata().num_rows());
+        assert_eq!(2, meta.num_row_groups());
+
+        let schema = meta.file_metadata().schema_descr();
+        let read_columns = schema
+            .columns()
+            .iter()
+            .map(|c| {
+                (
+                    c.name().to_string(),
+                    c.physical_type(),
+                    c.self_type().get_basic_info().repetition(),
+                    c.converted_type(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    "name".to_string(),
+                    PhysicalType::BYTE_ARRAY,
+                    Repetition::OPTIONAL,
+                    ConvertedType::UTF8
+                ),
+                (
+                    "age".to_string(),
+                    PhysicalType::INT64,
+                    Repetition::OPTIONAL,
+                    ConvertedType::NONE
+                ),
+                (
+                    "score".to_string(),
+                    PhysicalType::DOUBLE,
+                    Repetition::OPTIONAL,
+                    ConvertedType::NONE
+                ),
+                (
+                    "active".to_string(),
+                    PhysicalType::BOOLEAN,
+                    Repetition::OPTIONAL,
+                    ConvertedType::NONE
+                ),
+            ],
+            read_columns
+        );
+
+        let mut expected = vec![
+            vec![
+                Field::Str("a".to_string()),
+                Field::Long(1),
+                Field::Double(1.5),
+                Field::Bool(true),
+            ],
+            vec![
+                Field::Str("b".to_string()),
+                Field::Null,
+                Field::Double(2.0),
+                Field::Null,
+            ],
+        ];
+        for i in 0..20 {
+            expected.push(vec![
+                Field::Str(format!("[{}]", i)),
+                if i % 3 != 0 {
+                    Field::Long(i)
+                } else {
+                    Field::Null
+                },
+                Field::Null,
+                Field::Bool(i % 2 == 0),
+            ]);
+        }
+        let rows = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                row.unwrap()
+                    .get_column_iter()
+                    .map(|(_, field)| field.clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(expected, rows);
+    }
+}
@@ -1,12 +1,18 @@
 use crate::cli::util::convert_nu_value_to_json_value;
 use crate::cli::util::{
     cluster_identifiers_from, convert_row_to_nu_value, duration_to_golang_string,
-    get_active_cluster, is_http_status,
+    get_active_cluster, golang_string_to_duration, is_http_status, NuValueMap, QueryResults,
+};
+use crate::client::{
+    HttpResponse, HttpStreamResponse, PreparedStatement, QueryOptions, QueryProfile, QueryRequest,
+    QueryTransactionRequest, ScanConsistency,
 };
-use crate::client::{HttpResponse, QueryRequest, QueryTransactionRequest};
 use crate::state::State;
 use log::debug;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
 use std::ops::Add;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
@@ -24,8 +30,7 @@ use nu_protocol::ast::Call;
 use nu_protocol::engine::{Command, EngineState, Stack};
 use nu_protocol::Value::Nothing;
 use nu_protocol::{
-    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, Span, SyntaxShape,
-    Value,
+    Category, Example, ListStream, PipelineData, ShellError, Signature, Span, SyntaxShape, Value,
 };
 
 #[derive(Clone)]
@@ -45,7 +50,7 @@ impl Command for Query {
     }
 
     fn signature(&self) -> Signature {
-        Signature::build("query")
+        let signature = Signature::build("query")
             .required("statement", SyntaxShape::String, "the query statement")
             .named(
                 "clusters",
@@ -73,7 +78,31 @@ impl Command for Query {
             )
             .switch("with-meta", "include toplevel metadata", None)
             .switch("disable-context", "disable automatically detecting the query context based on the active bucket and scope", None)
-            .category(Category::Custom("couchbase".to_string()))
+            .named(
+                "scan-consistency",
+                SyntaxShape::String,
+                "how up to date the indexes must be: not-bounded, request-plus or at-plus, defaults to not-bounded",
+                None,
+            )
+            .named(
+                "scan-wait",
+                SyntaxShape::Int,
+                "the maximum time to wait for the indexes to catch up (in ms)",
+                None,
+            )
+            .switch(
+                "consistent-with-last-write",
+                "wait for the indexes to include the writes made by the doc commands, the same as --scan-consistency at-plus",
+                None,
+            )
+            .named(
+                "adhoc",
+                SyntaxShape::Boolean,
+                "set to false to prepare the statement and reuse its plan each time it is run, defaults to true",
+                None,
+            );
+
+        with_query_option_flags(signature).category(Category::Custom("couchbase".to_string()))
     }
 
     fn usage(&self) -> &str {
@@ -106,6 +135,21 @@ impl Command for Query {
                 description:  "Pass query parameters as a list",
                 example: "query \"SELECT airline FROM `travel-sample`.inventory.route WHERE sourceairport = $1 AND distance > $2\" --params [LAX 13000]",
                 result: None,
+            },
+            Example {
+                description: "Profile a query, showing the time spent in each operator",
+                example: "query \"SELECT * FROM `travel-sample`.inventory.airline LIMIT 10\" --profile timings --with-meta | get profile.operators",
+                result: None,
+            },
+            Example {
+                description: "Run a statement many times, planning it only once",
+                example: "1..100 | each {|i| query \"SELECT * FROM `travel-sample`.inventory.airline WHERE id = $1\" --params [$i] --adhoc false }",
+                result: None,
+            },
+            Example {
+                description: "Query a document written just before, waiting for it to be indexed",
+                example: "doc upsert airline_1 {name: 'New Air'}; query \"SELECT name FROM `travel-sample` WHERE META().id = 'airline_1'\" --consistent-with-last-write",
+                result: None,
             }
         ]
     }
@@ -125,32 +169,80 @@ fn query(
 
     let statement: String = call.req(engine_state, stack, 0)?;
 
-    let params: Option<serde_json::Value> =
-        match call.get_flag::<Value>(engine_state, stack, "params")? {
-            Some(p) => match p {
-                Value::Record { .. } => Some(convert_nu_value_to_json_value(&p, span).unwrap()),
-                Value::List { .. } => Some(convert_nu_value_to_json_value(&p, span).unwrap()),
-                _ => {
-                    return Err(generic_error(
-                        "Parameters must be a list or JSON object",
-                        "Run 'query --help' to see examples".to_string(),
-                        None,
-                    ));
-                }
-            },
-            None => None,
-        };
+    let params = query_params_from_args(engine_state, stack, call)?;
 
-    let mut results: Vec<Value> = vec![];
+    let with_meta = call.has_flag(engine_state, stack, "with-meta")?;
+    let adhoc = call
+        .get_flag::<bool>(engine_state, stack, "adhoc")?
+        .unwrap_or(true);
+    let base_options = query_options_from_args(engine_state, stack, call)?;
+    let consistency = consistency_from_args(engine_state, stack, call)?;
+    let scan_wait: Option<i64> = call.get_flag(engine_state, stack, "scan-wait")?;
+    if scan_wait.map(|w| w < 0).unwrap_or_default() {
+        return Err(generic_error("Scan wait must not be negative", None, span));
+    }
+
+    let mut results: Vec<Box<dyn Iterator<Item = Value> + Send>> = vec![];
     for identifier in cluster_identifiers {
         let guard = state.lock().unwrap();
         let active_cluster = get_active_cluster(identifier.clone(), &guard, span)?;
 
         let maybe_scope = query_context_from_args(active_cluster, engine_state, stack, call)?;
 
+        let scan_consistency = match consistency {
+            Some(Consistency::NotBounded) => Some(ScanConsistency::NotBounded),
+            Some(Consistency::RequestPlus) => Some(ScanConsistency::RequestPlus),
+            Some(Consistency::AtPlus) => {
+                // With no writes recorded there is nothing to wait for.
+                let mutations = guard.mutation_state(&identifier);
+                if mutations.is_empty() {
+                    None
+                } else {
+                    Some(ScanConsistency::AtPlus(mutations))
+                }
+            }
+            None => None,
+        };
+        let options = base_options
+            .clone()
+            .scan_consistency(scan_consistency)
+            .scan_wait(scan_wait.map(|w| Duration::from_millis(w as u64)));
+
+        if !adhoc {
+            let name = auto_prepared_name(&statement, &maybe_scope);
+            let prepared = match active_cluster.prepared_statement(&name) {
+                Some(p) => p,
+                None => prepare_query(
+                    active_cluster,
+                    &name,
+                    &statement,
+                    maybe_scope,
+                    ctrl_c.clone(),
+                    span,
+                )?,
+            };
+
+            debug!("Running prepared n1ql query {}", &statement);
+
+            let response = send_prepared_query(
+                active_cluster,
+                prepared,
+                params.clone(),
+                ctrl_c.clone(),
+                span,
+                options,
+            )?;
+            drop(guard);
+
+            results.push(Box::new(
+                handle_query_response(with_meta, identifier.clone(), response, span)?.into_iter(),
+            ));
+            continue;
+        }
+
         debug!("Running n1ql query {}", &statement);
 
-        let response = send_query(
+        let response = send_query_stream(
             active_cluster,
             statement.clone(),
             params.clone(),
@@ -159,29 +251,48 @@ fn query(
             None,
             span,
             None,
+            options,
         )?;
         drop(guard);
 
-        results.extend(handle_query_response(
-            call.has_flag(engine_state, stack, "with-meta")?,
-            identifier.clone(),
-            response,
-            span,
-        )?);
+        // The metadata is only complete once the whole response has been read, so that is
+        // returned all at once.
+        if with_meta || response.status() != 200 {
+            let response = response
+                .into_response()
+                .map_err(|e| client_error_to_shell_error(e, span))?;
+            results.push(Box::new(
+                handle_query_response(with_meta, identifier.clone(), response, span)?.into_iter(),
+            ));
+        } else {
+            results.push(Box::new(QueryResults::new(
+                response,
+                identifier.clone(),
+                query_errors,
+                span,
+            )));
+        }
     }
 
-    if !results.is_empty() {
-        return Ok(Value::List {
-            vals: results,
-            internal_span: call.head,
-        }
-        .into_pipeline_data());
+    let mut rows = results.into_iter().flatten().peekable();
+
+    // Queries which fail outright do so before returning any rows, so are errors of the command
+    // itself.
+    if let Some(Value::Error { error, .. }) = rows.next_if(|v| matches!(v, Value::Error { .. })) {
+        return Err(*error);
     }
 
-    Ok(PipelineData::Value(
-        Nothing {
-            internal_span: span,
-        },
+    if rows.peek().is_none() {
+        return Ok(PipelineData::Value(
+            Nothing {
+                internal_span: span,
+            },
+            None,
+        ));
+    }
+
+    Ok(PipelineData::ListStream(
+        ListStream::new(rows, span, Some(ctrl_c)),
         None,
     ))
 }
@@ -196,17 +307,45 @@ pub fn send_query(
     span: Span,
     transaction: impl Into<Option<QueryTransactionRequest>>,
 ) -> Result<HttpResponse, ShellError> {
+    send_query_stream(
+        cluster,
+        statement,
+        parameters,
+        scope,
+        ctrl_c,
+        timeout,
+        span,
+        transaction,
+        QueryOptions::default(),
+    )?
+    .into_response()
+    .map_err(|e| client_error_to_shell_error(e, span))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn send_query_stream(
+    cluster: &RemoteCluster,
+    statement: impl Into<String>,
+    parameters: Option<serde_json::Value>,
+    scope: Option<(String, String)>,
+    ctrl_c: Arc<AtomicBool>,
+    timeout: impl Into<Option<Duration>>,
+    span: Span,
+    transaction: impl Into<Option<QueryTransactionRequest>>,
+    options: QueryOptions,
+) -> Result<HttpStreamResponse, ShellError> {
     let timeout = timeout.into().unwrap_or(cluster.timeouts().query_timeout());
     let response = cluster
         .cluster()
         .http_client()
-        .query_request(
+        .query_request_stream(
             QueryRequest::Execute {
                 statement: statement.into(),
                 parameters,
                 scope,
                 timeout: duration_to_golang_string(timeout),
                 transaction: transaction.into(),
+                options,
             },
             Instant::now().add(timeout),
             ctrl_c,
@@ -216,6 +355,324 @@ pub fn send_query(
     Ok(response)
 }
 
+// The errors returned when the query service no longer has the plan of a prepared statement, or
+// can no longer use it.
+const REPREPARE_ERROR_CODES: [i64; 3] = [4040, 4050, 4070];
+
+// prepare_query prepares the statement under the given name and caches it on the cluster.
+pub(crate) fn prepare_query(
+    cluster: &RemoteCluster,
+    name: &str,
+    statement: &str,
+    scope: Option<(String, String)>,
+    ctrl_c: Arc<AtomicBool>,
+    span: Span,
+) -> Result<PreparedStatement, ShellError> {
+    // FORCE replaces any plan already prepared under the name, which is what we want when the
+    // old one has gone stale.
+    let response = send_query(
+        cluster,
+        format!("PREPARE FORCE `{}` AS {}", name, statement),
+        None,
+        scope.clone(),
+        ctrl_c,
+        None,
+        span,
+        None,
+    )?;
+
+    let content: serde_json::Value = serde_json::from_str(response.content())
+        .map_err(|e| deserialize_error(e.to_string(), span))?;
+    if let Some(content_errors) = content.get("errors") {
+        return Err(query_errors(content_errors, span));
+    }
+    is_http_status(&response, 200, span)?;
+
+    let result = match content["results"].get(0) {
+        Some(r) => r,
+        None => {
+            return Err(malformed_response_error(
+                "prepare results were empty",
+                content.to_string(),
+                span,
+            ))
+        }
+    };
+
+    let prepared = PreparedStatement::new(
+        name.to_string(),
+        statement.to_string(),
+        result["encoded_plan"].as_str().map(|p| p.to_string()),
+        response.endpoint(),
+        scope,
+    );
+    cluster.add_prepared_statement(prepared.clone());
+
+    Ok(prepared)
+}
+
+// send_prepared_query executes a prepared statement. If the query service has lost the plan, for
+// example because the node restarted, or can no longer use it then the statement is prepared
+// again and retried once.
+pub(crate) fn send_prepared_query(
+    cluster: &RemoteCluster,
+    prepared: PreparedStatement,
+    parameters: Option<serde_json::Value>,
+    ctrl_c: Arc<AtomicBool>,
+    span: Span,
+    options: QueryOptions,
+) -> Result<HttpResponse, ShellError> {
+    let response = execute_prepared(
+        cluster,
+        prepared.clone(),
+        parameters.clone(),
+        ctrl_c.clone(),
+        span,
+        options.clone(),
+    )?;
+    if response.status() == 200 || !needs_reprepare(response.content()) {
+        return Ok(response);
+    }
+
+    debug!("Preparing {} again", prepared.name());
+    let prepared = prepare_query(
+        cluster,
+        prepared.name(),
+        prepared.statement(),
+        prepared.scope(),
+        ctrl_c.clone(),
+        span,
+    )?;
+
+    execute_prepared(cluster, prepared, parameters, ctrl_c, span, options)
+}
+
+fn execute_prepared(
+    cluster: &RemoteCluster,
+    prepared: PreparedStatement,
+    parameters: Option<serde_json::Value>,
+    ctrl_c: Arc<AtomicBool>,
+    span: Span,
+    options: QueryOptions,
+) -> Result<HttpResponse, ShellError> {
+    let timeout = cluster.timeouts().query_timeout();
+    cluster
+        .cluster()
+        .http_client()
+        .query_request(
+            QueryRequest::ExecutePrepared {
+                prepared,
+                parameters,
+                timeout: duration_to_golang_string(timeout),
+                options,
+            },
+            Instant::now().add(timeout),
+            ctrl_c,
+        )
+        .map_err(|e| client_error_to_shell_error(e, span))
+}
+
+fn needs_reprepare(content: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(content)
+        .ok()
+        .and_then(|c| {
+            c["errors"].as_array().map(|errors| {
+                errors.iter().any(|e| {
+                    e["code"]
+                        .as_i64()
+                        .map(|code| REPREPARE_ERROR_CODES.contains(&code))
+                        .unwrap_or_default()
+                })
+            })
+        })
+        .unwrap_or_default()
+}
+
+// Statements prepared for `query --adhoc false` are named after the statement and its context, so
+// running the same statement again finds the plan in the cache.
+fn auto_prepared_name(statement: &str, scope: &Option<(String, String)>) -> String {
+    let mut hasher = DefaultHasher::new();
+    statement.hash(&mut hasher);
+    scope.hash(&mut hasher);
+    format!("cbsh_{:x}", hasher.finish())
+}
+
+// query_params_from_args reads the named or positional parameters given with --params.
+pub(crate) fn query_params_from_args(
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+) -> Result<Option<serde_json::Value>, ShellError> {
+    let span = call.head;
+    match call.get_flag::<Value>(engine_state, stack, "params")? {
+        Some(p) => match p {
+            Value::Record { .. } => Ok(Some(convert_nu_value_to_json_value(&p, span).unwrap())),
+            Value::List { .. } => Ok(Some(convert_nu_value_to_json_value(&p, span).unwrap())),
+            _ => Err(generic_error(
+                "Parameters must be a list or JSON object",
+                "Run 'query --help' to see examples".to_string(),
+                None,
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+// with_query_option_flags adds the flags for the query service request options shared by the
+// query commands.
+pub(crate) fn with_query_option_flags(signature: Signature) -> Signature {
+    signature
+        .switch(
+            "readonly",
+            "fail the query if it would modify any data",
+            None,
+        )
+        .named(
+            "profile",
+            SyntaxShape::String,
+            "the profiling information to return with --with-meta: off, phases or timings",
+            None,
+        )
+        .named(
+            "max-parallelism",
+            SyntaxShape::Int,
+            "the maximum number of index partitions to scan at once",
+            None,
+        )
+        .named(
+            "scan-cap",
+            SyntaxShape::Int,
+            "the maximum buffer size between the indexer and the query service",
+            None,
+        )
+        .named(
+            "pipeline-batch",
+            SyntaxShape::Int,
+            "the number of items each operator batches before passing them on",
+            None,
+        )
+        .named(
+            "pipeline-cap",
+            SyntaxShape::Int,
+            "the maximum number of items each operator can buffer",
+            None,
+        )
+        .switch(
+            "use-replica",
+            "allow documents to be read from replicas if the active copy is unavailable",
+            None,
+        )
+        .switch(
+            "flex-index",
+            "allow the query to use full text search indexes",
+            None,
+        )
+        .switch(
+            "preserve-expiry",
+            "keep the expiry of documents modified by the query",
+            None,
+        )
+        .named(
+            "client-context-id",
+            SyntaxShape::String,
+            "an identifier for the request, returned with the results and shown in the active requests",
+            None,
+        )
+}
+
+// query_options_from_args reads the flags added by with_query_option_flags.
+pub(crate) fn query_options_from_args(
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+) -> Result<QueryOptions, ShellError> {
+    let profile = match call.get_flag::<String>(engine_state, stack, "profile")? {
+        Some(p) => Some(QueryProfile::try_from(p.as_str()).map_err(|e| {
+            generic_error(
+                e,
+                "Profile must be one of off, phases or timings".to_string(),
+                call.head,
+            )
+        })?),
+        None => None,
+    };
+
+    let mut tuning = vec![];
+    for flag in [
+        "max-parallelism",
+        "scan-cap",
+        "pipeline-batch",
+        "pipeline-cap",
+    ] {
+        let value: Option<i64> = call.get_flag(engine_state, stack, flag)?;
+        if value.map(|v| u32::try_from(v).is_err()).unwrap_or_default() {
+            return Err(generic_error(
+                format!("--{} is out of range", flag),
+                "It must be a positive number".to_string(),
+                call.head,
+            ));
+        }
+        tuning.push(value.map(|v| v as u32));
+    }
+
+    Ok(QueryOptions::default()
+        .readonly(call.has_flag(engine_state, stack, "readonly")?)
+        .profile(profile)
+        .max_parallelism(tuning[0])
+        .scan_cap(tuning[1])
+        .pipeline_batch(tuning[2])
+        .pipeline_cap(tuning[3])
+        .use_replica(call.has_flag(engine_state, stack, "use-replica")?)
+        .flex_index(call.has_flag(engine_state, stack, "flex-index")?)
+        .preserve_expiry(call.has_flag(engine_state, stack, "preserve-expiry")?)
+        .client_context_id(call.get_flag::<String>(engine_state, stack, "client-context-id")?))
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Consistency {
+    NotBounded,
+    RequestPlus,
+    AtPlus,
+}
+
+// consistency_from_args reads the scan consistency asked for. The writes waited for with at-plus
+// differ between clusters, so are only looked up once the cluster is known.
+fn consistency_from_args(
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+) -> Result<Option<Consistency>, ShellError> {
+    let consistency = match call
+        .get_flag::<String>(engine_state, stack, "scan-consistency")?
+        .as_deref()
+    {
+        Some("not-bounded") => Some(Consistency::NotBounded),
+        Some("request-plus") => Some(Consistency::RequestPlus),
+        Some("at-plus") => Some(Consistency::AtPlus),
+        Some(c) => {
+            return Err(generic_error(
+                format!("Unknown scan consistency {}", c),
+                "Scan consistency must be one of not-bounded, request-plus or at-plus".to_string(),
+                call.head,
+            ))
+        }
+        None => None,
+    };
+
+    if call.has_flag(engine_state, stack, "consistent-with-last-write")? {
+        return match consistency {
+            Some(Consistency::AtPlus) | None => Ok(Some(Consistency::AtPlus)),
+            Some(_) => Err(generic_error(
+                "--consistent-with-last-write cannot be used with another scan consistency",
+                "It is the same as --scan-consistency at-plus".to_string(),
+                call.head,
+            )),
+        };
+    }
+
+    Ok(consistency)
+}
+
 pub fn handle_query_response(
     with_meta: bool,
     identifier: String,
@@ -226,56 +683,24 @@ pub fn handle_query_response(
 
     let mut results: Vec<Value> = vec![];
     if with_meta {
-        let content: serde_json::Value = serde_json::from_str(response.content())
+        let mut content: serde_json::Value = serde_json::from_str(response.content())
             .map_err(|e| deserialize_error(e.to_string(), span))?;
-        results.append(&mut convert_row_to_nu_value(&content, span, identifier)?);
+        let profile = content.as_object_mut().and_then(|c| c.remove("profile"));
+        let mut rows = convert_row_to_nu_value(&content, span, identifier)?;
+        if let Some(profile) = profile {
+            for row in rows.iter_mut() {
+                if let Value::Record { val, .. } = row {
+                    val.to_mut()
+                        .insert("profile", profile_into_value(&profile, span));
+                }
+            }
+        }
+        results.append(&mut rows);
     } else {
         let content: HashMap<String, serde_json::Value> = serde_json::from_str(response.content())
             .map_err(|e| deserialize_error(e.to_string(), span))?;
         if let Some(content_errors) = content.get("errors") {
-            return if let Some(arr) = content_errors.as_array() {
-                if arr.len() == 1 {
-                    let e = match arr.first() {
-                        Some(e) => e,
-                        None => {
-                            return Err(malformed_response_error(
-                                "query errors present but empty",
-                                content_errors.to_string(),
-                                span,
-                            ))
-                        }
-                    };
-                    let code = e.get("code").map(|c| c.as_i64().unwrap_or_default());
-                    let reason = match code {
-                        Some(c) => QueryErrorReason::from(c),
-                        None => QueryErrorReason::UnknownError,
-                    };
-                    let msg = match e.get("msg") {
-                        Some(msg) => msg.to_string(),
-                        None => "".to_string(),
-                    };
-                    Err(query_error(reason, code, msg, span))
-                } else {
-                    let messages = arr
-                        .iter()
-                        .map(|e| e.to_string())
-                        .collect::<Vec<String>>()
-                        .join(",");
-
-                    Err(query_error(
-                        QueryErrorReason::MultiErrors,
-                        None,
-                        messages,
-                        span,
-                    ))
-                }
-            } else {
-                Err(malformed_response_error(
-                    "query errors not an array",
-                    content_errors.to_string(),
-                    span,
-                ))
-            };
+            return Err(query_errors(content_errors, span));
         } else if let Some(content_results) = content.get("results") {
             if let Some(arr) = content_results.as_array() {
                 for result in arr {
@@ -298,6 +723,169 @@ pub fn handle_query_response(
     Ok(results)
 }
 
+// profile_into_value converts the profile of a query into a table of the time spent in each phase
+// and, when timings were requested, a table of the operators run.
+fn profile_into_value(profile: &serde_json::Value, span: Span) -> Value {
+    let mut phases = vec![];
+    if let Some(times) = profile.get("phaseTimes").and_then(|t| t.as_object()) {
+        for (phase, time) in times {
+            let mut row = NuValueMap::default();
+            row.add_string("phase", phase, span);
+            row.add("time", golang_duration_value(time, span));
+            row.add_i64(
+                "count",
+                profile["phaseCounts"][phase].as_i64().unwrap_or_default(),
+                span,
+            );
+            row.add_i64(
+                "operators",
+                profile["phaseOperators"][phase]
+                    .as_i64()
+                    .unwrap_or_default(),
+                span,
+            );
+            phases.push(row.into_value(span));
+        }
+    }
+
+    let mut operators = vec![];
+    if let Some(timings) = profile.get("executionTimings") {
+        add_operator_rows(timings, 0, &mut operators, span);
+    }
+
+    let mut collected = NuValueMap::default();
+    collected.add(
+        "phases",
+        Value::List {
+            vals: phases,
+            internal_span: span,
+        },
+    );
+    collected.add(
+        "operators",
+        Value::List {
+            vals: operators,
+            internal_span: span,
+        },
+    );
+    collected.into_value(span)
+}
+
+fn add_operator_rows(operator: &serde_json::Value, depth: i64, rows: &mut Vec<Value>, span: Span) {
+    let stats = &operator["#stats"];
+    let mut row = NuValueMap::default();
+    row.add_i64("depth", depth, span);
+    row.add_string(
+        "operator",
+        operator["#operator"].as_str().unwrap_or_default(),
+        span,
+    );
+    row.add_string(
+        "keyspace",
+        operator["keyspace"].as_str().unwrap_or_default(),
+        span,
+    );
+    row.add_string(
+        "index",
+        operator["index"].as_str().unwrap_or_default(),
+        span,
+    );
+    row.add_i64(
+        "items_in",
+        stats["#itemsIn"].as_i64().unwrap_or_default(),
+        span,
+    );
+    row.add_i64(
+        "items_out",
+        stats["#itemsOut"].as_i64().unwrap_or_default(),
+        span,
+    );
+    row.add("exec_time", golang_duration_value(&stats["execTime"], span));
+    row.add("kern_time", golang_duration_value(&stats["kernTime"], span));
+    row.add("serv_time", golang_duration_value(&stats["servTime"], span));
+    rows.push(row.into_value(span));
+
+    for child in plan_children(operator) {
+        add_operator_rows(child, depth + 1, rows, span);
+    }
+}
+
+fn golang_duration_value(value: &serde_json::Value, span: Span) -> Value {
+    let duration = value
+        .as_str()
+        .and_then(golang_string_to_duration)
+        .unwrap_or_default();
+    Value::duration(duration.as_nanos() as i64, span)
+}
+
+// plan_children returns the operators nested within an operator of a query plan, in the order
+// they appear in the plan.
+pub(crate) fn plan_children(operator: &serde_json::Value) -> Vec<&serde_json::Value> {
+    let mut children = vec![];
+    for key in ["~child", "scan", "first", "second"] {
+        if let Some(child) = operator.get(key).filter(|c| c.is_object()) {
+            children.push(child);
+        }
+    }
+    for key in ["~children", "scans", "~subqueries"] {
+        if let Some(nested) = operator.get(key).and_then(|c| c.as_array()) {
+            children.extend(nested);
+        }
+    }
+
+    // Subqueries wrap their operators along with the subquery text.
+    children
+        .into_iter()
+        .map(|c| {
+            c.get("executionTimings")
+                .or_else(|| c.get("plan"))
+                .unwrap_or(c)
+        })
+        .collect()
+}
+
+// query_errors converts the errors reported in a query response into a shell error.
+pub(crate) fn query_errors(content_errors: &serde_json::Value, span: Span) -> ShellError {
+    if let Some(arr) = content_errors.as_array() {
+        if arr.len() == 1 {
+            let e = match arr.first() {
+                Some(e) => e,
+                None => {
+                    return malformed_response_error(
+                        "query errors present but empty",
+                        content_errors.to_string(),
+                        span,
+                    )
+                }
+            };
+            let code = e.get("code").map(|c| c.as_i64().unwrap_or_default());
+            let reason = match code {
+                Some(c) => QueryErrorReason::from(c),
+                None => QueryErrorReason::UnknownError,
+            };
+            let msg = match e.get("msg") {
+                Some(msg) => msg.to_string(),
+                None => "".to_string(),
+            };
+            query_error(reason, code, msg, span)
+        } else {
+            let messages = arr
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+
+            query_error(QueryErrorReason::MultiErrors, None, messages, span)
+        }
+    } else {
+        malformed_response_error(
+            "query errors not an array",
+            content_errors.to_string(),
+            span,
+        )
+    }
+}
+
 pub fn query_context_from_args(
     cluster: &RemoteCluster,
     engine_state: &EngineState,
@@ -320,3 +908,216 @@ pub fn query_context_from_args(
         bucket.and_then(|b| scope.map(|s| (b, s)))
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Endpoint;
+    use crate::config::DEFAULT_KV_BATCH_SIZE;
+    use crate::remote_cluster::{ClusterTimeouts, RemoteClusterResources, RemoteClusterType};
+    use serde_json::json;
+    use std::collections::VecDeque;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    type Responses = Arc<Mutex<VecDeque<(u16, String)>>>;
+
+    // QueryNode is a single node serving its cluster config, and answering query requests with
+    // canned responses in turn. The bodies of the query requests are kept for inspection.
+    struct QueryNode {
+        port: u16,
+        requests: Arc<Mutex<Vec<serde_json::Value>>>,
+    }
+
+    impl QueryNode {
+        fn start(responses: Vec<(u16, serde_json::Value)>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let responses: Responses = Arc::new(Mutex::new(
+                responses
+                    .into_iter()
+                    .map(|(status, body)| (status, body.to_string()))
+                    .collect(),
+            ));
+            let requests = Arc::new(Mutex::new(vec![]));
+
+            let node_requests = requests.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let (responses, requests) = (responses.clone(), node_requests.clone());
+                    thread::spawn(move || {
+                        QueryNode::serve(stream.unwrap(), port, responses, requests)
+                    });
+                }
+            });
+
+            Self { port, requests }
+        }
+
+        fn serve(
+            stream: TcpStream,
+            port: u16,
+            responses: Responses,
+            requests: Arc<Mutex<Vec<serde_json::Value>>>,
+        ) {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            loop {
+                let mut request_line = String::new();
+                if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                    return;
+                }
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let (status, content) = if request_line.contains("/pools/default/nodeServices") {
+                    let config = json!({
+                        "nodesExt": [{
+                            "hostname": "127.0.0.1",
+                            "services": {"mgmt": port, "n1ql": port}
+                        }]
+                    });
+                    (200, config.to_string())
+                } else {
+                    requests
+                        .lock()
+                        .unwrap()
+                        .push(serde_json::from_slice(&body).unwrap());
+                    responses.lock().unwrap().pop_front().unwrap()
+                };
+
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    content.len(),
+                    content
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        }
+
+        fn cluster(&self) -> RemoteCluster {
+            RemoteCluster::new(
+                RemoteClusterResources {
+                    hostnames: vec![format!("127.0.0.1:{}", self.port)],
+                    username: "Administrator".to_string(),
+                    password: "password".to_string(),
+                    active_bucket: None,
+                    active_scope: None,
+                    active_collection: None,
+                    display_name: None,
+                },
+                None,
+                ClusterTimeouts::default(),
+                None,
+                None,
+                DEFAULT_KV_BATCH_SIZE,
+                None,
+                None,
+                RemoteClusterType::Other,
+            )
+        }
+
+        fn prepared(&self) -> PreparedStatement {
+            PreparedStatement::new(
+                "cbsh_test".to_string(),
+                "SELECT 1".to_string(),
+                Some("old-plan".to_string()),
+                Endpoint::new("127.0.0.1".to_string(), self.port as u32),
+                None,
+            )
+        }
+
+        fn requests(&self) -> Vec<serde_json::Value> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn lost_plan(code: i64) -> serde_json::Value {
+        json!({"errors": [{"code": code, "msg": "the plan is not available"}], "status": "fatal"})
+    }
+
+    #[test]
+    fn reprepares_only_for_lost_plans() {
+        for code in REPREPARE_ERROR_CODES {
+            assert!(needs_reprepare(&lost_plan(code).to_string()), "{}", code);
+        }
+        assert!(needs_reprepare(
+            &json!({"errors": [{"code": 5000}, {"code": 4050}]}).to_string()
+        ));
+
+        assert!(!needs_reprepare(&lost_plan(5000).to_string()));
+        assert!(!needs_reprepare(&json!({"results": []}).to_string()));
+        assert!(!needs_reprepare("not json"));
+    }
+
+    #[test]
+    fn prepares_again_and_retries_when_the_plan_is_lost() {
+        for code in REPREPARE_ERROR_CODES {
+            let node = QueryNode::start(vec![
+                (404, lost_plan(code)),
+                (
+                    200,
+                    json!({"results": [{"name": "cbsh_test", "encoded_plan": "new-plan"}]}),
+                ),
+                (200, json!({"results": [{"a": 1}], "status": "success"})),
+            ]);
+            let cluster = node.cluster();
+
+            let response = send_prepared_query(
+                &cluster,
+                node.prepared(),
+                None,
+                Arc::new(AtomicBool::new(false)),
+                Span::test_data(),
+                QueryOptions::default(),
+            )
+            .unwrap();
+            assert_eq!(200, response.status());
+
+            let requests = node.requests();
+            assert_eq!(3, requests.len(), "{}", code);
+            assert_eq!("old-plan", requests[0]["encoded_plan"]);
+            assert_eq!(
+                "PREPARE FORCE `cbsh_test` AS SELECT 1",
+                requests[1]["statement"]
+            );
+            assert_eq!("cbsh_test", requests[2]["prepared"]);
+            assert_eq!("new-plan", requests[2]["encoded_plan"]);
+
+            // The new plan replaces the old one in the cache.
+            assert!(cluster.prepared_statement("cbsh_test").is_some());
+        }
+    }
+
+    #[test]
+    fn does_not_retry_other_errors() {
+        let node = QueryNode::start(vec![(500, lost_plan(5000))]);
+
+        let response = send_prepared_query(
+            &node.cluster(),
+            node.prepared(),
+            None,
+            Arc::new(AtomicBool::new(false)),
+            Span::test_data(),
+            QueryOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(500, response.status());
+        assert_eq!(1, node.requests().len());
+    }
+}
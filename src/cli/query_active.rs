@@ -0,0 +1,179 @@
+use crate::cli::error::{client_error_to_shell_error, deserialize_error};
+use crate::cli::query::{query_errors, send_query_stream};
+use crate::cli::util::{
+    cluster_identifiers_from, convert_json_value_to_nu_value, get_active_cluster,
+    golang_string_to_duration, is_http_status, NuValueMap,
+};
+use crate::client::QueryOptions;
+use crate::state::State;
+use log::debug;
+use std::sync::{Arc, Mutex};
+
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, Span, SyntaxShape,
+    Value,
+};
+
+#[derive(Clone)]
+pub struct QueryActive {
+    state: Arc<Mutex<State>>,
+}
+
+impl QueryActive {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for QueryActive {
+    fn name(&self) -> &str {
+        "query active"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("query active")
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters to query against",
+                None,
+            )
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Lists the requests currently running on the query service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        _input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        system_requests(
+            self.state.clone(),
+            engine_state,
+            stack,
+            call,
+            "system:active_requests",
+        )
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![Example {
+            description: "Find the requests that have been running for over a minute",
+            example: "query active | where elapsed > 1min",
+            result: None,
+        }]
+    }
+}
+
+// system_requests lists the requests recorded in one of the system keyspaces of the query service,
+// which gather the requests of every query node in the cluster.
+pub(crate) fn system_requests(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    keyspace: &str,
+) -> Result<PipelineData, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let statement = format!(
+        "SELECT requestId, statement, elapsedTime, state, users, node, phaseCounts, clientContextID FROM {}",
+        keyspace
+    );
+
+    // The request listing the active requests is itself active, so is tagged to be left out.
+    let context_id = format!("cbsh-monitor-{}", uuid::Uuid::new_v4());
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+
+    let mut results: Vec<Value> = vec![];
+    for identifier in cluster_identifiers {
+        let guard = state.lock().unwrap();
+        let active_cluster = get_active_cluster(identifier.clone(), &guard, span)?;
+
+        debug!("Running n1ql query {}", &statement);
+
+        let response = send_query_stream(
+            active_cluster,
+            statement.clone(),
+            None,
+            None,
+            ctrl_c.clone(),
+            None,
+            span,
+            None,
+            QueryOptions::default().client_context_id(context_id.clone()),
+        )?
+        .into_response()
+        .map_err(|e| client_error_to_shell_error(e, span))?;
+        drop(guard);
+
+        let content: serde_json::Value = serde_json::from_str(response.content())
+            .map_err(|e| deserialize_error(e.to_string(), span))?;
+        if let Some(content_errors) = content.get("errors") {
+            return Err(query_errors(content_errors, span));
+        }
+        is_http_status(&response, 200, span)?;
+
+        if let Some(requests) = content["results"].as_array() {
+            for request in requests {
+                if request["clientContextID"] == context_id.as_str() {
+                    continue;
+                }
+                results.push(request_into_value(request, identifier.clone(), span)?);
+            }
+        }
+    }
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
+
+fn request_into_value(
+    request: &serde_json::Value,
+    identifier: String,
+    span: Span,
+) -> Result<Value, ShellError> {
+    let elapsed = request["elapsedTime"]
+        .as_str()
+        .and_then(golang_string_to_duration)
+        .unwrap_or_default();
+
+    let mut row = NuValueMap::default();
+    row.add_string(
+        "request_id",
+        request["requestId"].as_str().unwrap_or_default(),
+        span,
+    );
+    row.add_string(
+        "statement",
+        request["statement"].as_str().unwrap_or_default(),
+        span,
+    );
+    row.add("elapsed", Value::duration(elapsed.as_nanos() as i64, span));
+    row.add_string("state", request["state"].as_str().unwrap_or_default(), span);
+    row.add_string("user", request["users"].as_str().unwrap_or_default(), span);
+    row.add_string("node", request["node"].as_str().unwrap_or_default(), span);
+    row.add(
+        "phase_counts",
+        convert_json_value_to_nu_value(&request["phaseCounts"], span)?,
+    );
+    row.add_string(
+        "client_context_id",
+        request["clientContextID"].as_str().unwrap_or_default(),
+        span,
+    );
+    row.add_string("cluster", identifier, span);
+    Ok(row.into_value(span))
+}
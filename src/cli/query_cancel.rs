@@ -0,0 +1,167 @@
+use crate::cli::error::{client_error_to_shell_error, deserialize_error, generic_error};
+use crate::cli::query::{query_errors, send_query};
+use crate::cli::util::{cluster_identifiers_from, get_active_cluster, is_http_status};
+use crate::client::{ClientError, QueryAdminRequest};
+use crate::state::State;
+use log::debug;
+use std::ops::Add;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{Category, Example, PipelineData, ShellError, Signature, SyntaxShape};
+
+#[derive(Clone)]
+pub struct QueryCancel {
+    state: Arc<Mutex<State>>,
+}
+
+impl QueryCancel {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for QueryCancel {
+    fn name(&self) -> &str {
+        "query cancel"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("query cancel")
+            .required(
+                "request_id",
+                SyntaxShape::String,
+                "the id of the request to cancel",
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters to look for the request on",
+                None,
+            )
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Cancels a request running on the query service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![Example {
+            description: "Cancel every request that has been running for over ten minutes",
+            example: "query active | where elapsed > 10min | each {|r| query cancel $r.request_id --clusters $r.cluster }",
+            result: None,
+        }]
+    }
+}
+
+fn run(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    _input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let request_id: String = call.req(engine_state, stack, 0)?;
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+
+    let mut cancelled = false;
+    for identifier in cluster_identifiers {
+        let guard = state.lock().unwrap();
+        let active_cluster = get_active_cluster(identifier.clone(), &guard, span)?;
+
+        // Only the node running a request can cancel it, so find out which one that is first.
+        let response = send_query(
+            active_cluster,
+            "SELECT RAW node FROM system:active_requests WHERE requestId = $1",
+            Some(serde_json::json!([request_id])),
+            None,
+            ctrl_c.clone(),
+            None,
+            span,
+            None,
+        )?;
+        let client = active_cluster.cluster().http_client();
+        let deadline = Instant::now().add(active_cluster.timeouts().query_timeout());
+        drop(guard);
+
+        let content: serde_json::Value = serde_json::from_str(response.content())
+            .map_err(|e| deserialize_error(e.to_string(), span))?;
+        if let Some(content_errors) = content.get("errors") {
+            return Err(query_errors(content_errors, span));
+        }
+        is_http_status(&response, 200, span)?;
+
+        let node = match content["results"].get(0).and_then(|n| n.as_str()) {
+            Some(n) => n.to_string(),
+            None => continue,
+        };
+
+        let endpoint = match client
+            .query_endpoint_for_node(&node, deadline, ctrl_c.clone())
+            .map_err(|e| client_error_to_shell_error(e, span))?
+        {
+            Some(e) => e,
+            None => {
+                return Err(generic_error(
+                    format!(
+                        "Request {} is running on {}, which is not a known query node",
+                        request_id, node
+                    ),
+                    None,
+                    span,
+                ))
+            }
+        };
+
+        debug!("Cancelling query request {} on {}", &request_id, &endpoint);
+
+        let response = client
+            .query_admin_request(
+                QueryAdminRequest::CancelRequest {
+                    request_id: request_id.clone(),
+                },
+                endpoint,
+                deadline,
+                ctrl_c.clone(),
+            )
+            .map_err(|e| client_error_to_shell_error(e, span))?;
+
+        // The request may have finished in the meantime, which is as good as cancelled.
+        if response.status() != 200 && response.status() != 404 {
+            Err(ClientError::RequestFailed {
+                reason: Some(response.content().into()),
+                key: None,
+            })
+            .map_err(|e| client_error_to_shell_error(e, span))?;
+        }
+        cancelled = true;
+    }
+
+    if !cancelled {
+        return Err(generic_error(
+            format!("No active request with id {}", request_id),
+            "Run query active to list the requests that can be cancelled".to_string(),
+            span,
+        ));
+    }
+
+    Ok(PipelineData::empty())
+}
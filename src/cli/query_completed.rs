@@ -0,0 +1,64 @@
+use crate::cli::query_active::system_requests;
+use crate::state::State;
+use std::sync::{Arc, Mutex};
+
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{Category, Example, PipelineData, ShellError, Signature, SyntaxShape};
+
+#[derive(Clone)]
+pub struct QueryCompleted {
+    state: Arc<Mutex<State>>,
+}
+
+impl QueryCompleted {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for QueryCompleted {
+    fn name(&self) -> &str {
+        "query completed"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("query completed")
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters to query against",
+                None,
+            )
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Lists the completed requests the query service has kept, by default those which were slow or failed"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        _input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        system_requests(
+            self.state.clone(),
+            engine_state,
+            stack,
+            call,
+            "system:completed_requests",
+        )
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![Example {
+            description: "Show the slowest statements that have been run",
+            example:
+                "query completed | sort-by elapsed --reverse | first 10 | select statement elapsed",
+            result: None,
+        }]
+    }
+}
@@ -0,0 +1,142 @@
+use crate::cli::error::generic_error;
+use crate::cli::query::{
+    handle_query_response, query_options_from_args, query_params_from_args, send_prepared_query,
+    with_query_option_flags,
+};
+use crate::cli::util::{cluster_identifiers_from, get_active_cluster};
+use crate::state::State;
+use log::debug;
+use std::sync::{Arc, Mutex};
+
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+
+#[derive(Clone)]
+pub struct QueryExecute {
+    state: Arc<Mutex<State>>,
+}
+
+impl QueryExecute {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for QueryExecute {
+    fn name(&self) -> &str {
+        "query execute"
+    }
+
+    fn signature(&self) -> Signature {
+        let signature = Signature::build("query execute")
+            .required(
+                "name",
+                SyntaxShape::String,
+                "the name the statement was prepared as",
+            )
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters to query against",
+                None,
+            )
+            .named(
+                "params",
+                SyntaxShape::Any,
+                "named or positional parameters for the query",
+                None,
+            )
+            .switch("with-meta", "include toplevel metadata", None);
+
+        with_query_option_flags(signature).category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Runs a statement prepared with query prepare"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![Example {
+            description: "Run a prepared statement with named parameters",
+            example: "query prepare by_country \"SELECT name FROM `travel-sample`.inventory.landmark WHERE country = $country\"; query execute by_country --params {country: France}",
+            result: None,
+        }]
+    }
+}
+
+fn run(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    _input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let name: String = call.req(engine_state, stack, 0)?;
+    let params = query_params_from_args(engine_state, stack, call)?;
+    let options = query_options_from_args(engine_state, stack, call)?;
+    let with_meta = call.has_flag(engine_state, stack, "with-meta")?;
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+
+    let mut results: Vec<Value> = vec![];
+    for identifier in cluster_identifiers {
+        let guard = state.lock().unwrap();
+        let active_cluster = get_active_cluster(identifier.clone(), &guard, span)?;
+
+        let prepared = match active_cluster.prepared_statement(&name) {
+            Some(p) => p,
+            None => {
+                return Err(generic_error(
+                    format!(
+                        "No statement named {} has been prepared on {}",
+                        name, identifier
+                    ),
+                    "Prepare the statement first with query prepare".to_string(),
+                    span,
+                ))
+            }
+        };
+
+        debug!("Running prepared n1ql query {}", prepared.name());
+
+        let response = send_prepared_query(
+            active_cluster,
+            prepared,
+            params.clone(),
+            ctrl_c.clone(),
+            span,
+            options.clone(),
+        )?;
+        drop(guard);
+
+        results.extend(handle_query_response(
+            with_meta,
+            identifier.clone(),
+            response,
+            span,
+        )?);
+    }
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
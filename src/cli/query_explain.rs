@@ -0,0 +1,474 @@
+use crate::cli::error::{deserialize_error, malformed_response_error};
+use crate::cli::query::{plan_children, query_context_from_args, query_errors, send_query};
+use crate::cli::util::{cluster_identifiers_from, get_active_cluster, is_http_status, NuValueMap};
+use crate::state::State;
+use log::debug;
+use std::sync::{Arc, Mutex};
+
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, Span, SyntaxShape,
+    Value,
+};
+
+#[derive(Clone)]
+pub struct QueryExplain {
+    state: Arc<Mutex<State>>,
+}
+
+impl QueryExplain {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for QueryExplain {
+    fn name(&self) -> &str {
+        "query explain"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("query explain")
+            .required("statement", SyntaxShape::String, "the query statement")
+            .switch("disable-context", "disable automatically detecting the query context based on the active bucket and scope", None)
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters to query against",
+                None,
+            )
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Explains how a query would be executed and flags operators that are likely to be slow"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Show the operators that a query would use",
+                example: "query explain \"SELECT * FROM `travel-sample`.inventory.landmark WHERE country = 'France'\" | get operators.0",
+                result: None,
+            },
+            Example {
+                description: "Only list the problems found in the plan",
+                example: "query explain \"SELECT * FROM `travel-sample`.inventory.landmark\" | get summary.0",
+                result: None,
+            },
+        ]
+    }
+}
+
+fn run(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    _input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let statement: String = call.req(engine_state, stack, 0)?;
+    let statement = format!("EXPLAIN {}", statement);
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+
+    let mut results: Vec<Value> = vec![];
+    for identifier in cluster_identifiers {
+        let guard = state.lock().unwrap();
+        let active_cluster = get_active_cluster(identifier.clone(), &guard, span)?;
+
+        let maybe_scope = query_context_from_args(active_cluster, engine_state, stack, call)?;
+
+        debug!("Running n1ql explain query {}", &statement);
+
+        let response = send_query(
+            active_cluster,
+            statement.clone(),
+            None,
+            maybe_scope,
+            ctrl_c.clone(),
+            None,
+            span,
+            None,
+        )?;
+        drop(guard);
+
+        let content: serde_json::Value = serde_json::from_str(response.content())
+            .map_err(|e| deserialize_error(e.to_string(), span))?;
+        if let Some(content_errors) = content.get("errors") {
+            return Err(query_errors(content_errors, span));
+        }
+        is_http_status(&response, 200, span)?;
+
+        let plan = match content["results"].get(0).and_then(|r| r.get("plan")) {
+            Some(p) => p,
+            None => {
+                return Err(malformed_response_error(
+                    "explain results did not contain a plan",
+                    content.to_string(),
+                    span,
+                ))
+            }
+        };
+
+        let operators = explain_plan(plan);
+        let issues = plan_issues(&operators);
+
+        let mut collected = NuValueMap::default();
+        collected.add(
+            "operators",
+            Value::List {
+                vals: operators.iter().map(|o| o.to_value(span)).collect(),
+                internal_span: span,
+            },
+        );
+        collected.add(
+            "summary",
+            Value::List {
+                vals: issues.iter().map(|i| i.to_value(span)).collect(),
+                internal_span: span,
+            },
+        );
+        collected.add_string("cluster", identifier, span);
+        results.push(collected.into_value(span));
+    }
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
+
+#[derive(Debug)]
+struct ExplainedOperator {
+    depth: i64,
+    operator: String,
+    keyspace: String,
+    alias: String,
+    index: String,
+    spans: Vec<String>,
+    unbounded: bool,
+    covers: Vec<String>,
+    filter: String,
+    limit: String,
+}
+
+impl ExplainedOperator {
+    fn is_primary_scan(&self) -> bool {
+        self.operator.starts_with("PrimaryScan")
+    }
+
+    fn is_index_scan(&self) -> bool {
+        self.operator.starts_with("IndexScan")
+    }
+
+    // The alias is what ties a scan to the fetch that follows it, the keyspace name alone is
+    // ambiguous when a collection is joined with itself.
+    fn source(&self) -> &str {
+        if self.alias.is_empty() {
+            &self.keyspace
+        } else {
+            &self.alias
+        }
+    }
+
+    fn to_value(&self, span: Span) -> Value {
+        let mut row = NuValueMap::default();
+        row.add_i64("depth", self.depth, span);
+        row.add_string("operator", self.operator.clone(), span);
+        row.add_string("keyspace", self.keyspace.clone(), span);
+        row.add_string("index", self.index.clone(), span);
+        row.add("spans", strings_into_value(&self.spans, span));
+        row.add("covers", strings_into_value(&self.covers, span));
+        row.add_string("filter", self.filter.clone(), span);
+        row.add_string("limit", self.limit.clone(), span);
+        row.into_value(span)
+    }
+}
+
+#[derive(Debug)]
+struct PlanIssue {
+    operator: String,
+    keyspace: String,
+    index: String,
+    issue: String,
+}
+
+impl PlanIssue {
+    fn new(operator: &ExplainedOperator, issue: impl Into<String>) -> Self {
+        Self {
+            operator: operator.operator.clone(),
+            keyspace: operator.keyspace.clone(),
+            index: operator.index.clone(),
+            issue: issue.into(),
+        }
+    }
+
+    fn to_value(&self, span: Span) -> Value {
+        let mut row = NuValueMap::default();
+        row.add_string("operator", self.operator.clone(), span);
+        row.add_string("keyspace", self.keyspace.clone(), span);
+        row.add_string("index", self.index.clone(), span);
+        row.add_string("issue", self.issue.clone(), span);
+        row.into_value(span)
+    }
+}
+
+fn strings_into_value(vals: &[String], span: Span) -> Value {
+    Value::List {
+        vals: vals.iter().map(|v| Value::string(v, span)).collect(),
+        internal_span: span,
+    }
+}
+
+// explain_plan flattens the operator tree of a plan into a list, depth first, recording how deep
+// each operator was nested.
+fn explain_plan(plan: &serde_json::Value) -> Vec<ExplainedOperator> {
+    let mut operators = vec![];
+    add_explained_operators(plan, 0, &mut operators);
+    operators
+}
+
+fn add_explained_operators(
+    operator: &serde_json::Value,
+    depth: i64,
+    operators: &mut Vec<ExplainedOperator>,
+) {
+    let name = json_string(&operator["#operator"]);
+
+    let mut spans = vec![];
+    let mut unbounded = false;
+    if let Some(arr) = operator["spans"].as_array() {
+        for span in arr {
+            let (formatted, span_unbounded) = format_span(span);
+            spans.push(formatted);
+            unbounded |= span_unbounded;
+        }
+    }
+
+    let covers = operator["covers"]
+        .as_array()
+        .map(|c| c.iter().map(json_string).collect())
+        .unwrap_or_default();
+
+    let filter = if operator["filter"].is_null() {
+        json_string(&operator["condition"])
+    } else {
+        json_string(&operator["filter"])
+    };
+
+    let limit = if name == "Limit" {
+        json_string(&operator["expr"])
+    } else {
+        json_string(&operator["limit"])
+    };
+
+    operators.push(ExplainedOperator {
+        depth,
+        operator: name,
+        keyspace: json_string(&operator["keyspace"]),
+        alias: json_string(&operator["as"]),
+        index: json_string(&operator["index"]),
+        spans,
+        unbounded,
+        covers,
+        filter,
+        limit,
+    });
+
+    for child in plan_children(operator) {
+        add_explained_operators(child, depth + 1, operators);
+    }
+}
+
+fn json_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "".to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+// format_span renders an index span as an interval per index key, e.g.
+// `country` ["France", "France"], and reports whether the span places no bounds on the index.
+fn format_span(span: &serde_json::Value) -> (String, bool) {
+    let ranges = match span["range"].as_array() {
+        Some(r) => r,
+        None => return (span.to_string(), false),
+    };
+    if ranges.is_empty() {
+        return ("*".to_string(), true);
+    }
+
+    let mut formatted = vec![];
+    let mut unbounded = true;
+    for range in ranges {
+        let low = json_string(&range["low"]);
+        let high = json_string(&range["high"]);
+        let inclusion = range["inclusion"].as_i64().unwrap_or_default();
+
+        // A lower bound of null or missing only skips documents without the field, which is
+        // hardly any restriction at all.
+        let low_unbounded = low.is_empty() || low == "null" || low == "missing";
+        if !low_unbounded || !high.is_empty() {
+            unbounded = false;
+        }
+
+        formatted.push(format!(
+            "{} {}{}, {}{}",
+            json_string(&range["index_key"]),
+            if inclusion & 1 == 1 { "[" } else { "(" },
+            if low.is_empty() { "*" } else { low.as_str() },
+            if high.is_empty() { "*" } else { high.as_str() },
+            if inclusion & 2 == 2 { "]" } else { ")" },
+        ));
+    }
+
+    (formatted.join(" AND "), unbounded)
+}
+
+// plan_issues looks for the patterns that usually make a query slow: scanning the primary index,
+// scanning a whole secondary index and fetching documents that a covering index could avoid.
+fn plan_issues(operators: &[ExplainedOperator]) -> Vec<PlanIssue> {
+    let mut issues = vec![];
+    for operator in operators {
+        if operator.is_primary_scan() {
+            issues.push(PlanIssue::new(
+                operator,
+                "primary index scan reads every document in the keyspace, create a secondary index for the predicates used",
+            ));
+        } else if operator.is_index_scan() && operator.unbounded {
+            issues.push(PlanIssue::new(
+                operator,
+                "index scan has an unbounded span and reads the whole index, add a predicate on the leading index key",
+            ));
+        }
+    }
+
+    for fetch in operators.iter().filter(|o| o.operator == "Fetch") {
+        let scan = operators
+            .iter()
+            .find(|o| o.is_index_scan() && o.covers.is_empty() && o.source() == fetch.source());
+        if let Some(scan) = scan {
+            issues.push(PlanIssue {
+                operator: fetch.operator.clone(),
+                keyspace: fetch.keyspace.clone(),
+                index: scan.index.clone(),
+                issue: format!(
+                    "documents are fetched after scanning {}, an index covering every field the query uses would avoid the fetch",
+                    scan.index
+                ),
+            });
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn explains_plan_and_flags_issues() {
+        let plan = json!({
+            "#operator": "Sequence",
+            "~children": [
+                {
+                    "#operator": "IndexScan3",
+                    "as": "l",
+                    "index": "idx_country",
+                    "keyspace": "landmark",
+                    "spans": [{
+                        "exact": true,
+                        "range": [{
+                            "high": "\"France\"",
+                            "inclusion": 3,
+                            "index_key": "`country`",
+                            "low": "\"France\""
+                        }]
+                    }]
+                },
+                {
+                    "#operator": "Fetch",
+                    "as": "l",
+                    "keyspace": "landmark"
+                },
+                {
+                    "#operator": "IndexScan3",
+                    "index": "idx_name",
+                    "keyspace": "airline",
+                    "covers": ["cover ((`airline`.`name`))"],
+                    "limit": "10",
+                    "spans": [{
+                        "range": [{
+                            "inclusion": 0,
+                            "index_key": "`name`",
+                            "low": "null"
+                        }]
+                    }]
+                },
+                {
+                    "#operator": "PrimaryScan3",
+                    "index": "def_primary",
+                    "keyspace": "hotel"
+                },
+                {
+                    "#operator": "Filter",
+                    "condition": "(`hotel`.`city` = \"Paris\")"
+                },
+                {
+                    "#operator": "Limit",
+                    "expr": "5"
+                }
+            ]
+        });
+
+        let operators = explain_plan(&plan);
+        assert_eq!(7, operators.len());
+        assert_eq!("Sequence", operators[0].operator);
+        assert_eq!(0, operators[0].depth);
+        assert_eq!(1, operators[1].depth);
+        assert_eq!(
+            vec!["`country` [\"France\", \"France\"]".to_string()],
+            operators[1].spans
+        );
+        assert!(!operators[1].unbounded);
+        assert_eq!(vec!["`name` (null, *)".to_string()], operators[3].spans);
+        assert!(operators[3].unbounded);
+        assert_eq!("10", operators[3].limit);
+        assert_eq!("(`hotel`.`city` = \"Paris\")", operators[5].filter);
+        assert_eq!("5", operators[6].limit);
+
+        let issues = plan_issues(&operators);
+        let flagged: Vec<(&str, &str)> = issues
+            .iter()
+            .map(|i| (i.operator.as_str(), i.index.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("IndexScan3", "idx_name"),
+                ("PrimaryScan3", "def_primary"),
+                ("Fetch", "idx_country"),
+            ],
+            flagged
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::cli::query::{prepare_query, query_context_from_args};
+use crate::cli::util::{cluster_identifiers_from, get_active_cluster, NuValueMap};
+use crate::state::State;
+use log::debug;
+use std::sync::{Arc, Mutex};
+
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+
+#[derive(Clone)]
+pub struct QueryPrepare {
+    state: Arc<Mutex<State>>,
+}
+
+impl QueryPrepare {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for QueryPrepare {
+    fn name(&self) -> &str {
+        "query prepare"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("query prepare")
+            .required("name", SyntaxShape::String, "the name to prepare the statement as")
+            .required("statement", SyntaxShape::String, "the query statement")
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters to prepare the statement on",
+                None,
+            )
+            .named(
+                "bucket",
+                SyntaxShape::String,
+                "the bucket to query against",
+                None,
+            )
+            .named(
+                "scope",
+                SyntaxShape::String,
+                "the scope to query against",
+                None,
+            )
+            .switch("disable-context", "disable automatically detecting the query context based on the active bucket and scope", None)
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Prepares a n1ql statement to be run with query execute"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![Example {
+            description: "Prepare a statement and then run it with different parameters",
+            example: "query prepare routes \"SELECT * FROM `travel-sample`.inventory.route WHERE sourceairport = $1\"; [LAX SFO] | each {|a| query execute routes --params [$a] }",
+            result: None,
+        }]
+    }
+}
+
+fn run(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    _input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let name: String = call.req(engine_state, stack, 0)?;
+    let statement: String = call.req(engine_state, stack, 1)?;
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+
+    let mut results: Vec<Value> = vec![];
+    for identifier in cluster_identifiers {
+        let guard = state.lock().unwrap();
+        let active_cluster = get_active_cluster(identifier.clone(), &guard, span)?;
+
+        let maybe_scope = query_context_from_args(active_cluster, engine_state, stack, call)?;
+
+        debug!("Preparing n1ql query {} as {}", &statement, &name);
+
+        let prepared = prepare_query(
+            active_cluster,
+            &name,
+            &statement,
+            maybe_scope,
+            ctrl_c.clone(),
+            span,
+        )?;
+        drop(guard);
+
+        let mut collected = NuValueMap::default();
+        collected.add_string("name", prepared.name(), span);
+        collected.add_string("statement", prepared.statement(), span);
+        collected.add_string("node", prepared.endpoint().to_string(), span);
+        collected.add_string("cluster", identifier, span);
+        results.push(collected.into_value(span));
+    }
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
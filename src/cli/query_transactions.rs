@@ -6,8 +6,13 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::cli::error::{deserialize_error, generic_error, no_active_cluster_error};
-use crate::cli::query::{handle_query_response, query_context_from_args, send_query};
+use crate::cli::error::{
+    client_error_to_shell_error, deserialize_error, generic_error, no_active_cluster_error,
+};
+use crate::cli::query::{
+    handle_query_response, query_context_from_args, query_options_from_args, send_query_stream,
+    with_query_option_flags,
+};
 use nu_engine::CallExt;
 use nu_protocol::ast::Call;
 use nu_protocol::engine::{Command, EngineState, Stack};
@@ -33,7 +38,7 @@ impl Command for QueryTransactions {
     }
 
     fn signature(&self) -> Signature {
-        Signature::build("query transactions")
+        let signature = Signature::build("query transactions")
             .required("statement", SyntaxShape::String, "the query statement")
             .named(
                 "bucket",
@@ -50,8 +55,9 @@ impl Command for QueryTransactions {
             .named("query-timeout", SyntaxShape::Int, "timeout (milliseconds) to apply to the query", None)
             .named("transaction-timeout", SyntaxShape::Int, "timeout (milliseconds) to apply to the transaction, only applicable when starting the transaction", None)
             .switch("with-meta", "include toplevel metadata", None)
-            .switch("disable-context", "disable automatically detecting the query context based on the active bucket and scope", None)
-            .category(Category::Custom("couchbase".to_string()))
+            .switch("disable-context", "disable automatically detecting the query context based on the active bucket and scope", None);
+
+        with_query_option_flags(signature).category(Category::Custom("couchbase".to_string()))
     }
 
     fn usage(&self) -> &str {
@@ -84,6 +90,7 @@ fn query(
     validate_statement(&statement, span)?;
 
     let statement_type = parse_statement(&statement);
+    let options = query_options_from_args(engine_state, stack, call)?;
 
     let st = state.clone();
     let mut guard = st.lock().unwrap();
@@ -131,7 +138,7 @@ fn query(
 
     debug!("Running n1ql query transaction {}", &statement);
 
-    let response = send_query(
+    let response = send_query_stream(
         active_cluster,
         statement.clone(),
         None,
@@ -140,7 +147,12 @@ fn query(
         timeout,
         span,
         txn_request,
-    );
+        options,
+    )
+    .and_then(|r| {
+        r.into_response()
+            .map_err(|e| client_error_to_shell_error(e, span))
+    });
     if response.is_err() {
         info!("Ending transaction due to error");
         guard.end_transaction();
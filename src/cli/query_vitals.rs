@@ -0,0 +1,129 @@
+use crate::cli::error::{client_error_to_shell_error, deserialize_error};
+use crate::cli::util::{
+    cluster_identifiers_from, convert_json_value_to_nu_value, get_active_cluster, is_http_status,
+    NuValueMap,
+};
+use crate::client::QueryAdminRequest;
+use crate::state::State;
+use log::debug;
+use std::ops::Add;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{
+    Category, Example, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+};
+
+#[derive(Clone)]
+pub struct QueryVitals {
+    state: Arc<Mutex<State>>,
+}
+
+impl QueryVitals {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for QueryVitals {
+    fn name(&self) -> &str {
+        "query vitals"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("query vitals")
+            .named(
+                "clusters",
+                SyntaxShape::String,
+                "the clusters to query against",
+                None,
+            )
+            .category(Category::Custom("couchbase".to_string()))
+    }
+
+    fn usage(&self) -> &str {
+        "Shows the load, memory use and request rates of each query node"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run(self.state.clone(), engine_state, stack, call, input)
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![Example {
+            description: "Compare the number of active requests on each query node",
+            example: "query vitals | select node request_active_count",
+            result: None,
+        }]
+    }
+}
+
+fn run(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    _input: PipelineData,
+) -> Result<PipelineData, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+
+    let mut results: Vec<Value> = vec![];
+    for identifier in cluster_identifiers {
+        let guard = state.lock().unwrap();
+        let active_cluster = get_active_cluster(identifier.clone(), &guard, span)?;
+        let client = active_cluster.cluster().http_client();
+        let deadline = Instant::now().add(active_cluster.timeouts().query_timeout());
+        drop(guard);
+
+        let endpoints = client
+            .query_endpoints(deadline, ctrl_c.clone())
+            .map_err(|e| client_error_to_shell_error(e, span))?;
+
+        for endpoint in endpoints {
+            debug!("Fetching query vitals from {}", &endpoint);
+
+            let response = client
+                .query_admin_request(
+                    QueryAdminRequest::Vitals,
+                    endpoint.clone(),
+                    deadline,
+                    ctrl_c.clone(),
+                )
+                .map_err(|e| client_error_to_shell_error(e, span))?;
+            is_http_status(&response, 200, span)?;
+
+            let content: serde_json::Map<String, serde_json::Value> =
+                serde_json::from_str(response.content())
+                    .map_err(|e| deserialize_error(e.to_string(), span))?;
+
+            let mut collected = NuValueMap::default();
+            collected.add_string("node", endpoint.to_string(), span);
+            // Vitals are named like request.per.sec.1min, which would be read as a cell path.
+            for (name, value) in content.iter() {
+                collected.add(
+                    name.replace(['.', '-'], "_"),
+                    convert_json_value_to_nu_value(value, span)?,
+                );
+            }
+            collected.add_string("cluster", identifier.clone(), span);
+            results.push(collected.into_value(span));
+        }
+    }
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
@@ -0,0 +1,65 @@
+//! The `subdoc array-add-unique` command performs a KV sub-document mutation.
+
+use crate::cli::subdoc_common::{run_subdoc_mutation_command, subdoc_mutation_signature};
+use crate::client::SubdocMutationOp;
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{Example, PipelineData, ShellError, Signature};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct SubDocArrayAddUnique {
+    state: Arc<Mutex<State>>,
+}
+
+impl SubDocArrayAddUnique {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for SubDocArrayAddUnique {
+    fn name(&self) -> &str {
+        "subdoc array-add-unique"
+    }
+
+    fn signature(&self) -> Signature {
+        subdoc_mutation_signature(
+            "subdoc array-add-unique",
+            "the path of the array",
+            Some("the value to add"),
+        )
+    }
+
+    fn usage(&self) -> &str {
+        "Adds a value to the array at the provided path through the data service, failing if the value is already present"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_subdoc_mutation_command(
+            self.state.clone(),
+            engine_state,
+            stack,
+            call,
+            input,
+            SubdocMutationOp::ArrayAddUnique,
+        )
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Adds a tag to the tags array of the document with the ID landmark_10019 if not already present",
+                example: r#"subdoc array-add-unique landmark_10019 tags castle"#,
+                result: None,
+            },
+        ]
+    }
+}
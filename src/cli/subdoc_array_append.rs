@@ -0,0 +1,64 @@
+//! The `subdoc array-append` command performs a KV sub-document mutation.
+
+use crate::cli::subdoc_common::{run_subdoc_mutation_command, subdoc_mutation_signature};
+use crate::client::SubdocMutationOp;
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{Example, PipelineData, ShellError, Signature};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct SubDocArrayAppend {
+    state: Arc<Mutex<State>>,
+}
+
+impl SubDocArrayAppend {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for SubDocArrayAppend {
+    fn name(&self) -> &str {
+        "subdoc array-append"
+    }
+
+    fn signature(&self) -> Signature {
+        subdoc_mutation_signature(
+            "subdoc array-append",
+            "the path of the array",
+            Some("the value, or list of values, to append"),
+        )
+    }
+
+    fn usage(&self) -> &str {
+        "Appends one or more values to the end of the array at the provided path through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_subdoc_mutation_command(
+            self.state.clone(),
+            engine_state,
+            stack,
+            call,
+            input,
+            SubdocMutationOp::ArrayAppend,
+        )
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![Example {
+            description:
+                "Appends two tags to the tags array of the document with the ID landmark_10019",
+            example: r#"subdoc array-append landmark_10019 tags [castle museum]"#,
+            result: None,
+        }]
+    }
+}
@@ -0,0 +1,64 @@
+//! The `subdoc array-prepend` command performs a KV sub-document mutation.
+
+use crate::cli::subdoc_common::{run_subdoc_mutation_command, subdoc_mutation_signature};
+use crate::client::SubdocMutationOp;
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{Example, PipelineData, ShellError, Signature};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct SubDocArrayPrepend {
+    state: Arc<Mutex<State>>,
+}
+
+impl SubDocArrayPrepend {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for SubDocArrayPrepend {
+    fn name(&self) -> &str {
+        "subdoc array-prepend"
+    }
+
+    fn signature(&self) -> Signature {
+        subdoc_mutation_signature(
+            "subdoc array-prepend",
+            "the path of the array",
+            Some("the value, or list of values, to prepend"),
+        )
+    }
+
+    fn usage(&self) -> &str {
+        "Prepends one or more values to the start of the array at the provided path through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_subdoc_mutation_command(
+            self.state.clone(),
+            engine_state,
+            stack,
+            call,
+            input,
+            SubdocMutationOp::ArrayPrepend,
+        )
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![Example {
+            description:
+                "Prepends a tag to the tags array of the document with the ID landmark_10019",
+            example: r#"subdoc array-prepend landmark_10019 tags castle"#,
+            result: None,
+        }]
+    }
+}
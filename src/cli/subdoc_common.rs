@@ -0,0 +1,468 @@
+use crate::cli::doc_common::{
+    build_batched_kv_items, get_active_cluster_client_cid, id_from_value,
+};
+use crate::cli::error::{generic_error, serialize_error};
+use crate::cli::util::{
+    cluster_identifiers_from, convert_json_value_to_nu_value, convert_nu_value_to_json_value,
+    NuValueMap,
+};
+use crate::client::{ClientError, KeyValueRequest, SubdocMutation, SubdocMutationOp};
+use crate::state::State;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use log::debug;
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{EngineState, Stack};
+use nu_protocol::{
+    Category, IntoPipelineData, PipelineData, ShellError, Signature, Span, SyntaxShape, Value,
+};
+use std::collections::HashMap;
+use std::ops::Add;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
+use tokio::time::Instant;
+
+// The server rejects multi mutations containing more than 16 specs.
+const MAX_SUBDOC_SPECS: usize = 16;
+
+// subdoc_mutation_signature builds the signature shared by the subdoc mutation commands. Commands
+// which take no value, like remove, pass None for the value description.
+pub(crate) fn subdoc_mutation_signature(
+    name: &str,
+    path_description: &str,
+    value_description: Option<&str>,
+) -> Signature {
+    let mut signature = Signature::build(name)
+        .optional("id", SyntaxShape::String, "the document id")
+        .optional("path", SyntaxShape::String, path_description);
+    if let Some(description) = value_description {
+        signature = signature.optional("value", SyntaxShape::Any, description);
+    }
+
+    signature = signature
+        .named(
+            "id-column",
+            SyntaxShape::String,
+            "the name of the id column if used with an input stream",
+            None,
+        )
+        .named(
+            "path-column",
+            SyntaxShape::String,
+            "the name of the path column if used with an input stream",
+            None,
+        );
+    if value_description.is_some() {
+        signature = signature.named(
+            "value-column",
+            SyntaxShape::String,
+            "the name of the value column if used with an input stream",
+            None,
+        );
+    }
+
+    signature
+        .named(
+            "bucket",
+            SyntaxShape::String,
+            "the name of the bucket",
+            None,
+        )
+        .named(
+            "expiry",
+            SyntaxShape::Number,
+            "the expiry for the documents in seconds, or absolute",
+            None,
+        )
+        .named(
+            "cas",
+            SyntaxShape::Int,
+            "the cas value the documents must have for the mutation to succeed",
+            None,
+        )
+        .switch("create-path", "create any missing parent paths", None)
+        .switch("xattr", "the path refers to an extended attribute", None)
+        .named("scope", SyntaxShape::String, "the name of the scope", None)
+        .named(
+            "collection",
+            SyntaxShape::String,
+            "the name of the collection",
+            None,
+        )
+        .named(
+            "clusters",
+            SyntaxShape::String,
+            "the clusters which should be contacted",
+            None,
+        )
+        .named(
+            "batch-size",
+            SyntaxShape::Number,
+            "the maximum number of items to batch send at a time",
+            None,
+        )
+        .switch("halt-on-error", "halt on any errors", Some('e'))
+        .category(Category::Custom("couchbase".to_string()))
+}
+
+// run_subdoc_mutation_command runs a subdoc mutation command, grouping the paths given for each
+// document into a single multi mutation.
+pub(crate) fn run_subdoc_mutation_command(
+    state: Arc<Mutex<State>>,
+    engine_state: &EngineState,
+    stack: &mut Stack,
+    call: &Call,
+    input: PipelineData,
+    op: SubdocMutationOp,
+) -> Result<PipelineData, ShellError> {
+    let span = call.head;
+    let ctrl_c = engine_state.ctrlc.as_ref().unwrap().clone();
+
+    let id_column = call
+        .get_flag(engine_state, stack, "id-column")?
+        .unwrap_or_else(|| String::from("id"));
+    let path_column = call
+        .get_flag(engine_state, stack, "path-column")?
+        .unwrap_or_else(|| String::from("path"));
+    let value_column = call
+        .get_flag(engine_state, stack, "value-column")?
+        .unwrap_or_else(|| String::from("value"));
+
+    let cas: i64 = call.get_flag(engine_state, stack, "cas")?.unwrap_or(0);
+    let expiry: i64 = call.get_flag(engine_state, stack, "expiry")?.unwrap_or(0);
+    let create_path = call.has_flag(engine_state, stack, "create-path")?;
+    let xattr = call.has_flag(engine_state, stack, "xattr")?;
+    let batch_size: Option<i64> = call.get_flag(engine_state, stack, "batch-size")?;
+
+    let bucket_flag = call.get_flag(engine_state, stack, "bucket")?;
+    let scope_flag = call.get_flag(engine_state, stack, "scope")?;
+    let collection_flag = call.get_flag(engine_state, stack, "collection")?;
+    let halt_on_error = call.has_flag(engine_state, stack, "halt-on-error")?;
+
+    let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
+
+    let mut input_args = vec![];
+    if let Some(id) = call.opt::<String>(engine_state, stack, 0)? {
+        if let Some(path) = call.opt::<String>(engine_state, stack, 1)? {
+            if op.has_value() {
+                if let Some(v) = call.opt::<Value>(engine_state, stack, 2)? {
+                    input_args.push((id, path, Some(v)));
+                }
+            } else {
+                input_args.push((id, path, None));
+            }
+        }
+    }
+
+    let filtered = input.into_iter().filter_map(|i| {
+        if let Value::Record { val, .. } = i {
+            let mut id = None;
+            let mut path = None;
+            let mut value = None;
+            for (k, v) in val.iter() {
+                if k.clone() == id_column {
+                    id = id_from_value(v, span);
+                }
+                if k.clone() == path_column {
+                    path = v.as_str().ok().map(|p| p.to_string());
+                }
+                if k.clone() == value_column {
+                    value = Some(v.clone());
+                }
+            }
+
+            if let Some(p) = path {
+                if value.is_some() || !op.has_value() {
+                    return Some((id.unwrap_or_default(), p, value));
+                }
+            }
+        }
+        None
+    });
+
+    // Specs for the same document are grouped so that they are applied atomically, preserving the
+    // order in which they were received. Splitting a group would lose that atomicity, so a
+    // document with too many specs is rejected instead.
+    let mut docs: Vec<(String, Vec<SubdocMutation>)> = vec![];
+    let mut doc_indexes: HashMap<String, usize> = HashMap::new();
+    for (id, path, value) in filtered.chain(input_args) {
+        let encoded = match value {
+            Some(v) => encode_subdoc_value(op, &v, span)?,
+            None => vec![],
+        };
+        let spec = SubdocMutation::new(op, path, encoded)
+            .create_path(create_path)
+            .xattr(xattr);
+
+        match doc_indexes.get(&id).copied() {
+            Some(idx) => {
+                if docs[idx].1.len() == MAX_SUBDOC_SPECS {
+                    return Err(generic_error(
+                        format!(
+                            "Too many paths for document {}, at most {} can be mutated at once",
+                            id, MAX_SUBDOC_SPECS
+                        ),
+                        "Split the mutations across several commands".to_string(),
+                        span,
+                    ));
+                }
+                docs[idx].1.push(spec);
+            }
+            None => {
+                doc_indexes.insert(id.clone(), docs.len());
+                docs.push((id, vec![spec]));
+            }
+        }
+    }
+
+    let guard = state.lock().unwrap();
+
+    let mut all_docs = vec![];
+    if let Some(size) = batch_size {
+        all_docs = build_batched_kv_items(size as u32, docs.clone());
+    }
+
+    let mut results = vec![];
+    for identifier in cluster_identifiers {
+        let rt = Runtime::new().unwrap();
+        let (active_cluster, client, cid) = match get_active_cluster_client_cid(
+            &rt,
+            identifier.clone(),
+            &guard,
+            bucket_flag.clone(),
+            scope_flag.clone(),
+            collection_flag.clone(),
+            ctrl_c.clone(),
+            span,
+        ) {
+            Ok(c) => c,
+            Err(e) => {
+                if halt_on_error {
+                    return Err(e);
+                }
+
+                let collected = SubdocMutationResult::new(identifier.clone())
+                    .id_column(&id_column)
+                    .status(e.to_string())
+                    .into_value(span);
+                results.push(collected);
+                continue;
+            }
+        };
+
+        if all_docs.is_empty() {
+            all_docs = build_batched_kv_items(active_cluster.kv_batch_size(), docs.clone());
+        }
+
+        debug!("Running kv subdoc multi mutation for {} docs", docs.len());
+
+        for batch in all_docs.clone() {
+            let mut workers = FuturesUnordered::new();
+            for (id, specs) in batch {
+                let paths: Vec<String> = specs.iter().map(|s| s.path().to_string()).collect();
+                if id.is_empty() {
+                    for path in paths {
+                        results.push(
+                            SubdocMutationResult::new(&identifier)
+                                .id_column(&id_column)
+                                .path(path)
+                                .status("Missing doc id")
+                                .into_value(span),
+                        );
+                    }
+                    continue;
+                }
+
+                let deadline = Instant::now().add(active_cluster.timeouts().data_timeout());
+                let ctrl_c = ctrl_c.clone();
+                let client = client.clone();
+
+                workers.push(async move {
+                    let res = client
+                        .request(
+                            KeyValueRequest::SubdocMultiMutation {
+                                key: id.clone(),
+                                specs,
+                                cas: cas as u64,
+                                expiry: expiry as u32,
+                            },
+                            cid,
+                            deadline,
+                            ctrl_c,
+                        )
+                        .await;
+                    (id, paths, res)
+                });
+            }
+
+            rt.block_on(async {
+                while let Some((id, paths, response)) = workers.next().await {
+                    match response {
+                        Ok(mut res) => {
+                            let mut values: HashMap<u64, serde_json::Value> = HashMap::new();
+                            if let Some(serde_json::Value::Array(entries)) = res.content() {
+                                for entry in entries {
+                                    if let Some(index) = entry["index"].as_u64() {
+                                        values.insert(index, entry["value"].clone());
+                                    }
+                                }
+                            }
+
+                            for (i, path) in paths.into_iter().enumerate() {
+                                let mut collected = SubdocMutationResult::new(&identifier)
+                                    .id_column(&id_column)
+                                    .key(id.clone())
+                                    .path(path)
+                                    .cas(res.cas() as i64)
+                                    .status("success");
+                                if let Some(v) = values.get(&(i as u64)) {
+                                    collected =
+                                        collected.value(convert_json_value_to_nu_value(v, span)?);
+                                }
+                                results.push(collected.into_value(span));
+                            }
+                        }
+                        Err(e) => {
+                            if halt_on_error {
+                                return Err(generic_error(
+                                    "Failed to mutate document",
+                                    Some(e.to_string()),
+                                    span,
+                                ));
+                            }
+
+                            for (i, path) in paths.into_iter().enumerate() {
+                                let status = match &e {
+                                    ClientError::SubdocPathFailed { index, reason, .. } => {
+                                        if *index as usize == i {
+                                            reason.clone()
+                                        } else {
+                                            "not applied".to_string()
+                                        }
+                                    }
+                                    _ => e.to_string(),
+                                };
+                                results.push(
+                                    SubdocMutationResult::new(&identifier)
+                                        .id_column(&id_column)
+                                        .key(id.clone())
+                                        .path(path)
+                                        .status(status)
+                                        .into_value(span),
+                                );
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    Ok(Value::List {
+        vals: results,
+        internal_span: call.head,
+    }
+    .into_pipeline_data())
+}
+
+// encode_subdoc_value converts the value into the JSON fragment expected for the operation. Array
+// operations accept a list of values, which the server expects as a comma separated sequence.
+fn encode_subdoc_value(
+    op: SubdocMutationOp,
+    value: &Value,
+    span: Span,
+) -> Result<Vec<u8>, ShellError> {
+    let json = convert_nu_value_to_json_value(value, span)?;
+    match (op, json) {
+        (SubdocMutationOp::ArrayAppend, serde_json::Value::Array(vals))
+        | (SubdocMutationOp::ArrayPrepend, serde_json::Value::Array(vals)) => {
+            let encoded = vals
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<Result<Vec<String>, serde_json::Error>>()
+                .map_err(|e| serialize_error(e.to_string(), span))?;
+            Ok(encoded.join(",").into_bytes())
+        }
+        (SubdocMutationOp::Counter, serde_json::Value::Number(n)) if n.is_i64() => {
+            Ok(n.to_string().into_bytes())
+        }
+        (SubdocMutationOp::Counter, _) => Err(generic_error(
+            "Counter delta must be an integer",
+            "Run 'subdoc counter --help' to see examples".to_string(),
+            span,
+        )),
+        (_, json) => serde_json::to_vec(&json).map_err(|e| serialize_error(e.to_string(), span)),
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct SubdocMutationResult {
+    status: Option<String>,
+    value: Option<Value>,
+    key: Option<String>,
+    path: Option<String>,
+    cluster: String,
+    cas: Option<i64>,
+    id_column: Option<String>,
+}
+
+impl SubdocMutationResult {
+    pub fn new(cluster: impl Into<String>) -> Self {
+        Self {
+            status: None,
+            value: None,
+            key: None,
+            path: None,
+            cluster: cluster.into(),
+            cas: None,
+            id_column: None,
+        }
+    }
+
+    pub fn id_column(mut self, id_column: impl Into<String>) -> Self {
+        self.id_column = Some(id_column.into());
+        self
+    }
+
+    pub fn key(mut self, key: String) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub fn path(mut self, path: String) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    pub fn value(mut self, value: Value) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    pub fn cas(mut self, cas: i64) -> Self {
+        self.cas = Some(cas);
+        self
+    }
+
+    pub fn status(mut self, status: impl Into<String>) -> Self {
+        self.status = Some(status.into());
+        self
+    }
+
+    pub fn into_value(self, span: Span) -> Value {
+        let mut collected = NuValueMap::default();
+        collected.add_string(
+            self.id_column.unwrap_or_else(|| "id".to_string()),
+            self.key.unwrap_or_default(),
+            span,
+        );
+        collected.add_string("path", self.path.unwrap_or_default(), span);
+        collected.add("value", self.value.unwrap_or_default());
+        collected.add_i64("cas", self.cas.unwrap_or_default(), span);
+        collected.add_string("status", self.status.unwrap_or_default(), span);
+        collected.add_string("cluster", self.cluster, span);
+        collected.into_value(span)
+    }
+}
@@ -0,0 +1,70 @@
+//! The `subdoc counter` command performs a KV sub-document mutation.
+
+use crate::cli::subdoc_common::{run_subdoc_mutation_command, subdoc_mutation_signature};
+use crate::client::SubdocMutationOp;
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{Example, PipelineData, ShellError, Signature};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct SubDocCounter {
+    state: Arc<Mutex<State>>,
+}
+
+impl SubDocCounter {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for SubDocCounter {
+    fn name(&self) -> &str {
+        "subdoc counter"
+    }
+
+    fn signature(&self) -> Signature {
+        subdoc_mutation_signature(
+            "subdoc counter",
+            "the path of the counter",
+            Some("the amount to add to the counter, negative to subtract"),
+        )
+    }
+
+    fn usage(&self) -> &str {
+        "Increments or decrements the numeric value at the provided path through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_subdoc_mutation_command(
+            self.state.clone(),
+            engine_state,
+            stack,
+            call,
+            input,
+            SubdocMutationOp::Counter,
+        )
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Increments the visits counter of the document with the ID landmark_10019, creating it if needed",
+                example: r#"subdoc counter landmark_10019 stats.visits 1 --create-path"#,
+                result: None,
+            },
+            Example {
+                description: "Decrements the stock counter of the document with the ID item_1",
+                example: r#"subdoc counter item_1 stock -1"#,
+                result: None,
+            },
+        ]
+    }
+}
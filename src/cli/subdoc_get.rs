@@ -22,7 +22,7 @@ use nu_engine::CallExt;
 use nu_protocol::ast::Call;
 use nu_protocol::engine::{Command, EngineState, Stack};
 use nu_protocol::{
-    Category, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Value,
+    Category, IntoPipelineData, PipelineData, ShellError, Signature, Span, SyntaxShape, Value,
 };
 
 #[derive(Clone)]
@@ -61,6 +61,12 @@ impl Command for SubDocGet {
                 "the name of the bucket",
                 None,
             )
+            .named(
+                "xattr",
+                SyntaxShape::Any,
+                "the extended or virtual attribute path(s) to be fetched from the documents",
+                None,
+            )
             .named("scope", SyntaxShape::String, "the name of the scope", None)
             .named(
                 "collection",
@@ -110,6 +116,11 @@ impl Command for SubDocGet {
                 example: "[landmark_10019 landmark_10020] | subdoc get address",
                 result: None
             },
+            Example{
+                description: "Fetches the address field along with the expiry and revision of the document with the ID landmark_10019",
+                example: "subdoc get address landmark_10019 --xattr ['$document.exptime' '$document.revid']",
+                result: None
+            },
         ]
     }
 }
@@ -126,22 +137,18 @@ fn run_subdoc_lookup(
 
     let cluster_identifiers = cluster_identifiers_from(engine_state, stack, &state, call, true)?;
 
-    let paths: Vec<String> = match call.req::<Value>(engine_state, stack, 0)? {
-        Value::String { val, .. } => {
-            vec![val]
-        }
-        Value::List { vals, .. } => vals
-            .iter()
-            .map(|s| s.as_str().unwrap().to_string())
-            .collect(),
-        _ => {
-            return Err(generic_error(
-                "Field(s) must be a string or list",
-                "Run 'subdoc get --help' to see examples".to_string(),
-                None,
-            ));
-        }
+    let paths = paths_from_value(call.req::<Value>(engine_state, stack, 0)?)?;
+    let xattrs = match call.get_flag::<Value>(engine_state, stack, "xattr")? {
+        Some(v) => paths_from_value(v)?,
+        None => vec![],
     };
+    if paths.is_empty() && xattrs.is_empty() {
+        return Err(generic_error(
+            "No paths to fetch",
+            "Run 'subdoc get --help' to see examples".to_string(),
+            span,
+        ));
+    }
 
     let id_column: String = call
         .get_flag(engine_state, stack, "id-column")?
@@ -203,12 +210,11 @@ fn run_subdoc_lookup(
                 let id = id.clone();
 
                 let client = client.clone();
-                let copy = paths.clone();
-
-                let request = if paths.len() > 1 {
+                let request = if paths.len() != 1 || !xattrs.is_empty() {
                     KeyValueRequest::SubdocMultiLookup {
                         key: id,
-                        paths: copy,
+                        paths: paths.clone(),
+                        xattrs: xattrs.clone(),
                     }
                 } else {
                     KeyValueRequest::SubDocGet {
@@ -233,24 +239,31 @@ fn run_subdoc_lookup(
                             // Create a record where cols =  field and
                             match convert_json_value_to_nu_value(&content, call.head) {
                                 Ok(c) => {
-                                    if paths.len() == 1 {
+                                    if paths.len() == 1 && xattrs.is_empty() {
                                         collected = collected.content(c);
                                     } else {
-                                        let list = c.as_list().unwrap().to_vec();
-
-                                        let record = Value::Record {
-                                            val: SharedCow::new(
-                                                Record::from_raw_cols_vals(
-                                                    paths.clone(),
-                                                    list,
-                                                    span,
-                                                    span,
-                                                )
-                                                .unwrap(),
-                                            ),
-                                            internal_span: span,
-                                        };
-                                        collected = collected.content(record);
+                                        // Xattr values are returned before the document values.
+                                        let mut list = c.as_list().unwrap().to_vec();
+                                        let values = list.split_off(xattrs.len());
+
+                                        if !xattrs.is_empty() {
+                                            collected = collected.xattrs(record_from_paths(
+                                                xattrs.clone(),
+                                                list,
+                                                span,
+                                            ));
+                                        }
+                                        if paths.len() == 1 {
+                                            collected = collected.content(
+                                                values.into_iter().next().unwrap_or_default(),
+                                            );
+                                        } else {
+                                            collected = collected.content(record_from_paths(
+                                                paths.clone(),
+                                                values,
+                                                span,
+                                            ));
+                                        }
                                     }
                                 }
                                 Err(e) => {
@@ -292,3 +305,32 @@ fn run_subdoc_lookup(
     }
     .into_pipeline_data())
 }
+
+fn paths_from_value(value: Value) -> Result<Vec<String>, ShellError> {
+    match value {
+        Value::String { val, .. } => Ok(vec![val]),
+        Value::List { vals, .. } => vals
+            .iter()
+            .map(|v| match v {
+                Value::String { val, .. } => Ok(val.clone()),
+                _ => Err(generic_error(
+                    format!("Fields must be strings, got {}", v.get_type()),
+                    "Quote paths which start with $, such as ['$document.exptime']".to_string(),
+                    v.span(),
+                )),
+            })
+            .collect(),
+        _ => Err(generic_error(
+            "Field(s) must be a string or list",
+            "Run 'subdoc get --help' to see examples".to_string(),
+            None,
+        )),
+    }
+}
+
+fn record_from_paths(paths: Vec<String>, values: Vec<Value>, span: Span) -> Value {
+    Value::Record {
+        val: SharedCow::new(Record::from_raw_cols_vals(paths, values, span, span).unwrap()),
+        internal_span: span,
+    }
+}
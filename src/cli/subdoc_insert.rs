@@ -0,0 +1,65 @@
+//! The `subdoc insert` command performs a KV sub-document mutation.
+
+use crate::cli::subdoc_common::{run_subdoc_mutation_command, subdoc_mutation_signature};
+use crate::client::SubdocMutationOp;
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{Example, PipelineData, ShellError, Signature};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct SubDocInsert {
+    state: Arc<Mutex<State>>,
+}
+
+impl SubDocInsert {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for SubDocInsert {
+    fn name(&self) -> &str {
+        "subdoc insert"
+    }
+
+    fn signature(&self) -> Signature {
+        subdoc_mutation_signature(
+            "subdoc insert",
+            "the path to insert",
+            Some("the value to insert at the path"),
+        )
+    }
+
+    fn usage(&self) -> &str {
+        "Inserts a value at the provided path in the specified document through the data service, failing if the path already exists"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_subdoc_mutation_command(
+            self.state.clone(),
+            engine_state,
+            stack,
+            call,
+            input,
+            SubdocMutationOp::Insert,
+        )
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Adds a nested field to the document with the ID landmark_10019, creating the parent object",
+                example: r#"subdoc insert landmark_10019 meta.reviewed true --create-path"#,
+                result: None,
+            },
+        ]
+    }
+}
@@ -0,0 +1,59 @@
+//! The `subdoc remove` command performs a KV sub-document mutation.
+
+use crate::cli::subdoc_common::{run_subdoc_mutation_command, subdoc_mutation_signature};
+use crate::client::SubdocMutationOp;
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{Example, PipelineData, ShellError, Signature};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct SubDocRemove {
+    state: Arc<Mutex<State>>,
+}
+
+impl SubDocRemove {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for SubDocRemove {
+    fn name(&self) -> &str {
+        "subdoc remove"
+    }
+
+    fn signature(&self) -> Signature {
+        subdoc_mutation_signature("subdoc remove", "the path to remove", None)
+    }
+
+    fn usage(&self) -> &str {
+        "Removes the provided path from the specified document through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_subdoc_mutation_command(
+            self.state.clone(),
+            engine_state,
+            stack,
+            call,
+            input,
+            SubdocMutationOp::Remove,
+        )
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![Example {
+            description: "Removes the email field from the document with the ID landmark_10019",
+            example: r#"subdoc remove landmark_10019 email"#,
+            result: None,
+        }]
+    }
+}
@@ -0,0 +1,65 @@
+//! The `subdoc replace` command performs a KV sub-document mutation.
+
+use crate::cli::subdoc_common::{run_subdoc_mutation_command, subdoc_mutation_signature};
+use crate::client::SubdocMutationOp;
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{Example, PipelineData, ShellError, Signature};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct SubDocReplace {
+    state: Arc<Mutex<State>>,
+}
+
+impl SubDocReplace {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for SubDocReplace {
+    fn name(&self) -> &str {
+        "subdoc replace"
+    }
+
+    fn signature(&self) -> Signature {
+        subdoc_mutation_signature(
+            "subdoc replace",
+            "the path to replace",
+            Some("the value to replace at the path"),
+        )
+    }
+
+    fn usage(&self) -> &str {
+        "Replaces the value at the provided path in the specified document through the data service, failing if the path does not exist"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_subdoc_mutation_command(
+            self.state.clone(),
+            engine_state,
+            stack,
+            call,
+            input,
+            SubdocMutationOp::Replace,
+        )
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Replaces the name field of the document with the ID landmark_10019 only if it has not changed",
+                example: r#"subdoc replace landmark_10019 name "Gillingham" --cas 1712345678901234"#,
+                result: None,
+            },
+        ]
+    }
+}
@@ -0,0 +1,70 @@
+//! The `subdoc upsert` command performs a KV sub-document mutation.
+
+use crate::cli::subdoc_common::{run_subdoc_mutation_command, subdoc_mutation_signature};
+use crate::client::SubdocMutationOp;
+use crate::state::State;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{Command, EngineState, Stack};
+use nu_protocol::{Example, PipelineData, ShellError, Signature};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct SubDocUpsert {
+    state: Arc<Mutex<State>>,
+}
+
+impl SubDocUpsert {
+    pub fn new(state: Arc<Mutex<State>>) -> Self {
+        Self { state }
+    }
+}
+
+impl Command for SubDocUpsert {
+    fn name(&self) -> &str {
+        "subdoc upsert"
+    }
+
+    fn signature(&self) -> Signature {
+        subdoc_mutation_signature(
+            "subdoc upsert",
+            "the path to upsert",
+            Some("the value to upsert at the path"),
+        )
+    }
+
+    fn usage(&self) -> &str {
+        "Upserts (inserts or overrides) a value at the provided path in the specified document through the data service"
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        run_subdoc_mutation_command(
+            self.state.clone(),
+            engine_state,
+            stack,
+            call,
+            input,
+            SubdocMutationOp::Upsert,
+        )
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                description: "Sets the name field of the document with the ID landmark_10019",
+                example: r#"subdoc upsert landmark_10019 name "Gillingham""#,
+                result: None,
+            },
+            Example {
+                description: "Upserts paths in multiple documents from the previous command",
+                example: r#"[[id path value]; [landmark_10019 name Gillingham] [landmark_10020 name Crawley]] | subdoc upsert"#,
+                result: None,
+            },
+        ]
+    }
+}
@@ -0,0 +1,523 @@
+//! Rate limiting and progress reporting for the commands which run many KV operations.
+
+use crate::cli::error::generic_error;
+use crate::cli::util::{duration_to_golang_string, NuValueMap};
+use crate::client::{ClientError, KeyValueRequest, KvClient, KvResponse};
+use nu_engine::CallExt;
+use nu_protocol::ast::Call;
+use nu_protocol::engine::{EngineState, Stack};
+use nu_protocol::{ShellError, Span, Value};
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+static PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+// How often an operation waiting for its turn under the rate limit checks for ctrl-c.
+static THROTTLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Throughput runs KV operations within the rate limit and maximum in flight given by the user,
+// keeping statistics on them which are reported as they run when asked to.
+pub(crate) struct Throughput {
+    in_flight: Option<Semaphore>,
+    interval: Option<Duration>,
+    next_slot: Mutex<Instant>,
+    progress: Progress,
+}
+
+impl Throughput {
+    // from_args reads the rate-limit, max-in-flight and progress flags. The total number of
+    // operations, when known, is used to estimate how long is left.
+    pub(crate) fn from_args(
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        total: Option<u64>,
+    ) -> Result<Arc<Self>, ShellError> {
+        let rate_limit: Option<i64> = call.get_flag(engine_state, stack, "rate-limit")?;
+        if rate_limit.map(|r| r < 1).unwrap_or_default() {
+            return Err(generic_error(
+                "Rate limit must be at least 1",
+                None,
+                call.head,
+            ));
+        }
+        let max_in_flight: Option<i64> = call.get_flag(engine_state, stack, "max-in-flight")?;
+        if max_in_flight.map(|m| m < 1).unwrap_or_default() {
+            return Err(generic_error(
+                "Max in flight must be at least 1",
+                None,
+                call.head,
+            ));
+        }
+        let progress = call.has_flag(engine_state, stack, "progress")?;
+
+        Ok(Arc::new(Self::new(
+            rate_limit.map(|r| r as u64),
+            max_in_flight.map(|m| m as usize),
+            Progress::new(total, progress),
+        )))
+    }
+
+    fn new(rate_limit: Option<u64>, max_in_flight: Option<usize>, progress: Progress) -> Self {
+        Self {
+            in_flight: max_in_flight.map(Semaphore::new),
+            interval: rate_limit.map(|r| Duration::from_secs_f64(1.0 / r as f64)),
+            next_slot: Mutex::new(Instant::now()),
+            progress,
+        }
+    }
+
+    // request sends the request once there is room for it, recording its latency against the
+    // cluster. The timeout only starts once the request has been given its turn.
+    pub(crate) async fn request(
+        &self,
+        cluster: &str,
+        client: &KvClient,
+        request: KeyValueRequest,
+        cid: u32,
+        timeout: Duration,
+        ctrl_c: Arc<AtomicBool>,
+    ) -> Result<KvResponse, ClientError> {
+        let _permit = self.turn(&ctrl_c).await?;
+
+        let started = Instant::now();
+        let result = client
+            .request(request, cid, started + timeout, ctrl_c)
+            .await;
+        self.progress
+            .record(cluster, started.elapsed(), result.is_err());
+
+        result
+    }
+
+    // turn waits until there is room for another operation in flight and its slot under the rate
+    // limit has come, holding the room for as long as the permit is kept.
+    async fn turn(&self, ctrl_c: &AtomicBool) -> Result<Option<SemaphorePermit<'_>>, ClientError> {
+        let permit = match &self.in_flight {
+            Some(in_flight) => in_flight.acquire().await.ok(),
+            None => None,
+        };
+
+        if let Some(interval) = self.interval {
+            let slot = {
+                let mut next_slot = self.next_slot.lock().unwrap();
+                let slot = (*next_slot).max(Instant::now());
+                *next_slot = slot + interval;
+                slot
+            };
+
+            while Instant::now() < slot {
+                if ctrl_c.load(Ordering::SeqCst) {
+                    return Err(ClientError::Cancelled { key: None });
+                }
+                tokio::time::sleep_until(slot.min(Instant::now() + THROTTLE_POLL_INTERVAL)).await;
+            }
+        }
+
+        Ok(permit)
+    }
+
+    // summary describes the operations run against a cluster, if any were.
+    pub(crate) fn summary(&self, cluster: &str) -> Option<ThroughputSummary> {
+        self.progress.summary(cluster)
+    }
+
+    // finish replaces the progress line with a summary of every operation run, when progress is
+    // being reported.
+    pub(crate) fn finish(&self) {
+        self.progress.finish();
+    }
+}
+
+// ProgressLine draws a single line of progress on stderr, redrawing it in place at most once per
+// interval. The line is only drawn when stderr is a terminal.
+pub(crate) struct ProgressLine {
+    live: bool,
+    last_render: Mutex<Instant>,
+}
+
+impl ProgressLine {
+    pub(crate) fn new() -> Self {
+        Self {
+            live: io::stderr().is_terminal(),
+            last_render: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub(crate) fn is_live(&self) -> bool {
+        self.live
+    }
+
+    // update redraws the line with the output of render, if the interval has passed since the
+    // last time it was drawn.
+    pub(crate) fn update(&self, render: impl FnOnce() -> String) {
+        if !self.live {
+            return;
+        }
+        let now = Instant::now();
+        let mut last_render = self.last_render.lock().unwrap();
+        if now - *last_render >= PROGRESS_INTERVAL {
+            *last_render = now;
+            // Clear whatever is left of the previous line after the new one.
+            eprint!("\r{}\x1b[K", render());
+        }
+    }
+
+    // finish replaces the line with the final one, which is written whether or not stderr is a
+    // terminal.
+    pub(crate) fn finish(&self, line: &str) {
+        if self.live {
+            eprintln!("\r{}\x1b[K", line);
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+// Progress keeps the statistics of every operation, which are drawn on the progress line if there
+// is one.
+struct Progress {
+    total: Option<u64>,
+    started: Instant,
+    done: AtomicU64,
+    errors: AtomicU64,
+    finished: AtomicBool,
+    line: Option<ProgressLine>,
+    clusters: Mutex<HashMap<String, ClusterStats>>,
+}
+
+impl Progress {
+    fn new(total: Option<u64>, report: bool) -> Self {
+        Self {
+            total,
+            started: Instant::now(),
+            done: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            finished: AtomicBool::new(false),
+            line: if report {
+                Some(ProgressLine::new())
+            } else {
+                None
+            },
+            clusters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn record(&self, cluster: &str, latency: Duration, failed: bool) {
+        let now = Instant::now();
+        {
+            let mut clusters = self.clusters.lock().unwrap();
+            let stats = clusters
+                .entry(cluster.to_string())
+                .or_insert_with(|| ClusterStats::new(now - latency));
+            stats.latencies.record(latency);
+            stats.last = now;
+        }
+
+        self.done.fetch_add(1, Ordering::SeqCst);
+        if failed {
+            self.errors.fetch_add(1, Ordering::SeqCst);
+        }
+
+        if let Some(line) = &self.line {
+            line.update(|| self.render());
+        }
+    }
+
+    fn render(&self) -> String {
+        let done = self.done.load(Ordering::SeqCst);
+        let elapsed = self.started.elapsed();
+        let rate = done as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+
+        let mut line = match self.total {
+            Some(total) => format!("{}/{} operations", done, total),
+            None => format!("{} operations", done),
+        };
+        line.push_str(&format!(
+            ", {:.0} ops/s, {} errors",
+            rate,
+            self.errors.load(Ordering::SeqCst)
+        ));
+        if let Some(total) = self.total {
+            if rate > 0.0 && total > done {
+                let eta = Duration::from_secs_f64((total - done) as f64 / rate);
+                line.push_str(&format!(", ETA {}", duration_to_golang_string(eta)));
+            }
+        }
+
+        line
+    }
+
+    fn summary(&self, cluster: &str) -> Option<ThroughputSummary> {
+        let clusters = self.clusters.lock().unwrap();
+        clusters.get(cluster).map(|s| s.summary())
+    }
+
+    fn finish(&self) {
+        let progress_line = match &self.line {
+            Some(line) => line,
+            None => return,
+        };
+        if self.finished.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let mut latencies = Histogram::default();
+        for stats in self.clusters.lock().unwrap().values() {
+            latencies.merge(&stats.latencies);
+        }
+
+        let done = self.done.load(Ordering::SeqCst);
+        let elapsed = self.started.elapsed();
+        let line = format!(
+            "{} operations in {}, {:.0} ops/s, {} errors, latency p50 {:?} p90 {:?} p99 {:?} max {:?}",
+            done,
+            duration_to_golang_string(elapsed),
+            done as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            self.errors.load(Ordering::SeqCst),
+            latencies.percentile(0.5),
+            latencies.percentile(0.9),
+            latencies.percentile(0.99),
+            latencies.max(),
+        );
+        progress_line.finish(&line);
+    }
+}
+
+struct ClusterStats {
+    first: Instant,
+    last: Instant,
+    latencies: Histogram,
+}
+
+impl ClusterStats {
+    fn new(first: Instant) -> Self {
+        Self {
+            first,
+            last: first,
+            latencies: Histogram::default(),
+        }
+    }
+
+    fn summary(&self) -> ThroughputSummary {
+        let elapsed = self.last - self.first;
+        ThroughputSummary {
+            ops_per_sec: self.latencies.count() as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            p50: self.latencies.percentile(0.5),
+            p90: self.latencies.percentile(0.9),
+            p99: self.latencies.percentile(0.99),
+            max: self.latencies.max(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ThroughputSummary {
+    ops_per_sec: f64,
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+impl ThroughputSummary {
+    pub(crate) fn add_to(&self, collected: &mut NuValueMap, span: Span) {
+        collected.add("ops_per_sec", Value::float(self.ops_per_sec.round(), span));
+
+        let mut latency = NuValueMap::default();
+        for (name, value) in [
+            ("p50", self.p50),
+            ("p90", self.p90),
+            ("p99", self.p99),
+            ("max", self.max),
+        ] {
+            latency.add(name, Value::duration(value.as_nanos() as i64, span));
+        }
+        collected.add("latency", latency.into_value(span));
+    }
+}
+
+// Histogram counts latencies in buckets which are a 32nd of a power of two wide, so that the
+// percentiles stay within around 3% however many operations are run, in a fixed amount of memory.
+#[derive(Debug, Default)]
+struct Histogram {
+    counts: Vec<u64>,
+    max: Duration,
+}
+
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+
+impl Histogram {
+    fn record(&mut self, latency: Duration) {
+        let bucket = Self::bucket(latency.as_micros() as u64);
+        if self.counts.len() <= bucket {
+            self.counts.resize(bucket + 1, 0);
+        }
+        self.counts[bucket] += 1;
+        self.max = self.max.max(latency);
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.max = self.max.max(other.max);
+    }
+
+    fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    fn max(&self) -> Duration {
+        self.max
+    }
+
+    // percentile returns the lower bound of the bucket holding the given fraction of latencies.
+    fn percentile(&self, fraction: f64) -> Duration {
+        let target = (self.count() as f64 * fraction).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Duration::from_micros(Self::lower_bound(bucket));
+            }
+        }
+
+        Duration::ZERO
+    }
+
+    fn bucket(micros: u64) -> usize {
+        if micros < SUB_BUCKETS {
+            return micros as usize;
+        }
+        let exponent = 63 - micros.leading_zeros();
+        let sub_bucket = (micros >> (exponent - SUB_BUCKET_BITS)) & (SUB_BUCKETS - 1);
+        ((exponent - SUB_BUCKET_BITS + 1) as u64 * SUB_BUCKETS + sub_bucket) as usize
+    }
+
+    fn lower_bound(bucket: usize) -> u64 {
+        let bucket = bucket as u64;
+        if bucket < SUB_BUCKETS {
+            return bucket;
+        }
+        let exponent = bucket / SUB_BUCKETS + SUB_BUCKET_BITS as u64 - 1;
+        (SUB_BUCKETS + bucket % SUB_BUCKETS) << (exponent - SUB_BUCKET_BITS as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_are_bounded_by_their_latencies() {
+        for micros in [0, 1, 31, 32, 33, 63, 64, 100, 1000, 12345, 1 << 40] {
+            let lower = Histogram::lower_bound(Histogram::bucket(micros));
+            assert!(lower <= micros, "{} has lower bound {}", micros, lower);
+            assert!(
+                micros - lower <= micros / SUB_BUCKETS,
+                "{} has lower bound {}",
+                micros,
+                lower
+            );
+        }
+    }
+
+    #[test]
+    fn calculates_percentiles() {
+        let mut histogram = Histogram::default();
+        for micros in 1..=1000 {
+            histogram.record(Duration::from_micros(micros));
+        }
+
+        assert_eq!(1000, histogram.count());
+        let p50 = histogram.percentile(0.5).as_micros();
+        assert!((485..=500).contains(&p50), "p50 was {}", p50);
+        let p99 = histogram.percentile(0.99).as_micros();
+        assert!((960..=990).contains(&p99), "p99 was {}", p99);
+        assert_eq!(Duration::from_micros(1000), histogram.max());
+    }
+
+    #[test]
+    fn merges_histograms() {
+        let mut first = Histogram::default();
+        first.record(Duration::from_micros(10));
+        let mut second = Histogram::default();
+        second.record(Duration::from_millis(10));
+
+        first.merge(&second);
+        assert_eq!(2, first.count());
+        assert_eq!(Duration::from_millis(10), first.max());
+        assert_eq!(Duration::from_micros(10), first.percentile(0.5));
+    }
+
+    #[tokio::test]
+    async fn spaces_operations_by_the_rate_limit() {
+        let throughput = Throughput::new(Some(50), None, Progress::new(None, false));
+        let ctrl_c = AtomicBool::new(false);
+
+        let started = Instant::now();
+        for _ in 0..5 {
+            throughput.turn(&ctrl_c).await.unwrap();
+        }
+        // The first turn is immediate, each after it waits 20ms for its slot.
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(80), "took {:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "took {:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn limits_operations_in_flight() {
+        let throughput = Throughput::new(None, Some(1), Progress::new(None, false));
+        let ctrl_c = AtomicBool::new(false);
+
+        let first = throughput.turn(&ctrl_c).await.unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), throughput.turn(&ctrl_c))
+                .await
+                .is_err(),
+            "second operation started while the first was in flight"
+        );
+
+        drop(first);
+        tokio::time::timeout(Duration::from_millis(50), throughput.turn(&ctrl_c))
+            .await
+            .expect("second operation did not start once the first finished")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn stops_waiting_for_the_rate_limit_on_ctrl_c() {
+        let throughput = Throughput::new(Some(1), None, Progress::new(None, false));
+        let ctrl_c = AtomicBool::new(false);
+        throughput.turn(&ctrl_c).await.unwrap();
+
+        ctrl_c.store(true, Ordering::SeqCst);
+        let started = Instant::now();
+        assert_eq!(
+            ClientError::Cancelled { key: None },
+            throughput.turn(&ctrl_c).await.unwrap_err()
+        );
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn summarises_operations_without_progress() {
+        let progress = Progress::new(None, false);
+        progress.record("local", Duration::from_millis(2), false);
+        progress.record("local", Duration::from_millis(4), true);
+
+        let summary = progress.summary("local").unwrap();
+        assert_eq!(Duration::from_millis(4), summary.max);
+        assert!(progress.summary("remote").is_none());
+        assert_eq!(1, progress.errors.load(Ordering::SeqCst));
+    }
+}
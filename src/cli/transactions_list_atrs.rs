@@ -3,7 +3,7 @@ use crate::cli::error::{
     no_active_cluster_error, unexpected_status_code_error,
 };
 use crate::cli::util::{convert_json_value_to_nu_value, duration_to_golang_string};
-use crate::client::QueryRequest;
+use crate::client::{QueryOptions, QueryRequest};
 use crate::state::State;
 use nu_engine::CallExt;
 use nu_protocol::ast::Call;
@@ -112,6 +112,7 @@ impl Command for TransactionsListAtrs {
                     scope: None,
                     timeout: duration_to_golang_string(active_cluster.timeouts().query_timeout()),
                     transaction: None,
+                    options: QueryOptions::default(),
                 },
                 Instant::now().add(active_cluster.timeouts().query_timeout()),
                 ctrl_c,
@@ -6,7 +6,7 @@ use crate::cli::error::{
 use crate::cli::generic_error;
 use crate::cli::CBShellError::ClusterNotFound;
 use crate::client::cloud_json::Cluster;
-use crate::client::{CapellaClient, HttpResponse};
+use crate::client::{CapellaClient, HttpResponse, HttpStreamResponse, JsonResultsStream};
 use crate::state::State;
 use crate::{read_input, RemoteCluster, RemoteClusterType};
 use nu_engine::CallExt;
@@ -17,6 +17,7 @@ use nu_protocol::{IntoPipelineData, PipelineData, ShellError, Span, Value};
 use nu_utils::SharedCow;
 use num_traits::cast::ToPrimitive;
 use regex::Regex;
+use std::collections::VecDeque;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
@@ -78,6 +79,71 @@ pub fn convert_row_to_nu_value(
     }
 }
 
+// QueryResults converts the rows of a query response into values as the pipeline asks for them,
+// rather than reading the whole response upfront. Errors reported by the response after its rows
+// are given as a final error value.
+pub(crate) struct QueryResults {
+    rows: JsonResultsStream<HttpStreamResponse>,
+    pending: VecDeque<Value>,
+    identifier: String,
+    errors: fn(&serde_json::Value, Span) -> ShellError,
+    finished: bool,
+    span: Span,
+}
+
+impl QueryResults {
+    pub(crate) fn new(
+        response: HttpStreamResponse,
+        identifier: String,
+        errors: fn(&serde_json::Value, Span) -> ShellError,
+        span: Span,
+    ) -> Self {
+        Self {
+            rows: JsonResultsStream::new(response),
+            pending: VecDeque::new(),
+            identifier,
+            errors,
+            finished: false,
+            span,
+        }
+    }
+
+    fn fail(&mut self, error: ShellError) -> Option<Value> {
+        self.finished = true;
+        Some(Value::error(error, self.span))
+    }
+}
+
+impl Iterator for QueryResults {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.pending.pop_front() {
+                return Some(value);
+            }
+            if self.finished {
+                return None;
+            }
+
+            match self.rows.next() {
+                Some(Ok(row)) => {
+                    match convert_row_to_nu_value(&row, self.span, self.identifier.clone()) {
+                        Ok(values) => self.pending.extend(values),
+                        Err(e) => return self.fail(e),
+                    }
+                }
+                Some(Err(e)) => return self.fail(client_error_to_shell_error(e, self.span)),
+                None => {
+                    self.finished = true;
+                    let error = (self.errors)(self.rows.fields().get("errors")?, self.span);
+                    return Some(Value::error(error, self.span));
+                }
+            }
+        }
+    }
+}
+
 pub fn convert_json_value_to_nu_value(
     v: &serde_json::Value,
     span: Span,
@@ -425,6 +491,39 @@ pub fn duration_to_golang_string(duration: Duration) -> String {
     golang_string
 }
 
+// golang_string_to_duration parses a golang formatted duration, such as the 1m2.5s or 345.6µs
+// timings reported by the query service.
+pub fn golang_string_to_duration(input: &str) -> Option<Duration> {
+    let mut rest = input.trim();
+    if rest == "0" {
+        return Some(Duration::ZERO);
+    }
+
+    let mut nanos = 0f64;
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+        let number: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ns" => 1.0,
+            "us" | "µs" | "μs" => 1e3,
+            "ms" => 1e6,
+            "s" => 1e9,
+            "m" => 60e9,
+            "h" => 3600e9,
+            _ => return None,
+        };
+        nanos += number * unit;
+        rest = &rest[unit_len..];
+    }
+
+    Some(Duration::from_nanos(nanos.round() as u64))
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct NuValueMap {
     cols: Vec<String>,
@@ -527,7 +626,7 @@ pub fn get_username_and_password(
 
 #[cfg(test)]
 mod tests {
-    use crate::cli::util::duration_to_golang_string;
+    use crate::cli::util::{duration_to_golang_string, golang_string_to_duration};
     use std::time::Duration;
 
     #[test]
@@ -553,4 +652,27 @@ mod tests {
             duration_to_golang_string(Duration::from_secs(3902))
         );
     }
+
+    #[test]
+    fn golang_string_to_duration_parses_query_timings() {
+        assert_eq!(
+            Some(Duration::from_micros(345_600)),
+            golang_string_to_duration("345.6ms")
+        );
+        assert_eq!(
+            Some(Duration::from_nanos(12_300)),
+            golang_string_to_duration("12.3µs")
+        );
+        assert_eq!(
+            Some(Duration::from_millis(62_500)),
+            golang_string_to_duration("1m2.5s")
+        );
+        assert_eq!(
+            Some(Duration::from_secs(3902)),
+            golang_string_to_duration("1h5m2s")
+        );
+        assert_eq!(Some(Duration::ZERO), golang_string_to_duration("0"));
+        assert_eq!(None, golang_string_to_duration("12"));
+        assert_eq!(None, golang_string_to_duration("3 weeks"));
+    }
 }
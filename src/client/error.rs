@@ -1,4 +1,6 @@
+use crate::client::error_map::RetrySpecification;
 use crate::client::protocol::{KvResponse, Status};
+use bytes::Bytes;
 use serde::Deserialize;
 use std::fmt;
 use std::fmt::Debug;
@@ -61,6 +63,23 @@ pub enum ClientError {
     KeyAlreadyExists {
         key: String,
     },
+    CasMismatch {
+        key: String,
+    },
+    DocumentLocked {
+        key: String,
+    },
+    NotMyVbucket {
+        key: String,
+        config: Option<Bytes>,
+    },
+    TemporaryFailure {
+        key: String,
+        retry: Option<RetrySpecification>,
+    },
+    PartitionUnavailable {
+        key: Option<String>,
+    },
     AccessError {
         reason: Option<String>,
     },
@@ -88,12 +107,25 @@ pub enum ClientError {
         key: String,
         path: String,
     },
+    DurabilityImpossible {
+        key: String,
+    },
+    SyncWriteAmbiguous {
+        key: String,
+    },
+    SubdocPathFailed {
+        key: String,
+        path: String,
+        index: u8,
+        reason: String,
+    },
     InvalidSample {
         sample: String,
     },
     SampleAlreadyLoaded {
         sample: String,
     },
+    RangeScanUnsupported,
 }
 
 impl ClientError {
@@ -102,10 +134,18 @@ impl ClientError {
             ClientError::CollectionUnknownDuringRequest { key, .. } => Some(key.clone()),
             ClientError::KeyNotFound { key } => Some(key.clone()),
             ClientError::KeyAlreadyExists { key } => Some(key.clone()),
+            ClientError::CasMismatch { key } => Some(key.clone()),
+            ClientError::DocumentLocked { key } => Some(key.clone()),
+            ClientError::NotMyVbucket { key, .. } => Some(key.clone()),
+            ClientError::TemporaryFailure { key, .. } => Some(key.clone()),
+            ClientError::PartitionUnavailable { key } => key.clone(),
             ClientError::Timeout { key, .. } => key.clone(),
             ClientError::Cancelled { key } => key.clone(),
             ClientError::RequestFailed { key, .. } => key.clone(),
             ClientError::PathNotFound { key, .. } => Some(key.clone()),
+            ClientError::SubdocPathFailed { key, .. } => Some(key.clone()),
+            ClientError::DurabilityImpossible { key } => Some(key.clone()),
+            ClientError::SyncWriteAmbiguous { key } => Some(key.clone()),
             _ => None,
         }
     }
@@ -141,6 +181,11 @@ impl ClientError {
             Self::ScopeNotFound { .. } => "Scope unknown".to_string(),
             Self::KeyNotFound { .. } => "Key not found".to_string(),
             Self::KeyAlreadyExists { .. } => "Key already exists".to_string(),
+            Self::CasMismatch { .. } => "CAS mismatch".to_string(),
+            Self::DocumentLocked { .. } => "Document locked".to_string(),
+            Self::NotMyVbucket { .. } => "Not my vbucket".to_string(),
+            Self::TemporaryFailure { .. } => "Temporary failure".to_string(),
+            Self::PartitionUnavailable { .. } => "Partition unavailable".to_string(),
             Self::AccessError { .. } => "Access error".to_string(),
             Self::AuthError { .. } => "Authentication error".to_string(),
             Self::Timeout { .. } => "Timeout".to_string(),
@@ -156,8 +201,12 @@ impl ClientError {
             }
             Self::KVCouldNotConnect { .. } => "Could not establish kv connection".to_string(),
             Self::PathNotFound { .. } => "Path not found".to_string(),
+            Self::SubdocPathFailed { reason, .. } => format!("Path failed: {}", reason),
+            Self::DurabilityImpossible { .. } => "Durability impossible".to_string(),
+            Self::SyncWriteAmbiguous { .. } => "Sync write ambiguous".to_string(),
             Self::InvalidSample { .. } => "Invalid sample bucket".to_string(),
             Self::SampleAlreadyLoaded { .. } => "Sample bucket already loaded".to_string(),
+            Self::RangeScanUnsupported => "Range scan not supported".to_string(),
         }
     }
 
@@ -182,6 +231,14 @@ impl ClientError {
             },
             Self::KeyNotFound { key } => format!("Key {} was not found, does it exist in the specified collection?", key),
             Self::KeyAlreadyExists { key } => format!("Key {} already exists, is the correct collection being used?", key),
+            Self::CasMismatch { key } => format!("Key {} has been modified since its CAS was read, fetch the document again and retry", key),
+            Self::DocumentLocked { key } => format!("Key {} is locked, unlock it with the CAS returned when it was locked or wait for the lock to expire", key),
+            Self::NotMyVbucket { key, .. } => format!("The node serving key {} no longer owns its vbucket, is the cluster rebalancing?", key),
+            Self::TemporaryFailure { key, .. } => format!("The server could temporarily not handle the request for key {}, is the cluster under heavy load or rebalancing?", key),
+            Self::PartitionUnavailable { key } => match key {
+                Some(key) => format!("No node currently serves the vbucket of key {}, is the cluster failing over?", key),
+                None => "No node currently serves a vbucket, is the cluster failing over?".to_string(),
+            },
             Self::AccessError { reason } => {
                 if let Some(r) = reason {
                     r.to_string()
@@ -215,12 +272,24 @@ impl ClientError {
             Self::PathNotFound { key, path } => {
                 format!("Path {} was not found in doc with key {}", path, key)
             }
+            Self::DurabilityImpossible { key } => {
+                format!("Durability requirements for key {} cannot be met, are there enough data nodes for the bucket replicas?", key)
+            }
+            Self::SyncWriteAmbiguous { key } => {
+                format!("The durable write for key {} is ambiguous, it may or may not have been applied", key)
+            }
+            Self::SubdocPathFailed { key, path, reason, .. } => {
+                format!("Path {} failed in doc with key {}: {}", path, key, reason)
+            }
             Self::InvalidSample { sample } => {
                 format!("Sample {} is not a valid sample", sample)
             }
             Self::SampleAlreadyLoaded { sample} => {
                 format!("Sample bucket {} is already loaded", sample)
             }
+            Self::RangeScanUnsupported => {
+                "The cluster does not support range scans, they need Couchbase Server 7.6 or later".to_string()
+            }
         }
     }
 
@@ -240,17 +309,27 @@ impl ClientError {
         key: String,
         cid: u32,
         path: Option<String>,
+        cas: u64,
     ) -> Self {
         match status {
             Status::AuthError => ClientError::AuthError { reason },
             Status::AccessError => ClientError::AccessError { reason },
             Status::KeyNotFound => ClientError::KeyNotFound { key },
+            // Append and prepend report a missing document as not stored.
+            Status::NotStored => ClientError::KeyNotFound { key },
+            // When a CAS is supplied the server reports a mismatch as the key already existing.
+            Status::KeyExists if cas != 0 => ClientError::CasMismatch { key },
             Status::KeyExists => ClientError::KeyAlreadyExists { key },
+            Status::Locked => ClientError::DocumentLocked { key },
+            Status::NotMyVbucket => ClientError::NotMyVbucket { key, config: None },
+            Status::TemporaryFailure => ClientError::TemporaryFailure { key, retry: None },
             Status::PathNotFound => ClientError::PathNotFound {
                 key,
                 path: path.unwrap_or("".to_string()),
             },
             Status::CollectionUnknown => ClientError::CollectionUnknownDuringRequest { key, cid },
+            Status::DurabilityImpossible => ClientError::DurabilityImpossible { key },
+            Status::SyncWriteAmbiguous => ClientError::SyncWriteAmbiguous { key },
             _ => ClientError::RequestFailed {
                 reason: Some(status.as_string()),
                 key: Some(key),
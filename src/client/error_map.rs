@@ -0,0 +1,182 @@
+//! The KV error map, which describes how the client should handle each status code.
+
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+pub static ERROR_MAP_VERSION: u16 = 1;
+
+#[derive(Debug, Deserialize)]
+pub struct ErrorMap {
+    pub(crate) version: u16,
+    pub(crate) revision: u16,
+    errors: HashMap<String, ErrorCode>,
+}
+
+impl ErrorMap {
+    // retry_specification returns how a status should be retried, if the server marked it as
+    // retryable.
+    pub fn retry_specification(&self, status: u16) -> Option<RetrySpecification> {
+        let code = self.errors.get(&format!("{:x}", status))?;
+        let retryable = code.attrs.contains(&ErrorAttribute::AutoRetry)
+            || code.attrs.contains(&ErrorAttribute::RetryNow)
+            || code.attrs.contains(&ErrorAttribute::RetryLater);
+        if !retryable {
+            return None;
+        }
+
+        code.retry
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorCode {
+    attrs: HashSet<ErrorAttribute>,
+    retry: Option<RetrySpecification>,
+}
+
+#[derive(Debug, Deserialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct RetrySpecification {
+    strategy: RetryStrategy,
+    interval: u32,
+    after: u32,
+    #[serde(rename = "max-duration")]
+    max_duration: u32,
+    ceil: u32,
+}
+
+impl RetrySpecification {
+    // backoff returns how long to wait before the given retry attempt, starting at 0, or None once
+    // the request has been retried for longer than the maximum duration.
+    pub fn backoff(&self, attempt: u32, elapsed: Duration) -> Option<Duration> {
+        if self.max_duration > 0 && elapsed >= Duration::from_millis(self.max_duration as u64) {
+            return None;
+        }
+
+        if attempt == 0 {
+            return Some(Duration::from_millis(self.after as u64));
+        }
+
+        let interval = self.interval as u64;
+        let wait = match self.strategy {
+            RetryStrategy::Constant => interval,
+            RetryStrategy::Linear => interval.saturating_mul(attempt as u64),
+            RetryStrategy::Exponential => {
+                interval.saturating_mul(1u64.checked_shl(attempt - 1).unwrap_or(u64::MAX))
+            }
+        };
+
+        let wait = if self.ceil > 0 {
+            wait.min(self.ceil as u64)
+        } else {
+            wait
+        };
+
+        Some(Duration::from_millis(wait))
+    }
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq, Hash)]
+enum ErrorAttribute {
+    #[serde(rename = "success")]
+    Success,
+    #[serde(rename = "item-only")]
+    ItemOnly,
+    #[serde(rename = "invalid-input")]
+    InvalidInput,
+    #[serde(rename = "fetch-config")]
+    FetchConfig,
+    #[serde(rename = "conn-state-invalidated")]
+    ConnStateInvalidated,
+    #[serde(rename = "auth")]
+    Auth,
+    #[serde(rename = "special-handling")]
+    SpecialHandling,
+    #[serde(rename = "support")]
+    Support,
+    #[serde(rename = "temp")]
+    Temp,
+    #[serde(rename = "internal")]
+    Internal,
+    #[serde(rename = "retry-now")]
+    RetryNow,
+    #[serde(rename = "retry-later")]
+    RetryLater,
+    #[serde(rename = "subdoc")]
+    Subdoc,
+    #[serde(rename = "dcp")]
+    Dcp,
+    #[serde(rename = "auto-retry")]
+    AutoRetry,
+    #[serde(rename = "item-locked")]
+    ItemLocked,
+    #[serde(rename = "item-deleted")]
+    ItemDeleted,
+    // Newer servers may add attributes, which we have no special handling for.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+enum RetryStrategy {
+    #[serde(rename = "exponential")]
+    Exponential,
+    #[serde(rename = "linear")]
+    Linear,
+    #[serde(rename = "constant")]
+    Constant,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_map() -> ErrorMap {
+        serde_json::from_str(
+            r#"{
+                "version": 1,
+                "revision": 4,
+                "errors": {
+                    "86": {
+                        "name": "ETMPFAIL",
+                        "desc": "Temporary failure",
+                        "attrs": ["temp", "retry-later", "rate-limit"],
+                        "retry": {
+                            "strategy": "exponential",
+                            "interval": 10,
+                            "after": 5,
+                            "max-duration": 500,
+                            "ceil": 100
+                        }
+                    },
+                    "1": {
+                        "name": "KEY_ENOENT",
+                        "desc": "Not Found",
+                        "attrs": ["item-only"]
+                    }
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn retry_specification_for_retryable_status() {
+        let map = error_map();
+        assert!(map.retry_specification(0x86).is_some());
+        assert!(map.retry_specification(0x01).is_none());
+        assert!(map.retry_specification(0x02).is_none());
+    }
+
+    #[test]
+    fn exponential_backoff_is_capped() {
+        let spec = error_map().retry_specification(0x86).unwrap();
+        let elapsed = Duration::from_millis(0);
+        assert_eq!(Some(Duration::from_millis(5)), spec.backoff(0, elapsed));
+        assert_eq!(Some(Duration::from_millis(10)), spec.backoff(1, elapsed));
+        assert_eq!(Some(Duration::from_millis(40)), spec.backoff(3, elapsed));
+        assert_eq!(Some(Duration::from_millis(100)), spec.backoff(10, elapsed));
+        assert_eq!(Some(Duration::from_millis(100)), spec.backoff(80, elapsed));
+        assert_eq!(None, spec.backoff(1, Duration::from_millis(500)));
+    }
+}
@@ -1,12 +1,13 @@
 use crate::client::error::{ClientError, ConfigurationLoadFailedReason};
-use crate::client::http_handler::{HTTPHandler, HttpResponse, HttpVerb};
-use crate::client::kv_client::NodeExtConfig;
+use crate::client::http_handler::{HTTPHandler, HttpResponse, HttpStreamResponse, HttpVerb};
+use crate::client::kv_client::{MutationState, NodeExtConfig};
 use crate::RustTlsConfig;
 use log::{debug, trace};
 use rand::Rng;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde_json::json;
+use std::convert::TryFrom;
 use std::fmt::{Debug, Display, Formatter};
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
@@ -286,8 +287,19 @@ impl HTTPClient {
         deadline: Instant,
         ctrl_c: Arc<AtomicBool>,
     ) -> Result<HttpResponse, ClientError> {
+        self.query_request_stream(request, deadline, ctrl_c)?
+            .into_response()
+    }
+
+    // query_request_stream sends the query, leaving the results to be read as they're needed.
+    pub fn query_request_stream(
+        &self,
+        request: QueryRequest,
+        deadline: Instant,
+        ctrl_c: Arc<AtomicBool>,
+    ) -> Result<HttpStreamResponse, ClientError> {
         let rt = Runtime::new().unwrap();
-        rt.block_on(async {
+        let (response, seed) = rt.block_on(async {
             let config: ClusterConfig = HTTPClient::get_config(
                 &self.seeds,
                 self.tls_enabled,
@@ -306,6 +318,11 @@ impl HTTPClient {
                     });
                 }
                 e
+            } else if let Some(e) = request
+                .preferred_endpoint()
+                .filter(|e| config.has_query_seed(e, self.tls_enabled))
+            {
+                e
             } else if let Some(s) = config.random_query_seed(self.tls_enabled) {
                 s
             } else {
@@ -317,11 +334,29 @@ impl HTTPClient {
 
             let path = request.path();
             let uri = format!("{}:{}{}", seed.hostname(), seed.port(), &path);
-            let (content, status) = match request.verb() {
-                HttpVerb::Get => self.http_client.http_get(&uri, deadline, ctrl_c).await?,
+            let response = match request.verb() {
+                HttpVerb::Get => {
+                    self.http_client
+                        .http_stream(
+                            &uri,
+                            HttpVerb::Get,
+                            None,
+                            HashMap::new(),
+                            deadline,
+                            ctrl_c.clone(),
+                        )
+                        .await?
+                }
                 HttpVerb::Post => {
                     self.http_client
-                        .http_post(&uri, request.payload(), request.headers(), deadline, ctrl_c)
+                        .http_stream(
+                            &uri,
+                            HttpVerb::Post,
+                            request.payload(),
+                            request.headers(),
+                            deadline,
+                            ctrl_c.clone(),
+                        )
                         .await?
                 }
                 _ => {
@@ -332,7 +367,89 @@ impl HTTPClient {
                 }
             };
 
-            Ok(HttpResponse::new(content, status, seed))
+            Ok::<_, ClientError>((response, seed))
+        })?;
+
+        Ok(HttpStreamResponse::new(
+            response, seed, rt, deadline, ctrl_c,
+        ))
+    }
+
+    // query_endpoints returns the address of every node running the query service.
+    pub fn query_endpoints(
+        &self,
+        deadline: Instant,
+        ctrl_c: Arc<AtomicBool>,
+    ) -> Result<Vec<Endpoint>, ClientError> {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let config: ClusterConfig = HTTPClient::get_config(
+                &self.seeds,
+                self.tls_enabled,
+                &self.http_client,
+                None,
+                deadline,
+                ctrl_c.clone(),
+            )
+            .await?;
+
+            Ok(config.query_seeds(self.tls_enabled))
+        })
+    }
+
+    // query_endpoint_for_node returns the query address of the node with the given management
+    // address, which is how the query service names the node running a request.
+    pub fn query_endpoint_for_node(
+        &self,
+        node: &str,
+        deadline: Instant,
+        ctrl_c: Arc<AtomicBool>,
+    ) -> Result<Option<Endpoint>, ClientError> {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let config: ClusterConfig = HTTPClient::get_config(
+                &self.seeds,
+                self.tls_enabled,
+                &self.http_client,
+                None,
+                deadline,
+                ctrl_c.clone(),
+            )
+            .await?;
+
+            Ok(config.query_seed_for_node(node, self.tls_enabled))
+        })
+    }
+
+    // query_admin_request sends a request to the admin API of a single query node, as each node
+    // only reports on and manages its own requests.
+    pub fn query_admin_request(
+        &self,
+        request: QueryAdminRequest,
+        endpoint: Endpoint,
+        deadline: Instant,
+        ctrl_c: Arc<AtomicBool>,
+    ) -> Result<HttpResponse, ClientError> {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let uri = format!(
+                "{}:{}{}",
+                endpoint.hostname(),
+                endpoint.port(),
+                request.path()
+            );
+            let (content, status) = match request.verb() {
+                HttpVerb::Get => self.http_client.http_get(&uri, deadline, ctrl_c).await?,
+                HttpVerb::Delete => self.http_client.http_delete(&uri, deadline, ctrl_c).await?,
+                _ => {
+                    return Err(ClientError::RequestFailed {
+                        reason: Some("Method not allowed for query admin requests".to_string()),
+                        key: None,
+                    });
+                }
+            };
+
+            Ok(HttpResponse::new(content, status, endpoint))
         })
     }
 
@@ -342,8 +459,20 @@ impl HTTPClient {
         deadline: Instant,
         ctrl_c: Arc<AtomicBool>,
     ) -> Result<HttpResponse, ClientError> {
+        self.analytics_query_request_stream(request, deadline, ctrl_c)?
+            .into_response()
+    }
+
+    // analytics_query_request_stream sends the query, leaving the results to be read as they're
+    // needed.
+    pub fn analytics_query_request_stream(
+        &self,
+        request: AnalyticsQueryRequest,
+        deadline: Instant,
+        ctrl_c: Arc<AtomicBool>,
+    ) -> Result<HttpStreamResponse, ClientError> {
         let rt = Runtime::new().unwrap();
-        rt.block_on(async {
+        let (response, seed) = rt.block_on(async {
             let config: ClusterConfig = HTTPClient::get_config(
                 &self.seeds,
                 self.tls_enabled,
@@ -357,11 +486,29 @@ impl HTTPClient {
             let path = request.path();
             if let Some(seed) = config.random_analytics_seed(self.tls_enabled) {
                 let uri = format!("{}:{}{}", seed.hostname(), seed.port(), &path);
-                let (content, status) = match request.verb() {
-                    HttpVerb::Get => self.http_client.http_get(&uri, deadline, ctrl_c).await?,
+                let response = match request.verb() {
+                    HttpVerb::Get => {
+                        self.http_client
+                            .http_stream(
+                                &uri,
+                                HttpVerb::Get,
+                                None,
+                                HashMap::new(),
+                                deadline,
+                                ctrl_c.clone(),
+                            )
+                            .await?
+                    }
                     HttpVerb::Post => {
                         self.http_client
-                            .http_post(&uri, request.payload(), request.headers(), deadline, ctrl_c)
+                            .http_stream(
+                                &uri,
+                                HttpVerb::Post,
+                                request.payload(),
+                                request.headers(),
+                                deadline,
+                                ctrl_c.clone(),
+                            )
                             .await?
                     }
                     _ => {
@@ -372,14 +519,18 @@ impl HTTPClient {
                     }
                 };
 
-                return Ok(HttpResponse::new(content, status, seed));
+                return Ok::<_, ClientError>((response, seed));
             }
 
             Err(ClientError::RequestFailed {
                 reason: Some("No nodes found for service".to_string()),
                 key: None,
             })
-        })
+        })?;
+
+        Ok(HttpStreamResponse::new(
+            response, seed, rt, deadline, ctrl_c,
+        ))
     }
 
     pub fn search_query_request(
@@ -466,6 +617,9 @@ pub enum ManagementRequest {
     GetBucket {
         name: String,
     },
+    GetBucketConfig {
+        name: String,
+    },
     GetCollections {
         bucket: String,
     },
@@ -506,6 +660,7 @@ impl ManagementRequest {
         match self {
             Self::GetBuckets => "/pools/default/buckets".to_string(),
             Self::GetBucket { name } => format!("/pools/default/buckets/{}", name),
+            Self::GetBucketConfig { name } => format!("/pools/default/b/{}", name),
             Self::IndexStatus => "/indexStatus".to_string(),
             Self::SettingsAutoFailover => "/settings/autoFailover".to_string(),
             Self::BucketStats { name } => format!("/pools/default/buckets/{}/stats", name),
@@ -563,6 +718,7 @@ impl ManagementRequest {
         match self {
             Self::GetBuckets => HttpVerb::Get,
             Self::GetBucket { .. } => HttpVerb::Get,
+            Self::GetBucketConfig { .. } => HttpVerb::Get,
             Self::IndexStatus => HttpVerb::Get,
             Self::SettingsAutoFailover => HttpVerb::Get,
             Self::BucketStats { .. } => HttpVerb::Get,
@@ -652,6 +808,236 @@ impl QueryTransactionRequest {
     }
 }
 
+// ScanConsistency is how up to date the indexes used by a query must be before it runs.
+#[derive(Debug, Clone)]
+pub enum ScanConsistency {
+    NotBounded,
+    RequestPlus,
+    // AtPlus waits only for the given writes to be indexed.
+    AtPlus(MutationState),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QueryProfile {
+    Off,
+    Phases,
+    Timings,
+}
+
+impl QueryProfile {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Phases => "phases",
+            Self::Timings => "timings",
+        }
+    }
+}
+
+impl TryFrom<&str> for QueryProfile {
+    type Error = String;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        match input {
+            "off" => Ok(Self::Off),
+            "phases" => Ok(Self::Phases),
+            "timings" => Ok(Self::Timings),
+            _ => Err(format!("unknown profile mode {}", input)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct QueryOptions {
+    scan_consistency: Option<ScanConsistency>,
+    scan_wait: Option<Duration>,
+    readonly: bool,
+    profile: Option<QueryProfile>,
+    max_parallelism: Option<u32>,
+    scan_cap: Option<u32>,
+    pipeline_batch: Option<u32>,
+    pipeline_cap: Option<u32>,
+    use_replica: bool,
+    flex_index: bool,
+    preserve_expiry: bool,
+    client_context_id: Option<String>,
+}
+
+impl QueryOptions {
+    pub fn scan_consistency(
+        mut self,
+        scan_consistency: impl Into<Option<ScanConsistency>>,
+    ) -> Self {
+        self.scan_consistency = scan_consistency.into();
+        self
+    }
+
+    pub fn scan_wait(mut self, scan_wait: impl Into<Option<Duration>>) -> Self {
+        self.scan_wait = scan_wait.into();
+        self
+    }
+
+    pub fn readonly(mut self, readonly: bool) -> Self {
+        self.readonly = readonly;
+        self
+    }
+
+    pub fn profile(mut self, profile: impl Into<Option<QueryProfile>>) -> Self {
+        self.profile = profile.into();
+        self
+    }
+
+    pub fn max_parallelism(mut self, max_parallelism: impl Into<Option<u32>>) -> Self {
+        self.max_parallelism = max_parallelism.into();
+        self
+    }
+
+    pub fn scan_cap(mut self, scan_cap: impl Into<Option<u32>>) -> Self {
+        self.scan_cap = scan_cap.into();
+        self
+    }
+
+    pub fn pipeline_batch(mut self, pipeline_batch: impl Into<Option<u32>>) -> Self {
+        self.pipeline_batch = pipeline_batch.into();
+        self
+    }
+
+    pub fn pipeline_cap(mut self, pipeline_cap: impl Into<Option<u32>>) -> Self {
+        self.pipeline_cap = pipeline_cap.into();
+        self
+    }
+
+    // use_replica allows documents to be read from replicas when the active copy is unavailable.
+    pub fn use_replica(mut self, use_replica: bool) -> Self {
+        self.use_replica = use_replica;
+        self
+    }
+
+    // flex_index allows the query to use full text search indexes.
+    pub fn flex_index(mut self, flex_index: bool) -> Self {
+        self.flex_index = flex_index;
+        self
+    }
+
+    pub fn preserve_expiry(mut self, preserve_expiry: bool) -> Self {
+        self.preserve_expiry = preserve_expiry;
+        self
+    }
+
+    pub fn client_context_id(mut self, client_context_id: impl Into<Option<String>>) -> Self {
+        self.client_context_id = client_context_id.into();
+        self
+    }
+
+    fn add_to(&self, json: &mut HashMap<String, serde_json::Value>) {
+        match &self.scan_consistency {
+            Some(ScanConsistency::NotBounded) => {
+                json.insert("scan_consistency".to_string(), json!("not_bounded"));
+            }
+            Some(ScanConsistency::RequestPlus) => {
+                json.insert("scan_consistency".to_string(), json!("request_plus"));
+            }
+            Some(ScanConsistency::AtPlus(mutations)) => {
+                // Scan vectors are keyed by bucket, then by partition, each entry being the
+                // sequence number and partition uuid to wait for.
+                let mut vectors: HashMap<&str, serde_json::Map<String, serde_json::Value>> =
+                    HashMap::new();
+                for token in mutations.tokens() {
+                    vectors.entry(token.bucket()).or_default().insert(
+                        token.partition().to_string(),
+                        json!([token.seqno(), token.partition_uuid().to_string()]),
+                    );
+                }
+                json.insert("scan_consistency".to_string(), json!("at_plus"));
+                json.insert("scan_vectors".to_string(), json!(vectors));
+            }
+            None => {}
+        }
+
+        if let Some(wait) = self.scan_wait {
+            json.insert(
+                "scan_wait".to_string(),
+                serde_json::Value::String(format!("{}ms", wait.as_millis())),
+            );
+        }
+
+        if self.readonly {
+            json.insert("readonly".to_string(), json!(true));
+        }
+        if let Some(profile) = self.profile {
+            json.insert("profile".to_string(), json!(profile.as_str()));
+        }
+        // The tuning options are sent as strings, as the query service expects.
+        for (name, value) in [
+            ("max_parallelism", self.max_parallelism),
+            ("scan_cap", self.scan_cap),
+            ("pipeline_batch", self.pipeline_batch),
+            ("pipeline_cap", self.pipeline_cap),
+        ] {
+            if let Some(value) = value {
+                json.insert(name.to_string(), json!(value.to_string()));
+            }
+        }
+        if self.use_replica {
+            json.insert("use_replica".to_string(), json!("on"));
+        }
+        if self.flex_index {
+            json.insert("use_fts".to_string(), json!(true));
+        }
+        if self.preserve_expiry {
+            json.insert("preserve_expiry".to_string(), json!(true));
+        }
+        if let Some(id) = &self.client_context_id {
+            json.insert("client_context_id".to_string(), json!(id));
+        }
+    }
+}
+
+// PreparedStatement is a statement the query service has already planned, along with the node
+// that planned it.
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    name: String,
+    statement: String,
+    encoded_plan: Option<String>,
+    endpoint: Endpoint,
+    scope: Option<(String, String)>,
+}
+
+impl PreparedStatement {
+    pub fn new(
+        name: String,
+        statement: String,
+        encoded_plan: Option<String>,
+        endpoint: Endpoint,
+        scope: Option<(String, String)>,
+    ) -> Self {
+        Self {
+            name,
+            statement,
+            encoded_plan,
+            endpoint,
+            scope,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn statement(&self) -> &str {
+        &self.statement
+    }
+
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    pub fn scope(&self) -> Option<(String, String)> {
+        self.scope.clone()
+    }
+}
+
 pub enum QueryRequest {
     Execute {
         statement: String,
@@ -659,19 +1045,26 @@ pub enum QueryRequest {
         scope: Option<(String, String)>,
         timeout: String,
         transaction: Option<QueryTransactionRequest>,
+        options: QueryOptions,
+    },
+    ExecutePrepared {
+        prepared: PreparedStatement,
+        parameters: Option<serde_json::Value>,
+        timeout: String,
+        options: QueryOptions,
     },
 }
 
 impl QueryRequest {
     pub fn path(&self) -> String {
         match self {
-            Self::Execute { .. } => "/query".to_string(),
+            Self::Execute { .. } | Self::ExecutePrepared { .. } => "/query".to_string(),
         }
     }
 
     pub fn verb(&self) -> HttpVerb {
         match self {
-            Self::Execute { .. } => HttpVerb::Post,
+            Self::Execute { .. } | Self::ExecutePrepared { .. } => HttpVerb::Post,
         }
     }
 
@@ -683,6 +1076,7 @@ impl QueryRequest {
                 timeout,
                 transaction,
                 parameters,
+                options,
             } => {
                 let mut json = HashMap::new();
                 if let Some(scope) = scope {
@@ -710,25 +1104,42 @@ impl QueryRequest {
                     }
                 }
 
-                if let Some(params) = parameters {
-                    match params {
-                        serde_json::Value::Array(_) => {
-                            json.insert("args".to_string(), params.clone());
-                        }
-                        serde_json::Value::Object(map) => {
-                            for (k, v) in map.iter() {
-                                let key = if k.starts_with('$') {
-                                    k.clone()
-                                } else {
-                                    format!("${}", *k)
-                                };
-                                json.insert(key, v.clone());
-                            }
-                        }
-                        _ => {}
-                    }
+                add_parameters(&mut json, parameters);
+                options.add_to(&mut json);
+
+                Some(serde_json::to_vec(&json).unwrap())
+            }
+            Self::ExecutePrepared {
+                prepared,
+                parameters,
+                timeout,
+                options,
+            } => {
+                let mut json = HashMap::new();
+                // The name of a prepared statement is only unique within its query context.
+                if let Some(scope) = &prepared.scope {
+                    let ctx = format!("`default`:`{}`.`{}`", scope.0, scope.1);
+                    json.insert("query_context".to_string(), serde_json::Value::String(ctx));
                 }
 
+                json.insert(
+                    "prepared".to_string(),
+                    serde_json::Value::String(prepared.name.clone()),
+                );
+                if let Some(plan) = &prepared.encoded_plan {
+                    json.insert(
+                        "encoded_plan".to_string(),
+                        serde_json::Value::String(plan.clone()),
+                    );
+                }
+                json.insert(
+                    "timeout".to_string(),
+                    serde_json::Value::String(timeout.to_string()),
+                );
+
+                add_parameters(&mut json, parameters);
+                options.add_to(&mut json);
+
                 Some(serde_json::to_vec(&json).unwrap())
             }
         }
@@ -736,7 +1147,7 @@ impl QueryRequest {
 
     pub fn headers(&self) -> HashMap<&str, &str> {
         match self {
-            Self::Execute { .. } => {
+            Self::Execute { .. } | Self::ExecutePrepared { .. } => {
                 let mut h = HashMap::new();
                 h.insert("Content-Type", "application/json");
                 h
@@ -754,6 +1165,65 @@ impl QueryRequest {
                 }
                 None
             }
+            Self::ExecutePrepared { .. } => None,
+        }
+    }
+
+    // preferred_endpoint is the node to send the request to while it is still part of the
+    // cluster, unlike endpoint any other node will do once it is not.
+    pub fn preferred_endpoint(&self) -> Option<Endpoint> {
+        match self {
+            Self::Execute { .. } => None,
+            Self::ExecutePrepared { prepared, .. } => Some(prepared.endpoint.clone()),
+        }
+    }
+}
+
+// add_parameters adds positional parameters as args, and named parameters with the $ prefix the
+// query service expects.
+fn add_parameters(
+    json: &mut HashMap<String, serde_json::Value>,
+    parameters: &Option<serde_json::Value>,
+) {
+    if let Some(params) = parameters {
+        match params {
+            serde_json::Value::Array(_) => {
+                json.insert("args".to_string(), params.clone());
+            }
+            serde_json::Value::Object(map) => {
+                for (k, v) in map.iter() {
+                    let key = if k.starts_with('$') {
+                        k.clone()
+                    } else {
+                        format!("${}", *k)
+                    };
+                    json.insert(key, v.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub enum QueryAdminRequest {
+    Vitals,
+    CancelRequest { request_id: String },
+}
+
+impl QueryAdminRequest {
+    pub fn path(&self) -> String {
+        match self {
+            Self::Vitals => "/admin/vitals".to_string(),
+            Self::CancelRequest { request_id } => {
+                format!("/admin/active_requests/{}", request_id)
+            }
+        }
+    }
+
+    pub fn verb(&self) -> HttpVerb {
+        match self {
+            Self::Vitals => HttpVerb::Get,
+            Self::CancelRequest { .. } => HttpVerb::Delete,
         }
     }
 }
@@ -1107,6 +1577,40 @@ impl ClusterConfig {
         default
     }
 
+    // query_seed_for_node finds the node whose management service is at the given host:port and
+    // returns the address of its query service, using its alternate address if that is how the
+    // cluster is being reached.
+    pub fn query_seed_for_node(&self, node: &str, tls: bool) -> Option<Endpoint> {
+        let (host, port) = node.rsplit_once(':')?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let port: u32 = port.parse().ok()?;
+        let key = if tls { "n1qlSSL" } else { "n1ql" };
+
+        let loaded_from = self.loaded_from.clone().unwrap_or_default();
+        let found = self.nodes_ext.iter().find(|n| {
+            let hostname = n.hostname.as_deref().unwrap_or(&loaded_from);
+            hostname == host
+                && ["mgmt", "mgmtSSL"]
+                    .iter()
+                    .any(|mgmt| n.services.get(*mgmt) == Some(&port))
+        })?;
+
+        let mut candidates = vec![];
+        if let Some(query_port) = found.services.get(key) {
+            let hostname = found.hostname.clone().unwrap_or(loaded_from.clone());
+            candidates.push(Endpoint::new(hostname, *query_port));
+        }
+        if let Some(external) = found.alternate_addresses.get("external") {
+            if let Some(query_port) = external.ports.get(key) {
+                let hostname = external.hostname.clone().unwrap_or(loaded_from);
+                candidates.push(Endpoint::new(hostname, *query_port));
+            }
+        }
+
+        let seeds = self.query_seeds(tls);
+        candidates.into_iter().find(|c| seeds.contains(c))
+    }
+
     pub fn has_query_seed(&self, endpoint: &Endpoint, tls: bool) -> bool {
         let seeds = self.query_seeds(tls);
         seeds.contains(endpoint)
@@ -1145,3 +1649,141 @@ impl ClusterConfig {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::kv_client::MutationToken;
+
+    fn payload(options: QueryOptions) -> serde_json::Value {
+        let request = QueryRequest::Execute {
+            statement: "SELECT 1".to_string(),
+            parameters: None,
+            scope: None,
+            timeout: "75s".to_string(),
+            transaction: None,
+            options,
+        };
+        serde_json::from_slice(&request.payload().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn sends_only_the_options_given() {
+        let json = payload(QueryOptions::default());
+        assert_eq!(json!({"statement": "SELECT 1", "timeout": "75s"}), json);
+    }
+
+    #[test]
+    fn sends_query_options() {
+        let mut mutations = MutationState::default();
+        mutations.add(MutationToken::new("default".to_string(), 7, 1234, 42));
+
+        let json = payload(
+            QueryOptions::default()
+                .scan_consistency(ScanConsistency::AtPlus(mutations))
+                .scan_wait(Duration::from_millis(500))
+                .readonly(true)
+                .profile(QueryProfile::Timings)
+                .max_parallelism(4)
+                .pipeline_batch(16)
+                .use_replica(true)
+                .client_context_id("my-id".to_string()),
+        );
+
+        assert_eq!("at_plus", json["scan_consistency"]);
+        assert_eq!(
+            json!({"default": {"7": [42, "1234"]}}),
+            json["scan_vectors"]
+        );
+        assert_eq!("500ms", json["scan_wait"]);
+        assert_eq!(true, json["readonly"]);
+        assert_eq!("timings", json["profile"]);
+        assert_eq!("4", json["max_parallelism"]);
+        assert_eq!("16", json["pipeline_batch"]);
+        assert_eq!(serde_json::Value::Null, json["scan_cap"]);
+        assert_eq!("on", json["use_replica"]);
+        assert_eq!("my-id", json["client_context_id"]);
+    }
+
+    fn cluster_config(config: serde_json::Value) -> ClusterConfig {
+        let mut config: ClusterConfig = serde_json::from_value(config).unwrap();
+        config.set_loaded_from("10.0.0.1".to_string());
+        config
+    }
+
+    #[test]
+    fn finds_the_query_service_of_a_node() {
+        let config = cluster_config(json!({"nodesExt": [
+            {"hostname": "10.0.0.1", "services": {"mgmt": 8091, "mgmtSSL": 18091, "kv": 11210}},
+            {"hostname": "10.0.0.2", "services": {"mgmt": 8091, "mgmtSSL": 18091, "n1ql": 8093, "n1qlSSL": 18093}},
+            {"hostname": "10.0.0.2", "services": {"mgmt": 9000, "mgmtSSL": 19000, "n1ql": 9499, "n1qlSSL": 19499}},
+        ]}));
+
+        assert_eq!(
+            Some(Endpoint::new("10.0.0.2".to_string(), 8093)),
+            config.query_seed_for_node("10.0.0.2:8091", false)
+        );
+        assert_eq!(
+            Some(Endpoint::new("10.0.0.2".to_string(), 19499)),
+            config.query_seed_for_node("10.0.0.2:9000", true)
+        );
+        assert_eq!(
+            Some(Endpoint::new("10.0.0.2".to_string(), 9499)),
+            config.query_seed_for_node("10.0.0.2:19000", false)
+        );
+        // A node without the query service, and one which is not in the cluster.
+        assert_eq!(None, config.query_seed_for_node("10.0.0.1:8091", false));
+        assert_eq!(None, config.query_seed_for_node("10.0.0.3:8091", false));
+        assert_eq!(None, config.query_seed_for_node("10.0.0.2", false));
+    }
+
+    #[test]
+    fn finds_the_query_service_of_a_node_by_its_external_address() {
+        let config = cluster_config(json!({"nodesExt": [
+            {"hostname": "172.17.0.2", "services": {"mgmt": 8091, "n1ql": 8093},
+             "alternateAddresses": {"external": {"hostname": "10.0.0.1", "ports": {"mgmt": 9091, "n1ql": 9093}}}},
+            {"hostname": "172.17.0.3", "services": {"mgmt": 8091, "n1ql": 8093},
+             "alternateAddresses": {"external": {"hostname": "10.0.0.2", "ports": {"mgmt": 9091, "n1ql": 9093}}}},
+        ]}));
+
+        assert_eq!(
+            Some(Endpoint::new("10.0.0.2".to_string(), 9093)),
+            config.query_seed_for_node("172.17.0.3:8091", false)
+        );
+    }
+
+    #[test]
+    fn sends_prepared_statement() {
+        let prepared = PreparedStatement::new(
+            "by_country".to_string(),
+            "SELECT * FROM landmark WHERE country = $1".to_string(),
+            Some("H4sIAAAAAAAA".to_string()),
+            Endpoint::new("10.0.0.1".to_string(), 8093),
+            Some(("travel-sample".to_string(), "inventory".to_string())),
+        );
+        let request = QueryRequest::ExecutePrepared {
+            prepared,
+            parameters: Some(json!(["France"])),
+            timeout: "75s".to_string(),
+            options: QueryOptions::default(),
+        };
+
+        assert_eq!(
+            Some(Endpoint::new("10.0.0.1".to_string(), 8093)),
+            request.preferred_endpoint()
+        );
+        assert_eq!(None, request.endpoint());
+
+        let json: serde_json::Value = serde_json::from_slice(&request.payload().unwrap()).unwrap();
+        assert_eq!(
+            json!({
+                "prepared": "by_country",
+                "encoded_plan": "H4sIAAAAAAAA",
+                "query_context": "`default`:`travel-sample`.`inventory`",
+                "timeout": "75s",
+                "args": ["France"]
+            }),
+            json
+        );
+    }
+}
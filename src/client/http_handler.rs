@@ -2,13 +2,16 @@ use crate::cli::CtrlcFuture;
 use crate::client::error::ClientError;
 use crate::client::Endpoint;
 use crate::RustTlsConfig;
+use bytes::Bytes;
 use log::debug;
 use reqwest::ClientBuilder;
 use std::collections::HashMap;
-use std::ops::Sub;
+use std::io::{self, Read};
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use tokio::{select, time::Instant};
+use tokio::runtime::Runtime;
+use tokio::select;
+use tokio::time::{timeout_at, Instant};
 
 pub enum HttpVerb {
     Delete,
@@ -57,6 +60,104 @@ impl HttpResponse {
     }
 }
 
+// HttpStreamResponse is a response whose body has not been read yet, it is read as it is consumed
+// so that large responses never need to be held in memory all at once.
+pub struct HttpStreamResponse {
+    response: reqwest::Response,
+    status: u16,
+    endpoint: Endpoint,
+    pending: Bytes,
+    ctrl_c: CtrlcFuture,
+    // The runtime that the request was sent on, which the body must also be read on.
+    runtime: Runtime,
+    // The deadline the request was sent with, which bounds reading the body when it is read as a
+    // whole.
+    deadline: Instant,
+}
+
+impl HttpStreamResponse {
+    pub(crate) fn new(
+        response: reqwest::Response,
+        endpoint: Endpoint,
+        runtime: Runtime,
+        deadline: Instant,
+        ctrl_c: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            status: response.status().into(),
+            response,
+            endpoint,
+            pending: Bytes::new(),
+            ctrl_c: CtrlcFuture::new(ctrl_c),
+            runtime,
+            deadline,
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    // into_response reads the rest of the body, for when the response is needed as a whole. Unlike
+    // reading it as a stream this is bounded by the deadline, as reading a non-streaming response is.
+    pub fn into_response(self) -> Result<HttpResponse, ClientError> {
+        let Self {
+            mut response,
+            status,
+            endpoint,
+            pending,
+            mut ctrl_c,
+            runtime,
+            deadline,
+        } = self;
+
+        let content = runtime.block_on(async {
+            let mut content = pending.to_vec();
+            let read = async {
+                while let Some(chunk) = response.chunk().await? {
+                    content.extend_from_slice(&chunk);
+                }
+                Ok::<_, ClientError>(())
+            };
+            select! {
+                result = timeout_at(deadline, read) => result.map_err(|_| ClientError::Timeout{key: None})??,
+                () = &mut ctrl_c => return Err(ClientError::Cancelled{key: None}),
+            }
+            Ok(content)
+        })?;
+
+        Ok(HttpResponse::new(
+            String::from_utf8_lossy(&content).into_owned(),
+            status,
+            endpoint,
+        ))
+    }
+}
+
+impl Read for HttpStreamResponse {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            let response = &mut self.response;
+            let ctrl_c = &mut self.ctrl_c;
+            let chunk = self.runtime.block_on(async {
+                select! {
+                    chunk = response.chunk() => chunk.map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+                    () = ctrl_c => Err(io::Error::new(io::ErrorKind::Other, "request cancelled")),
+                }
+            })?;
+
+            match chunk {
+                Some(chunk) => self.pending = chunk,
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.pending.len());
+        buf[..len].copy_from_slice(&self.pending.split_to(len));
+        Ok(len)
+    }
+}
+
 pub(crate) struct HTTPHandler {
     username: String,
     password: String,
@@ -92,14 +193,59 @@ impl HTTPHandler {
         deadline: Instant,
         ctrl_c: Arc<AtomicBool>,
     ) -> Result<(String, u16), ClientError> {
+        let ctrl_c_fut = CtrlcFuture::new(ctrl_c);
+
+        let res_fut = async {
+            let response = self
+                .http_send(uri, method, payload, headers, deadline)
+                .await?;
+            let status = response.status().into();
+            // The whole body is read here, so unlike a streamed response it is within the deadline.
+            let content = timeout_at(deadline, response.text())
+                .await
+                .map_err(|_| ClientError::Timeout { key: None })??;
+            Ok::<_, ClientError>((content, status))
+        };
+
+        select! {
+            result = res_fut => result,
+            () = ctrl_c_fut => Err(ClientError::Cancelled{key: None}),
+        }
+    }
+
+    // http_stream sends the request but leaves the body of the response to be read by the caller.
+    // The deadline only applies to receiving the response headers, the body is read at whatever
+    // pace the caller consumes it and can be cancelled with ctrl_c.
+    pub async fn http_stream(
+        &self,
+        uri: &str,
+        method: HttpVerb,
+        payload: Option<Vec<u8>>,
+        headers: HashMap<&str, &str>,
+        deadline: Instant,
+        ctrl_c: Arc<AtomicBool>,
+    ) -> Result<reqwest::Response, ClientError> {
+        let ctrl_c_fut = CtrlcFuture::new(ctrl_c);
+
+        select! {
+            result = self.http_send(uri, method, payload, headers, deadline) => result,
+            () = ctrl_c_fut => Err(ClientError::Cancelled{key: None}),
+        }
+    }
+
+    async fn http_send(
+        &self,
+        uri: &str,
+        method: HttpVerb,
+        payload: Option<Vec<u8>>,
+        headers: HashMap<&str, &str>,
+        deadline: Instant,
+    ) -> Result<reqwest::Response, ClientError> {
         let uri = format!("{}://{}", self.http_prefix(), uri);
-        let now = Instant::now();
-        if now >= deadline {
+        if Instant::now() >= deadline {
             debug!("HTTP request timed out before sending {}", uri);
             return Err(ClientError::Timeout { key: None });
         }
-        let timeout = deadline.sub(now);
-        let ctrl_c_fut = CtrlcFuture::new(ctrl_c);
 
         let mut client_builder = ClientBuilder::new();
 
@@ -115,9 +261,15 @@ impl HTTPHandler {
             HttpVerb::Put => client.put(uri),
         };
 
-        res_builder = res_builder
-            .basic_auth(&self.username, Some(&self.password))
-            .timeout(timeout);
+        // With a client certificate the server identifies the user from the certificate instead.
+        let client_auth = self
+            .tls_config
+            .as_ref()
+            .map(|c| c.client_auth_enabled())
+            .unwrap_or(false);
+        if !client_auth {
+            res_builder = res_builder.basic_auth(&self.username, Some(&self.password));
+        }
 
         for (key, value) in headers {
             res_builder = res_builder.header(key, value);
@@ -129,30 +281,14 @@ impl HTTPHandler {
 
         debug!("Performing http request {:?}", &res_builder);
 
-        let res_fut = res_builder.send();
-
-        select! {
-            result = res_fut => {
-                let response = match result {
-                    Ok(r) => Ok(r),
-                    Err(e) => {
-                        if e.is_timeout() {
-                            Err(ClientError::Timeout {
-                                key: None,
-                            })
-                        } else {
-                            Err(ClientError::RequestFailed {
-                                reason: Some(format!("{}", e)),
-                                key: None,
-                            })
-                        }
-                    }
-                }?;
-                let status = response.status().into();
-                let content = response.text().await?;
-                Ok((content, status))
-            },
-            () = ctrl_c_fut => Err(ClientError::Cancelled{key: None}),
+        // Only sending the request and receiving the headers is bounded by the deadline, a timeout
+        // on the request itself would also cover reading the body.
+        match timeout_at(deadline, res_builder.send()).await {
+            Ok(response) => response.map_err(|e| ClientError::RequestFailed {
+                reason: Some(format!("{}", e)),
+                key: None,
+            }),
+            Err(_) => Err(ClientError::Timeout { key: None }),
         }
     }
 
@@ -207,3 +343,118 @@ impl HTTPHandler {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::time::sleep;
+
+    // serve answers a single request, sending the body the given delay after the headers, or
+    // never responding at all without a body.
+    async fn serve(body: Option<(&'static str, Duration)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            let (body, delay) = match body {
+                Some(b) => b,
+                None => return sleep(Duration::from_secs(60)).await,
+            };
+
+            let headers = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len());
+            socket.write_all(headers.as_bytes()).await.unwrap();
+            sleep(delay).await;
+            socket.write_all(body.as_bytes()).await.unwrap();
+        });
+
+        address
+    }
+
+    fn handler() -> HTTPHandler {
+        HTTPHandler::new("user".to_string(), "password".to_string(), None)
+    }
+
+    #[tokio::test]
+    async fn stream_deadline_only_covers_the_headers() {
+        let address = serve(Some(("slow", Duration::from_millis(1000)))).await;
+
+        let response = handler()
+            .http_stream(
+                &address,
+                HttpVerb::Get,
+                None,
+                HashMap::new(),
+                Instant::now() + Duration::from_millis(500),
+                Arc::new(AtomicBool::new(false)),
+            )
+            .await
+            .unwrap();
+        assert_eq!("slow", response.text().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn deadline_covers_the_whole_body_when_not_streaming() {
+        let address = serve(Some(("slow", Duration::from_millis(1000)))).await;
+
+        let result = handler()
+            .http_get(
+                &address,
+                Instant::now() + Duration::from_millis(500),
+                Arc::new(AtomicBool::new(false)),
+            )
+            .await;
+        assert!(matches!(result, Err(ClientError::Timeout { .. })));
+    }
+
+    #[test]
+    fn deadline_covers_the_whole_body_of_a_stream_read_at_once() {
+        let runtime = Runtime::new().unwrap();
+        let deadline = Instant::now() + Duration::from_millis(500);
+        let response = runtime.block_on(async {
+            let address = serve(Some(("slow", Duration::from_millis(1000)))).await;
+            handler()
+                .http_stream(
+                    &address,
+                    HttpVerb::Get,
+                    None,
+                    HashMap::new(),
+                    deadline,
+                    Arc::new(AtomicBool::new(false)),
+                )
+                .await
+                .unwrap()
+        });
+
+        let result = HttpStreamResponse::new(
+            response,
+            Endpoint::new("127.0.0.1".to_string(), 0),
+            runtime,
+            deadline,
+            Arc::new(AtomicBool::new(false)),
+        )
+        .into_response();
+        assert!(matches!(result, Err(ClientError::Timeout { .. })));
+    }
+
+    #[tokio::test]
+    async fn times_out_waiting_for_the_headers() {
+        let address = serve(None).await;
+
+        let result = handler()
+            .http_stream(
+                &address,
+                HttpVerb::Get,
+                None,
+                HashMap::new(),
+                Instant::now() + Duration::from_millis(500),
+                Arc::new(AtomicBool::new(false)),
+            )
+            .await;
+        assert!(matches!(result, Err(ClientError::Timeout { .. })));
+    }
+}
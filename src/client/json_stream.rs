@@ -0,0 +1,316 @@
+//! Incremental parsing of query style responses, which hold their rows in a `results` array, so
+//! that rows can be handed out as they arrive rather than once the whole response is read.
+
+use crate::client::error::ClientError;
+use serde_json::{Map, Value};
+use std::io::Read;
+
+static READ_BUFFER_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    // Before the opening brace of the response.
+    Start,
+    // Between fields of the top level object.
+    Fields,
+    Results,
+    Done,
+}
+
+pub struct JsonResultsStream<R: Read> {
+    reader: R,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+    state: State,
+    fields: Map<String, Value>,
+}
+
+impl<R: Read> JsonResultsStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: vec![0; READ_BUFFER_SIZE],
+            pos: 0,
+            len: 0,
+            state: State::Start,
+            fields: Map::new(),
+        }
+    }
+
+    // fields are the top level fields of the response other than the results, such as errors and
+    // metrics. Those after the results are only known once every row has been read.
+    pub fn fields(&self) -> &Map<String, Value> {
+        &self.fields
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, ClientError> {
+        if self.pos == self.len {
+            self.len = self.reader.read(&mut self.buf)?;
+            self.pos = 0;
+            if self.len == 0 {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(self.buf[self.pos]))
+    }
+
+    fn next_byte(&mut self) -> Result<u8, ClientError> {
+        let byte = self
+            .peek()?
+            .ok_or_else(|| malformed("unexpected end of response"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn skip_whitespace(&mut self) -> Result<Option<u8>, ClientError> {
+        while let Some(byte) = self.peek()? {
+            if !byte.is_ascii_whitespace() {
+                return Ok(Some(byte));
+            }
+            self.pos += 1;
+        }
+
+        Ok(None)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), ClientError> {
+        self.skip_whitespace()?;
+        let byte = self.next_byte()?;
+        if byte != expected {
+            return Err(malformed(format!(
+                "expected '{}' but found '{}'",
+                expected as char, byte as char
+            )));
+        }
+
+        Ok(())
+    }
+
+    // read_string appends the string at the current position, including its quotes, to out.
+    fn read_string(&mut self, out: &mut Vec<u8>) -> Result<(), ClientError> {
+        let quote = self.next_byte()?;
+        if quote != b'"' {
+            return Err(malformed("expected a string"));
+        }
+        out.push(quote);
+
+        loop {
+            let byte = self.next_byte()?;
+            out.push(byte);
+            match byte {
+                b'\\' => out.push(self.next_byte()?),
+                b'"' => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    // read_value appends the raw bytes of the value at the current position to out, without
+    // parsing it.
+    fn read_value(&mut self, out: &mut Vec<u8>) -> Result<(), ClientError> {
+        match self.skip_whitespace()? {
+            Some(b'"') => self.read_string(out),
+            Some(b'{') | Some(b'[') => {
+                let mut depth = 0;
+                loop {
+                    match self.peek()? {
+                        Some(b'"') => {
+                            self.read_string(out)?;
+                            continue;
+                        }
+                        Some(b'{') | Some(b'[') => depth += 1,
+                        Some(b'}') | Some(b']') => depth -= 1,
+                        Some(_) => {}
+                        None => return Err(malformed("unexpected end of response")),
+                    }
+                    out.push(self.next_byte()?);
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+            }
+            Some(_) => {
+                // Numbers, booleans and null run until the next delimiter.
+                while let Some(byte) = self.peek()? {
+                    if byte == b',' || byte == b'}' || byte == b']' || byte.is_ascii_whitespace() {
+                        break;
+                    }
+                    out.push(byte);
+                    self.pos += 1;
+                }
+                Ok(())
+            }
+            None => Err(malformed("unexpected end of response")),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ClientError> {
+        let mut raw = vec![];
+        self.read_value(&mut raw)?;
+        Ok(serde_json::from_slice(&raw)?)
+    }
+
+    // read_fields reads top level fields until the results array is reached or the response ends.
+    fn read_fields(&mut self) -> Result<(), ClientError> {
+        loop {
+            match self.skip_whitespace()? {
+                Some(b'}') => {
+                    self.pos += 1;
+                    self.state = State::Done;
+                    return Ok(());
+                }
+                Some(b',') => {
+                    self.pos += 1;
+                    continue;
+                }
+                Some(_) => {}
+                None => return Err(malformed("unexpected end of response")),
+            }
+
+            let mut raw_key = vec![];
+            self.read_string(&mut raw_key)?;
+            let key: String = serde_json::from_slice(&raw_key)?;
+            self.expect(b':')?;
+
+            if key == "results" && self.skip_whitespace()? == Some(b'[') {
+                self.pos += 1;
+                self.state = State::Results;
+                return Ok(());
+            }
+
+            let value = self.parse_value()?;
+            self.fields.insert(key, value);
+        }
+    }
+
+    fn next_row(&mut self) -> Result<Option<Value>, ClientError> {
+        loop {
+            match self.state {
+                State::Start => {
+                    self.expect(b'{')?;
+                    self.state = State::Fields;
+                }
+                State::Fields => self.read_fields()?,
+                State::Results => match self.skip_whitespace()? {
+                    Some(b']') => {
+                        self.pos += 1;
+                        self.state = State::Fields;
+                    }
+                    Some(b',') => {
+                        self.pos += 1;
+                    }
+                    Some(_) => return self.parse_value().map(Some),
+                    None => return Err(malformed("unexpected end of response")),
+                },
+                State::Done => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for JsonResultsStream<R> {
+    type Item = Result<Value, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_row() {
+            Ok(row) => row.map(Ok),
+            Err(e) => {
+                // The position in the response is unknown after an error, so stop there.
+                self.state = State::Done;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn malformed(reason: impl Into<String>) -> ClientError {
+    ClientError::RequestFailed {
+        reason: Some(format!("Malformed response: {}", reason.into())),
+        key: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Reads a single byte at a time, to check that values split across reads are handled.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn streams_results_and_keeps_other_fields() {
+        let response = r#"{
+            "requestID": "abc",
+            "signature": {"*": "*"},
+            "results": [
+                {"name": "a \"quoted\" ] }", "nested": [1, {"x": [2]}]},
+                42,
+                "text",
+                null
+            ],
+            "status": "success",
+            "metrics": {"resultCount": 4}
+        }"#;
+
+        let mut stream = JsonResultsStream::new(Trickle(response.as_bytes()));
+        let rows: Vec<Value> = stream.by_ref().map(|r| r.unwrap()).collect();
+
+        assert_eq!(
+            vec![
+                json!({"name": "a \"quoted\" ] }", "nested": [1, {"x": [2]}]}),
+                json!(42),
+                json!("text"),
+                Value::Null
+            ],
+            rows
+        );
+        assert_eq!(Some(&json!("abc")), stream.fields().get("requestID"));
+        assert_eq!(Some(&json!("success")), stream.fields().get("status"));
+        assert_eq!(
+            Some(&json!({"resultCount": 4})),
+            stream.fields().get("metrics")
+        );
+    }
+
+    #[test]
+    fn handles_responses_without_results() {
+        let response =
+            r#"{"requestID":"abc","errors":[{"code":3000,"msg":"syntax error"}],"status":"fatal"}"#;
+
+        let mut stream = JsonResultsStream::new(response.as_bytes());
+        assert!(stream.next().is_none());
+        assert_eq!(
+            Some(&json!([{"code": 3000, "msg": "syntax error"}])),
+            stream.fields().get("errors")
+        );
+    }
+
+    #[test]
+    fn handles_empty_results() {
+        let mut stream = JsonResultsStream::new(r#"{"results":[],"status":"success"}"#.as_bytes());
+        assert!(stream.next().is_none());
+        assert_eq!(Some(&json!("success")), stream.fields().get("status"));
+    }
+
+    #[test]
+    fn fails_on_truncated_responses() {
+        let mut stream = JsonResultsStream::new(r#"{"results":[{"a":1},{"b""#.as_bytes());
+        assert_eq!(json!({"a": 1}), stream.next().unwrap().unwrap());
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+    }
+}
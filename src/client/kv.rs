@@ -1,15 +1,19 @@
 use crate::client::codec::KeyValueCodec;
+use crate::client::error_map::{ErrorMap, RetrySpecification, ERROR_MAP_VERSION};
+use crate::client::kv_client::{Durability, SubdocMutation};
 use crate::client::protocol::{request, KvRequest, KvResponse, Status};
+use crate::client::scram::{ScramClient, ScramHash};
 use crate::client::{protocol, ClientError};
 use crate::RustTlsConfig;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use futures::lock::Mutex as AsyncMutex;
 use futures::{SinkExt, StreamExt};
 use log::{debug, trace, warn};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
 use tokio::sync::oneshot::Receiver;
@@ -19,15 +23,30 @@ use tokio_rustls::TlsConnector;
 use tokio_util::codec::{FramedRead, FramedWrite};
 use uuid::Uuid;
 
+// Range scans are identified by a uuid.
+static RANGE_SCAN_ID_LEN: usize = 16;
+
+// Responder is how a response is handed back to whoever sent the request.
+enum Responder {
+    Once(oneshot::Sender<KvResponse>),
+    Stream(mpsc::UnboundedSender<KvResponse>),
+}
+
 pub struct KvEndpoint {
     tx: mpsc::Sender<Bytes>,
     opaque: AtomicU32,
-    in_flight: Arc<AsyncMutex<HashMap<u32, oneshot::Sender<KvResponse>>>>,
+    in_flight: Arc<AsyncMutex<HashMap<u32, Responder>>>,
     collections_enabled: bool,
+    sync_replication_enabled: bool,
+    snappy_enabled: bool,
+    compression_min_size: Option<usize>,
     local_addr: String,
     remote_addr: String,
     uuid: String,
-    // error_map: Option<ErrorMap>,
+    error_map: Option<ErrorMap>,
+    closed: Arc<AtomicBool>,
+    // The newest config sent by the server after a topology change, which is yet to be applied.
+    pushed_config: Arc<Mutex<Option<Bytes>>>,
 }
 
 impl KvEndpoint {
@@ -38,8 +57,28 @@ impl KvEndpoint {
         password: String,
         bucket: String,
         tls_config: Option<RustTlsConfig>,
+        compression_min_size: Option<u32>,
+        auth_mechanism: Option<AuthMechanism>,
     ) -> Result<KvEndpoint, ClientError> {
         let remote_addr = format!("{}:{}", hostname, port);
+        // Without TLS the strongest SCRAM mechanism is used so that the password is not sent in the
+        // clear. PLAIN, which servers authenticating users against LDAP need, is only used without
+        // TLS when it is configured explicitly. When a client certificate is used the connection is
+        // already authenticated by the TLS handshake, so there is nothing to negotiate.
+        let client_auth = tls_config
+            .as_ref()
+            .map(|c| c.client_auth_enabled())
+            .unwrap_or(false);
+        let auth_mechanisms = match auth_mechanism {
+            _ if client_auth => vec![],
+            Some(m) => vec![m],
+            None if tls_config.is_some() => vec![AuthMechanism::Plain],
+            None => vec![
+                AuthMechanism::ScramSha512,
+                AuthMechanism::ScramSha256,
+                AuthMechanism::ScramSha1,
+            ],
+        };
 
         debug!(
             "Connecting to {}, TLS enabled: {}",
@@ -71,6 +110,8 @@ impl KvEndpoint {
                 socket,
                 local_addr.to_string(),
                 remote_addr,
+                compression_min_size,
+                auth_mechanisms,
             )
             .await
         } else {
@@ -89,6 +130,8 @@ impl KvEndpoint {
                 socket,
                 local_addr.to_string(),
                 remote_addr,
+                compression_min_size,
+                auth_mechanisms,
             )
             .await
         }
@@ -101,21 +144,26 @@ impl KvEndpoint {
         stream: C,
         local_addr: String,
         remote_addr: String,
+        compression_min_size: Option<u32>,
+        auth_mechanisms: Vec<AuthMechanism>,
     ) -> Result<KvEndpoint, ClientError> {
         let uuid = Uuid::new_v4().to_string();
         let (tx, mut rx) = mpsc::channel::<Bytes>(1024);
-        let in_flight = Arc::new(AsyncMutex::new(
-            HashMap::<u32, oneshot::Sender<KvResponse>>::new(),
-        ));
+        let in_flight = Arc::new(AsyncMutex::new(HashMap::<u32, Responder>::new()));
         let mut ep = KvEndpoint {
             opaque: AtomicU32::new(0),
             in_flight: Arc::clone(&in_flight),
             tx,
             collections_enabled: false,
+            sync_replication_enabled: false,
+            snappy_enabled: false,
+            compression_min_size: compression_min_size.map(|s| s as usize),
             local_addr,
             remote_addr,
             uuid: uuid.clone(),
-            // error_map: None,
+            error_map: None,
+            closed: Arc::new(AtomicBool::new(false)),
+            pushed_config: Arc::new(Mutex::new(None)),
         };
 
         let (r, w) = tokio::io::split(stream);
@@ -124,12 +172,31 @@ impl KvEndpoint {
 
         // Read thread.
         let recv_uuid = uuid.clone();
+        let recv_closed = ep.closed.clone();
+        let recv_pushed_config = ep.pushed_config.clone();
         tokio::spawn(async move {
             loop {
                 if let Some(frame) = input.next().await {
                     match frame {
                         Ok(input) => {
-                            let response = KvResponse::from(&input.freeze());
+                            let mut response = KvResponse::from(&input.freeze());
+                            if response.is_server_request() {
+                                if response.opcode().encoded()
+                                    == protocol::SERVER_OPCODE_CLUSTERMAP_CHANGE_NOTIFICATION
+                                {
+                                    trace!("{} received clustermap change notification", recv_uuid);
+                                    if let Some(config) = response.body() {
+                                        *recv_pushed_config.lock().unwrap() = Some(config);
+                                    }
+                                } else {
+                                    debug!(
+                                        "{} ignoring unsupported server request {}",
+                                        recv_uuid,
+                                        response.opcode()
+                                    );
+                                }
+                                continue;
+                            }
                             trace!(
                                 "Resolving response on {}. Opcode={}. Opaque={}. Status={}",
                                 recv_uuid,
@@ -139,17 +206,31 @@ impl KvEndpoint {
                             );
                             let requests = Arc::clone(&in_flight);
                             let mut map = requests.lock().await;
-                            let t = map.remove(&response.opaque());
+                            let t = match map.remove(&response.opaque()) {
+                                // Streams stay registered until a response which is not a
+                                // success, which marks the end of the stream.
+                                Some(Responder::Stream(sender))
+                                    if response.status() == Status::Success =>
+                                {
+                                    map.insert(
+                                        response.opaque(),
+                                        Responder::Stream(sender.clone()),
+                                    );
+                                    Some(Responder::Stream(sender))
+                                }
+                                t => t,
+                            };
                             drop(map);
                             drop(requests);
 
-                            if let Some(sender) = t {
-                                match sender.send(response) {
-                                    Ok(_) => {}
-                                    Err(_e) => {
-                                        warn!("{} could not send kv response", recv_uuid)
-                                    }
+                            if let Some(responder) = t {
+                                let sent = match responder {
+                                    Responder::Once(sender) => sender.send(response).is_ok(),
+                                    Responder::Stream(sender) => sender.send(response).is_ok(),
                                 };
+                                if !sent {
+                                    warn!("{} could not send kv response", recv_uuid)
+                                }
                             } else {
                                 warn!(
                                     "{} has no entry in request map for {}",
@@ -160,14 +241,23 @@ impl KvEndpoint {
                         }
                         Err(e) => {
                             warn!("{} failed to read frame {}", recv_uuid, e.to_string());
+                            break;
                         }
                     };
+                } else {
+                    debug!("{} connection closed by the server", recv_uuid);
+                    break;
                 }
             }
+
+            // Nothing more can be read from the socket, so fail anything still waiting on it.
+            recv_closed.store(true, Ordering::SeqCst);
+            in_flight.lock().await.clear();
         });
 
         // Send thread.
         let send_uuid = uuid.clone();
+        let send_closed = ep.closed.clone();
         tokio::spawn(async move {
             loop {
                 if let Some(packet) = rx.recv().await {
@@ -175,18 +265,20 @@ impl KvEndpoint {
                         Ok(_) => {}
                         Err(_e) => {
                             warn!("{} could not send kv request", send_uuid);
+                            send_closed.store(true, Ordering::SeqCst);
                         }
                     };
                 } else {
+                    // The endpoint has been dropped, closing our half of the socket lets the
+                    // server close the connection and so stops the read thread.
+                    let _ = output.close().await;
                     return;
                 }
             }
         });
 
         let hello_rcvr = ep.send_hello().await?;
-        // let err_map_rcvr = ep.send_error_map().await.map(|r| Some(r))?;
-        let auth_rcvr = ep.send_auth(username, password).await?;
-        let bucket_rcvr = ep.send_select_bucket(bucket).await?;
+        let err_map_rcvr = ep.send_error_map().await?;
 
         let features = match hello_rcvr.await {
             Ok(r) => match r {
@@ -203,31 +295,30 @@ impl KvEndpoint {
             }
         };
         debug!("{} negotiated features {:?}", ep.uuid, features);
-        // if let Some(rcvr) = err_map_rcvr {
-        //     let error_map = match rcvr.await {
-        //         Ok(r) => match r {
-        //             Ok(result) => Some(result),
-        //             Err(_e) => None,
-        //         },
-        //         Err(_e) => None,
-        //     };
-        //     ep.error_map = error_map;
-        // }
-        match auth_rcvr.await {
-            Ok(r) => match r {
-                Ok(result) => result,
-                Err(e) => {
-                    return Err(e);
-                }
-            },
+        // The error map is only used to tune retries, so failing to fetch it is not fatal.
+        ep.error_map = match err_map_rcvr.await {
+            Ok(Ok(error_map)) => {
+                debug!(
+                    "{} loaded error map version {} revision {}",
+                    ep.uuid, error_map.version, error_map.revision
+                );
+                Some(error_map)
+            }
+            Ok(Err(e)) => {
+                debug!("{} failed to fetch error map: {}", ep.uuid, e);
+                None
+            }
             Err(e) => {
-                return Err(ClientError::RequestFailed {
-                    reason: Some(e.to_string()),
-                    key: None,
-                });
+                debug!("{} failed to fetch error map: {}", ep.uuid, e);
+                None
             }
         };
-        debug!("{} authenticated successfully", ep.uuid);
+        // SASL takes several round trips, so unlike the rest of the setup it cannot be pipelined.
+        if !auth_mechanisms.is_empty() {
+            ep.authenticate(username, password, auth_mechanisms).await?;
+            debug!("{} authenticated successfully", ep.uuid);
+        }
+        let bucket_rcvr = ep.send_select_bucket(bucket).await?;
         match bucket_rcvr.await {
             Ok(r) => match r {
                 Ok(result) => result,
@@ -248,7 +339,16 @@ impl KvEndpoint {
             ep.collections_enabled = true;
         }
 
-        // debug!("Error Map: {:?}", ep.error_map);
+        if features.contains(&ServerFeature::SyncReplication) {
+            debug!("{} enabling sync replication", ep.uuid);
+            ep.sync_replication_enabled = true;
+        }
+
+        if features.contains(&ServerFeature::Snappy) {
+            debug!("{} enabling snappy compression", ep.uuid);
+            ep.snappy_enabled = true;
+        }
+
         Ok(ep)
     }
 
@@ -272,20 +372,50 @@ impl KvEndpoint {
         key: String,
         cid: u32,
         path: impl Into<Option<String>>,
+        cas: u64,
     ) -> Result<KvResponse, ClientError> {
         let mut response = self.await_response(rx, key.clone()).await?;
+        if response.status() != Status::Success {
+            return Err(self.doc_op_error(&mut response, key, cid, path.into(), cas));
+        }
+        Ok(response)
+    }
+
+    // doc_op_error converts a failed document response into an error, attaching the information
+    // needed to retry the request where the failure is transient.
+    fn doc_op_error(
+        &self,
+        response: &mut KvResponse,
+        key: String,
+        cid: u32,
+        path: Option<String>,
+        cas: u64,
+    ) -> ClientError {
         let status = response.status();
-        if status != Status::Success {
-            let reason = ClientError::try_parse_kv_fail_body(&mut response);
-            return Err(ClientError::make_kv_doc_op_error(
-                status,
-                reason,
+        if status == Status::NotMyVbucket {
+            // The body may hold a newer config, which tells us where the request should go.
+            return ClientError::NotMyVbucket {
                 key,
-                cid,
-                path.into(),
-            ));
+                config: response.body(),
+            };
         }
-        Ok(response)
+
+        let retry = self.retry_specification(status);
+        let reason = ClientError::try_parse_kv_fail_body(response);
+        match ClientError::make_kv_doc_op_error(status, reason, key, cid, path, cas) {
+            ClientError::TemporaryFailure { key, .. } => {
+                ClientError::TemporaryFailure { key, retry }
+            }
+            ClientError::RequestFailed { key: Some(key), .. } if retry.is_some() => {
+                ClientError::TemporaryFailure { key, retry }
+            }
+            e => e,
+        }
+    }
+
+    // retry_specification looks up how the server wants the status retried in the error map.
+    fn retry_specification(&self, status: Status) -> Option<RetrySpecification> {
+        self.error_map.as_ref()?.retry_specification(status.code()?)
     }
 
     pub async fn get_cid(
@@ -350,7 +480,58 @@ impl KvEndpoint {
         let (tx, rx) = oneshot::channel::<KvResponse>();
         self.send(req, tx).await?;
 
-        self.await_and_handle_doc_response(rx, key, collection_id, None)
+        self.await_and_handle_doc_response(rx, key, collection_id, None, 0)
+            .await
+    }
+
+    pub async fn get_meta(
+        &self,
+        key: String,
+        partition: u16,
+        collection_id: u32,
+    ) -> Result<KvResponse, ClientError> {
+        let mut extras = BytesMut::with_capacity(1);
+        // Version 2 of the response includes the datatype of the document.
+        extras.put_u8(0x02);
+        let req = KvRequest::new(
+            protocol::Opcode::GetMeta,
+            0,
+            partition,
+            0,
+            Some(Bytes::from(key.clone())),
+            Some(extras.freeze()),
+            None,
+            collection_id,
+        );
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        self.await_and_handle_doc_response(rx, key, collection_id, None, 0)
+            .await
+    }
+
+    pub async fn get_replica(
+        &self,
+        key: String,
+        partition: u16,
+        collection_id: u32,
+    ) -> Result<KvResponse, ClientError> {
+        let req = KvRequest::new(
+            protocol::Opcode::GetReplica,
+            0,
+            partition,
+            0,
+            Some(Bytes::from(key.clone())),
+            None,
+            None,
+            collection_id,
+        );
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        self.await_and_handle_doc_response(rx, key, collection_id, None, 0)
             .await
     }
 
@@ -381,7 +562,7 @@ impl KvEndpoint {
         let (tx, rx) = oneshot::channel::<KvResponse>();
         self.send(req, tx).await?;
 
-        self.await_and_handle_doc_response(rx, key, collection_id, path)
+        self.await_and_handle_doc_response(rx, key, collection_id, path, 0)
             .await
     }
 
@@ -391,20 +572,28 @@ impl KvEndpoint {
         partition: u16,
         collection_id: u32,
         paths: Vec<String>,
+        xattrs: Vec<String>,
     ) -> Result<KvResponse, ClientError> {
-        let mut path_bytes_list: Vec<Vec<u8>> = vec![];
+        let mut path_bytes_list: Vec<(Vec<u8>, bool)> = vec![];
         let mut path_bytes_total = 0;
 
-        for p in paths {
+        // The server requires xattr paths to come before any document paths, results are returned
+        // in the same order.
+        let all_paths = xattrs
+            .into_iter()
+            .map(|p| (p, true))
+            .chain(paths.into_iter().map(|p| (p, false)));
+        for (p, xattr) in all_paths {
             let path_bytes = Bytes::from(p.clone());
-            path_bytes_list.push(path_bytes.to_vec());
+            path_bytes_list.push((path_bytes.to_vec(), xattr));
             path_bytes_total += path_bytes.len();
         }
 
         let mut value_buf = BytesMut::with_capacity(path_bytes_total * 4);
-        for path_bytes in path_bytes_list {
+        for (path_bytes, xattr) in path_bytes_list {
             value_buf.put_u8(protocol::Opcode::SubdocGet.encoded());
-            value_buf.put_u8(0);
+            // 0x04 flags the path as an extended attribute
+            value_buf.put_u8(if xattr { 0x04 } else { 0 });
             value_buf.put_u16(path_bytes.len() as u16);
             for pb in path_bytes {
                 value_buf.put_u8(pb);
@@ -428,22 +617,98 @@ impl KvEndpoint {
         let (tx, rx) = oneshot::channel::<KvResponse>();
         self.send(req, tx).await?;
 
-        self.await_and_handle_doc_response(rx, key, collection_id, None)
+        self.await_and_handle_doc_response(rx, key, collection_id, None, 0)
             .await
     }
 
+    pub async fn sub_doc_multi_mutation(
+        &self,
+        key: String,
+        partition: u16,
+        collection_id: u32,
+        specs: Vec<SubdocMutation>,
+        cas: u64,
+        expiry: u32,
+    ) -> Result<KvResponse, ClientError> {
+        let mut value_buf = BytesMut::new();
+        for spec in &specs {
+            value_buf.put_u8(spec.opcode().encoded());
+            value_buf.put_u8(spec.flags());
+            value_buf.put_u16(spec.path().len() as u16);
+            value_buf.put_u32(spec.value().len() as u32);
+            value_buf.put(spec.path().as_bytes());
+            value_buf.put(spec.value());
+        }
+
+        // The expiry is optional for multi mutations, the server infers its presence from the
+        // extras length.
+        let extras = if expiry > 0 {
+            let mut extras = BytesMut::with_capacity(4);
+            extras.put_u32(expiry);
+            Some(extras.freeze())
+        } else {
+            None
+        };
+
+        let req = KvRequest::new(
+            protocol::Opcode::SubdocMultiMutation,
+            0,
+            partition,
+            cas,
+            Some(Bytes::from(key.clone())),
+            extras,
+            Some(value_buf.freeze()),
+            collection_id,
+        );
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        let mut response = self.await_response(rx, key.clone()).await?;
+        match response.status() {
+            Status::Success => Ok(response),
+            Status::SubdocMultiPathFailure => {
+                // The body contains the index and status of the first spec which failed.
+                match response.body() {
+                    Some(mut body) if body.len() >= 3 => {
+                        let index = body.get_u8();
+                        let status = Status::from(body.get_u16());
+                        let path = specs
+                            .get(index as usize)
+                            .map(|s| s.path().to_string())
+                            .unwrap_or_default();
+                        Err(ClientError::SubdocPathFailed {
+                            key,
+                            path,
+                            index,
+                            reason: status.as_string(),
+                        })
+                    }
+                    _ => Err(ClientError::RequestFailed {
+                        reason: Some(Status::SubdocMultiPathFailure.as_string()),
+                        key: Some(key),
+                    }),
+                }
+            }
+            _ => Err(self.doc_op_error(&mut response, key, collection_id, None, cas)),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn set(
         &self,
         key: String,
         value: Vec<u8>,
+        flags: u32,
         expiry: u32,
         partition: u16,
         collection_id: u32,
+        durability: Durability,
     ) -> Result<KvResponse, ClientError> {
         let mut extras = BytesMut::with_capacity(8);
-        extras.put_u32(0);
+        extras.put_u32(flags);
         extras.put_u32(expiry);
-        let req = KvRequest::new(
+        let mut req = KvRequest::new(
             protocol::Opcode::Set,
             0,
             partition,
@@ -453,26 +718,30 @@ impl KvEndpoint {
             Some(value.into()),
             collection_id,
         );
+        self.apply_durability(&mut req, durability, &key)?;
 
         let (tx, rx) = oneshot::channel::<KvResponse>();
         self.send(req, tx).await?;
 
-        self.await_and_handle_doc_response(rx, key, collection_id, None)
+        self.await_and_handle_doc_response(rx, key, collection_id, None, 0)
             .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn add(
         &self,
         key: String,
         value: Vec<u8>,
+        flags: u32,
         expiry: u32,
         partition: u16,
         collection_id: u32,
+        durability: Durability,
     ) -> Result<KvResponse, ClientError> {
         let mut extras = BytesMut::with_capacity(8);
-        extras.put_u32(0);
+        extras.put_u32(flags);
         extras.put_u32(expiry);
-        let req = KvRequest::new(
+        let mut req = KvRequest::new(
             protocol::Opcode::Add,
             0,
             partition,
@@ -482,40 +751,46 @@ impl KvEndpoint {
             Some(value.into()),
             collection_id,
         );
+        self.apply_durability(&mut req, durability, &key)?;
 
         let (tx, rx) = oneshot::channel::<KvResponse>();
         self.send(req, tx).await?;
 
-        self.await_and_handle_doc_response(rx, key, collection_id, None)
+        self.await_and_handle_doc_response(rx, key, collection_id, None, 0)
             .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn replace(
         &self,
         key: String,
         value: Vec<u8>,
+        flags: u32,
         expiry: u32,
         partition: u16,
         collection_id: u32,
+        cas: u64,
+        durability: Durability,
     ) -> Result<KvResponse, ClientError> {
         let mut extras = BytesMut::with_capacity(8);
-        extras.put_u32(0);
+        extras.put_u32(flags);
         extras.put_u32(expiry);
-        let req = KvRequest::new(
+        let mut req = KvRequest::new(
             protocol::Opcode::Replace,
             0,
             partition,
-            0,
+            cas,
             Some(Bytes::from(key.clone())),
             Some(extras.freeze()),
             Some(value.into()),
             collection_id,
         );
+        self.apply_durability(&mut req, durability, &key)?;
 
         let (tx, rx) = oneshot::channel::<KvResponse>();
         self.send(req, tx).await?;
 
-        self.await_and_handle_doc_response(rx, key, collection_id, None)
+        self.await_and_handle_doc_response(rx, key, collection_id, None, cas)
             .await
     }
 
@@ -524,14 +799,138 @@ impl KvEndpoint {
         key: String,
         partition: u16,
         collection_id: u32,
+        cas: u64,
+        durability: Durability,
     ) -> Result<KvResponse, ClientError> {
-        let req = KvRequest::new(
+        let mut req = KvRequest::new(
             protocol::Opcode::Remove,
             0,
             partition,
+            cas,
+            Some(Bytes::from(key.clone())),
+            None,
+            None,
+            collection_id,
+        );
+        self.apply_durability(&mut req, durability, &key)?;
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        self.await_and_handle_doc_response(rx, key, collection_id, None, cas)
+            .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn counter(
+        &self,
+        opcode: protocol::Opcode,
+        key: String,
+        delta: u64,
+        initial: Option<u64>,
+        expiry: u32,
+        partition: u16,
+        collection_id: u32,
+        durability: Durability,
+    ) -> Result<KvResponse, ClientError> {
+        // An expiry of all ones tells the server not to create the document if it does not exist.
+        let expiry = if initial.is_some() { expiry } else { u32::MAX };
+        let mut extras = BytesMut::with_capacity(20);
+        extras.put_u64(delta);
+        extras.put_u64(initial.unwrap_or_default());
+        extras.put_u32(expiry);
+        let mut req = KvRequest::new(
+            opcode,
+            0,
+            partition,
+            0,
+            Some(Bytes::from(key.clone())),
+            Some(extras.freeze()),
+            None,
+            collection_id,
+        );
+        self.apply_durability(&mut req, durability, &key)?;
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        self.await_and_handle_doc_response(rx, key, collection_id, None, 0)
+            .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn concat(
+        &self,
+        opcode: protocol::Opcode,
+        key: String,
+        value: Vec<u8>,
+        partition: u16,
+        collection_id: u32,
+        cas: u64,
+        durability: Durability,
+    ) -> Result<KvResponse, ClientError> {
+        let mut req = KvRequest::new(
+            opcode,
+            0,
+            partition,
+            cas,
+            Some(Bytes::from(key.clone())),
+            None,
+            Some(value.into()),
+            collection_id,
+        );
+        self.apply_durability(&mut req, durability, &key)?;
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        self.await_and_handle_doc_response(rx, key, collection_id, None, cas)
+            .await
+    }
+
+    pub async fn touch(
+        &self,
+        key: String,
+        expiry: u32,
+        partition: u16,
+        collection_id: u32,
+    ) -> Result<KvResponse, ClientError> {
+        let mut extras = BytesMut::with_capacity(4);
+        extras.put_u32(expiry);
+        let req = KvRequest::new(
+            protocol::Opcode::Touch,
+            0,
+            partition,
             0,
             Some(Bytes::from(key.clone())),
+            Some(extras.freeze()),
             None,
+            collection_id,
+        );
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        self.await_and_handle_doc_response(rx, key, collection_id, None, 0)
+            .await
+    }
+
+    pub async fn get_and_touch(
+        &self,
+        key: String,
+        expiry: u32,
+        partition: u16,
+        collection_id: u32,
+    ) -> Result<KvResponse, ClientError> {
+        let mut extras = BytesMut::with_capacity(4);
+        extras.put_u32(expiry);
+        let req = KvRequest::new(
+            protocol::Opcode::GetAndTouch,
+            0,
+            partition,
+            0,
+            Some(Bytes::from(key.clone())),
+            Some(extras.freeze()),
             None,
             collection_id,
         );
@@ -539,10 +938,226 @@ impl KvEndpoint {
         let (tx, rx) = oneshot::channel::<KvResponse>();
         self.send(req, tx).await?;
 
-        self.await_and_handle_doc_response(rx, key, collection_id, None)
+        self.await_and_handle_doc_response(rx, key, collection_id, None, 0)
             .await
     }
 
+    pub async fn get_and_lock(
+        &self,
+        key: String,
+        lock_time: u32,
+        partition: u16,
+        collection_id: u32,
+    ) -> Result<KvResponse, ClientError> {
+        let mut extras = BytesMut::with_capacity(4);
+        extras.put_u32(lock_time);
+        let req = KvRequest::new(
+            protocol::Opcode::GetLocked,
+            0,
+            partition,
+            0,
+            Some(Bytes::from(key.clone())),
+            Some(extras.freeze()),
+            None,
+            collection_id,
+        );
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        self.await_and_handle_doc_response(rx, key, collection_id, None, 0)
+            .await
+    }
+
+    pub async fn unlock(
+        &self,
+        key: String,
+        cas: u64,
+        partition: u16,
+        collection_id: u32,
+    ) -> Result<KvResponse, ClientError> {
+        let req = KvRequest::new(
+            protocol::Opcode::UnlockKey,
+            0,
+            partition,
+            cas,
+            Some(Bytes::from(key.clone())),
+            None,
+            None,
+            collection_id,
+        );
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        self.await_and_handle_doc_response(rx, key, collection_id, None, cas)
+            .await
+    }
+
+    // get_cluster_config fetches the config of the bucket which the endpoint is connected to.
+    pub async fn get_cluster_config(&self) -> Result<Bytes, ClientError> {
+        let req = KvRequest::new(
+            protocol::Opcode::GetClusterConfig,
+            0,
+            0,
+            0,
+            None,
+            None,
+            None,
+            0,
+        );
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        let mut resp = self.await_response(rx, None).await?;
+        if resp.status() != Status::Success {
+            let reason = ClientError::try_parse_kv_fail_body(&mut resp);
+            return Err(ClientError::RequestFailed {
+                reason: Some(reason.unwrap_or_else(|| resp.status().as_string())),
+                key: None,
+            });
+        }
+
+        resp.body().ok_or_else(|| ClientError::RequestFailed {
+            reason: Some("Server returned an empty config".to_string()),
+            key: None,
+        })
+    }
+
+    // range_scan_create starts a scan of the keys of the partition, returning the id of the scan
+    // or None if there is nothing in the range.
+    pub async fn range_scan_create(
+        &self,
+        partition: u16,
+        collection_id: u32,
+        scan: serde_json::Value,
+    ) -> Result<Option<Bytes>, ClientError> {
+        let req = KvRequest::new(
+            protocol::Opcode::RangeScanCreate,
+            protocol::DATATYPE_JSON,
+            partition,
+            0,
+            None,
+            None,
+            Some(Bytes::from(scan.to_string())),
+            collection_id,
+        );
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        let mut resp = self.await_response(rx, None).await?;
+        match resp.status() {
+            Status::Success => {}
+            Status::KeyNotFound => return Ok(None),
+            Status::NotMyVbucket => {
+                return Err(ClientError::NotMyVbucket {
+                    key: String::new(),
+                    config: resp.body(),
+                })
+            }
+            status => {
+                let reason = ClientError::try_parse_kv_fail_body(&mut resp);
+                return Err(match status {
+                    Status::AuthError => ClientError::AuthError { reason },
+                    Status::AccessError => ClientError::AccessError { reason },
+                    Status::CollectionUnknown => ClientError::CollectionUnknownDuringRequest {
+                        key: String::new(),
+                        cid: collection_id,
+                    },
+                    // Servers before 7.6 do not know the command.
+                    Status::UnknownCommand | Status::NotSupported => {
+                        ClientError::RangeScanUnsupported
+                    }
+                    _ => ClientError::RequestFailed {
+                        reason: Some(reason.unwrap_or_else(|| status.as_string())),
+                        key: None,
+                    },
+                });
+            }
+        }
+
+        match resp.body() {
+            Some(id) if id.len() == RANGE_SCAN_ID_LEN => Ok(Some(id)),
+            _ => Err(ClientError::RequestFailed {
+                reason: Some("Response from range scan create not expected format".to_string()),
+                key: None,
+            }),
+        }
+    }
+
+    // range_scan_continue asks for the next batch of the scan. The items arrive over several
+    // responses, the last of which has a status saying whether the scan has more to return.
+    pub async fn range_scan_continue(
+        &self,
+        partition: u16,
+        scan_id: Bytes,
+        item_limit: u32,
+        byte_limit: u32,
+    ) -> Result<mpsc::UnboundedReceiver<KvResponse>, ClientError> {
+        let mut extras = BytesMut::with_capacity(RANGE_SCAN_ID_LEN + 12);
+        extras.put(scan_id);
+        extras.put_u32(item_limit);
+        // No time limit, the batch is bounded by the item and byte limits.
+        extras.put_u32(0);
+        extras.put_u32(byte_limit);
+
+        let req = KvRequest::new(
+            protocol::Opcode::RangeScanContinue,
+            0,
+            partition,
+            0,
+            None,
+            Some(extras.freeze()),
+            None,
+            0,
+        );
+
+        self.send_streaming(req).await
+    }
+
+    // range_scan_cancel stops a scan which is no longer needed, rather than leaving it open on
+    // the server until it times out.
+    pub async fn range_scan_cancel(
+        &self,
+        partition: u16,
+        scan_id: Bytes,
+    ) -> Result<(), ClientError> {
+        let req = KvRequest::new(
+            protocol::Opcode::RangeScanCancel,
+            0,
+            partition,
+            0,
+            None,
+            Some(scan_id),
+            None,
+            0,
+        );
+
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        let mut resp = self.await_response(rx, None).await?;
+        match resp.status() {
+            // The scan may have completed or timed out in the meantime.
+            Status::Success | Status::KeyNotFound | Status::RangeScanCancelled => Ok(()),
+            status => {
+                let reason = ClientError::try_parse_kv_fail_body(&mut resp);
+                Err(ClientError::RequestFailed {
+                    reason: Some(reason.unwrap_or_else(|| status.as_string())),
+                    key: None,
+                })
+            }
+        }
+    }
+
+    // take_pushed_config returns the newest config which the server sent after a topology change,
+    // if there is one which has not already been taken.
+    pub fn take_pushed_config(&self) -> Option<Bytes> {
+        self.pushed_config.lock().unwrap().take()
+    }
+
     pub async fn noop(&self) -> Result<KvResponse, ClientError> {
         let req = KvRequest::new(protocol::Opcode::Noop, 0, 0, 0, None, None, None, 0);
 
@@ -569,13 +1184,80 @@ impl KvEndpoint {
         self.remote_addr.clone()
     }
 
+    // hostname is the host part of the remote address, which configs sent by the server refer to
+    // as $HOST.
+    pub fn hostname(&self) -> &str {
+        self.remote_addr
+            .rsplit_once(':')
+            .map(|(host, _)| host)
+            .unwrap_or(&self.remote_addr)
+    }
+
+    // is_closed is whether the socket has failed or been closed by the server, after which the
+    // endpoint can no longer be used.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    // apply_durability adds the durability frame to the request, if a durability level is
+    // required and sync replication was negotiated with the server.
+    fn apply_durability(
+        &self,
+        req: &mut KvRequest,
+        durability: Durability,
+        key: &str,
+    ) -> Result<(), ClientError> {
+        if let Some(frame) = durability.framing_extras() {
+            if !self.sync_replication_enabled {
+                return Err(ClientError::RequestFailed {
+                    reason: Some("Server does not support sync replication durability".to_string()),
+                    key: Some(key.to_string()),
+                });
+            }
+            req.set_framing_extras(frame);
+        }
+
+        Ok(())
+    }
+
     async fn send(
         &self,
-        mut req: KvRequest,
+        req: KvRequest,
         chan: oneshot::Sender<KvResponse>,
     ) -> Result<(), ClientError> {
+        self.send_with_responder(req, Responder::Once(chan)).await
+    }
+
+    // send_streaming sends a request which the server answers with several responses, all of them
+    // successes apart from the last.
+    async fn send_streaming(
+        &self,
+        req: KvRequest,
+    ) -> Result<mpsc::UnboundedReceiver<KvResponse>, ClientError> {
+        let (tx, rx) = mpsc::unbounded_channel::<KvResponse>();
+        self.send_with_responder(req, Responder::Stream(tx)).await?;
+
+        Ok(rx)
+    }
+
+    async fn send_with_responder(
+        &self,
+        mut req: KvRequest,
+        responder: Responder,
+    ) -> Result<(), ClientError> {
+        if self.is_closed() {
+            return Err(ClientError::RequestFailed {
+                reason: Some(format!("Connection to {} is closed", self.remote_addr)),
+                key: None,
+            });
+        }
         let opaque = self.opaque.fetch_add(1, Ordering::SeqCst);
         req.set_opaque(opaque);
+        if self.snappy_enabled {
+            if let Some(min_size) = self.compression_min_size {
+                req.compress(min_size);
+            }
+        }
         trace!(
             "Writing request on {}. {} to {}. Opcode = {}. Opaque = {}",
             self.uuid,
@@ -585,7 +1267,7 @@ impl KvEndpoint {
             req.opaque()
         );
         let mut map = self.in_flight.lock().await;
-        map.insert(opaque, chan);
+        map.insert(opaque, responder);
         drop(map);
 
         match self
@@ -613,13 +1295,18 @@ impl KvEndpoint {
     ) -> Result<oneshot::Receiver<Result<Vec<ServerFeature>, ClientError>>, ClientError> {
         let features = vec![
             ServerFeature::SelectBucket,
+            ServerFeature::MutationSeqno,
             ServerFeature::Xattr,
+            ServerFeature::Vattr,
             ServerFeature::Xerror,
             ServerFeature::AltRequest,
             ServerFeature::SyncReplication,
             ServerFeature::Collections,
             ServerFeature::Tracing,
             ServerFeature::UnorderedExecution,
+            ServerFeature::Snappy,
+            ServerFeature::Duplex,
+            ServerFeature::ClustermapChangeNotification,
         ];
         let mut body = BytesMut::with_capacity(features.len() * 2);
         for feature in &features {
@@ -648,50 +1335,18 @@ impl KvEndpoint {
         Ok(completerx)
     }
 
-    // async fn send_error_map(
-    //     &mut self,
-    // ) -> Result<oneshot::Receiver<Result<ErrorMap, ClientError>>, ClientError> {
-    //     let mut body = BytesMut::with_capacity(2);
-    //     body.put_u16(protocol::ERROR_MAP_VERSION);
-    //
-    //     let req = KvRequest::new(
-    //         protocol::Opcode::ErrorMap,
-    //         0,
-    //         0,
-    //         0,
-    //         None,
-    //         None,
-    //         Some(body.freeze()),
-    //         0,
-    //     );
-    //     let (tx, rx) = oneshot::channel::<KvResponse>();
-    //     self.send(req, tx).await?;
-    //
-    //     let (completetx, completerx) = oneshot::channel::<Result<ErrorMap, ClientError>>();
-    //     tokio::spawn(async move {
-    //         receive_error_map(rx, completetx).await;
-    //     });
-    //
-    //     Ok(completerx)
-    // }
-
-    async fn send_auth(
+    async fn send_error_map(
         &mut self,
-        username: String,
-        password: String,
-    ) -> Result<oneshot::Receiver<Result<(), ClientError>>, ClientError> {
-        let mut body = BytesMut::with_capacity(username.len() + password.len() + 2);
-        body.put_u8(0);
-        body.put(username.as_bytes());
-        body.put_u8(0);
-        body.put(password.as_bytes());
+    ) -> Result<oneshot::Receiver<Result<ErrorMap, ClientError>>, ClientError> {
+        let mut body = BytesMut::with_capacity(2);
+        body.put_u16(ERROR_MAP_VERSION);
 
         let req = KvRequest::new(
-            protocol::Opcode::Auth,
+            protocol::Opcode::ErrorMap,
             0,
             0,
             0,
-            Some(Bytes::from("PLAIN")),
+            None,
             None,
             Some(body.freeze()),
             0,
@@ -699,14 +1354,121 @@ impl KvEndpoint {
         let (tx, rx) = oneshot::channel::<KvResponse>();
         self.send(req, tx).await?;
 
-        let (completetx, completerx) = oneshot::channel::<Result<(), ClientError>>();
+        let (completetx, completerx) = oneshot::channel::<Result<ErrorMap, ClientError>>();
         tokio::spawn(async move {
-            receive_auth(rx, completetx).await;
+            receive_error_map(rx, completetx).await;
         });
 
         Ok(completerx)
     }
 
+    // authenticate runs the SASL exchange for the first of the mechanisms which the server also
+    // supports.
+    async fn authenticate(
+        &self,
+        username: String,
+        password: String,
+        mechanisms: Vec<AuthMechanism>,
+    ) -> Result<(), ClientError> {
+        let mut response = self
+            .sasl_request(protocol::Opcode::SaslListMechs, None, None)
+            .await?;
+        let supported: Vec<String> = response
+            .body()
+            .map(|b| {
+                String::from_utf8_lossy(b.as_ref())
+                    .split_whitespace()
+                    .map(|m| m.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mechanism = match mechanisms
+            .iter()
+            .find(|m| supported.iter().any(|s| s == m.name()))
+        {
+            Some(m) => *m,
+            None => {
+                let mut reason = format!(
+                    "No common authentication mechanism, the server supports {} but the client requested {}",
+                    supported.join(", "),
+                    mechanisms
+                        .iter()
+                        .map(|m| m.name())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                );
+                if supported.iter().any(|s| s == AuthMechanism::Plain.name()) {
+                    reason.push_str(", set kv-auth-mechanism = \"plain\" in the cluster config to send the password in the clear");
+                }
+                return Err(ClientError::AuthError {
+                    reason: Some(reason),
+                });
+            }
+        };
+        debug!("{} authenticating using {}", self.uuid, mechanism.name());
+
+        let hash = match mechanism.scram_hash() {
+            Some(h) => h,
+            None => {
+                let mut body = BytesMut::with_capacity(username.len() + password.len() + 2);
+                body.put_u8(0);
+                body.put(username.as_bytes());
+                body.put_u8(0);
+                body.put(password.as_bytes());
+
+                let response = self
+                    .sasl_request(protocol::Opcode::Auth, mechanism, body.freeze())
+                    .await?;
+                return expect_auth_status(response, Status::Success).map(|_| ());
+            }
+        };
+
+        let mut client = ScramClient::new(hash, &username, password);
+        let response = self
+            .sasl_request(
+                protocol::Opcode::Auth,
+                mechanism,
+                Bytes::from(client.client_first()),
+            )
+            .await?;
+        let server_first = expect_auth_status(response, Status::AuthContinue)?;
+
+        let client_final = client.client_final(&server_first)?;
+        let response = self
+            .sasl_request(
+                protocol::Opcode::SaslStep,
+                mechanism,
+                Bytes::from(client_final),
+            )
+            .await?;
+        let server_final = expect_auth_status(response, Status::Success)?;
+
+        client.verify_server_final(&server_final)
+    }
+
+    async fn sasl_request(
+        &self,
+        opcode: protocol::Opcode,
+        mechanism: impl Into<Option<AuthMechanism>>,
+        body: impl Into<Option<Bytes>>,
+    ) -> Result<KvResponse, ClientError> {
+        let req = KvRequest::new(
+            opcode,
+            0,
+            0,
+            0,
+            mechanism.into().map(|m| Bytes::from(m.name())),
+            None,
+            body.into(),
+            0,
+        );
+        let (tx, rx) = oneshot::channel::<KvResponse>();
+        self.send(req, tx).await?;
+
+        self.await_response(rx, None).await
+    }
+
     async fn send_select_bucket(
         &mut self,
         bucket: String,
@@ -785,61 +1547,28 @@ async fn receive_hello(
     };
 }
 
-// async fn receive_error_map(
-//     rx: oneshot::Receiver<KvResponse>,
-//     completetx: oneshot::Sender<Result<ErrorMap, ClientError>>,
-// ) {
-//     let r = match rx.await {
-//         Ok(r) => Some(r),
-//         Err(_e) => None,
-//     };
-//     let result = if let Some(mut response) = r {
-//         let status = response.status();
-//
-//         match status {
-//             Status::Success => {
-//                 if let Some(body) = response.body() {
-//                     let error_map = serde_json::from_slice(body.as_ref()).unwrap();
-//                     Ok(error_map)
-//                 } else {
-//                     Err(ClientError::RequestFailed {
-//                         reason: None,
-//                         key: None,
-//                     })
-//                 }
-//             }
-//             _ => Err(ClientError::RequestFailed {
-//                 reason: Some(status.as_string()),
-//                 key: None,
-//             }),
-//         }
-//     } else {
-//         Err(ClientError::RequestFailed {
-//             reason: None,
-//             key: None,
-//         })
-//     };
-//
-//     match completetx.send(result) {
-//         Ok(()) => {}
-//         Err(_e) => {
-//             warn!("error map receive failed");
-//         }
-//     };
-// }
-
-async fn receive_auth(
+async fn receive_error_map(
     rx: oneshot::Receiver<KvResponse>,
-    completetx: oneshot::Sender<Result<(), ClientError>>,
+    completetx: oneshot::Sender<Result<ErrorMap, ClientError>>,
 ) {
     let r = match rx.await {
         Ok(r) => Some(r),
         Err(_e) => None,
     };
-    let result = if let Some(response) = r {
+    let result = if let Some(mut response) = r {
         let status = response.status();
+
         match status {
-            Status::Success => Ok(()),
+            Status::Success => {
+                if let Some(body) = response.body() {
+                    serde_json::from_slice(body.as_ref()).map_err(ClientError::from)
+                } else {
+                    Err(ClientError::RequestFailed {
+                        reason: None,
+                        key: None,
+                    })
+                }
+            }
             _ => Err(ClientError::RequestFailed {
                 reason: Some(status.as_string()),
                 key: None,
@@ -855,11 +1584,27 @@ async fn receive_auth(
     match completetx.send(result) {
         Ok(()) => {}
         Err(_e) => {
-            warn!("auth receive failed");
+            warn!("error map receive failed");
         }
     };
 }
 
+// expect_auth_status checks the status of a SASL response, returning the body of the response.
+fn expect_auth_status(mut response: KvResponse, expected: Status) -> Result<String, ClientError> {
+    if response.status() != expected {
+        let reason = ClientError::try_parse_kv_fail_body(&mut response)
+            .unwrap_or_else(|| response.status().as_string());
+        return Err(ClientError::AuthError {
+            reason: Some(reason),
+        });
+    }
+
+    Ok(response
+        .body()
+        .map(|b| String::from_utf8_lossy(b.as_ref()).to_string())
+        .unwrap_or_default())
+}
+
 async fn receive_select_bucket(
     rx: oneshot::Receiver<KvResponse>,
     completetx: oneshot::Sender<Result<(), ClientError>>,
@@ -892,6 +1637,39 @@ async fn receive_select_bucket(
     };
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub enum AuthMechanism {
+    #[serde(rename = "plain")]
+    Plain,
+    #[serde(rename = "scram-sha1")]
+    ScramSha1,
+    #[serde(rename = "scram-sha256")]
+    ScramSha256,
+    #[serde(rename = "scram-sha512")]
+    ScramSha512,
+}
+
+impl AuthMechanism {
+    // name is how the server refers to the mechanism.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Plain => "PLAIN",
+            Self::ScramSha1 => "SCRAM-SHA1",
+            Self::ScramSha256 => "SCRAM-SHA256",
+            Self::ScramSha512 => "SCRAM-SHA512",
+        }
+    }
+
+    fn scram_hash(&self) -> Option<ScramHash> {
+        match self {
+            Self::Plain => None,
+            Self::ScramSha1 => Some(ScramHash::Sha1),
+            Self::ScramSha256 => Some(ScramHash::Sha256),
+            Self::ScramSha512 => Some(ScramHash::Sha512),
+        }
+    }
+}
+
 #[derive(Debug)]
 enum ServerFeature {
     SelectBucket,
@@ -906,6 +1684,8 @@ enum ServerFeature {
     UnorderedExecution,
     Vattr,
     CreateAsDeleted,
+    Duplex,
+    ClustermapChangeNotification,
 }
 
 impl ServerFeature {
@@ -923,6 +1703,8 @@ impl ServerFeature {
             Self::UnorderedExecution => 0x0E,
             Self::Vattr => 0x15,
             Self::CreateAsDeleted => 0x17,
+            Self::Duplex => 0x0C,
+            Self::ClustermapChangeNotification => 0x0D,
         }
     }
 }
@@ -950,80 +1732,9 @@ impl TryFrom<u16> for ServerFeature {
             0x0E => Self::UnorderedExecution,
             0x15 => Self::Vattr,
             0x17 => Self::CreateAsDeleted,
+            0x0C => Self::Duplex,
+            0x0D => Self::ClustermapChangeNotification,
             _ => return Err(input),
         })
     }
 }
-
-// #[derive(Debug, Deserialize)]
-// struct ErrorMap {
-//     version: u16,
-//     revision: u16,
-//     errors: HashMap<String, ErrorCode>,
-// }
-//
-// #[derive(Debug, Deserialize)]
-// struct ErrorCode {
-//     name: String,
-//     desc: String,
-//     attrs: HashSet<ErrorAttribute>,
-//     retry: Option<RetrySpecification>,
-// }
-//
-// #[derive(Debug, Deserialize)]
-// struct RetrySpecification {
-//     strategy: RetryStrategy,
-//     interval: u32,
-//     after: u32,
-//     #[serde(rename = "max-duration")]
-//     max_duration: u32,
-//     ceil: u32,
-// }
-//
-// #[derive(Debug, Deserialize, Eq, PartialEq, Hash)]
-// enum ErrorAttribute {
-//     #[serde(rename = "success")]
-//     Success,
-//     #[serde(rename = "item-only")]
-//     ItemOnly,
-//     #[serde(rename = "invalid-input")]
-//     InvalidInput,
-//     #[serde(rename = "fetch-config")]
-//     FetchConfig,
-//     #[serde(rename = "conn-state-invalidated")]
-//     ConnStateInvalidated,
-//     #[serde(rename = "auth")]
-//     Auth,
-//     #[serde(rename = "special-handling")]
-//     SpecialHandling,
-//     #[serde(rename = "support")]
-//     Support,
-//     #[serde(rename = "temp")]
-//     Temp,
-//     #[serde(rename = "internal")]
-//     Internal,
-//     #[serde(rename = "retry-now")]
-//     RetryNow,
-//     #[serde(rename = "retry-later")]
-//     RetryLater,
-//     #[serde(rename = "subdoc")]
-//     Subdoc,
-//     #[serde(rename = "dcp")]
-//     Dcp,
-//     #[serde(rename = "auto-retry")]
-//     AutoRetry,
-//     #[serde(rename = "item-locked")]
-//     ItemLocked,
-//     #[serde(rename = "item-deleted")]
-//     ItemDeleted,
-// }
-//
-// #[derive(Debug, Deserialize)]
-// enum RetryStrategy {
-//     #[serde(rename = "exponential")]
-//     Exponential,
-//     #[serde(rename = "linear")]
-//     Linear,
-//     #[serde(rename = "constant")]
-//     Constant,
-// }
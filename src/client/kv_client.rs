@@ -3,29 +3,39 @@ use crate::client::crc::cb_vb_map;
 use crate::client::error::ClientError;
 use crate::client::http_client::{Config, PingResponse, ServiceType};
 use crate::client::http_handler::HTTPHandler;
-use crate::client::kv::KvEndpoint;
+use crate::client::kv::{AuthMechanism, KvEndpoint};
 use crate::client::{protocol, HTTPClient};
 use crate::RustTlsConfig;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine as _;
 use bytes::{Buf, Bytes};
+use futures::lock::Mutex as AsyncMutex;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
-use log::{debug, trace};
+use log::{debug, info, trace};
 use serde::Deserialize;
 use serde_json::json;
+use std::convert::TryFrom;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use std::{collections::HashMap, ops::Sub};
+use tokio::runtime::Handle;
 use tokio::select;
-use tokio::time::{sleep, Instant, Sleep};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, timeout_at, Instant, Sleep};
 
 #[derive(Debug)]
 pub struct KvResponse {
     content: Option<serde_json::Value>,
+    body: Option<Bytes>,
     cas: u64,
     key: String,
     extras: Option<Bytes>,
+    retries: u32,
+    mutation_token: Option<MutationToken>,
 }
 
 impl KvResponse {
@@ -33,6 +43,20 @@ impl KvResponse {
         self.content.take()
     }
 
+    // body is the raw document returned by get operations, which are not decoded as JSON as the
+    // document may be stored in another format.
+    pub fn body(&mut self) -> Option<Bytes> {
+        self.body.take()
+    }
+
+    // flags are the user flags stored alongside a document, returned by get operations.
+    pub fn flags(&self) -> u32 {
+        match &self.extras {
+            Some(e) if e.len() >= 4 => u32::from_be_bytes([e[0], e[1], e[2], e[3]]),
+            _ => 0,
+        }
+    }
+
     pub fn cas(&self) -> u64 {
         self.cas
     }
@@ -41,24 +65,210 @@ impl KvResponse {
         self.key.clone()
     }
 
+    // retries is the number of times the request was retried before it succeeded.
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
     pub fn extras(&mut self) -> Option<Bytes> {
         self.extras.take()
     }
+
+    // mutation_token identifies the change made by a mutation, so that later queries can wait for
+    // their indexes to include it.
+    pub fn mutation_token(&self) -> Option<MutationToken> {
+        self.mutation_token.clone()
+    }
+
+    // document_meta parses the extras of a get meta response.
+    pub fn document_meta(&mut self) -> Option<DocumentMeta> {
+        let mut extras = self.extras.take()?;
+        if extras.len() < 20 {
+            return None;
+        }
+
+        let deleted = extras.get_u32() != 0;
+        let flags = extras.get_u32();
+        let expiry = extras.get_u32();
+        let seqno = extras.get_u64();
+        let datatype = if extras.has_remaining() {
+            extras.get_u8()
+        } else {
+            0
+        };
+
+        Some(DocumentMeta {
+            deleted,
+            flags,
+            expiry,
+            seqno,
+            datatype,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MutationToken {
+    bucket: String,
+    partition: u16,
+    partition_uuid: u64,
+    seqno: u64,
+}
+
+impl MutationToken {
+    pub fn new(bucket: String, partition: u16, partition_uuid: u64, seqno: u64) -> Self {
+        Self {
+            bucket,
+            partition,
+            partition_uuid,
+            seqno,
+        }
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    pub fn partition(&self) -> u16 {
+        self.partition
+    }
+
+    pub fn partition_uuid(&self) -> u64 {
+        self.partition_uuid
+    }
+
+    pub fn seqno(&self) -> u64 {
+        self.seqno
+    }
+}
+
+// MutationState holds the newest mutation token of each partition written to, which is all that
+// is needed to wait for every one of those writes.
+#[derive(Debug, Clone, Default)]
+pub struct MutationState {
+    tokens: HashMap<(String, u16), MutationToken>,
+}
+
+impl MutationState {
+    pub fn add(&mut self, token: MutationToken) {
+        let key = (token.bucket.clone(), token.partition);
+        match self.tokens.get(&key) {
+            // A different partition uuid means the partition failed over, so only the newer
+            // history is relevant.
+            Some(existing)
+                if existing.partition_uuid == token.partition_uuid
+                    && existing.seqno >= token.seqno => {}
+            _ => {
+                self.tokens.insert(key, token);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: MutationState) {
+        for token in other.tokens.into_values() {
+            self.add(token);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &MutationToken> {
+        self.tokens.values()
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct DocumentMeta {
+    deleted: bool,
+    flags: u32,
+    expiry: u32,
+    seqno: u64,
+    datatype: u8,
+}
+
+impl DocumentMeta {
+    pub fn deleted(&self) -> bool {
+        self.deleted
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    // expiry is the absolute unix time at which the document expires, 0 if it does not.
+    pub fn expiry(&self) -> u32 {
+        self.expiry
+    }
+
+    pub fn seqno(&self) -> u64 {
+        self.seqno
+    }
+
+    pub fn datatype(&self) -> Vec<&'static str> {
+        let mut datatype = vec![];
+        if self.datatype & 0x01 != 0 {
+            datatype.push("json");
+        }
+        if self.datatype & 0x02 != 0 {
+            datatype.push("snappy");
+        }
+        if self.datatype & 0x04 != 0 {
+            datatype.push("xattr");
+        }
+        if datatype.is_empty() {
+            datatype.push("raw");
+        }
+        datatype
+    }
 }
 
 pub struct KvClient {
-    endpoints: HashMap<String, KvEndpoint>,
-    config: BucketConfig,
+    endpoints: AsyncMutex<HashMap<String, Arc<KvEndpoint>>>,
+    // Held while connecting to the node with the address, so only one connection is made to it.
+    connecting: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+    config: RwLock<Arc<BucketConfig>>,
     tls_enabled: bool,
+    seeds: Vec<String>,
+    options: EndpointOptions,
+    // Connections are made on the runtime which created the client, as a pooled client outlives
+    // the runtime of any single command.
+    runtime: Handle,
+    last_config_poll: Mutex<Instant>,
+}
+
+// How many scanned items are buffered ahead of the consumer.
+static RANGE_SCAN_BUFFER: usize = 1024;
+// How often creating a range scan is retried while the partition is moving.
+static RANGE_SCAN_RETRIES: u32 = 10;
+
+// How often the config is polled while requests are being made, when the server has not pushed a
+// newer one.
+static CONFIG_POLL_INTERVAL: Duration = Duration::from_millis(2500);
+
+// EndpointOptions holds what is needed to connect to nodes which join the cluster after the client
+// was created.
+#[derive(Clone)]
+struct EndpointOptions {
+    username: String,
+    password: String,
+    bucket: String,
+    tls_config: Option<RustTlsConfig>,
+    compression_min_size: Option<u32>,
+    auth_mechanism: Option<AuthMechanism>,
 }
 
 impl KvClient {
+    #[allow(clippy::too_many_arguments)]
     pub async fn connect(
         seeds: Vec<String>,
         username: String,
         password: String,
         tls_config: Option<RustTlsConfig>,
         bucket: String,
+        compression_min_size: Option<u32>,
+        auth_mechanism: Option<AuthMechanism>,
         deadline: Instant,
         ctrl_c: Arc<AtomicBool>,
     ) -> Result<Self, ClientError> {
@@ -93,7 +303,17 @@ impl KvClient {
             let tls = tls_config.clone();
 
             workers.push(tokio::spawn(async move {
-                KvEndpoint::connect(hostname, port, u, p, b, tls).await
+                KvEndpoint::connect(
+                    hostname,
+                    port,
+                    u,
+                    p,
+                    b,
+                    tls,
+                    compression_min_size,
+                    auth_mechanism,
+                )
+                .await
             }));
         }
 
@@ -117,31 +337,297 @@ impl KvClient {
                 () = &mut ctrl_c_fut => Err(ClientError::Cancelled{key: None}),
                 else => {break}
             }?;
-            endpoints.insert(endpoint.remote(), endpoint);
+            endpoints.insert(endpoint.remote(), Arc::new(endpoint));
         }
 
         Ok(Self {
-            config,
-            endpoints,
+            config: RwLock::new(Arc::new(config)),
+            endpoints: AsyncMutex::new(endpoints),
+            connecting: Mutex::new(HashMap::new()),
             tls_enabled: tls_config.is_some(),
+            seeds,
+            options: EndpointOptions {
+                username,
+                password,
+                bucket,
+                tls_config,
+                compression_min_size,
+                auth_mechanism,
+            },
+            runtime: Handle::current(),
+            last_config_poll: Mutex::new(Instant::now()),
         })
     }
 
+    // config returns the most recent bucket config seen by the client.
+    fn config(&self) -> Arc<BucketConfig> {
+        self.config.read().unwrap().clone()
+    }
+
     fn partition_for_key(&self, key: String) -> u32 {
-        let num_partitions = self.config.vbucket_server_map.vbucket_map.len() as u32;
+        let num_partitions = self.config().vbucket_server_map.vbucket_map.len() as u32;
 
         cb_vb_map(key.as_bytes().to_vec(), num_partitions)
     }
 
-    fn node_for_partition(&self, partition: u32) -> (String, u32) {
-        let seeds = self.config.key_value_seeds(self.tls_enabled);
-        let node = self.config.vbucket_server_map.vbucket_map[partition as usize][0];
+    // node_for_partition returns the node hosting the active copy of the partition, if there is
+    // one. There is none while the partition is being failed over.
+    fn node_for_partition(&self, partition: u32) -> Option<(String, u32)> {
+        let config = self.config();
+        let seeds = config.key_value_seeds(self.tls_enabled);
+        let node = *config.vbucket_server_map.vbucket_map[partition as usize].first()?;
+        if node < 0 {
+            return None;
+        }
+
+        let seed = seeds.get(node as usize)?;
+        Some((seed.0.clone(), seed.1))
+    }
+
+    // replica_node_for_partition returns the node hosting the given replica of the partition, if
+    // the replica is currently available.
+    fn replica_node_for_partition(
+        &self,
+        partition: u32,
+        replica_index: u32,
+    ) -> Option<(String, u32)> {
+        let config = self.config();
+        let seeds = config.key_value_seeds(self.tls_enabled);
+        let node = *config.vbucket_server_map.vbucket_map[partition as usize]
+            .get(replica_index as usize + 1)?;
+        if node < 0 {
+            return None;
+        }
+
+        let seed = seeds.get(node as usize)?;
+        Some((seed.0.clone(), seed.1))
+    }
+
+    pub fn num_replicas(&self) -> u32 {
+        self.config().vbucket_server_map.num_replicas
+    }
+
+    // endpoint returns the connection to the node, connecting to it first if the node joined the
+    // cluster after the client was created or the previous connection to it failed.
+    async fn endpoint(&self, addr: String, port: u32) -> Result<Arc<KvEndpoint>, ClientError> {
+        let address = format!("{}:{}", addr, port);
+        if let Some(ep) = self.endpoints.lock().await.get(&address) {
+            if !ep.is_closed() {
+                return Ok(ep.clone());
+            }
+        }
+
+        // Connecting is done without holding the endpoints lock, so that a slow node does not hold
+        // up requests to the others. Requests to the same node wait for a single connection.
+        let connecting = self
+            .connecting
+            .lock()
+            .unwrap()
+            .entry(address.clone())
+            .or_insert_with(|| Arc::new(AsyncMutex::new(())))
+            .clone();
+        let _connecting = connecting.lock().await;
+        match self.endpoints.lock().await.get(&address) {
+            Some(ep) if !ep.is_closed() => return Ok(ep.clone()),
+            Some(_) => debug!("Reconnecting to {} as the connection was closed", &address),
+            None => debug!(
+                "Connecting to {} which was not part of the initial config",
+                &address
+            ),
+        }
+
+        let options = self.options.clone();
+        let connect = self.runtime.spawn(KvEndpoint::connect(
+            addr,
+            port,
+            options.username,
+            options.password,
+            options.bucket,
+            options.tls_config,
+            options.compression_min_size,
+            options.auth_mechanism,
+        ));
+        let ep = match connect.await {
+            Ok(ep) => Arc::new(ep?),
+            Err(e) => {
+                return Err(ClientError::RequestFailed {
+                    reason: Some(e.to_string()),
+                    key: None,
+                })
+            }
+        };
+        self.endpoints.lock().await.insert(address, ep.clone());
+
+        Ok(ep)
+    }
+
+    // keepalive sends a noop on every connection, so that idle connections are not closed by the
+    // server or anything in between. Connections which fail are dropped, to be reconnected on next
+    // use.
+    pub async fn keepalive(&self, noop_timeout: Duration) {
+        let endpoints: Vec<(String, Arc<KvEndpoint>)> = self
+            .endpoints
+            .lock()
+            .await
+            .iter()
+            .map(|(address, ep)| (address.clone(), ep.clone()))
+            .collect();
+
+        for (address, ep) in endpoints {
+            if let Ok(Ok(_)) = timeout(noop_timeout, ep.noop()).await {
+                continue;
+            }
+
+            debug!("Keepalive to {} failed, dropping the connection", &address);
+            self.endpoints.lock().await.remove(&address);
+        }
+    }
+
+    // connected_nodes returns the addresses of the nodes which the client has an open connection to.
+    pub async fn connected_nodes(&self) -> Vec<String> {
+        let mut nodes: Vec<String> = self
+            .endpoints
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, ep)| !ep.is_closed())
+            .map(|(address, _)| address.clone())
+            .collect();
+        nodes.sort();
+        nodes
+    }
+
+    // update_config applies a config sent by the server, if it is newer than the current one,
+    // fetching the config over HTTP instead if the server did not include one.
+    async fn update_config(
+        &self,
+        config: Option<Bytes>,
+        hostname: &str,
+        deadline: Instant,
+        ctrl_c: Arc<AtomicBool>,
+    ) {
+        let current = self.config();
+        let config = match config {
+            Some(c) => {
+                let raw = String::from_utf8_lossy(c.as_ref()).replace("$HOST", hostname);
+                match serde_json::from_str::<BucketConfig>(&raw) {
+                    Ok(mut config) => {
+                        // Keep resolving addresses against the network the client connected on.
+                        config.loaded_from = current.loaded_from.clone();
+                        config
+                    }
+                    Err(e) => {
+                        debug!("Failed to parse config sent by the server: {}", e);
+                        return;
+                    }
+                }
+            }
+            None => {
+                let http_agent = HTTPHandler::new(
+                    self.options.username.clone(),
+                    self.options.password.clone(),
+                    self.options.tls_config.clone(),
+                );
+                match HTTPClient::get_config(
+                    &self.seeds,
+                    self.tls_enabled,
+                    &http_agent,
+                    self.options.bucket.clone(),
+                    deadline,
+                    ctrl_c,
+                )
+                .await
+                {
+                    Ok(c) => c,
+                    Err(e) => {
+                        debug!("Failed to refresh config: {}", e);
+                        return;
+                    }
+                }
+            }
+        };
+
+        let mut current = self.config.write().unwrap();
+        if config.revision() <= current.revision() {
+            return;
+        }
+
+        let nodes = config.key_value_seeds(self.tls_enabled);
+        if nodes != current.key_value_seeds(self.tls_enabled) {
+            info!(
+                "Bucket {} topology changed in config revision {:?}, nodes are now {}",
+                &self.options.bucket,
+                config.revision(),
+                nodes
+                    .iter()
+                    .map(|(host, port)| format!("{}:{}", host, port))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+        } else if config.vbucket_server_map.vbucket_map != current.vbucket_server_map.vbucket_map {
+            info!(
+                "Bucket {} partitions moved in config revision {:?}",
+                &self.options.bucket,
+                config.revision()
+            );
+        } else {
+            debug!("Applying config revision {:?}", config.revision());
+        }
+        *current = Arc::new(config);
+    }
+
+    // refresh_config applies any config which the server pushed since the last request, or polls
+    // for the config if it has not been checked recently. This keeps long running commands
+    // following rebalances and failovers, rather than relying on not my vbucket responses alone.
+    async fn refresh_config(&self, deadline: Instant, ctrl_c: Arc<AtomicBool>) {
+        let endpoints: Vec<Arc<KvEndpoint>> =
+            self.endpoints.lock().await.values().cloned().collect();
+
+        let mut pushed = false;
+        for ep in &endpoints {
+            if let Some(config) = ep.take_pushed_config() {
+                pushed = true;
+                self.update_config(Some(config), ep.hostname(), deadline, ctrl_c.clone())
+                    .await;
+            }
+        }
+
+        {
+            // A pushed config is as good as a poll, so delays the next one.
+            let mut last_poll = self.last_config_poll.lock().unwrap();
+            let poll = !pushed && last_poll.elapsed() >= CONFIG_POLL_INTERVAL;
+            if pushed || poll {
+                *last_poll = Instant::now();
+            }
+            if !poll {
+                return;
+            }
+        }
 
-        let seed = &seeds[node as usize];
-        let addr = seed.0.clone();
-        let port = seed.1;
+        self.poll_config(deadline, ctrl_c).await
+    }
 
-        (addr, port)
+    // poll_config fetches the config from a connected node and applies it if it is newer.
+    async fn poll_config(&self, deadline: Instant, ctrl_c: Arc<AtomicBool>) {
+        // Every node knows the config, so ask any which is still connected.
+        let ep = match self
+            .endpoints
+            .lock()
+            .await
+            .values()
+            .find(|ep| !ep.is_closed())
+        {
+            Some(ep) => ep.clone(),
+            None => return,
+        };
+        match timeout_at(deadline, ep.get_cluster_config()).await {
+            Ok(Ok(config)) => {
+                self.update_config(Some(config), ep.hostname(), deadline, ctrl_c)
+                    .await
+            }
+            Ok(Err(e)) => debug!("Failed to poll config from {}: {}", ep.remote(), e),
+            Err(_) => debug!("Timed out polling config from {}", ep.remote()),
+        }
     }
 
     pub async fn ping_all(
@@ -161,13 +647,10 @@ impl KvClient {
         tokio::pin!(ctrl_c_fut);
 
         let mut results: Vec<PingResponse> = Vec::new();
-        for seed in self.config.key_value_seeds(self.tls_enabled) {
+        for seed in self.config().key_value_seeds(self.tls_enabled) {
             let addr = seed.0.clone();
             let port = seed.1;
-            let ep = self
-                .endpoints
-                .get(format!("{}:{}", addr.clone(), port).as_str())
-                .unwrap();
+            let ep = self.endpoint(addr.clone(), port).await?;
 
             let op = ep.noop();
 
@@ -206,6 +689,8 @@ impl KvClient {
             || (!collection.is_empty() && collection != "_default")
     }
 
+    // request sends the request to the node owning the key, retrying it until the deadline while
+    // the failure is transient, such as during a rebalance.
     pub async fn request(
         &self,
         request: KeyValueRequest,
@@ -219,30 +704,133 @@ impl KvClient {
                 key: Some(request.key()),
             });
         }
-        let deadline = deadline.sub(now);
-        let deadline_sleep = sleep(deadline);
+        let deadline_sleep = sleep(deadline.sub(now));
         tokio::pin!(deadline_sleep);
 
         let ctrl_c_fut = CtrlcFuture::new(ctrl_c.clone());
         tokio::pin!(ctrl_c_fut);
 
+        self.refresh_config(deadline, ctrl_c.clone()).await;
+
+        let mut retries = 0;
+        loop {
+            let (addr, result) = self
+                .dispatch(
+                    request.clone(),
+                    cid,
+                    deadline_sleep.as_mut(),
+                    ctrl_c_fut.as_mut(),
+                )
+                .await;
+            let error = match result {
+                Ok(mut response) => {
+                    response.retries = retries;
+                    return Ok(response);
+                }
+                Err(e) => e,
+            };
+
+            let backoff = match retry_backoff(&request, &error, retries, now.elapsed()) {
+                Some(b) => b,
+                None => return Err(error),
+            };
+
+            match &error {
+                ClientError::NotMyVbucket { config, .. } => {
+                    if let Some(addr) = addr {
+                        self.update_config(config.clone(), &addr, deadline, ctrl_c.clone())
+                            .await;
+                    }
+                }
+                // The partition will be served again once a replica has been promoted.
+                ClientError::PartitionUnavailable { .. } => {
+                    self.poll_config(deadline, ctrl_c.clone()).await
+                }
+                _ => {}
+            }
+
+            retries += 1;
+            trace!(
+                "Retrying request for {} in {:?}, attempt {}: {}",
+                request.key(),
+                backoff,
+                retries,
+                error
+            );
+            select! {
+                () = sleep(backoff) => {},
+                // Report why the request kept failing rather than only that it timed out.
+                () = &mut deadline_sleep => return Err(error),
+                () = &mut ctrl_c_fut => return Err(ClientError::Cancelled{key: Some(request.key())}),
+            }
+        }
+    }
+
+    // dispatch sends the request once, returning the address of the node it was sent to.
+    async fn dispatch(
+        &self,
+        request: KeyValueRequest,
+        cid: u32,
+        mut deadline_sleep: Pin<&mut Sleep>,
+        mut ctrl_c_fut: Pin<&mut CtrlcFuture>,
+    ) -> (Option<String>, Result<KvResponse, ClientError>) {
         let key = match request {
             KeyValueRequest::Get { ref key } => key.clone(),
+            KeyValueRequest::GetReplica { ref key, .. } => key.clone(),
+            KeyValueRequest::GetMeta { ref key } => key.clone(),
             KeyValueRequest::Set { ref key, .. } => key.clone(),
             KeyValueRequest::Insert { ref key, .. } => key.clone(),
             KeyValueRequest::Replace { ref key, .. } => key.clone(),
             KeyValueRequest::Remove { ref key, .. } => key.clone(),
+            KeyValueRequest::Increment { ref key, .. } => key.clone(),
+            KeyValueRequest::Decrement { ref key, .. } => key.clone(),
+            KeyValueRequest::Append { ref key, .. } => key.clone(),
+            KeyValueRequest::Prepend { ref key, .. } => key.clone(),
+            KeyValueRequest::Touch { ref key, .. } => key.clone(),
+            KeyValueRequest::GetAndTouch { ref key, .. } => key.clone(),
+            KeyValueRequest::GetAndLock { ref key, .. } => key.clone(),
+            KeyValueRequest::Unlock { ref key, .. } => key.clone(),
             KeyValueRequest::SubDocGet { ref key, .. } => key.clone(),
             KeyValueRequest::SubdocMultiLookup { ref key, .. } => key.clone(),
+            KeyValueRequest::SubdocMultiMutation { ref key, .. } => key.clone(),
         };
 
         let partition = self.partition_for_key(key.clone());
-        let (addr, port) = self.node_for_partition(partition);
+        let (addr, port) = match request {
+            KeyValueRequest::GetReplica { replica_index, .. } => {
+                match self.replica_node_for_partition(partition, replica_index) {
+                    Some(node) => node,
+                    None => {
+                        return (
+                            None,
+                            Err(ClientError::RequestFailed {
+                                reason: Some(format!("replica {} is not available", replica_index)),
+                                key: Some(key),
+                            }),
+                        );
+                    }
+                }
+            }
+            _ => match self.node_for_partition(partition) {
+                Some(node) => node,
+                None => {
+                    return (
+                        None,
+                        Err(ClientError::PartitionUnavailable { key: Some(key) }),
+                    );
+                }
+            },
+        };
 
-        let ep = self
-            .endpoints
-            .get(format!("{}:{}", addr.clone(), port).as_str())
-            .unwrap();
+        let ep = select! {
+            res = self.endpoint(addr.clone(), port) => res,
+            () = &mut deadline_sleep => Err(ClientError::Timeout{key: Some(key.clone())}),
+            () = &mut ctrl_c_fut => Err(ClientError::Cancelled{key: Some(key.clone())}),
+        };
+        let ep = match ep {
+            Ok(ep) => ep,
+            Err(e) => return (Some(addr), Err(e)),
+        };
 
         let result = match request {
             KeyValueRequest::Get { key } => {
@@ -251,26 +839,190 @@ impl KvClient {
                 self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
                     .await
             }
-            KeyValueRequest::Set { key, value, expiry } => {
-                let op = ep.set(key.clone(), value, expiry, partition as u16, cid);
+            KeyValueRequest::GetMeta { key } => {
+                let op = ep.get_meta(key.clone(), partition as u16, cid);
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::GetReplica { key, .. } => {
+                let op = ep.get_replica(key.clone(), partition as u16, cid);
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::Set {
+                key,
+                value,
+                flags,
+                expiry,
+                durability,
+            } => {
+                let op = ep.set(
+                    key.clone(),
+                    value,
+                    flags,
+                    expiry,
+                    partition as u16,
+                    cid,
+                    durability,
+                );
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::Insert {
+                key,
+                value,
+                flags,
+                expiry,
+                durability,
+            } => {
+                let op = ep.add(
+                    key.clone(),
+                    value,
+                    flags,
+                    expiry,
+                    partition as u16,
+                    cid,
+                    durability,
+                );
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::Replace {
+                key,
+                value,
+                flags,
+                expiry,
+                cas,
+                durability,
+            } => {
+                let op = ep.replace(
+                    key.clone(),
+                    value,
+                    flags,
+                    expiry,
+                    partition as u16,
+                    cid,
+                    cas,
+                    durability,
+                );
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::Remove {
+                key,
+                cas,
+                durability,
+            } => {
+                let op = ep.remove(key.clone(), partition as u16, cid, cas, durability);
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::Increment {
+                key,
+                delta,
+                initial,
+                expiry,
+                durability,
+            } => {
+                let op = ep.counter(
+                    protocol::Opcode::Increment,
+                    key.clone(),
+                    delta,
+                    initial,
+                    expiry,
+                    partition as u16,
+                    cid,
+                    durability,
+                );
 
                 self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
                     .await
             }
-            KeyValueRequest::Insert { key, value, expiry } => {
-                let op = ep.add(key.clone(), value, expiry, partition as u16, cid);
+            KeyValueRequest::Decrement {
+                key,
+                delta,
+                initial,
+                expiry,
+                durability,
+            } => {
+                let op = ep.counter(
+                    protocol::Opcode::Decrement,
+                    key.clone(),
+                    delta,
+                    initial,
+                    expiry,
+                    partition as u16,
+                    cid,
+                    durability,
+                );
 
                 self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
                     .await
             }
-            KeyValueRequest::Replace { key, value, expiry } => {
-                let op = ep.replace(key.clone(), value, expiry, partition as u16, cid);
+            KeyValueRequest::Append {
+                key,
+                value,
+                cas,
+                durability,
+            } => {
+                let op = ep.concat(
+                    protocol::Opcode::Append,
+                    key.clone(),
+                    value,
+                    partition as u16,
+                    cid,
+                    cas,
+                    durability,
+                );
 
                 self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
                     .await
             }
-            KeyValueRequest::Remove { key } => {
-                let op = ep.remove(key.clone(), partition as u16, cid);
+            KeyValueRequest::Prepend {
+                key,
+                value,
+                cas,
+                durability,
+            } => {
+                let op = ep.concat(
+                    protocol::Opcode::Prepend,
+                    key.clone(),
+                    value,
+                    partition as u16,
+                    cid,
+                    cas,
+                    durability,
+                );
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::Touch { key, expiry } => {
+                let op = ep.touch(key.clone(), expiry, partition as u16, cid);
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::GetAndTouch { key, expiry } => {
+                let op = ep.get_and_touch(key.clone(), expiry, partition as u16, cid);
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::GetAndLock { key, lock_time } => {
+                let op = ep.get_and_lock(key.clone(), lock_time, partition as u16, cid);
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::Unlock { key, cas } => {
+                let op = ep.unlock(key.clone(), cas, partition as u16, cid);
 
                 self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
                     .await
@@ -281,25 +1033,67 @@ impl KvClient {
                 self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
                     .await
             }
-            KeyValueRequest::SubdocMultiLookup { key, paths } => {
-                let op = ep.sub_doc_multi_lookup(key.clone(), partition as u16, cid, paths);
+            KeyValueRequest::SubdocMultiLookup { key, paths, xattrs } => {
+                let op = ep.sub_doc_multi_lookup(key.clone(), partition as u16, cid, paths, xattrs);
+
+                self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
+                    .await
+            }
+            KeyValueRequest::SubdocMultiMutation {
+                key,
+                specs,
+                cas,
+                expiry,
+            } => {
+                let op = ep.sub_doc_multi_mutation(
+                    key.clone(),
+                    partition as u16,
+                    cid,
+                    specs,
+                    cas,
+                    expiry,
+                );
 
                 self.handle_op_future(key, op, deadline_sleep, ctrl_c_fut)
                     .await
             }
         };
 
-        self.handle_op_result(result)
+        (
+            Some(addr),
+            self.handle_op_result(result, Some(partition as u16)),
+        )
     }
 
+    // handle_op_result converts the response of an operation, the partition it was sent to being
+    // used to build the mutation token of mutations.
     fn handle_op_result(
         &self,
         result: Result<(protocol::KvResponse, Option<String>), ClientError>,
+        partition: Option<u16>,
     ) -> Result<KvResponse, ClientError> {
         match result {
             Ok(mut r) => {
+                let mutation_token = match (partition, r.0.mutation_seqno()) {
+                    (Some(partition), Some((partition_uuid, seqno))) => Some(MutationToken::new(
+                        self.config().name.clone(),
+                        partition,
+                        partition_uuid,
+                        seqno,
+                    )),
+                    _ => None,
+                };
+
+                let mut raw_body = None;
                 let content = if let Some(body) = r.0.body() {
                     match r.0.opcode() {
+                        protocol::Opcode::Get
+                        | protocol::Opcode::GetReplica
+                        | protocol::Opcode::GetAndTouch
+                        | protocol::Opcode::GetLocked => {
+                            raw_body = Some(body);
+                            None
+                        }
                         protocol::Opcode::SubdocMultiLookup => {
                             let mut results: Vec<serde_json::Value> = vec![];
                             let mut bytes = body.clone();
@@ -325,6 +1119,50 @@ impl KvClient {
                             }
                             Some(json!(results))
                         }
+                        protocol::Opcode::Increment | protocol::Opcode::Decrement => {
+                            // Counters respond with the new value as a big endian u64 rather
+                            // than JSON.
+                            let mut bytes = body.clone();
+                            if bytes.len() != 8 {
+                                return Err(ClientError::RequestFailed {
+                                    reason: Some(format!(
+                                        "unexpected counter response length {}",
+                                        bytes.len()
+                                    )),
+                                    key: r.1,
+                                });
+                            }
+                            Some(json!(bytes.get_u64()))
+                        }
+                        protocol::Opcode::SubdocMultiMutation => {
+                            // Only specs which produce a value (e.g. counters) are present in the
+                            // body, so each entry is keyed by the index of the spec it belongs to.
+                            let mut results: Vec<serde_json::Value> = vec![];
+                            let mut bytes = body.clone();
+
+                            while !bytes.is_empty() {
+                                let index = bytes.get_u8();
+                                // Drop the per spec status, which is always success here
+                                bytes.get_u16();
+                                let len = bytes.get_u32() as usize;
+                                let temp = bytes.split_off(len);
+
+                                let value: serde_json::Value =
+                                    match serde_json::from_slice(bytes.as_ref()) {
+                                        Ok(v) => v,
+                                        Err(e) => {
+                                            return Err(ClientError::RequestFailed {
+                                                reason: Some(e.to_string()),
+                                                key: r.1,
+                                            });
+                                        }
+                                    };
+
+                                results.push(json!({"index": index, "value": value}));
+                                bytes = temp;
+                            }
+                            Some(json!(results))
+                        }
                         _ => match serde_json::from_slice(body.as_ref()) {
                             Ok(v) => Some(v),
                             Err(e) => {
@@ -340,9 +1178,12 @@ impl KvClient {
                 };
                 Ok(KvResponse {
                     content,
+                    body: raw_body,
                     cas: r.0.cas(),
                     key: r.1.unwrap_or_default(),
                     extras: r.0.extras(),
+                    retries: 0,
+                    mutation_token,
                 })
             }
             Err(e) => Err(e),
@@ -382,11 +1223,10 @@ impl KvClient {
         let ctrl_c_fut = CtrlcFuture::new(ctrl_c.clone());
         tokio::pin!(ctrl_c_fut);
 
-        let (addr, port) = self.node_for_partition(0);
-        let ep = self
-            .endpoints
-            .get(format!("{}:{}", addr.clone(), port).as_str())
-            .unwrap();
+        let (addr, port) = self
+            .node_for_partition(0)
+            .ok_or(ClientError::PartitionUnavailable { key: None })?;
+        let ep = self.endpoint(addr, port).await?;
 
         let op = ep.get_cid(scope_name, collection_name);
 
@@ -394,7 +1234,7 @@ impl KvClient {
             .handle_op_future(None, op, deadline_sleep, ctrl_c_fut)
             .await;
 
-        let mut result = self.handle_op_result(resp)?;
+        let mut result = self.handle_op_result(resp, None)?;
         match result.extras() {
             Some(mut e) => {
                 if e.len() < 12 {
@@ -416,28 +1256,227 @@ impl KvClient {
         }
     }
 
-    // handle_op_future resolves the future into a result containing (response, key) or an error.
-    async fn handle_op_future(
-        &self,
-        key: impl Into<Option<String>>,
-        op: impl Future<Output = Result<protocol::KvResponse, ClientError>>,
-        mut deadline_sleep: Pin<&mut Sleep>,
-        mut ctrl_c: Pin<&mut CtrlcFuture>,
-    ) -> Result<(protocol::KvResponse, Option<String>), ClientError> {
-        let key = key.into();
-        let res = select! {
-            res = op => res,
-            () = &mut deadline_sleep => Err(ClientError::Timeout{key: key.clone()}),
-            () = &mut ctrl_c => Err(ClientError::Cancelled{key: key.clone()}),
-        }?;
+    // range_scan scans every partition of the collection, up to the concurrency of the scan at a
+    // time, sending the items to the returned channel as they arrive. The scan runs on the runtime
+    // of the client so that the items can be consumed after the runtime of the command is gone,
+    // and stops once the receiver is dropped or ctrl_c is set.
+    pub fn range_scan(
+        self: Arc<Self>,
+        scan: RangeScan,
+        cid: u32,
+        timeout: Duration,
+        ctrl_c: Arc<AtomicBool>,
+    ) -> mpsc::Receiver<Result<ScanItem, ClientError>> {
+        let (tx, rx) = mpsc::channel(RANGE_SCAN_BUFFER);
+        let runtime = self.runtime.clone();
+        runtime.spawn(async move {
+            let partitions = self.config().vbucket_server_map.vbucket_map.len() as u16;
+            let items = ScanItemSender {
+                tx,
+                limit: scan.limit(),
+                sent: AtomicU64::new(0),
+            };
+            let (items, scan) = (&items, &scan);
+
+            futures::stream::iter(0..partitions)
+                .map(|partition| {
+                    let client = self.clone();
+                    let ctrl_c = ctrl_c.clone();
+                    async move {
+                        if items.is_closed() || ctrl_c.load(Ordering::SeqCst) {
+                            return;
+                        }
+                        if let Err(e) = client
+                            .scan_partition(partition, cid, scan, timeout, ctrl_c, items)
+                            .await
+                        {
+                            items.send(Err(e)).await;
+                        }
+                    }
+                })
+                .buffer_unordered(scan.concurrency)
+                .collect::<()>()
+                .await;
+        });
 
-        Ok((res, key))
+        rx
     }
+
+    // scan_partition runs the scan against a single partition, batch by batch, until there are no
+    // more items or they are no longer wanted.
+    async fn scan_partition(
+        &self,
+        partition: u16,
+        cid: u32,
+        scan: &RangeScan,
+        timeout: Duration,
+        ctrl_c: Arc<AtomicBool>,
+        items: &ScanItemSender,
+    ) -> Result<(), ClientError> {
+        let mut attempt = 0;
+        let (ep, scan_id) = loop {
+            let deadline = Instant::now() + timeout;
+            let (addr, port) = match self.node_for_partition(partition as u32) {
+                Some(node) => node,
+                None if attempt < RANGE_SCAN_RETRIES => {
+                    self.poll_config(deadline, ctrl_c.clone()).await;
+                    sleep(controlled_backoff(attempt)).await;
+                    attempt += 1;
+                    continue;
+                }
+                None => return Err(ClientError::PartitionUnavailable { key: None }),
+            };
+            let ep = self.endpoint(addr, port).await?;
+            let created = timeout_at(
+                deadline,
+                ep.range_scan_create(partition, cid, scan.create_body(cid)),
+            )
+            .await
+            .map_err(|_| ClientError::Timeout { key: None })?;
+            match created {
+                Ok(Some(scan_id)) => break (ep, scan_id),
+                // There is nothing in the range on this partition.
+                Ok(None) => return Ok(()),
+                // The partition moved, so the scan has to be created where it is now.
+                Err(ClientError::NotMyVbucket { config, .. }) if attempt < RANGE_SCAN_RETRIES => {
+                    self.update_config(config, ep.hostname(), deadline, ctrl_c.clone())
+                        .await;
+                    sleep(controlled_backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        loop {
+            let mut responses = ep
+                .range_scan_continue(
+                    partition,
+                    scan_id.clone(),
+                    scan.batch_item_limit,
+                    scan.batch_byte_limit,
+                )
+                .await?;
+            loop {
+                let mut response = match tokio::time::timeout(timeout, responses.recv()).await {
+                    Ok(Some(r)) => r,
+                    Ok(None) => {
+                        return Err(ClientError::RequestFailed {
+                            reason: Some(format!(
+                                "Connection to {} closed during range scan",
+                                ep.remote()
+                            )),
+                            key: None,
+                        })
+                    }
+                    Err(_) => {
+                        let _ = ep.range_scan_cancel(partition, scan_id.clone()).await;
+                        return Err(ClientError::Timeout { key: None });
+                    }
+                };
+
+                if let Some(body) = response.body() {
+                    for item in parse_scan_items(body, scan.ids_only)? {
+                        if !items.send(Ok(item)).await || ctrl_c.load(Ordering::SeqCst) {
+                            let _ = ep.range_scan_cancel(partition, scan_id.clone()).await;
+                            return Ok(());
+                        }
+                    }
+                }
+
+                match response.status() {
+                    // More responses follow for this batch.
+                    protocol::Status::Success => {}
+                    protocol::Status::RangeScanMore => break,
+                    protocol::Status::RangeScanComplete => return Ok(()),
+                    status => {
+                        let reason = ClientError::try_parse_kv_fail_body(&mut response);
+                        return Err(ClientError::RequestFailed {
+                            reason: Some(format!(
+                                "Range scan of partition {} failed: {}",
+                                partition,
+                                reason.unwrap_or_else(|| status.as_string())
+                            )),
+                            key: None,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // handle_op_future resolves the future into a result containing (response, key) or an error.
+    async fn handle_op_future(
+        &self,
+        key: impl Into<Option<String>>,
+        op: impl Future<Output = Result<protocol::KvResponse, ClientError>>,
+        mut deadline_sleep: Pin<&mut Sleep>,
+        mut ctrl_c: Pin<&mut CtrlcFuture>,
+    ) -> Result<(protocol::KvResponse, Option<String>), ClientError> {
+        let key = key.into();
+        let res = select! {
+            res = op => res,
+            () = &mut deadline_sleep => Err(ClientError::Timeout{key: key.clone()}),
+            () = &mut ctrl_c => Err(ClientError::Cancelled{key: key.clone()}),
+        }?;
+
+        Ok((res, key))
+    }
+}
+
+// retry_backoff decides whether a failed request can be retried, and how long to wait before doing
+// so. None of these failures mean that the request was applied, so mutations are safe to retry.
+fn retry_backoff(
+    request: &KeyValueRequest,
+    error: &ClientError,
+    attempt: u32,
+    elapsed: Duration,
+) -> Option<Duration> {
+    match error {
+        ClientError::NotMyVbucket { .. } | ClientError::PartitionUnavailable { .. } => {
+            Some(controlled_backoff(attempt))
+        }
+        // A specification without any wait would retry in a busy loop until the deadline, so the
+        // controlled backoff is used instead.
+        ClientError::TemporaryFailure {
+            retry: Some(spec), ..
+        } => spec.backoff(attempt, elapsed).map(|b| {
+            if b.is_zero() {
+                controlled_backoff(attempt)
+            } else {
+                b
+            }
+        }),
+        ClientError::TemporaryFailure { retry: None, .. } => Some(controlled_backoff(attempt)),
+        // Unlocking with the wrong CAS also reports the document as locked, waiting won't help.
+        ClientError::DocumentLocked { .. } => match request {
+            KeyValueRequest::Unlock { .. } => None,
+            _ => Some(controlled_backoff(attempt)),
+        },
+        _ => None,
+    }
+}
+
+// controlled_backoff is used for retries which the server gave no retry specification for.
+fn controlled_backoff(attempt: u32) -> Duration {
+    Duration::from_millis(match attempt {
+        0 => 1,
+        1 => 10,
+        2 => 50,
+        3 => 100,
+        4 => 500,
+        _ => 1000,
+    })
 }
 
 #[derive(Deserialize, Debug)]
 struct BucketConfig {
-    // rev: u64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    rev: u64,
+    #[serde(alias = "revEpoch", default)]
+    rev_epoch: u64,
     #[serde(alias = "nodesExt")]
     nodes_ext: Vec<NodeExtConfig>,
     nodes: Vec<NodeConfig>,
@@ -453,6 +1492,11 @@ impl Config for BucketConfig {
 }
 
 impl BucketConfig {
+    // revision orders configs, newer configs having a higher revision.
+    fn revision(&self) -> (u64, u64) {
+        (self.rev_epoch, self.rev)
+    }
+
     pub fn key_value_seeds(&self, tls: bool) -> Vec<(String, u32)> {
         let key = if tls { "kvSSL" } else { "kv" };
 
@@ -552,35 +1596,94 @@ pub(crate) struct NodeConfig {
 
 #[derive(Deserialize, Debug)]
 struct VBucketServerMap {
-    // #[serde(alias = "numReplicas")]
-    // num_replicas: u32,
+    #[serde(alias = "numReplicas")]
+    num_replicas: u32,
     // #[serde(alias = "serverList")]
     // server_list: Vec<String>,
     #[serde(alias = "vBucketMap")]
     vbucket_map: Vec<Vec<i32>>,
 }
 
+#[derive(Clone)]
 pub enum KeyValueRequest {
     Get {
         key: String,
     },
+    GetReplica {
+        key: String,
+        replica_index: u32,
+    },
+    GetMeta {
+        key: String,
+    },
     Set {
         key: String,
         value: Vec<u8>,
+        flags: u32,
         expiry: u32,
+        durability: Durability,
     },
     Insert {
         key: String,
         value: Vec<u8>,
+        flags: u32,
         expiry: u32,
+        durability: Durability,
     },
     Replace {
         key: String,
         value: Vec<u8>,
+        flags: u32,
         expiry: u32,
+        cas: u64,
+        durability: Durability,
     },
     Remove {
         key: String,
+        cas: u64,
+        durability: Durability,
+    },
+    Increment {
+        key: String,
+        delta: u64,
+        initial: Option<u64>,
+        expiry: u32,
+        durability: Durability,
+    },
+    Decrement {
+        key: String,
+        delta: u64,
+        initial: Option<u64>,
+        expiry: u32,
+        durability: Durability,
+    },
+    Append {
+        key: String,
+        value: Vec<u8>,
+        cas: u64,
+        durability: Durability,
+    },
+    Prepend {
+        key: String,
+        value: Vec<u8>,
+        cas: u64,
+        durability: Durability,
+    },
+    Touch {
+        key: String,
+        expiry: u32,
+    },
+    GetAndTouch {
+        key: String,
+        expiry: u32,
+    },
+    GetAndLock {
+        key: String,
+        lock_time: u32,
+    },
+    Unlock {
+        key: String,
+        cas: u64,
     },
     SubDocGet {
         key: String,
@@ -589,6 +1692,13 @@ pub enum KeyValueRequest {
     SubdocMultiLookup {
         key: String,
         paths: Vec<String>,
+        xattrs: Vec<String>,
+    },
+    SubdocMultiMutation {
+        key: String,
+        specs: Vec<SubdocMutation>,
+        cas: u64,
+        expiry: u32,
     },
 }
 
@@ -596,12 +1706,1275 @@ impl KeyValueRequest {
     pub fn key(&self) -> String {
         match self {
             KeyValueRequest::Get { key } => key.clone(),
+            KeyValueRequest::GetReplica { key, .. } => key.clone(),
+            KeyValueRequest::GetMeta { key } => key.clone(),
             KeyValueRequest::Set { key, .. } => key.clone(),
             KeyValueRequest::Insert { key, .. } => key.clone(),
             KeyValueRequest::Replace { key, .. } => key.clone(),
-            KeyValueRequest::Remove { key } => key.clone(),
+            KeyValueRequest::Remove { key, .. } => key.clone(),
+            KeyValueRequest::Increment { key, .. } => key.clone(),
+            KeyValueRequest::Decrement { key, .. } => key.clone(),
+            KeyValueRequest::Append { key, .. } => key.clone(),
+            KeyValueRequest::Prepend { key, .. } => key.clone(),
+            KeyValueRequest::Touch { key, .. } => key.clone(),
+            KeyValueRequest::GetAndTouch { key, .. } => key.clone(),
+            KeyValueRequest::GetAndLock { key, .. } => key.clone(),
+            KeyValueRequest::Unlock { key, .. } => key.clone(),
             KeyValueRequest::SubDocGet { key, .. } => key.clone(),
             KeyValueRequest::SubdocMultiLookup { key, .. } => key.clone(),
+            KeyValueRequest::SubdocMultiMutation { key, .. } => key.clone(),
+        }
+    }
+}
+
+// DocumentFormat is the format of a document as recorded in the common flags, the top byte of the
+// flags stored alongside the document.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DocumentFormat {
+    #[default]
+    Json,
+    String,
+    Binary,
+}
+
+impl DocumentFormat {
+    pub fn flags(&self) -> u32 {
+        match self {
+            Self::Json => 0x02 << 24,
+            Self::Binary => 0x03 << 24,
+            Self::String => 0x04 << 24,
+        }
+    }
+
+    // from_flags treats legacy documents without a format as JSON.
+    pub fn from_flags(flags: u32) -> Self {
+        match flags >> 24 {
+            0x03 => Self::Binary,
+            0x04 => Self::String,
+            _ => Self::Json,
+        }
+    }
+}
+
+impl TryFrom<&str> for DocumentFormat {
+    type Error = String;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        match input {
+            "json" => Ok(Self::Json),
+            "string" => Ok(Self::String),
+            "binary" => Ok(Self::Binary),
+            _ => Err(format!("unknown format {}", input)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DurabilityLevel {
+    #[default]
+    None,
+    Majority,
+    MajorityAndPersistActive,
+    PersistToMajority,
+}
+
+impl DurabilityLevel {
+    pub(crate) fn encoded(&self) -> u8 {
+        match self {
+            Self::None => 0x00,
+            Self::Majority => 0x01,
+            Self::MajorityAndPersistActive => 0x02,
+            Self::PersistToMajority => 0x03,
+        }
+    }
+}
+
+impl TryFrom<&str> for DurabilityLevel {
+    type Error = String;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        match input {
+            "none" => Ok(Self::None),
+            "majority" => Ok(Self::Majority),
+            "majority-and-persist-active" => Ok(Self::MajorityAndPersistActive),
+            "persist-to-majority" => Ok(Self::PersistToMajority),
+            _ => Err(format!("unknown durability level {}", input)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Durability {
+    level: DurabilityLevel,
+    timeout: Option<Duration>,
+}
+
+impl Durability {
+    // The timeout is sent as a u16 number of milliseconds, 0 and u16::MAX are reserved by the
+    // server for "default" and "infinite" respectively.
+    pub fn new(level: DurabilityLevel, timeout: Option<Duration>) -> Result<Self, String> {
+        if let Some(t) = timeout {
+            let millis = t.as_millis();
+            if millis == 0 || millis >= u16::MAX as u128 {
+                return Err(format!(
+                    "durability timeout must be between 1 and {}ms, got {}ms",
+                    u16::MAX - 1,
+                    millis
+                ));
+            }
+        }
+
+        Ok(Self { level, timeout })
+    }
+
+    // framing_extras creates the durability frame for this requirement, or None if no durability
+    // is required.
+    pub(crate) fn framing_extras(&self) -> Option<Bytes> {
+        if self.level == DurabilityLevel::None {
+            return None;
+        }
+
+        // The timeout range is validated on construction.
+        let timeout = self.timeout.map(|t| t.as_millis() as u16);
+
+        Some(protocol::durability_frame(self.level.encoded(), timeout))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SubdocMutationOp {
+    Upsert,
+    Insert,
+    Replace,
+    Remove,
+    ArrayAppend,
+    ArrayPrepend,
+    ArrayAddUnique,
+    Counter,
+}
+
+impl SubdocMutationOp {
+    fn opcode(&self) -> protocol::Opcode {
+        match self {
+            Self::Upsert => protocol::Opcode::SubdocDictUpsert,
+            Self::Insert => protocol::Opcode::SubdocDictAdd,
+            Self::Replace => protocol::Opcode::SubdocReplace,
+            Self::Remove => protocol::Opcode::SubdocDelete,
+            Self::ArrayAppend => protocol::Opcode::SubdocArrayPushLast,
+            Self::ArrayPrepend => protocol::Opcode::SubdocArrayPushFirst,
+            Self::ArrayAddUnique => protocol::Opcode::SubdocArrayAddUnique,
+            Self::Counter => protocol::Opcode::SubdocCounter,
+        }
+    }
+
+    // has_value reports whether the operation carries a value alongside the path.
+    pub fn has_value(&self) -> bool {
+        !matches!(self, Self::Remove)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SubdocMutation {
+    op: SubdocMutationOp,
+    path: String,
+    value: Vec<u8>,
+    create_path: bool,
+    xattr: bool,
+}
+
+impl SubdocMutation {
+    pub fn new(op: SubdocMutationOp, path: String, value: Vec<u8>) -> Self {
+        Self {
+            op,
+            path,
+            value,
+            create_path: false,
+            xattr: false,
+        }
+    }
+
+    pub fn create_path(mut self, create_path: bool) -> Self {
+        self.create_path = create_path;
+        self
+    }
+
+    pub fn xattr(mut self, xattr: bool) -> Self {
+        self.xattr = xattr;
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    pub(crate) fn opcode(&self) -> protocol::Opcode {
+        self.op.opcode()
+    }
+
+    pub(crate) fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.create_path {
+            flags |= 0x01;
+        }
+        if self.xattr {
+            flags |= 0x04;
+        }
+        flags
+    }
+}
+
+// ScanType is which documents a range scan returns.
+#[derive(Debug, Clone)]
+pub enum ScanType {
+    // Range returns the documents with keys from and to inclusive, every document if neither are
+    // set.
+    Range {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    Prefix(String),
+    // Sample returns a random selection of at most limit documents, the seed making the selection
+    // repeatable.
+    Sample {
+        limit: u64,
+        seed: u32,
+    },
+}
+
+// Keys are UTF-8, so these sort before and after every key.
+static RANGE_SCAN_MIN_TERM: &[u8] = &[0x00];
+static RANGE_SCAN_MAX_TERM: &[u8] = &[0xf4, 0x8f, 0xbf, 0xbf];
+
+#[derive(Debug, Clone)]
+pub struct RangeScan {
+    scan_type: ScanType,
+    ids_only: bool,
+    concurrency: usize,
+    batch_item_limit: u32,
+    batch_byte_limit: u32,
+}
+
+impl RangeScan {
+    pub fn new(scan_type: ScanType) -> Self {
+        Self {
+            scan_type,
+            ids_only: false,
+            concurrency: 1,
+            batch_item_limit: 50,
+            batch_byte_limit: 15000,
+        }
+    }
+
+    // ids_only returns only the keys of the documents, rather than their content and metadata.
+    pub fn ids_only(mut self, ids_only: bool) -> Self {
+        self.ids_only = ids_only;
+        self
+    }
+
+    // concurrency is how many partitions are scanned at the same time.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    fn limit(&self) -> Option<u64> {
+        match self.scan_type {
+            ScanType::Sample { limit, .. } => Some(limit),
+            _ => None,
+        }
+    }
+
+    // create_body is the JSON sent to create the scan on each partition.
+    fn create_body(&self, cid: u32) -> serde_json::Value {
+        let mut body = json!({
+            "collection": format!("{:x}", cid),
+            "key_only": self.ids_only,
+        });
+
+        let range = |start: Vec<u8>, end: Vec<u8>| {
+            json!({
+                "start": BASE64_STANDARD.encode(start),
+                "end": BASE64_STANDARD.encode(end),
+            })
+        };
+        match &self.scan_type {
+            ScanType::Range { from, to } => {
+                body["range"] = range(
+                    from.as_ref()
+                        .map(|f| f.as_bytes().to_vec())
+                        .unwrap_or_else(|| RANGE_SCAN_MIN_TERM.to_vec()),
+                    to.as_ref()
+                        .map(|t| t.as_bytes().to_vec())
+                        .unwrap_or_else(|| RANGE_SCAN_MAX_TERM.to_vec()),
+                );
+            }
+            ScanType::Prefix(prefix) => {
+                let mut end = prefix.as_bytes().to_vec();
+                end.extend_from_slice(RANGE_SCAN_MAX_TERM);
+                body["range"] = range(prefix.as_bytes().to_vec(), end);
+            }
+            ScanType::Sample { limit, seed } => {
+                body["sampling"] = json!({"samples": limit, "seed": seed});
+            }
+        }
+
+        body
+    }
+}
+
+// ScanItem is a document returned by a range scan, only the key is set for ids only scans.
+#[derive(Debug)]
+pub struct ScanItem {
+    key: String,
+    cas: u64,
+    body: Option<Bytes>,
+    meta: Option<DocumentMeta>,
+}
+
+impl ScanItem {
+    pub fn key(&self) -> String {
+        self.key.clone()
+    }
+
+    pub fn cas(&self) -> u64 {
+        self.cas
+    }
+
+    pub fn body(&mut self) -> Option<Bytes> {
+        self.body.take()
+    }
+
+    pub fn meta(&self) -> Option<DocumentMeta> {
+        self.meta
+    }
+}
+
+// ScanItemSender hands scanned items to the consumer, enforcing the limit across all partitions.
+struct ScanItemSender {
+    tx: mpsc::Sender<Result<ScanItem, ClientError>>,
+    limit: Option<u64>,
+    sent: AtomicU64,
+}
+
+impl ScanItemSender {
+    // send returns whether more items are wanted.
+    async fn send(&self, item: Result<ScanItem, ClientError>) -> bool {
+        if let Some(limit) = self.limit {
+            if item.is_ok() && self.sent.fetch_add(1, Ordering::SeqCst) >= limit {
+                return false;
+            }
+        }
+
+        self.tx.send(item).await.is_ok()
+    }
+
+    fn is_closed(&self) -> bool {
+        let limit_reached = self
+            .limit
+            .map(|l| self.sent.load(Ordering::SeqCst) >= l)
+            .unwrap_or(false);
+
+        limit_reached || self.tx.is_closed()
+    }
+}
+
+// parse_scan_items decodes the items in a range scan continue response. Keys are prefixed with
+// their length, documents are additionally preceded by their metadata and followed by their
+// length prefixed value.
+fn parse_scan_items(mut body: Bytes, ids_only: bool) -> Result<Vec<ScanItem>, ClientError> {
+    let malformed = || ClientError::RequestFailed {
+        reason: Some("Response from range scan continue not expected format".to_string()),
+        key: None,
+    };
+
+    let mut items = vec![];
+    while body.has_remaining() {
+        if ids_only {
+            let key = read_length_prefixed(&mut body).ok_or_else(malformed)?;
+            items.push(ScanItem {
+                key: String::from_utf8_lossy(&key).to_string(),
+                cas: 0,
+                body: None,
+                meta: None,
+            });
+            continue;
+        }
+
+        if body.remaining() < 25 {
+            return Err(malformed());
+        }
+        let flags = body.get_u32();
+        let expiry = body.get_u32();
+        let seqno = body.get_u64();
+        let cas = body.get_u64();
+        let datatype = body.get_u8();
+        let key = read_length_prefixed(&mut body).ok_or_else(malformed)?;
+        let mut value = read_length_prefixed(&mut body).ok_or_else(malformed)?;
+        if datatype & protocol::DATATYPE_SNAPPY != 0 {
+            value = snap::raw::Decoder::new()
+                .decompress_vec(&value)
+                .map(Bytes::from)
+                .map_err(|e| ClientError::RequestFailed {
+                    reason: Some(e.to_string()),
+                    key: Some(String::from_utf8_lossy(&key).to_string()),
+                })?;
+        }
+
+        items.push(ScanItem {
+            key: String::from_utf8_lossy(&key).to_string(),
+            cas,
+            body: Some(value),
+            meta: Some(DocumentMeta {
+                deleted: false,
+                flags,
+                expiry,
+                seqno,
+                datatype: datatype & !protocol::DATATYPE_SNAPPY,
+            }),
+        });
+    }
+
+    Ok(items)
+}
+
+// read_length_prefixed reads a value prefixed with its unsigned LEB128 encoded length.
+fn read_length_prefixed(body: &mut Bytes) -> Option<Bytes> {
+    let mut len: usize = 0;
+    let mut shift = 0;
+    loop {
+        if !body.has_remaining() || shift > 28 {
+            return None;
+        }
+        let byte = body.get_u8();
+        len |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+
+    if body.remaining() < len {
+        return None;
+    }
+    Some(body.split_to(len))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::client::codec::KeyValueCodec;
+    use futures::SinkExt;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use tokio::net::TcpListener;
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    // The statuses and bodies which MockServer answers document requests with, in order.
+    type Script = Arc<Mutex<VecDeque<(u16, Option<Bytes>)>>>;
+
+    // MockServer is a scripted KV node, which answers document requests with the queued statuses
+    // and bodies in order and acknowledges everything else. It counts the bytes it receives and
+    // sends.
+    pub(crate) struct MockServer {
+        port: u32,
+        script: Script,
+        requests: Arc<Mutex<Vec<u8>>>,
+        connections: Arc<AtomicU32>,
+        received: Arc<AtomicUsize>,
+        sent: Arc<AtomicUsize>,
+    }
+
+    // Scripting this status makes the server close the connection instead of responding.
+    pub(crate) static CLOSE_CONNECTION: u16 = u16::MAX;
+    // Scripting this status makes the server push the scripted body as a clustermap change
+    // notification, before responding with the next scripted status.
+    static PUSH_CONFIG: u16 = u16::MAX - 1;
+    // The revision of the config the server answers GetClusterConfig with.
+    static POLLED_CONFIG_REVISION: u64 = 3;
+
+    impl MockServer {
+        pub(crate) async fn start() -> MockServer {
+            MockServer::listen(None).await
+        }
+
+        // start_storing starts a server which keeps the last stored document and serves it for
+        // every get instead of following the script, compressing it with snappy when snappy is
+        // set.
+        async fn start_storing(snappy: bool) -> MockServer {
+            MockServer::listen(Some(snappy)).await
+        }
+
+        async fn listen(store: Option<bool>) -> MockServer {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port() as u32;
+            let script = Arc::new(Mutex::new(VecDeque::new()));
+            let requests = Arc::new(Mutex::new(vec![]));
+            let received = Arc::new(AtomicUsize::new(0));
+            let sent = Arc::new(AtomicUsize::new(0));
+            let stored: Arc<Mutex<Option<Bytes>>> = Arc::new(Mutex::new(None));
+            let snappy = store == Some(true);
+
+            let accept_script = script.clone();
+            let accept_requests = requests.clone();
+            let connections = Arc::new(AtomicU32::new(0));
+            let accept_connections = connections.clone();
+            let accept_received = received.clone();
+            let accept_sent = sent.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (socket, _) = listener.accept().await.unwrap();
+                    accept_connections.fetch_add(1, Ordering::SeqCst);
+                    let conn_script = accept_script.clone();
+                    let conn_requests = accept_requests.clone();
+                    let received = accept_received.clone();
+                    let sent = accept_sent.clone();
+                    let stored = stored.clone();
+                    tokio::spawn(async move {
+                        let (r, w) = tokio::io::split(socket);
+                        let mut input = FramedRead::new(r, KeyValueCodec::new());
+                        let mut output = FramedWrite::new(w, KeyValueCodec::new());
+                        let next = || {
+                            conn_script
+                                .lock()
+                                .unwrap()
+                                .pop_front()
+                                .unwrap_or((0x86, None))
+                        };
+                        while let Some(Ok(frame)) = input.next().await {
+                            received.fetch_add(frame.len(), Ordering::SeqCst);
+                            // Requests share their header layout with responses, apart from the
+                            // vbucket. Parsing them decompresses snappy bodies, so documents are
+                            // stored uncompressed.
+                            let mut req = protocol::KvResponse::from(&frame.freeze());
+                            let mut datatype = 0;
+                            let (mut status, mut body) = match req.opcode() {
+                                // 0x0A is the snappy HELLO feature.
+                                protocol::Opcode::Hello if snappy => {
+                                    (0, Some(Bytes::from(0x0Au16.to_be_bytes().to_vec())))
+                                }
+                                protocol::Opcode::Hello
+                                | protocol::Opcode::Auth
+                                | protocol::Opcode::SelectBucket => (0, None),
+                                protocol::Opcode::ErrorMap => (0, Some(Bytes::from(ERROR_MAP))),
+                                protocol::Opcode::SaslListMechs => (0, Some(Bytes::from("PLAIN"))),
+                                protocol::Opcode::GetClusterConfig => (
+                                    0,
+                                    Some(Bytes::from(raw_bucket_config(
+                                        "$HOST",
+                                        port,
+                                        POLLED_CONFIG_REVISION,
+                                    ))),
+                                ),
+                                protocol::Opcode::Set if store.is_some() => {
+                                    *stored.lock().unwrap() = req.body();
+                                    (0, None)
+                                }
+                                protocol::Opcode::Get if snappy => {
+                                    datatype = protocol::DATATYPE_SNAPPY;
+                                    let body = stored.lock().unwrap().as_ref().map(|b| {
+                                        Bytes::from(
+                                            snap::raw::Encoder::new().compress_vec(b).unwrap(),
+                                        )
+                                    });
+                                    (0, body)
+                                }
+                                protocol::Opcode::Get if store.is_some() => {
+                                    (0, stored.lock().unwrap().clone())
+                                }
+                                opcode => {
+                                    conn_requests.lock().unwrap().push(opcode.encoded());
+                                    next()
+                                }
+                            };
+                            while status == PUSH_CONFIG {
+                                let mut push = protocol::_response(
+                                    protocol::Opcode::Set,
+                                    0,
+                                    0,
+                                    0,
+                                    0,
+                                    None,
+                                    None,
+                                    body,
+                                );
+                                push[0] = protocol::Magic::ServerRequest.encoded();
+                                output.send(push.freeze()).await.unwrap();
+                                (status, body) = next();
+                            }
+                            if status == CLOSE_CONNECTION {
+                                return;
+                            }
+                            let response = protocol::_response(
+                                req.opcode(),
+                                datatype,
+                                status,
+                                req.opaque(),
+                                1,
+                                None,
+                                None,
+                                body,
+                            );
+                            sent.fetch_add(response.len(), Ordering::SeqCst);
+                            output.send(response.freeze()).await.unwrap();
+                        }
+                    });
+                }
+            });
+
+            MockServer {
+                port,
+                script,
+                requests,
+                connections,
+                received,
+                sent,
+            }
+        }
+
+        pub(crate) async fn client(&self) -> KvClient {
+            connect_client(self.port, None).await
+        }
+
+        pub(crate) fn script(&self, responses: Vec<(u16, Option<Bytes>)>) {
+            self.script.lock().unwrap().extend(responses);
+        }
+
+        pub(crate) fn requests(&self) -> Vec<u8> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        fn remaining(&self) -> usize {
+            self.script.lock().unwrap().len()
+        }
+    }
+
+    async fn connect_client(port: u32, compression_min_size: Option<u32>) -> KvClient {
+        let endpoint = KvEndpoint::connect(
+            "127.0.0.1".to_string(),
+            port,
+            "user".to_string(),
+            "password".to_string(),
+            "default".to_string(),
+            None,
+            compression_min_size,
+            Some(AuthMechanism::Plain),
+        )
+        .await
+        .unwrap();
+        let mut endpoints = HashMap::new();
+        endpoints.insert(endpoint.remote(), Arc::new(endpoint));
+
+        KvClient {
+            endpoints: AsyncMutex::new(endpoints),
+            connecting: Mutex::new(HashMap::new()),
+            config: RwLock::new(Arc::new(bucket_config(port, 1))),
+            tls_enabled: false,
+            seeds: vec![],
+            options: EndpointOptions {
+                username: "user".to_string(),
+                password: "password".to_string(),
+                bucket: "default".to_string(),
+                tls_config: None,
+                compression_min_size,
+                auth_mechanism: Some(AuthMechanism::Plain),
+            },
+            runtime: Handle::current(),
+            last_config_poll: Mutex::new(Instant::now()),
+        }
+    }
+
+    static ERROR_MAP: &str = r#"{
+        "version": 1,
+        "revision": 1,
+        "errors": {
+            "86": {
+                "name": "ETMPFAIL",
+                "desc": "Temporary failure",
+                "attrs": ["temp", "retry-later"],
+                "retry": {"strategy": "constant", "interval": 5, "after": 5, "max-duration": 1000, "ceil": 5}
+            }
+        }
+    }"#;
+
+    fn raw_bucket_config(host: &str, port: u32, rev: u64) -> String {
+        format!(
+            r#"{{
+                "rev": {},
+                "nodesExt": [{{"services": {{"kv": {}}}, "hostname": "{}"}}],
+                "nodes": [{{}}],
+                "vBucketServerMap": {{"numReplicas": 0, "vBucketMap": [[0]]}}
+            }}"#,
+            rev, port, host
+        )
+    }
+
+    fn bucket_config(port: u32, rev: u64) -> BucketConfig {
+        let mut config: BucketConfig =
+            serde_json::from_str(&raw_bucket_config("127.0.0.1", port, rev)).unwrap();
+        config.set_loaded_from("127.0.0.1".to_string());
+        config
+    }
+
+    async fn get(client: &KvClient, key: &str) -> Result<KvResponse, ClientError> {
+        client
+            .request(
+                KeyValueRequest::Get {
+                    key: key.to_string(),
+                },
+                0,
+                Instant::now() + Duration::from_secs(2),
+                Arc::new(AtomicBool::new(false)),
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn retries_temporary_failures_and_not_my_vbucket() {
+        let server = MockServer::start().await;
+        server.script(vec![
+            (0x86, None),
+            (
+                0x07,
+                Some(Bytes::from(raw_bucket_config("$HOST", server.port, 2))),
+            ),
+            (0x00, Some(Bytes::from(r#"{"a":1}"#))),
+        ]);
+        let client = server.client().await;
+
+        let mut response = get(&client, "key").await.unwrap();
+        assert_eq!(2, response.retries());
+        assert_eq!(Some(Bytes::from(r#"{"a":1}"#)), response.body());
+        assert_eq!(3, server.requests().len());
+        // The config from the not my vbucket response was newer, so has been applied.
+        assert_eq!((0, 2), client.config().revision());
+    }
+
+    #[tokio::test]
+    async fn gives_up_at_the_deadline_with_the_last_failure() {
+        let server = MockServer::start().await;
+        let client = server.client().await;
+
+        let err = get(&client, "key").await.unwrap_err();
+        assert_eq!(
+            ClientError::TemporaryFailure {
+                key: "key".to_string(),
+                retry: None,
+            }
+            .message(),
+            err.message()
+        );
+        assert!(server.requests().len() > 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_locked_unlock() {
+        let server = MockServer::start().await;
+        server.script(vec![(0x09, None), (0x00, None)]);
+        let client = server.client().await;
+
+        let err = client
+            .request(
+                KeyValueRequest::Unlock {
+                    key: "key".to_string(),
+                    cas: 1,
+                },
+                0,
+                Instant::now() + Duration::from_secs(2),
+                Arc::new(AtomicBool::new(false)),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            ClientError::DocumentLocked {
+                key: "key".to_string()
+            },
+            err
+        );
+        assert_eq!(
+            vec![protocol::Opcode::UnlockKey.encoded()],
+            server.requests()
+        );
+        assert_eq!(1, server.remaining());
+    }
+
+    #[tokio::test]
+    async fn fails_without_a_common_auth_mechanism() {
+        let server = MockServer::start().await;
+
+        let result = KvEndpoint::connect(
+            "127.0.0.1".to_string(),
+            server.port,
+            "user".to_string(),
+            "password".to_string(),
+            "default".to_string(),
+            None,
+            None,
+            Some(AuthMechanism::ScramSha512),
+        )
+        .await;
+        let err = match result {
+            Ok(_) => panic!("connect should fail without a common mechanism"),
+            Err(e) => e,
+        };
+        assert_eq!(
+            ClientError::AuthError {
+                reason: Some(
+                    "No common authentication mechanism, the server supports PLAIN but the client requested SCRAM-SHA512, set kv-auth-mechanism = \"plain\" in the cluster config to send the password in the clear"
+                        .to_string()
+                ),
+            },
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn only_uses_plain_without_tls_when_configured() {
+        let server = MockServer::start().await;
+        let connect = |mechanism| {
+            KvEndpoint::connect(
+                "127.0.0.1".to_string(),
+                server.port,
+                "user".to_string(),
+                "password".to_string(),
+                "default".to_string(),
+                None,
+                None,
+                mechanism,
+            )
+        };
+
+        let err = match connect(None).await {
+            Ok(_) => panic!("connect should not fall back to PLAIN"),
+            Err(e) => e,
+        };
+        assert_eq!(
+            ClientError::AuthError {
+                reason: Some(
+                    "No common authentication mechanism, the server supports PLAIN but the client requested SCRAM-SHA512, SCRAM-SHA256, SCRAM-SHA1, set kv-auth-mechanism = \"plain\" in the cluster config to send the password in the clear"
+                        .to_string()
+                ),
+            },
+            err
+        );
+
+        assert!(connect(Some(AuthMechanism::Plain)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_the_connection_is_closed() {
+        let server = MockServer::start().await;
+        server.script(vec![
+            (CLOSE_CONNECTION, None),
+            (0x00, Some(Bytes::from(r#"{"a":1}"#))),
+        ]);
+        let client = server.client().await;
+
+        assert!(get(&client, "key").await.is_err());
+        assert!(client.connected_nodes().await.is_empty());
+
+        let mut response = get(&client, "key").await.unwrap();
+        assert_eq!(Some(Bytes::from(r#"{"a":1}"#)), response.body());
+        assert_eq!(2, server.connections.load(Ordering::SeqCst));
+        assert_eq!(1, client.connected_nodes().await.len());
+    }
+
+    #[tokio::test]
+    async fn applies_configs_pushed_by_the_server() {
+        let server = MockServer::start().await;
+        server.script(vec![
+            (
+                PUSH_CONFIG,
+                Some(Bytes::from(raw_bucket_config("$HOST", server.port, 5))),
+            ),
+            (0x00, None),
+            (0x00, None),
+        ]);
+        let client = server.client().await;
+
+        get(&client, "key").await.unwrap();
+        assert_eq!((0, 1), client.config().revision());
+        // The pushed config is applied before the next request is sent.
+        get(&client, "key").await.unwrap();
+        assert_eq!((0, 5), client.config().revision());
+    }
+
+    #[tokio::test]
+    async fn polls_for_the_config() {
+        let server = MockServer::start().await;
+        server.script(vec![(0x00, None), (0x00, None)]);
+        let client = server.client().await;
+
+        get(&client, "key").await.unwrap();
+        assert_eq!((0, 1), client.config().revision());
+
+        *client.last_config_poll.lock().unwrap() = Instant::now() - CONFIG_POLL_INTERVAL;
+        get(&client, "key").await.unwrap();
+        assert_eq!((0, POLLED_CONFIG_REVISION), client.config().revision());
+        assert_eq!(
+            vec![
+                protocol::Opcode::Get.encoded(),
+                protocol::Opcode::Get.encoded()
+            ],
+            server.requests()
+        );
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_partition_without_an_active_node() {
+        let server = MockServer::start().await;
+        server.script(vec![(0x00, Some(Bytes::from(r#"{"a":1}"#)))]);
+        let client = server.client().await;
+        // The partition is being failed over, so has no active copy until the next config.
+        let raw = raw_bucket_config("127.0.0.1", server.port, 2).replace("[[0]]", "[[-1]]");
+        let mut config: BucketConfig = serde_json::from_str(&raw).unwrap();
+        config.set_loaded_from("127.0.0.1".to_string());
+        *client.config.write().unwrap() = Arc::new(config);
+
+        let mut response = get(&client, "key").await.unwrap();
+        assert_eq!(1, response.retries());
+        assert_eq!(Some(Bytes::from(r#"{"a":1}"#)), response.body());
+        assert_eq!((0, POLLED_CONFIG_REVISION), client.config().revision());
+        assert_eq!(vec![protocol::Opcode::Get.encoded()], server.requests());
+    }
+
+    fn scanned_doc(key: &str, value: &str, cas: u64) -> Vec<u8> {
+        let mut item = vec![];
+        item.extend_from_slice(&2u32.to_be_bytes());
+        item.extend_from_slice(&0u32.to_be_bytes());
+        item.extend_from_slice(&7u64.to_be_bytes());
+        item.extend_from_slice(&cas.to_be_bytes());
+        item.push(protocol::DATATYPE_JSON);
+        item.push(key.len() as u8);
+        item.extend_from_slice(key.as_bytes());
+        item.push(value.len() as u8);
+        item.extend_from_slice(value.as_bytes());
+        item
+    }
+
+    #[test]
+    fn parses_scanned_ids() {
+        let body = Bytes::from_static(b"\x03abc\x02de");
+        let keys: Vec<String> = parse_scan_items(body, true)
+            .unwrap()
+            .iter()
+            .map(|i| i.key())
+            .collect();
+        assert_eq!(vec!["abc".to_string(), "de".to_string()], keys);
+
+        let truncated = Bytes::from_static(b"\x05abc");
+        assert!(parse_scan_items(truncated, true).is_err());
+    }
+
+    #[test]
+    fn parses_scanned_documents() {
+        let mut body = scanned_doc("a", r#"{"x":1}"#, 10);
+        body.extend(scanned_doc("b", r#"{"x":2}"#, 20));
+
+        let mut items = parse_scan_items(Bytes::from(body), false).unwrap();
+        assert_eq!(2, items.len());
+        assert_eq!("a", items[0].key());
+        assert_eq!(10, items[0].cas());
+        assert_eq!(Some(Bytes::from(r#"{"x":1}"#)), items[0].body());
+        let meta = items[0].meta().unwrap();
+        assert_eq!(2, meta.flags());
+        assert_eq!(7, meta.seqno());
+        assert_eq!(vec!["json"], meta.datatype());
+        assert_eq!("b", items[1].key());
+        assert_eq!(Some(Bytes::from(r#"{"x":2}"#)), items[1].body());
+    }
+
+    #[test]
+    fn creates_scans_for_each_kind() {
+        let body = RangeScan::new(ScanType::Prefix("air".to_string()))
+            .ids_only(true)
+            .create_body(8);
+        assert_eq!("8", body["collection"]);
+        assert_eq!(true, body["key_only"]);
+        assert_eq!(BASE64_STANDARD.encode("air"), body["range"]["start"]);
+        assert_eq!(
+            BASE64_STANDARD.encode(b"air\xf4\x8f\xbf\xbf"),
+            body["range"]["end"]
+        );
+
+        let body = RangeScan::new(ScanType::Sample { limit: 5, seed: 42 }).create_body(0);
+        assert_eq!(json!({"samples": 5, "seed": 42}), body["sampling"]);
+        assert!(body.get("range").is_none());
+    }
+
+    #[tokio::test]
+    async fn scans_until_the_scan_is_complete() {
+        let server = MockServer::start().await;
+        let mut first = scanned_doc("a", r#"{"x":1}"#, 10);
+        first.extend(scanned_doc("b", r#"{"x":2}"#, 20));
+        server.script(vec![
+            (0x00, Some(Bytes::from_static(&[1; 16]))),
+            (0xa6, Some(Bytes::from(first))),
+            (0xa7, Some(Bytes::from(scanned_doc("c", r#"{"x":3}"#, 30)))),
+        ]);
+        let client = Arc::new(server.client().await);
+
+        let mut items = client.range_scan(
+            RangeScan::new(ScanType::Range {
+                from: None,
+                to: None,
+            }),
+            0,
+            Duration::from_secs(2),
+            Arc::new(AtomicBool::new(false)),
+        );
+        let mut keys = vec![];
+        while let Some(item) = items.recv().await {
+            keys.push(item.unwrap().key());
+        }
+
+        assert_eq!(vec!["a", "b", "c"], keys);
+        assert_eq!(
+            vec![
+                protocol::Opcode::RangeScanCreate.encoded(),
+                protocol::Opcode::RangeScanContinue.encoded(),
+                protocol::Opcode::RangeScanContinue.encoded()
+            ],
+            server.requests()
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_sampling_at_the_limit() {
+        let server = MockServer::start().await;
+        server.script(vec![
+            (0x00, Some(Bytes::from_static(&[1; 16]))),
+            (0xa6, Some(Bytes::from_static(b"\x01a\x01b\x01c"))),
+            // The cancel sent once the limit is reached.
+            (0x00, None),
+        ]);
+        let client = Arc::new(server.client().await);
+
+        let mut items = client.range_scan(
+            RangeScan::new(ScanType::Sample { limit: 2, seed: 1 }).ids_only(true),
+            0,
+            Duration::from_secs(2),
+            Arc::new(AtomicBool::new(false)),
+        );
+        let mut keys = vec![];
+        while let Some(item) = items.recv().await {
+            keys.push(item.unwrap().key());
+        }
+
+        assert_eq!(vec!["a", "b"], keys);
+        assert_eq!(
+            vec![
+                protocol::Opcode::RangeScanCreate.encoded(),
+                protocol::Opcode::RangeScanContinue.encoded(),
+                protocol::Opcode::RangeScanCancel.encoded()
+            ],
+            server.requests()
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_scans_return_nothing() {
+        let server = MockServer::start().await;
+        server.script(vec![(0x01, None)]);
+        let client = Arc::new(server.client().await);
+
+        let mut items = client.range_scan(
+            RangeScan::new(ScanType::Prefix("missing".to_string())),
+            0,
+            Duration::from_secs(2),
+            Arc::new(AtomicBool::new(false)),
+        );
+
+        assert!(items.recv().await.is_none());
+        assert_eq!(
+            vec![protocol::Opcode::RangeScanCreate.encoded()],
+            server.requests()
+        );
+    }
+
+    #[test]
+    fn mutation_state_keeps_newest_token_per_partition() {
+        let token = |partition, uuid, seqno| {
+            MutationToken::new("default".to_string(), partition, uuid, seqno)
+        };
+
+        let mut state = MutationState::default();
+        state.add(token(1, 10, 5));
+        state.add(token(1, 10, 3));
+        state.add(token(2, 10, 1));
+
+        let mut other = MutationState::default();
+        other.add(token(2, 11, 1));
+        state.merge(other);
+
+        let mut tokens: Vec<MutationToken> = state.tokens().cloned().collect();
+        tokens.sort_by_key(|t| t.partition());
+        assert_eq!(vec![token(1, 10, 5), token(2, 11, 1)], tokens);
+    }
+
+    #[test]
+    fn durability_timeout_must_fit_the_frame() {
+        let durability = |millis| {
+            Durability::new(
+                DurabilityLevel::Majority,
+                Some(Duration::from_millis(millis)),
+            )
+        };
+
+        assert!(durability(0).is_err());
+        assert!(durability(u16::MAX as u64).is_err());
+        assert!(durability(100_000).is_err());
+
+        let frame = durability(u16::MAX as u64 - 1)
+            .unwrap()
+            .framing_extras()
+            .unwrap();
+        assert_eq!(&[0x13, 0x01, 0xff, 0xfe], frame.as_ref());
+    }
+
+    #[tokio::test]
+    async fn backs_off_while_a_document_is_locked() {
+        let server = MockServer::start().await;
+        server.script(vec![(0x09, None), (0x09, None), (0x09, None), (0x00, None)]);
+        let client = server.client().await;
+
+        let start = Instant::now();
+        let response = client
+            .request(
+                KeyValueRequest::Set {
+                    key: "key".to_string(),
+                    value: br#"{"a":1}"#.to_vec(),
+                    flags: 0,
+                    expiry: 0,
+                    durability: Durability::default(),
+                },
+                0,
+                Instant::now() + Duration::from_secs(2),
+                Arc::new(AtomicBool::new(false)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(3, response.retries());
+        assert!(
+            start.elapsed()
+                >= controlled_backoff(0) + controlled_backoff(1) + controlled_backoff(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn connecting_to_a_node_does_not_block_other_requests() {
+        let server = MockServer::start().await;
+        server.script(vec![(0x00, Some(Bytes::from(r#"{"a":1}"#)))]);
+        let client = Arc::new(server.client().await);
+
+        // This node accepts connections but never answers, so connecting to it does not finish.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stalled_port = listener.local_addr().unwrap().port() as u32;
+        tokio::spawn(async move {
+            let mut sockets = vec![];
+            loop {
+                sockets.push(listener.accept().await.unwrap());
+            }
+        });
+
+        let connecting = client.clone();
+        let stalled = tokio::spawn(async move {
+            connecting
+                .endpoint("127.0.0.1".to_string(), stalled_port)
+                .await
+                .map(|_| ())
+        });
+        sleep(Duration::from_millis(50)).await;
+
+        let response = timeout(Duration::from_secs(1), get(&client, "key")).await;
+        assert!(matches!(response, Ok(Ok(_))));
+        assert!(!stalled.is_finished());
+        stalled.abort();
+    }
+
+    // The benchmarks compare upserting and getting a compressible document with and without
+    // snappy against a storing MockServer, run them with
+    // cargo test --release bench_ -- --ignored --nocapture
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn bench_snappy_compression() {
+        const OPERATIONS: usize = 10_000;
+        const IN_FLIGHT: usize = 64;
+
+        let document = serde_json::to_vec(&json!({
+            "reviews": (0..100)
+                .map(|i| json!({"author": format!("Reviewer {}", i % 7), "content": "Great location, the rooms were clean and the staff friendly.", "ratings": {"Overall": 4, "Cleanliness": 5}}))
+                .collect::<Vec<_>>(),
+        }))
+        .unwrap();
+
+        for (name, snappy, compression_min_size) in
+            [("uncompressed", false, None), ("snappy", true, Some(32))]
+        {
+            let server = MockServer::start_storing(snappy).await;
+            let client = Arc::new(connect_client(server.port, compression_min_size).await);
+            let request = |request: KeyValueRequest| {
+                let client = client.clone();
+                async move {
+                    client
+                        .request(
+                            request,
+                            0,
+                            Instant::now() + Duration::from_secs(10),
+                            Arc::new(AtomicBool::new(false)),
+                        )
+                        .await
+                        .unwrap()
+                }
+            };
+
+            let (received, sent) = (
+                server.received.load(Ordering::SeqCst),
+                server.sent.load(Ordering::SeqCst),
+            );
+            let start = Instant::now();
+            futures::stream::iter(0..OPERATIONS)
+                .map(|i| {
+                    request(KeyValueRequest::Set {
+                        key: format!("bench-{}", i),
+                        value: document.clone(),
+                        flags: 0,
+                        expiry: 0,
+                        durability: Durability::default(),
+                    })
+                })
+                .buffer_unordered(IN_FLIGHT)
+                .collect::<Vec<_>>()
+                .await;
+            let upsert_elapsed = start.elapsed();
+            let upsert_bytes = server.received.load(Ordering::SeqCst) - received;
+
+            let start = Instant::now();
+            futures::stream::iter(0..OPERATIONS)
+                .map(|i| {
+                    request(KeyValueRequest::Get {
+                        key: format!("bench-{}", i),
+                    })
+                })
+                .buffer_unordered(IN_FLIGHT)
+                .collect::<Vec<_>>()
+                .await;
+            let get_elapsed = start.elapsed();
+            let get_bytes = server.sent.load(Ordering::SeqCst) - sent;
+
+            println!(
+                "{:<12} {} byte documents: upsert {:>8.0} ops/s {:>11} bytes sent, get {:>8.0} ops/s {:>11} bytes received",
+                name,
+                document.len(),
+                OPERATIONS as f64 / upsert_elapsed.as_secs_f64(),
+                upsert_bytes,
+                OPERATIONS as f64 / get_elapsed.as_secs_f64(),
+                get_bytes,
+            );
         }
     }
 }
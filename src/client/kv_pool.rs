@@ -0,0 +1,390 @@
+//! A pool of live KV clients, so that the commands of an interactive session reuse connections
+//! rather than bootstrapping new ones to every node each time.
+
+use crate::client::error::ClientError;
+use crate::client::{Client, KvClient};
+use futures::lock::Mutex as AsyncMutex;
+use log::debug;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+use tokio::time::{interval, Instant};
+
+pub static DEFAULT_KV_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+static KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+static KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+struct PoolKey {
+    cluster: String,
+    bucket: String,
+}
+
+struct PooledClient {
+    client: Arc<KvClient>,
+    // The cluster client which the KV client was created from. Registering the cluster again, for
+    // example with different credentials, creates a new one and so invalidates the pooled client.
+    source: Arc<Client>,
+    created: Instant,
+    last_used: Instant,
+    uses: u64,
+}
+
+type PooledClients = Arc<Mutex<HashMap<PoolKey, PooledClient>>>;
+
+pub struct KvConnectionPool {
+    // Commands each run on their own runtime, which is dropped once the command completes, so
+    // pooled connections are made on and owned by a runtime which lives as long as the pool.
+    runtime: Runtime,
+    clients: PooledClients,
+    // Held while creating the client for the key, so that only one is created for it.
+    connecting: Mutex<HashMap<PoolKey, Arc<AsyncMutex<()>>>>,
+}
+
+impl KvConnectionPool {
+    pub fn new(idle_timeout: Duration) -> Self {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("kv-pool")
+            .enable_all()
+            .build()
+            .expect("Failed to create KV connection pool runtime");
+        let clients: PooledClients = Arc::new(Mutex::new(HashMap::new()));
+        runtime.spawn(keepalive(clients.clone(), idle_timeout));
+
+        Self {
+            runtime,
+            clients,
+            connecting: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // client returns the pooled client for the bucket, connecting a new one if there is none.
+    pub async fn client(
+        &self,
+        cluster: &str,
+        client: Arc<Client>,
+        bucket: String,
+        deadline: Instant,
+        ctrl_c: Arc<AtomicBool>,
+    ) -> Result<Arc<KvClient>, ClientError> {
+        let key = PoolKey {
+            cluster: cluster.to_string(),
+            bucket: bucket.clone(),
+        };
+        if let Some(pooled) = self.pooled(&key, &client) {
+            return Ok(pooled);
+        }
+
+        // Connecting is done without holding the lock on the pool, so that other buckets can be
+        // used in the meantime, but only once for each bucket.
+        let connecting = self
+            .connecting
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(AsyncMutex::new(())))
+            .clone();
+        let _connecting = connecting.lock().await;
+        if let Some(pooled) = self.pooled(&key, &client) {
+            return Ok(pooled);
+        }
+
+        debug!("Creating pooled KV client for {:?}", &key);
+        let source = client.clone();
+        let connect = self
+            .runtime
+            .spawn(async move { client.key_value_client(bucket, deadline, ctrl_c).await });
+        let kv_client = match connect.await {
+            Ok(c) => Arc::new(c?),
+            Err(e) => {
+                return Err(ClientError::RequestFailed {
+                    reason: Some(e.to_string()),
+                    key: None,
+                })
+            }
+        };
+
+        let now = Instant::now();
+        self.clients.lock().unwrap().insert(
+            key,
+            PooledClient {
+                client: kv_client.clone(),
+                source,
+                created: now,
+                last_used: now,
+                uses: 1,
+            },
+        );
+
+        Ok(kv_client)
+    }
+
+    // pooled returns the pooled client for the key and marks it as used, if there is one which was
+    // created from the cluster client.
+    fn pooled(&self, key: &PoolKey, source: &Arc<Client>) -> Option<Arc<KvClient>> {
+        let mut clients = self.clients.lock().unwrap();
+        match clients.get_mut(key) {
+            Some(pooled) if Arc::ptr_eq(&pooled.source, source) => {
+                pooled.last_used = Instant::now();
+                pooled.uses += 1;
+                Some(pooled.client.clone())
+            }
+            Some(_) => {
+                debug!(
+                    "Dropping pooled KV client for {:?} as the cluster was registered again",
+                    key
+                );
+                clients.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    // connections describes the pooled clients, optionally only those of a cluster or bucket.
+    pub async fn connections(
+        &self,
+        cluster: Option<&str>,
+        bucket: Option<&str>,
+    ) -> Vec<PooledConnection> {
+        let pooled: Vec<(PoolKey, Arc<KvClient>, Instant, Instant, u64)> = self
+            .clients
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(key, _)| matches_key(key, cluster, bucket))
+            .map(|(key, p)| {
+                (
+                    key.clone(),
+                    p.client.clone(),
+                    p.created,
+                    p.last_used,
+                    p.uses,
+                )
+            })
+            .collect();
+
+        let mut connections = Vec::with_capacity(pooled.len());
+        for (key, client, created, last_used, uses) in pooled {
+            connections.push(PooledConnection {
+                cluster: key.cluster,
+                bucket: key.bucket,
+                nodes: client.connected_nodes().await,
+                age: created.elapsed(),
+                idle: last_used.elapsed(),
+                uses,
+            });
+        }
+        connections.sort_by(|a, b| (&a.cluster, &a.bucket).cmp(&(&b.cluster, &b.bucket)));
+
+        connections
+    }
+
+    // drop_connections removes clients from the pool, their connections are closed once no
+    // command is still using them. Returns the number of clients removed.
+    pub fn drop_connections(&self, cluster: Option<&str>, bucket: Option<&str>) -> usize {
+        let mut clients = self.clients.lock().unwrap();
+        let before = clients.len();
+        clients.retain(|key, _| !matches_key(key, cluster, bucket));
+
+        before - clients.len()
+    }
+}
+
+fn matches_key(key: &PoolKey, cluster: Option<&str>, bucket: Option<&str>) -> bool {
+    cluster.map(|c| c == key.cluster).unwrap_or(true)
+        && bucket.map(|b| b == key.bucket).unwrap_or(true)
+}
+
+// keepalive periodically drops clients which have been idle for too long and sends a noop on the
+// connections of the rest, so that they are not closed for inactivity.
+async fn keepalive(clients: PooledClients, idle_timeout: Duration) {
+    let mut ticker = interval(KEEPALIVE_INTERVAL);
+    loop {
+        ticker.tick().await;
+
+        for client in drop_idle(&clients, idle_timeout) {
+            client.keepalive(KEEPALIVE_TIMEOUT).await;
+        }
+    }
+}
+
+// drop_idle removes the clients which have not been used within the idle timeout from the pool,
+// returning the remaining ones.
+fn drop_idle(clients: &PooledClients, idle_timeout: Duration) -> Vec<Arc<KvClient>> {
+    let mut clients = clients.lock().unwrap();
+    clients.retain(|key, pooled| {
+        let expired = pooled.last_used.elapsed() >= idle_timeout;
+        if expired {
+            debug!("Dropping idle pooled KV client for {:?}", key);
+        }
+        !expired
+    });
+    clients.values().map(|p| p.client.clone()).collect()
+}
+
+pub struct PooledConnection {
+    cluster: String,
+    bucket: String,
+    nodes: Vec<String>,
+    age: Duration,
+    idle: Duration,
+    uses: u64,
+}
+
+impl PooledConnection {
+    pub fn cluster(&self) -> &str {
+        &self.cluster
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    // nodes are the addresses of the nodes which the client currently has an open connection to.
+    pub fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+
+    pub fn age(&self) -> Duration {
+        self.age
+    }
+
+    pub fn idle(&self) -> Duration {
+        self.idle
+    }
+
+    pub fn uses(&self) -> u64 {
+        self.uses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::kv_client::tests::{MockServer, CLOSE_CONNECTION};
+    use crate::client::protocol::Opcode;
+
+    fn key(cluster: &str, bucket: &str) -> PoolKey {
+        PoolKey {
+            cluster: cluster.to_string(),
+            bucket: bucket.to_string(),
+        }
+    }
+
+    fn source() -> Arc<Client> {
+        Arc::new(Client::new(
+            vec!["127.0.0.1".to_string()],
+            "user".to_string(),
+            "password".to_string(),
+            None,
+            None,
+            None,
+        ))
+    }
+
+    fn pooled(client: Arc<KvClient>, source: &Arc<Client>, idle: Duration) -> PooledClient {
+        let last_used = Instant::now() - idle;
+        PooledClient {
+            client,
+            source: source.clone(),
+            created: last_used,
+            last_used,
+            uses: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn drops_idle_clients_and_keeps_the_rest_alive() {
+        let server = MockServer::start().await;
+        let source = source();
+        let idle = Arc::new(server.client().await);
+        let active = Arc::new(server.client().await);
+        let clients: PooledClients = Arc::new(Mutex::new(HashMap::new()));
+        clients.lock().unwrap().insert(
+            key("local", "idle"),
+            pooled(idle, &source, Duration::from_secs(2)),
+        );
+        clients.lock().unwrap().insert(
+            key("local", "active"),
+            pooled(active.clone(), &source, Duration::ZERO),
+        );
+
+        let live = drop_idle(&clients, Duration::from_secs(1));
+        assert_eq!(1, live.len());
+        assert!(Arc::ptr_eq(&active, &live[0]));
+        assert_eq!(
+            vec![key("local", "active")],
+            clients.lock().unwrap().keys().cloned().collect::<Vec<_>>()
+        );
+
+        // A successful noop keeps the connection, a failed one drops it to be reconnected.
+        server.script(vec![(0x00, None), (CLOSE_CONNECTION, None)]);
+        active.keepalive(KEEPALIVE_TIMEOUT).await;
+        assert_eq!(vec![Opcode::Noop.encoded()], server.requests());
+        assert_eq!(1, active.connected_nodes().await.len());
+
+        active.keepalive(KEEPALIVE_TIMEOUT).await;
+        assert!(active.connected_nodes().await.is_empty());
+    }
+
+    #[test]
+    fn lists_and_drops_connections() {
+        let rt = Runtime::new().unwrap();
+        let server = rt.block_on(MockServer::start());
+        let source = source();
+        let pool = KvConnectionPool::new(DEFAULT_KV_IDLE_TIMEOUT);
+        for (cluster, bucket) in [("one", "a"), ("one", "b"), ("two", "a")] {
+            let client = Arc::new(rt.block_on(server.client()));
+            pool.clients.lock().unwrap().insert(
+                key(cluster, bucket),
+                pooled(client, &source, Duration::from_secs(1)),
+            );
+        }
+
+        let connections = rt.block_on(pool.connections(Some("one"), None));
+        assert_eq!(
+            vec![("one", "a"), ("one", "b")],
+            connections
+                .iter()
+                .map(|c| (c.cluster(), c.bucket()))
+                .collect::<Vec<_>>()
+        );
+        assert!(connections.iter().all(|c| c.uses() == 1));
+
+        assert_eq!(1, pool.drop_connections(Some("one"), Some("b")));
+        assert_eq!(0, pool.drop_connections(Some("one"), Some("b")));
+        let connections = rt.block_on(pool.connections(None, Some("a")));
+        assert_eq!(2, connections.len());
+
+        assert_eq!(2, pool.drop_connections(None, None));
+        assert!(rt.block_on(pool.connections(None, None)).is_empty());
+    }
+
+    #[test]
+    fn replaces_clients_of_a_cluster_registered_again() {
+        let rt = Runtime::new().unwrap();
+        let server = rt.block_on(MockServer::start());
+        let registered = source();
+        let pool = KvConnectionPool::new(DEFAULT_KV_IDLE_TIMEOUT);
+        let client = Arc::new(rt.block_on(server.client()));
+        pool.clients.lock().unwrap().insert(
+            key("local", "default"),
+            pooled(client.clone(), &registered, Duration::from_secs(1)),
+        );
+
+        let reused = pool.pooled(&key("local", "default"), &registered).unwrap();
+        assert!(Arc::ptr_eq(&client, &reused));
+        assert_eq!(
+            2,
+            pool.clients.lock().unwrap()[&key("local", "default")].uses
+        );
+
+        // Registering the cluster again, e.g. with other credentials, creates a new cluster client.
+        assert!(pool.pooled(&key("local", "default"), &source()).is_none());
+        assert!(pool.clients.lock().unwrap().is_empty());
+    }
+}
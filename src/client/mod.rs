@@ -3,11 +3,18 @@ pub use crate::client::cloud::CAPELLA_SRV_SUFFIX;
 pub use crate::client::cloud::CLOUD_URL;
 pub use crate::client::error::ClientError;
 pub use crate::client::http_client::{
-    AnalyticsQueryRequest, Endpoint, HTTPClient, ManagementRequest, QueryRequest,
-    QueryTransactionRequest, TextSearchQueryRequest, VectorSearchQueryRequest,
+    AnalyticsQueryRequest, Endpoint, HTTPClient, ManagementRequest, PreparedStatement,
+    QueryAdminRequest, QueryOptions, QueryProfile, QueryRequest, QueryTransactionRequest,
+    ScanConsistency, TextSearchQueryRequest, VectorSearchQueryRequest,
 };
-pub use crate::client::http_handler::HttpResponse;
-pub use crate::client::kv_client::{KeyValueRequest, KvClient, KvResponse};
+pub use crate::client::http_handler::{HttpResponse, HttpStreamResponse};
+pub use crate::client::json_stream::JsonResultsStream;
+pub use crate::client::kv::AuthMechanism;
+pub use crate::client::kv_client::{
+    DocumentFormat, DocumentMeta, Durability, DurabilityLevel, KeyValueRequest, KvClient,
+    KvResponse, MutationState, RangeScan, ScanItem, ScanType, SubdocMutation, SubdocMutationOp,
+};
+pub use crate::client::kv_pool::{KvConnectionPool, DEFAULT_KV_IDLE_TIMEOUT};
 pub use crate::client::tls::RustTlsConfig;
 use log::debug;
 
@@ -25,14 +32,18 @@ pub mod cloud_json;
 mod codec;
 mod crc;
 mod error;
+mod error_map;
 mod gemini_client;
 mod http_client;
 mod http_handler;
+mod json_stream;
 mod kv;
 mod kv_client;
+mod kv_pool;
 mod llm_client;
 mod openai_client;
 mod protocol;
+mod scram;
 mod tls;
 
 pub use llm_client::LLMClients;
@@ -42,6 +53,8 @@ pub struct Client {
     username: String,
     password: String,
     tls_config: Option<RustTlsConfig>,
+    kv_compression_min_size: Option<u32>,
+    kv_auth_mechanism: Option<AuthMechanism>,
 }
 
 impl Client {
@@ -50,6 +63,8 @@ impl Client {
         username: String,
         password: String,
         tls_config: Option<RustTlsConfig>,
+        kv_compression_min_size: Option<u32>,
+        kv_auth_mechanism: Option<AuthMechanism>,
     ) -> Self {
         let seeds = if Client::might_be_srv(&seeds) {
             match utilities::try_lookup_srv(seeds[0].clone()) {
@@ -68,6 +83,8 @@ impl Client {
             username,
             password,
             tls_config,
+            kv_compression_min_size,
+            kv_auth_mechanism,
         }
     }
 
@@ -92,6 +109,8 @@ impl Client {
             self.password.clone(),
             self.tls_config.clone(),
             bucket.clone(),
+            self.kv_compression_min_size,
+            self.kv_auth_mechanism,
             deadline,
             ctrl_c,
         )
@@ -6,7 +6,13 @@ use std::fmt::Write as _;
 use std::fmt::{Display, Formatter};
 
 pub static HEADER_SIZE: usize = 24;
-// pub static ERROR_MAP_VERSION: u16 = 1;
+pub static DATATYPE_JSON: u8 = 0x01;
+pub static DATATYPE_SNAPPY: u8 = 0x02;
+// Requests sent by the server have their own opcodes, which overlap with those of the client so
+// are not part of Opcode.
+pub static SERVER_OPCODE_CLUSTERMAP_CHANGE_NOTIFICATION: u8 = 0x01;
+// Compressed bodies are only sent if they are at most this fraction of the original size.
+static COMPRESSION_MIN_RATIO: f64 = 0.83;
 
 #[derive(Debug)]
 pub struct KvRequest {
@@ -16,6 +22,7 @@ pub struct KvRequest {
     opaque: u32,
     cas: u64,
     key: Option<Bytes>,
+    framing_extras: Option<Bytes>,
     extras: Option<Bytes>,
     body: Option<Bytes>,
     collection_id: u32,
@@ -39,6 +46,7 @@ impl KvRequest {
             partition,
             cas,
             key,
+            framing_extras: None,
             extras,
             body,
             opaque: 0,
@@ -46,6 +54,12 @@ impl KvRequest {
         }
     }
 
+    // set_framing_extras sets the flexible framing extras, causing the request to be sent using
+    // the flexible request magic.
+    pub fn set_framing_extras(&mut self, framing_extras: Bytes) {
+        self.framing_extras = Some(framing_extras);
+    }
+
     pub fn set_opaque(&mut self, opaque: u32) {
         self.opaque = opaque;
     }
@@ -57,10 +71,36 @@ impl KvRequest {
     pub fn opcode(&self) -> Opcode {
         self.opcode
     }
+
+    // compress snappy compresses the document body if it is at least min_size bytes and
+    // compressing it actually saves enough space. Only document mutations are compressed.
+    pub fn compress(&mut self, min_size: usize) {
+        if self.datatype & DATATYPE_SNAPPY != 0 || !self.opcode.supports_compression() {
+            return;
+        }
+
+        let body = match &self.body {
+            Some(b) if !b.is_empty() && b.len() >= min_size => b,
+            _ => return,
+        };
+
+        let compressed = match snap::raw::Encoder::new().compress_vec(body) {
+            Ok(c) => c,
+            Err(_) => return,
+        };
+
+        if (compressed.len() as f64) / (body.len() as f64) > COMPRESSION_MIN_RATIO {
+            return;
+        }
+
+        self.body = Some(Bytes::from(compressed));
+        self.datatype |= DATATYPE_SNAPPY;
+    }
 }
 
 #[derive(Debug)]
 pub struct KvResponse {
+    magic: Magic,
     opcode: Opcode,
     // datatype: u8,
     status: Status,
@@ -80,6 +120,8 @@ impl From<&Bytes> for KvResponse {
         let flexible = magic.is_flexible();
 
         // 1
+        // Server requests reuse the values of client opcodes, so only the encoded value is
+        // meaningful for them.
         let opcode = Opcode::try_from(slice.get_u8()).expect("unknown opcode");
 
         let flexible_extras_len = if flexible {
@@ -100,9 +142,9 @@ impl From<&Bytes> for KvResponse {
         // 4
         let extras_len = slice.get_u8() as usize;
         // 5
-        let _datatype = slice.get_u8();
+        let datatype = slice.get_u8();
         // 6, 7
-        let status = slice.get_u16();
+        let mut status = Status::from(slice.get_u16());
 
         // 8, 9
         let total_body_len = slice.get_u32() as usize;
@@ -130,18 +172,33 @@ impl From<&Bytes> for KvResponse {
             None
         };
 
-        let body = if body_len > 0 {
+        let mut body = if body_len > 0 {
             Some(input.slice((HEADER_SIZE + flexible_extras_len + extras_len + key_len)..))
         } else {
             None
         };
 
+        if datatype & DATATYPE_SNAPPY != 0 {
+            if let Some(b) = &body {
+                match snap::raw::Decoder::new().decompress_vec(b) {
+                    Ok(decompressed) => {
+                        body = Some(Bytes::from(decompressed));
+                    }
+                    Err(_) => {
+                        body = None;
+                        status = Status::DecompressionFailed;
+                    }
+                }
+            }
+        }
+
         KvResponse {
+            magic,
             opaque,
             body,
             extras,
             // key,
-            status: Status::from(status),
+            status,
             // datatype,
             cas,
             opcode,
@@ -167,6 +224,12 @@ impl KvResponse {
         self.cas
     }
 
+    // is_server_request is whether the server sent this unprompted, rather than it being the
+    // response to a request.
+    pub fn is_server_request(&self) -> bool {
+        self.magic == Magic::ServerRequest
+    }
+
     pub fn opcode(&self) -> Opcode {
         self.opcode
     }
@@ -175,9 +238,26 @@ impl KvResponse {
     pub fn extras(&mut self) -> Option<Bytes> {
         self.extras.take()
     }
+
+    // mutation_seqno returns the partition uuid and sequence number of a successful mutation,
+    // which are only sent when the MutationSeqno feature was negotiated.
+    pub fn mutation_seqno(&self) -> Option<(u64, u64)> {
+        if !self.opcode.is_mutation() {
+            return None;
+        }
+
+        match &self.extras {
+            Some(extras) if extras.len() == 16 => {
+                let mut extras = extras.clone();
+                Some((extras.get_u64(), extras.get_u64()))
+            }
+            _ => None,
+        }
+    }
 }
 
-/// Creates a regular, non-flex request with all fields necessary.
+/// Creates a request with all fields necessary, using the flexible format if framing extras
+/// are present.
 pub fn request(req: KvRequest, collections_enabled: bool) -> BytesMut {
     let key = match req.key {
         Some(k) => {
@@ -191,6 +271,20 @@ pub fn request(req: KvRequest, collections_enabled: bool) -> BytesMut {
         None => None,
     };
 
+    if req.framing_extras.is_some() {
+        return flexible_request(
+            req.opcode,
+            req.datatype,
+            req.partition,
+            req.opaque,
+            req.cas,
+            key,
+            req.framing_extras,
+            req.extras,
+            req.body,
+        );
+    }
+
     let key_size = key.as_ref().map(|b| b.len()).unwrap_or_default();
     let extras_size = req.extras.as_ref().map(|b| b.len()).unwrap_or_default();
     let total_body_size =
@@ -224,7 +318,7 @@ pub fn request(req: KvRequest, collections_enabled: bool) -> BytesMut {
 
 // Creates a flexible request with optional framing extras
 #[allow(clippy::too_many_arguments)]
-pub fn _flexible_request(
+pub fn flexible_request(
     opcode: Opcode,
     datatype: u8,
     partition: u16,
@@ -274,6 +368,27 @@ pub fn _flexible_request(
     builder
 }
 
+/// Creates a durability requirement frame for use in the flexible framing extras.
+///
+/// The frame header holds the frame id (0x01) in the upper nibble and the frame length in the
+/// lower nibble, followed by the level and optionally the timeout in milliseconds.
+pub fn durability_frame(level: u8, timeout: Option<u16>) -> Bytes {
+    let mut builder = BytesMut::with_capacity(4);
+    match timeout {
+        Some(t) => {
+            builder.put_u8(0x13);
+            builder.put_u8(level);
+            builder.put_u16(t);
+        }
+        None => {
+            builder.put_u8(0x11);
+            builder.put_u8(level);
+        }
+    }
+
+    builder.freeze()
+}
+
 /// Creates a regular, non-flex response with all fields necessary.
 #[allow(clippy::too_many_arguments)]
 pub fn _response(
@@ -395,38 +510,122 @@ pub fn _dump(input: &Bytes) -> String {
 #[derive(Debug, Copy, Clone)]
 pub enum Opcode {
     Get,
+    GetReplica,
+    GetMeta,
     Set,
     Add,
     Replace,
     Remove,
+    Increment,
+    Decrement,
+    Append,
+    Prepend,
+    Touch,
+    GetAndTouch,
+    GetLocked,
+    UnlockKey,
     Hello,
     Noop,
     ErrorMap,
+    SaslListMechs,
     Auth,
+    SaslStep,
     SelectBucket,
     GetCollectionID,
+    GetClusterConfig,
     SubdocGet,
+    SubdocDictAdd,
+    SubdocDictUpsert,
+    SubdocDelete,
+    SubdocReplace,
+    SubdocArrayPushLast,
+    SubdocArrayPushFirst,
+    SubdocArrayAddUnique,
+    SubdocCounter,
     SubdocMultiLookup,
+    SubdocMultiMutation,
+    RangeScanCreate,
+    RangeScanContinue,
+    RangeScanCancel,
 }
 
 impl Opcode {
     pub fn encoded(&self) -> u8 {
         match self {
             Self::Get => 0x00,
+            Self::GetReplica => 0x83,
+            Self::GetMeta => 0xa0,
             Self::Set => 0x01,
             Self::Add => 0x02,
             Self::Replace => 0x03,
             Self::Remove => 0x04,
+            Self::Increment => 0x05,
+            Self::Decrement => 0x06,
             Self::Noop => 0x0A,
+            Self::Append => 0x0e,
+            Self::Prepend => 0x0f,
+            Self::Touch => 0x1c,
+            Self::GetAndTouch => 0x1d,
+            Self::GetLocked => 0x94,
+            Self::UnlockKey => 0x95,
             Self::Hello => 0x1F,
+            Self::SaslListMechs => 0x20,
             Self::Auth => 0x21,
+            Self::SaslStep => 0x22,
             Self::SelectBucket => 0x89,
             Self::ErrorMap => 0xFE,
             Self::GetCollectionID => 0xBB,
+            Self::GetClusterConfig => 0xb5,
             Self::SubdocGet => 0xc5,
+            Self::SubdocDictAdd => 0xc7,
+            Self::SubdocDictUpsert => 0xc8,
+            Self::SubdocDelete => 0xc9,
+            Self::SubdocReplace => 0xca,
+            Self::SubdocArrayPushLast => 0xcb,
+            Self::SubdocArrayPushFirst => 0xcc,
+            Self::SubdocArrayAddUnique => 0xce,
+            Self::SubdocCounter => 0xcf,
             Self::SubdocMultiLookup => 0xd0,
+            Self::SubdocMultiMutation => 0xd1,
+            Self::RangeScanCreate => 0xda,
+            Self::RangeScanContinue => 0xdb,
+            Self::RangeScanCancel => 0xdc,
         }
     }
+
+    // supports_compression returns whether the body of a request with this opcode is a document
+    // value, which the server accepts snappy compressed.
+    pub fn supports_compression(&self) -> bool {
+        matches!(
+            self,
+            Self::Set | Self::Add | Self::Replace | Self::Append | Self::Prepend
+        )
+    }
+
+    // is_mutation returns whether the operation changes a document, in which case the response
+    // carries the partition uuid and sequence number of the change.
+    pub fn is_mutation(&self) -> bool {
+        matches!(
+            self,
+            Self::Set
+                | Self::Add
+                | Self::Replace
+                | Self::Remove
+                | Self::Increment
+                | Self::Decrement
+                | Self::Append
+                | Self::Prepend
+                | Self::SubdocDictAdd
+                | Self::SubdocDictUpsert
+                | Self::SubdocDelete
+                | Self::SubdocReplace
+                | Self::SubdocArrayPushLast
+                | Self::SubdocArrayPushFirst
+                | Self::SubdocArrayAddUnique
+                | Self::SubdocCounter
+                | Self::SubdocMultiMutation
+        )
+    }
 }
 
 impl Display for Opcode {
@@ -441,29 +640,55 @@ impl TryFrom<u8> for Opcode {
     fn try_from(input: u8) -> Result<Self, Self::Error> {
         Ok(match input {
             0x00 => Opcode::Get,
+            0x83 => Opcode::GetReplica,
+            0xa0 => Opcode::GetMeta,
             0x01 => Opcode::Set,
             0x02 => Opcode::Add,
             0x03 => Opcode::Replace,
             0x04 => Opcode::Remove,
+            0x05 => Opcode::Increment,
+            0x06 => Opcode::Decrement,
             0x0A => Opcode::Noop,
+            0x0e => Opcode::Append,
+            0x0f => Opcode::Prepend,
+            0x1c => Opcode::Touch,
+            0x1d => Opcode::GetAndTouch,
+            0x94 => Opcode::GetLocked,
+            0x95 => Opcode::UnlockKey,
             0x1F => Opcode::Hello,
+            0x20 => Opcode::SaslListMechs,
             0x21 => Opcode::Auth,
+            0x22 => Opcode::SaslStep,
             0x89 => Opcode::SelectBucket,
             0xFE => Opcode::ErrorMap,
             0xBB => Opcode::GetCollectionID,
+            0xb5 => Opcode::GetClusterConfig,
             0xc5 => Opcode::SubdocGet,
+            0xc7 => Opcode::SubdocDictAdd,
+            0xc8 => Opcode::SubdocDictUpsert,
+            0xc9 => Opcode::SubdocDelete,
+            0xca => Opcode::SubdocReplace,
+            0xcb => Opcode::SubdocArrayPushLast,
+            0xcc => Opcode::SubdocArrayPushFirst,
+            0xce => Opcode::SubdocArrayAddUnique,
+            0xcf => Opcode::SubdocCounter,
             0xd0 => Opcode::SubdocMultiLookup,
+            0xd1 => Opcode::SubdocMultiMutation,
+            0xda => Opcode::RangeScanCreate,
+            0xdb => Opcode::RangeScanContinue,
+            0xdc => Opcode::RangeScanCancel,
             _ => return Err(input),
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Magic {
     Request,
     FlexibleRequest,
     Response,
     FlexibleResponse,
+    ServerRequest,
     Unknown,
 }
 
@@ -474,6 +699,7 @@ impl Magic {
             Self::Request => 0x80,
             Self::FlexibleResponse => 0x18,
             Self::Response => 0x81,
+            Self::ServerRequest => 0x82,
             Self::Unknown => panic!("Cannot convert unknown magic"),
         }
     }
@@ -490,6 +716,7 @@ impl From<u8> for Magic {
             0x08 => Magic::FlexibleRequest,
             0x81 => Magic::Response,
             0x18 => Magic::FlexibleResponse,
+            0x82 => Magic::ServerRequest,
             _ => Magic::Unknown,
         }
     }
@@ -499,12 +726,40 @@ impl From<u8> for Magic {
 pub enum Status {
     Success,
     AuthError,
+    AuthContinue,
     AccessError,
     KeyNotFound,
     KeyExists,
+    NotStored,
+    DeltaBadValue,
+    NotMyVbucket,
+    Locked,
+    TemporaryFailure,
+    UnknownCommand,
+    NotSupported,
     CollectionUnknown,
     ScopeUnknown,
     PathNotFound,
+    PathMismatch,
+    PathInvalid,
+    DocNotJson,
+    NumRange,
+    DeltaRange,
+    PathExists,
+    ValueCantInsert,
+    SubdocMultiPathFailure,
+    DurabilityInvalidLevel,
+    DurabilityImpossible,
+    SyncWriteInProgress,
+    SyncWriteAmbiguous,
+    SyncWriteReCommitInProgress,
+    RangeScanCancelled,
+    RangeScanMore,
+    RangeScanComplete,
+    RangeScanVbUuidNotEqual,
+    // DecompressionFailed is never sent by the server, it is set when a snappy compressed body
+    // could not be decompressed.
+    DecompressionFailed,
     Unknown(u16),
 }
 
@@ -519,15 +774,82 @@ impl Status {
         match self {
             Status::Success => "success".into(),
             Status::AuthError => "authentication error".into(),
+            Status::AuthContinue => "authentication continue".into(),
             Status::AccessError => "access error".into(),
             Status::KeyNotFound => "key not found".into(),
             Status::KeyExists => "key already exists".into(),
+            Status::NotStored => "not stored".into(),
+            Status::DeltaBadValue => "value is not a number".into(),
+            Status::NotMyVbucket => "not my vbucket".into(),
+            Status::Locked => "document locked".into(),
+            Status::TemporaryFailure => "temporary failure".into(),
+            Status::UnknownCommand => "unknown command".into(),
+            Status::NotSupported => "not supported".into(),
             Status::CollectionUnknown => "collection unknown".into(),
             Status::ScopeUnknown => "scope unknown".into(),
             Status::PathNotFound => "field not found".into(),
+            Status::PathMismatch => "path mismatch".into(),
+            Status::PathInvalid => "path invalid".into(),
+            Status::DocNotJson => "document is not json".into(),
+            Status::NumRange => "number out of range".into(),
+            Status::DeltaRange => "delta out of range".into(),
+            Status::PathExists => "path already exists".into(),
+            Status::ValueCantInsert => "value cannot be inserted".into(),
+            Status::SubdocMultiPathFailure => "sub-document path failure".into(),
+            Status::DurabilityInvalidLevel => "durability level invalid".into(),
+            Status::DurabilityImpossible => "durability impossible".into(),
+            Status::SyncWriteInProgress => "sync write in progress".into(),
+            Status::SyncWriteAmbiguous => "sync write ambiguous".into(),
+            Status::SyncWriteReCommitInProgress => "sync write re-commit in progress".into(),
+            Status::RangeScanCancelled => "range scan cancelled".into(),
+            Status::RangeScanMore => "range scan has more".into(),
+            Status::RangeScanComplete => "range scan complete".into(),
+            Status::RangeScanVbUuidNotEqual => "range scan vbucket uuid not equal".into(),
+            Status::DecompressionFailed => "failed to decompress value".into(),
             Status::Unknown(status) => format!("{:#04x}", status),
         }
     }
+
+    // code returns the status code as sent by the server, which client side statuses do not have.
+    pub fn code(&self) -> Option<u16> {
+        Some(match self {
+            Status::Success => 0x00,
+            Status::KeyNotFound => 0x01,
+            Status::KeyExists => 0x02,
+            Status::NotStored => 0x05,
+            Status::DeltaBadValue => 0x06,
+            Status::NotMyVbucket => 0x07,
+            Status::Locked => 0x09,
+            Status::UnknownCommand => 0x81,
+            Status::NotSupported => 0x83,
+            Status::TemporaryFailure => 0x86,
+            Status::CollectionUnknown => 0x88,
+            Status::ScopeUnknown => 0x8c,
+            Status::AuthError => 0x20,
+            Status::AuthContinue => 0x21,
+            Status::AccessError => 0x24,
+            Status::DurabilityInvalidLevel => 0xa0,
+            Status::DurabilityImpossible => 0xa1,
+            Status::SyncWriteInProgress => 0xa2,
+            Status::SyncWriteAmbiguous => 0xa3,
+            Status::SyncWriteReCommitInProgress => 0xa4,
+            Status::RangeScanCancelled => 0xa5,
+            Status::RangeScanMore => 0xa6,
+            Status::RangeScanComplete => 0xa7,
+            Status::RangeScanVbUuidNotEqual => 0xa8,
+            Status::PathNotFound => 0xc0,
+            Status::PathMismatch => 0xc1,
+            Status::PathInvalid => 0xc2,
+            Status::ValueCantInsert => 0xc5,
+            Status::DocNotJson => 0xc6,
+            Status::NumRange => 0xc7,
+            Status::DeltaRange => 0xc8,
+            Status::PathExists => 0xc9,
+            Status::SubdocMultiPathFailure => 0xcc,
+            Status::DecompressionFailed => return None,
+            Status::Unknown(status) => *status,
+        })
+    }
 }
 
 impl From<u16> for Status {
@@ -536,11 +858,36 @@ impl From<u16> for Status {
             0x00 => Status::Success,
             0x01 => Status::KeyNotFound,
             0x02 => Status::KeyExists,
+            0x05 => Status::NotStored,
+            0x06 => Status::DeltaBadValue,
+            0x07 => Status::NotMyVbucket,
+            0x09 => Status::Locked,
+            0x81 => Status::UnknownCommand,
+            0x83 => Status::NotSupported,
+            0x86 => Status::TemporaryFailure,
             0x88 => Status::CollectionUnknown,
             0x8c => Status::ScopeUnknown,
             0x20 => Status::AuthError,
+            0x21 => Status::AuthContinue,
             0x24 => Status::AccessError,
+            0xa0 => Status::DurabilityInvalidLevel,
+            0xa1 => Status::DurabilityImpossible,
+            0xa2 => Status::SyncWriteInProgress,
+            0xa3 => Status::SyncWriteAmbiguous,
+            0xa4 => Status::SyncWriteReCommitInProgress,
+            0xa5 => Status::RangeScanCancelled,
+            0xa6 => Status::RangeScanMore,
+            0xa7 => Status::RangeScanComplete,
+            0xa8 => Status::RangeScanVbUuidNotEqual,
             0xc0 => Status::PathNotFound,
+            0xc1 => Status::PathMismatch,
+            0xc2 => Status::PathInvalid,
+            0xc5 => Status::ValueCantInsert,
+            0xc6 => Status::DocNotJson,
+            0xc7 => Status::NumRange,
+            0xc8 => Status::DeltaRange,
+            0xc9 => Status::PathExists,
+            0xcc => Status::SubdocMultiPathFailure,
             _ => Status::Unknown(input),
         }
     }
@@ -567,3 +914,199 @@ fn make_uleb128_32(key: Bytes, collection_id: u32) -> Bytes {
 
     builder.freeze()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_with_framing_extras_is_flexible() {
+        let mut req = KvRequest::new(
+            Opcode::Set,
+            0,
+            1,
+            0,
+            Some(Bytes::from("key")),
+            None,
+            Some(Bytes::from("{}")),
+            0,
+        );
+        req.set_framing_extras(durability_frame(0x01, Some(1500)));
+
+        let encoded = request(req, false).freeze();
+        assert_eq!(Magic::FlexibleRequest.encoded(), encoded[0]);
+        // Framing extras length, key length and extras length.
+        assert_eq!(4, encoded[2]);
+        assert_eq!(3, encoded[3]);
+        assert_eq!(0, encoded[4]);
+        assert_eq!(
+            &[0x13, 0x01, 0x05, 0xdc],
+            &encoded[HEADER_SIZE..HEADER_SIZE + 4]
+        );
+        assert_eq!(Some(Bytes::from("{}")), _body(&encoded));
+    }
+
+    #[test]
+    fn request_without_framing_extras_is_regular() {
+        let req = KvRequest::new(
+            Opcode::Get,
+            0,
+            1,
+            0,
+            Some(Bytes::from("key")),
+            None,
+            None,
+            0,
+        );
+
+        let encoded = request(req, false).freeze();
+        assert_eq!(Magic::Request.encoded(), encoded[0]);
+        assert_eq!(3, u16::from_be_bytes([encoded[2], encoded[3]]));
+    }
+
+    #[test]
+    fn compresses_large_document_bodies() {
+        let value = Bytes::from("a".repeat(1024));
+        let mut req = KvRequest::new(
+            Opcode::Set,
+            0,
+            1,
+            0,
+            Some(Bytes::from("key")),
+            None,
+            Some(value.clone()),
+            0,
+        );
+        req.compress(32);
+
+        let encoded = request(req, false).freeze();
+        assert_eq!(DATATYPE_SNAPPY, encoded[5]);
+        let body = _body(&encoded).unwrap();
+        assert!(body.len() < value.len());
+        assert_eq!(
+            value.to_vec(),
+            snap::raw::Decoder::new().decompress_vec(&body).unwrap()
+        );
+    }
+
+    #[test]
+    fn does_not_compress_small_or_non_document_bodies() {
+        let mut req = KvRequest::new(
+            Opcode::Set,
+            0,
+            1,
+            0,
+            Some(Bytes::from("key")),
+            None,
+            Some(Bytes::from("a".repeat(16))),
+            0,
+        );
+        req.compress(32);
+        assert_eq!(0, request(req, false)[5]);
+
+        let mut req = KvRequest::new(
+            Opcode::SubdocMultiMutation,
+            0,
+            1,
+            0,
+            Some(Bytes::from("key")),
+            None,
+            Some(Bytes::from("a".repeat(1024))),
+            0,
+        );
+        req.compress(32);
+        assert_eq!(0, request(req, false)[5]);
+    }
+
+    #[test]
+    fn decompresses_snappy_responses() {
+        let value = "b".repeat(1024);
+        let compressed = snap::raw::Encoder::new()
+            .compress_vec(value.as_bytes())
+            .unwrap();
+        let encoded = _response(
+            Opcode::Get,
+            DATATYPE_SNAPPY | 0x01,
+            0,
+            1,
+            0,
+            None,
+            None,
+            Some(Bytes::from(compressed)),
+        )
+        .freeze();
+
+        let mut response = KvResponse::from(&encoded);
+        assert_eq!(Status::Success, response.status());
+        assert_eq!(Some(Bytes::from(value)), response.body());
+    }
+
+    #[test]
+    fn invalid_snappy_response_fails() {
+        let encoded = _response(
+            Opcode::Get,
+            DATATYPE_SNAPPY,
+            0,
+            1,
+            0,
+            None,
+            None,
+            Some(Bytes::from(vec![0xff, 0xff, 0xff, 0xff, 0xff])),
+        )
+        .freeze();
+
+        let mut response = KvResponse::from(&encoded);
+        assert_eq!(Status::DecompressionFailed, response.status());
+        assert_eq!(None, response.body());
+    }
+
+    #[test]
+    fn parses_mutation_seqnos() {
+        let mut extras = BytesMut::new();
+        extras.put_u64(0xabcd);
+        extras.put_u64(42);
+        let encoded =
+            _response(Opcode::Set, 0, 0, 1, 0, None, Some(extras.freeze()), None).freeze();
+        assert_eq!(
+            Some((0xabcd, 42)),
+            KvResponse::from(&encoded).mutation_seqno()
+        );
+
+        let encoded = _response(
+            Opcode::Get,
+            0,
+            0,
+            1,
+            0,
+            None,
+            Some(Bytes::from(vec![0; 16])),
+            None,
+        )
+        .freeze();
+        assert_eq!(None, KvResponse::from(&encoded).mutation_seqno());
+    }
+
+    #[test]
+    fn server_request_is_not_a_response() {
+        let mut frame = _response(
+            Opcode::Set,
+            0,
+            0,
+            0,
+            0,
+            Some(Bytes::from("default")),
+            None,
+            Some(Bytes::from(r#"{"rev":2}"#)),
+        );
+        assert!(!KvResponse::from(&frame.clone().freeze()).is_server_request());
+
+        frame[0] = Magic::ServerRequest.encoded();
+        let mut response = KvResponse::from(&frame.freeze());
+        assert!(response.is_server_request());
+        assert_eq!(
+            SERVER_OPCODE_CLUSTERMAP_CHANGE_NOTIFICATION,
+            response.opcode().encoded()
+        );
+        assert_eq!(Some(Bytes::from(r#"{"rev":2}"#)), response.body());
+    }
+}
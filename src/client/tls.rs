@@ -2,13 +2,15 @@ use crate::client::capella_ca::CAPELLA_CERT;
 use crate::client::ClientError;
 use crate::ClusterTlsConfig;
 use log::{debug, error};
-use rustls_pemfile::{read_all, Item};
+use rustls_pemfile::{certs, private_key, read_all, Item};
 use std::convert::TryFrom;
 use std::fs;
 use std::io::BufReader;
 use std::sync::Arc;
+use tokio_rustls::rustls::client::WantsClientCert;
 use tokio_rustls::rustls::crypto::{aws_lc_rs::default_provider, CryptoProvider};
-use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::{ClientConfig, ConfigBuilder, RootCertStore};
 
 #[derive(Clone)]
 pub struct RustTlsConfig {
@@ -16,25 +18,43 @@ pub struct RustTlsConfig {
     // We need to hold onto these so that we can later rewrite them into the config file if needed.
     accept_all_certs: bool,
     cert_path: Option<String>,
+    client_cert_path: Option<String>,
+    client_key_path: Option<String>,
 }
 
 impl RustTlsConfig {
     pub fn new(
         accept_all_certs: bool,
         cert_path: Option<String>,
+        client_cert_path: Option<String>,
+        client_key_path: Option<String>,
     ) -> Result<RustTlsConfig, ClientError> {
         let _ = CryptoProvider::install_default(default_provider());
+        let client_auth = match (&client_cert_path, &client_key_path) {
+            (Some(cert), Some(key)) => Some(load_client_auth(cert, key)?),
+            (None, None) => None,
+            _ => {
+                return Err(ClientError::RequestFailed {
+                    reason: Some(
+                        "Both a client certificate and a client key are required for client certificate authentication".to_string(),
+                    ),
+                    key: None,
+                })
+            }
+        };
+
         let builder = ClientConfig::builder();
         if accept_all_certs {
-            let config = builder
+            let builder = builder
                 .dangerous()
-                .with_custom_certificate_verifier(Arc::new(utilities::InsecureCertVerifier {}))
-                .with_no_client_auth();
+                .with_custom_certificate_verifier(Arc::new(utilities::InsecureCertVerifier {}));
 
             return Ok(RustTlsConfig {
-                config,
+                config: with_client_auth(builder, client_auth)?,
                 accept_all_certs,
                 cert_path,
+                client_cert_path,
+                client_key_path,
             });
         }
 
@@ -98,14 +118,14 @@ impl RustTlsConfig {
                 }
             };
         };
-        let config = builder
-            .with_root_certificates(root_cert_store)
-            .with_no_client_auth();
+        let builder = builder.with_root_certificates(root_cert_store);
 
         Ok(RustTlsConfig {
-            config,
+            config: with_client_auth(builder, client_auth)?,
             accept_all_certs,
             cert_path,
+            client_cert_path,
+            client_key_path,
         })
     }
 
@@ -120,6 +140,72 @@ impl RustTlsConfig {
     pub fn cert_path(&self) -> Option<String> {
         self.cert_path.clone()
     }
+
+    pub fn client_cert_path(&self) -> Option<String> {
+        self.client_cert_path.clone()
+    }
+
+    pub fn client_key_path(&self) -> Option<String> {
+        self.client_key_path.clone()
+    }
+
+    // client_auth_enabled is whether the client authenticates using a certificate, in which case
+    // the username and password are not sent to the server.
+    pub fn client_auth_enabled(&self) -> bool {
+        self.client_cert_path.is_some()
+    }
+}
+
+// load_client_auth reads the client certificate chain and its private key from PEM files.
+fn load_client_auth(
+    cert_path: &str,
+    key_path: &str,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), ClientError> {
+    let cert = fs::read(cert_path).map_err(ClientError::from)?;
+    let mut reader = BufReader::new(&cert[..]);
+    let chain = certs(&mut reader)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ClientError::RequestFailed {
+            reason: Some(format!("Failed to read client cert file {}", e)),
+            key: None,
+        })?;
+    if chain.is_empty() {
+        return Err(ClientError::RequestFailed {
+            reason: Some(format!("No certificates found in {}", cert_path)),
+            key: None,
+        });
+    }
+
+    let key = fs::read(key_path).map_err(ClientError::from)?;
+    let mut reader = BufReader::new(&key[..]);
+    let key = private_key(&mut reader)
+        .map_err(|e| ClientError::RequestFailed {
+            reason: Some(format!("Failed to read client key file {}", e)),
+            key: None,
+        })?
+        .ok_or_else(|| ClientError::RequestFailed {
+            reason: Some(format!("No private key found in {}", key_path)),
+            key: None,
+        })?;
+
+    Ok((chain, key))
+}
+
+fn with_client_auth(
+    builder: ConfigBuilder<ClientConfig, WantsClientCert>,
+    client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+) -> Result<ClientConfig, ClientError> {
+    match client_auth {
+        Some((chain, key)) => {
+            builder
+                .with_client_auth_cert(chain, key)
+                .map_err(|e| ClientError::RequestFailed {
+                    reason: Some(format!("Invalid client certificate or key {}", e)),
+                    key: None,
+                })
+        }
+        None => Ok(builder.with_no_client_auth()),
+    }
 }
 
 impl TryFrom<ClusterTlsConfig> for RustTlsConfig {
@@ -129,6 +215,130 @@ impl TryFrom<ClusterTlsConfig> for RustTlsConfig {
         RustTlsConfig::new(
             tls_config.accept_all_certs(),
             tls_config.cert_path().clone(),
+            tls_config.client_cert_path().clone(),
+            tls_config.client_key_path().clone(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::http_handler::{HTTPHandler, HttpVerb};
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::time::Instant;
+    use tokio_rustls::rustls::server::WebPkiClientVerifier;
+    use tokio_rustls::rustls::ServerConfig;
+    use tokio_rustls::TlsAcceptor;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    // start_server accepts a single connection, which must present a certificate signed by the
+    // test CA, and answers its HTTP request by echoing the request back in the body.
+    async fn start_server() -> u16 {
+        let _ = CryptoProvider::install_default(default_provider());
+        let ca = fs::read(fixture("ca.pem")).unwrap();
+        let mut roots = RootCertStore::empty();
+        for cert in certs(&mut BufReader::new(&ca[..])) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+            .build()
+            .unwrap();
+        let (chain, key) =
+            load_client_auth(&fixture("server.pem"), &fixture("server.key")).unwrap();
+        let config = ServerConfig::builder()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(chain, key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut stream = match acceptor.accept(socket).await {
+                Ok(s) => s,
+                Err(_) => return,
+            };
+
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+
+            let body = String::from_utf8_lossy(&request).to_lowercase();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+
+        port
+    }
+
+    async fn get(port: u16, tls_config: RustTlsConfig) -> Result<(String, u16), ClientError> {
+        let handler = HTTPHandler::new(
+            "Administrator".to_string(),
+            "password".to_string(),
+            Some(tls_config),
+        );
+        handler
+            .http_do(
+                &format!("localhost:{}/pools", port),
+                HttpVerb::Get,
+                None,
+                HashMap::new(),
+                Instant::now() + Duration::from_secs(5),
+                Arc::new(AtomicBool::new(false)),
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn authenticates_with_client_certificate() {
+        let port = start_server().await;
+        let tls_config = RustTlsConfig::new(
+            false,
+            Some(fixture("ca.pem")),
+            Some(fixture("client.pem")),
+            Some(fixture("client.key")),
         )
+        .unwrap();
+        assert!(tls_config.client_auth_enabled());
+
+        let (content, status) = get(port, tls_config).await.unwrap();
+        assert_eq!(200, status);
+        assert!(content.starts_with("get /pools "));
+        // The certificate identifies the user, so the credentials must not be sent as well.
+        assert!(!content.contains("authorization:"));
+    }
+
+    #[tokio::test]
+    async fn rejected_without_client_certificate() {
+        let port = start_server().await;
+        let tls_config = RustTlsConfig::new(false, Some(fixture("ca.pem")), None, None).unwrap();
+        assert!(!tls_config.client_auth_enabled());
+
+        assert!(get(port, tls_config).await.is_err());
+    }
+
+    #[test]
+    fn requires_client_certificate_and_key() {
+        assert!(RustTlsConfig::new(false, None, Some(fixture("client.pem")), None).is_err());
+        assert!(RustTlsConfig::new(false, None, None, Some(fixture("client.key"))).is_err());
     }
 }
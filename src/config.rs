@@ -1,3 +1,4 @@
+use crate::client::AuthMechanism;
 use crate::remote_cluster::{RemoteCluster, RemoteClusterType};
 use crate::state::Provider;
 use log::debug;
@@ -405,6 +406,8 @@ impl ClusterConfigBuilder {
             timeouts: ClusterConfigTimeouts::default(),
             tls: self.tls.unwrap_or_default(),
             kv_batch_size: None,
+            kv_compression_min_size: None,
+            kv_auth_mechanism: None,
             capella_org: None,
             project: None,
             cluster_type: None,
@@ -436,6 +439,21 @@ pub struct ClusterConfig {
     #[serde(rename(deserialize = "kv-batch-size", serialize = "kv-batch-size"))]
     kv_batch_size: Option<u32>,
 
+    // Document bodies of at least this many bytes are snappy compressed when sent to the server,
+    // compression of requests is disabled if not set.
+    #[serde(rename(
+        deserialize = "kv-compression-min-size",
+        serialize = "kv-compression-min-size"
+    ))]
+    kv_compression_min_size: Option<u32>,
+
+    // The SASL mechanism used to authenticate KV connections. If not set then PLAIN is used over
+    // TLS and otherwise the strongest SCRAM mechanism supported by the server. Servers which only
+    // support PLAIN, such as those authenticating users against LDAP, need this set to plain when
+    // TLS is not used.
+    #[serde(rename(deserialize = "kv-auth-mechanism", serialize = "kv-auth-mechanism"))]
+    kv_auth_mechanism: Option<AuthMechanism>,
+
     #[serde(rename(
         deserialize = "capella-organization",
         serialize = "capella-organization"
@@ -460,6 +478,10 @@ impl ClusterConfig {
         if let Some(u) = &self.credentials.username {
             return u.clone();
         }
+        // The client certificate identifies the user, so no credentials are needed.
+        if self.tls.enabled && self.tls.client_cert_path.is_some() {
+            return String::new();
+        }
         error!(
             "No username found in config or credentials file for identifier \"{}\"!",
             self.identifier
@@ -470,6 +492,9 @@ impl ClusterConfig {
         if let Some(p) = &self.credentials.password {
             return p.clone();
         }
+        if self.tls.enabled && self.tls.client_cert_path.is_some() {
+            return String::new();
+        }
         error!(
             "No password found in config or credentials file for identifier \"{}\"!",
             self.identifier
@@ -503,6 +528,12 @@ impl ClusterConfig {
     pub fn kv_batch_size(&self) -> Option<u32> {
         self.kv_batch_size
     }
+    pub fn kv_compression_min_size(&self) -> Option<u32> {
+        self.kv_compression_min_size
+    }
+    pub fn kv_auth_mechanism(&self) -> Option<AuthMechanism> {
+        self.kv_auth_mechanism
+    }
     pub fn display_name(&self) -> Option<String> {
         self.display_name.clone()
     }
@@ -520,12 +551,16 @@ impl From<(String, &RemoteCluster)> for ClusterConfig {
                 enabled: true,
                 cert_path: tls_config.cert_path(),
                 accept_all_certs: tls_config.accept_all_certs(),
+                client_cert_path: tls_config.client_cert_path(),
+                client_key_path: tls_config.client_key_path(),
             }
         } else {
             ClusterTlsConfig {
                 enabled: false,
                 cert_path: None,
                 accept_all_certs: false,
+                client_cert_path: None,
+                client_key_path: None,
             }
         };
 
@@ -551,6 +586,8 @@ impl From<(String, &RemoteCluster)> for ClusterConfig {
             capella_org: cloud,
             project: cluster.1.project(),
             kv_batch_size: Some(cluster.1.kv_batch_size()),
+            kv_compression_min_size: cluster.1.kv_compression_min_size(),
+            kv_auth_mechanism: cluster.1.kv_auth_mechanism(),
             display_name: cluster.1.display_name(),
             // This is a config option for dev ony so we won't want to write to file
             cluster_type: None,
@@ -695,6 +732,13 @@ pub struct ClusterTlsConfig {
     ))]
     #[serde(default)]
     accept_all_certs: bool,
+    #[serde(rename(
+        deserialize = "tls-client-cert-path",
+        serialize = "tls-client-cert-path"
+    ))]
+    client_cert_path: Option<String>,
+    #[serde(rename(deserialize = "tls-client-key-path", serialize = "tls-client-key-path"))]
+    client_key_path: Option<String>,
 }
 
 impl ClusterTlsConfig {
@@ -703,6 +747,8 @@ impl ClusterTlsConfig {
             enabled,
             cert_path,
             accept_all_certs,
+            client_cert_path: None,
+            client_key_path: None,
         }
     }
     pub fn enabled(&self) -> bool {
@@ -716,6 +762,14 @@ impl ClusterTlsConfig {
     pub fn accept_all_certs(&self) -> bool {
         self.accept_all_certs
     }
+
+    pub fn client_cert_path(&self) -> &Option<String> {
+        &self.client_cert_path
+    }
+
+    pub fn client_key_path(&self) -> &Option<String> {
+        &self.client_key_path
+    }
 }
 
 impl Default for ClusterTlsConfig {
@@ -724,6 +778,8 @@ impl Default for ClusterTlsConfig {
             enabled: true,
             cert_path: None,
             accept_all_certs: false,
+            client_cert_path: None,
+            client_key_path: None,
         }
     }
 }
@@ -366,7 +366,7 @@ fn remote_cluster_from_opts(opt: CliOptions, password: Option<String>) -> Remote
             );
         None
     } else {
-        Some(RustTlsConfig::new(opt.tls_accept_all_certs, opt.tls_cert_path).unwrap())
+        Some(RustTlsConfig::new(opt.tls_accept_all_certs, opt.tls_cert_path, None, None).unwrap())
     };
     let (cluster_type, hostnames) =
         validate_hostnames(conn_string.split(',').map(|v| v.to_owned()).collect());
@@ -385,6 +385,8 @@ fn remote_cluster_from_opts(opt: CliOptions, password: Option<String>) -> Remote
         None,
         None,
         DEFAULT_KV_BATCH_SIZE,
+        None,
+        None,
         cluster_type,
     )
 }
@@ -670,6 +672,8 @@ fn make_state(
                 v.cloud_org(),
                 v.project(),
                 kv_batch_size,
+                v.kv_compression_min_size(),
+                v.kv_auth_mechanism(),
                 v.cluster_type().unwrap_or(cluster_type),
             );
             if !v.tls().clone().enabled() {
@@ -756,6 +760,7 @@ fn merge_couchbase_delta(context: &mut EngineState, state: Arc<Mutex<State>>) {
         working_set.add_decl(Box::new(BucketsSample::new(state.clone())));
         working_set.add_decl(Box::new(BucketsUpdate::new(state.clone())));
         working_set.add_decl(Box::new(CbEnvCluster::new(state.clone())));
+        working_set.add_decl(Box::new(CbEnvConnections::new(state.clone())));
         working_set.add_decl(Box::new(CbEnvLLM::new(state.clone())));
         working_set.add_decl(Box::new(CBEnvManaged::new(state.clone())));
         working_set.add_decl(Box::new(CbEnvRegister::new(state.clone())));
@@ -775,11 +780,21 @@ fn merge_couchbase_delta(context: &mut EngineState, state: Arc<Mutex<State>>) {
         working_set.add_decl(Box::new(ColumnarQuery::new(state.clone())));
         working_set.add_decl(Box::new(CredentialsCreate::new(state.clone())));
         working_set.add_decl(Box::new(Doc));
+        working_set.add_decl(Box::new(DocAppend::new(state.clone())));
+        working_set.add_decl(Box::new(DocDecrement::new(state.clone())));
+        working_set.add_decl(Box::new(DocExport::new(state.clone())));
         working_set.add_decl(Box::new(DocGet::new(state.clone())));
+        working_set.add_decl(Box::new(DocGetAndLock::new(state.clone())));
+        working_set.add_decl(Box::new(DocGetAndTouch::new(state.clone())));
         working_set.add_decl(Box::new(DocImport::new(state.clone())));
+        working_set.add_decl(Box::new(DocIncrement::new(state.clone())));
         working_set.add_decl(Box::new(DocInsert::new(state.clone())));
+        working_set.add_decl(Box::new(DocPrepend::new(state.clone())));
         working_set.add_decl(Box::new(DocReplace::new(state.clone())));
         working_set.add_decl(Box::new(DocRemove::new(state.clone())));
+        working_set.add_decl(Box::new(DocScan::new(state.clone())));
+        working_set.add_decl(Box::new(DocTouch::new(state.clone())));
+        working_set.add_decl(Box::new(DocUnlock::new(state.clone())));
         working_set.add_decl(Box::new(DocUpsert::new(state.clone())));
         working_set.add_decl(Box::new(HealthCheck::new(state.clone())));
         working_set.add_decl(Box::new(Help));
@@ -791,14 +806,30 @@ fn merge_couchbase_delta(context: &mut EngineState, state: Arc<Mutex<State>>) {
         working_set.add_decl(Box::new(ProjectsCreate::new(state.clone())));
         working_set.add_decl(Box::new(ProjectsDrop::new(state.clone())));
         working_set.add_decl(Box::new(Query::new(state.clone())));
+        working_set.add_decl(Box::new(QueryActive::new(state.clone())));
         working_set.add_decl(Box::new(QueryAdvise::new(state.clone())));
+        working_set.add_decl(Box::new(QueryCancel::new(state.clone())));
+        working_set.add_decl(Box::new(QueryCompleted::new(state.clone())));
+        working_set.add_decl(Box::new(QueryExecute::new(state.clone())));
+        working_set.add_decl(Box::new(QueryExplain::new(state.clone())));
         working_set.add_decl(Box::new(QueryIndexes::new(state.clone())));
+        working_set.add_decl(Box::new(QueryPrepare::new(state.clone())));
         working_set.add_decl(Box::new(QueryTransactions::new(state.clone())));
+        working_set.add_decl(Box::new(QueryVitals::new(state.clone())));
         working_set.add_decl(Box::new(Scopes::new(state.clone())));
         working_set.add_decl(Box::new(ScopesCreate::new(state.clone())));
         working_set.add_decl(Box::new(ScopesDrop::new(state.clone())));
         working_set.add_decl(Box::new(Search::new(state.clone())));
+        working_set.add_decl(Box::new(SubDoc));
+        working_set.add_decl(Box::new(SubDocArrayAddUnique::new(state.clone())));
+        working_set.add_decl(Box::new(SubDocArrayAppend::new(state.clone())));
+        working_set.add_decl(Box::new(SubDocArrayPrepend::new(state.clone())));
+        working_set.add_decl(Box::new(SubDocCounter::new(state.clone())));
         working_set.add_decl(Box::new(SubDocGet::new(state.clone())));
+        working_set.add_decl(Box::new(SubDocInsert::new(state.clone())));
+        working_set.add_decl(Box::new(SubDocRemove::new(state.clone())));
+        working_set.add_decl(Box::new(SubDocReplace::new(state.clone())));
+        working_set.add_decl(Box::new(SubDocUpsert::new(state.clone())));
         working_set.add_decl(Box::new(Transactions));
         working_set.add_decl(Box::new(TransactionsListAtrs::new(state.clone())));
         working_set.add_decl(Box::new(Tutorial::new(state.clone())));
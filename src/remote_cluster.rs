@@ -1,9 +1,10 @@
-use crate::client::{Client, RustTlsConfig, CAPELLA_SRV_SUFFIX};
+use crate::client::{AuthMechanism, Client, PreparedStatement, RustTlsConfig, CAPELLA_SRV_SUFFIX};
 use crate::{
     DEFAULT_ANALYTICS_TIMEOUT, DEFAULT_DATA_TIMEOUT, DEFAULT_MANAGEMENT_TIMEOUT,
     DEFAULT_QUERY_TIMEOUT, DEFAULT_SEARCH_TIMEOUT, DEFAULT_TRANSACTION_TIMEOUT,
 };
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -67,8 +68,11 @@ pub struct RemoteCluster {
     capella_org: Option<String>,
     project: Option<String>,
     kv_batch_size: u32,
+    kv_compression_min_size: Option<u32>,
+    kv_auth_mechanism: Option<AuthMechanism>,
     cluster_type: RemoteClusterType,
     display_name: Option<String>,
+    prepared_statements: Mutex<HashMap<String, PreparedStatement>>,
 }
 
 impl RemoteCluster {
@@ -80,6 +84,8 @@ impl RemoteCluster {
         capella_org: Option<String>,
         project: Option<String>,
         kv_batch_size: u32,
+        kv_compression_min_size: Option<u32>,
+        kv_auth_mechanism: Option<AuthMechanism>,
         cluster_type: RemoteClusterType,
     ) -> Self {
         Self {
@@ -95,8 +101,11 @@ impl RemoteCluster {
             capella_org,
             project,
             kv_batch_size,
+            kv_compression_min_size,
+            kv_auth_mechanism,
             cluster_type,
             display_name: resources.display_name,
+            prepared_statements: Mutex::new(HashMap::new()),
         }
     }
 
@@ -108,6 +117,8 @@ impl RemoteCluster {
                 self.username.clone(),
                 self.password.clone(),
                 self.tls_config.clone(),
+                self.kv_compression_min_size,
+                self.kv_auth_mechanism,
             )));
         }
         c.as_ref().unwrap().clone()
@@ -185,6 +196,14 @@ impl RemoteCluster {
         self.kv_batch_size
     }
 
+    pub fn kv_compression_min_size(&self) -> Option<u32> {
+        self.kv_compression_min_size
+    }
+
+    pub fn kv_auth_mechanism(&self) -> Option<AuthMechanism> {
+        self.kv_auth_mechanism
+    }
+
     #[allow(dead_code)]
     pub fn cluster_type(&self) -> RemoteClusterType {
         self.cluster_type
@@ -193,6 +212,18 @@ impl RemoteCluster {
     pub fn display_name(&self) -> Option<String> {
         self.display_name.clone()
     }
+
+    pub fn prepared_statement(&self, name: &str) -> Option<PreparedStatement> {
+        self.prepared_statements.lock().unwrap().get(name).cloned()
+    }
+
+    // add_prepared_statement caches a prepared statement, replacing any with the same name.
+    pub fn add_prepared_statement(&self, prepared: PreparedStatement) {
+        self.prepared_statements
+            .lock()
+            .unwrap()
+            .insert(prepared.name().to_string(), prepared);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1,4 +1,6 @@
-use crate::client::{CapellaClient, Endpoint};
+use crate::client::{
+    CapellaClient, Endpoint, KvConnectionPool, MutationState, DEFAULT_KV_IDLE_TIMEOUT,
+};
 
 use crate::cli::{
     embed_model_missing, generic_error, no_active_project_error, no_llm_configured,
@@ -89,6 +91,9 @@ pub struct State {
     active_transaction: Mutex<Option<TransactionState>>,
     llms: HashMap<String, Llm>,
     active_llm: Mutex<Option<String>>,
+    kv_pool: KvConnectionPool,
+    // The writes made through the doc commands, by cluster identifier.
+    mutation_states: Mutex<HashMap<String, MutationState>>,
 }
 
 impl State {
@@ -113,6 +118,8 @@ impl State {
             active_transaction: Mutex::new(None),
             llms,
             active_llm: Mutex::new(active_llm),
+            kv_pool: KvConnectionPool::new(DEFAULT_KV_IDLE_TIMEOUT),
+            mutation_states: Mutex::new(HashMap::new()),
         };
         if !active.is_empty() {
             state.set_active(active).unwrap();
@@ -133,6 +140,8 @@ impl State {
     }
 
     pub fn remove_cluster(&mut self, alias: String) -> Option<RemoteCluster> {
+        self.kv_pool.drop_connections(Some(alias.as_str()), None);
+        self.mutation_states.lock().unwrap().remove(alias.as_str());
         self.clusters.remove(alias.as_str())
     }
 
@@ -185,6 +194,10 @@ impl State {
         self.clusters.get(&*active)
     }
 
+    pub fn kv_pool(&self) -> &KvConnectionPool {
+        &self.kv_pool
+    }
+
     pub fn tutorial(&self) -> &Tutorial {
         &self.tutorial
     }
@@ -295,6 +308,30 @@ impl State {
         }
     }
 
+    // record_mutations remembers writes made to the cluster, so that later queries can be made
+    // consistent with them.
+    pub fn record_mutations(&self, cluster: &str, mutations: MutationState) {
+        if mutations.is_empty() {
+            return;
+        }
+
+        self.mutation_states
+            .lock()
+            .unwrap()
+            .entry(cluster.to_string())
+            .or_default()
+            .merge(mutations);
+    }
+
+    pub fn mutation_state(&self, cluster: &str) -> MutationState {
+        self.mutation_states
+            .lock()
+            .unwrap()
+            .get(cluster)
+            .cloned()
+            .unwrap_or_default()
+    }
+
     pub fn active_llm_id(&self) -> Option<String> {
         self.active_llm.lock().unwrap().clone()
     }
@@ -0,0 +1,31 @@
+mod common;
+
+use crate::common::{new_doc_id, playground::CBPlayground, support};
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn lists_and_drops_pooled_connections() {
+    CBPlayground::setup(
+        "lists_and_drops_pooled_connections",
+        None,
+        None,
+        |dirs, sandbox| {
+            let key = new_doc_id();
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!(r#"doc upsert {} {{"a": 1}} | ignore; doc get {} | ignore; cb-env connections | to json"#, &key, &key)));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            let connections = json.as_array().unwrap();
+            assert_eq!(1, connections.len());
+            assert!(connections[0]["uses"].as_i64().unwrap() >= 2);
+            assert!(!connections[0]["nodes"].as_array().unwrap().is_empty());
+            assert_eq!(false, connections[0]["dropped"]);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!(r#"doc get {} | ignore; cb-env connections --drop | ignore; cb-env connections | to json"#, &key)));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert!(json.as_array().unwrap().is_empty());
+        },
+    );
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use crate::common::{new_doc_id, playground::CBPlayground, support};
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn increment_a_counter() {
+    CBPlayground::setup("increment_a_counter", None, None, |dirs, sandbox| {
+        let key = new_doc_id();
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("doc increment {} --initial 10 | first | to json", &key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!(10, json["value"]);
+        assert_eq!("", json["error"]);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("doc increment {} --delta 5 | first | to json", &key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!(15, json["value"]);
+        assert_ne!(0, json["cas"]);
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn decrement_missing_counter() {
+    CBPlayground::setup("decrement_missing_counter", None, None, |dirs, sandbox| {
+        let key = new_doc_id();
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("doc decrement {} | first | to json", &key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!(key, json["id"]);
+        assert_eq!("Key not found", json["error"]);
+    });
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use crate::common::{new_doc_id, playground::CBPlayground, support};
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn export_documents_with_ids_and_meta() {
+    CBPlayground::setup(
+        "export_documents_with_ids_and_meta",
+        None,
+        None,
+        |dirs, sandbox| {
+            let key = new_doc_id();
+            sandbox.create_document(&dirs, &key, r#"{"testkey": "testvalue"}"#);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline("doc export docs.jsonl --id-field key --include-meta | first | to json"));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert!(json["exported"].as_i64().unwrap() >= 1);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("open docs.jsonl | from json --objects | where key == \"{}\" | first | to json", key)));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert_eq!("testvalue", json["testkey"]);
+            assert!(json["meta"]["cas"].as_u64().unwrap() > 0);
+            assert_eq!(0, json["meta"]["expiry"]);
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn export_does_not_overwrite_meta_fields() {
+    CBPlayground::setup(
+        "export_does_not_overwrite_meta_fields",
+        None,
+        None,
+        |dirs, sandbox| {
+            let key = new_doc_id();
+            sandbox.create_document(&dirs, &key, r#"{"meta": "mine"}"#);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline("doc export docs.jsonl --include-meta | first | to json"));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert!(json["failed"].as_i64().unwrap() >= 1);
+            assert!(json["failures"]
+                .as_str()
+                .unwrap()
+                .contains("Document already has a meta field"));
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline("doc export docs.jsonl --id-field key --include-meta --meta-field _cbsh_meta | first | to json"));
+            assert_eq!("", out.err);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("open docs.jsonl | from json --objects | where key == \"{}\" | first | to json", key)));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert_eq!("mine", json["meta"]);
+            assert!(json["_cbsh_meta"]["cas"].as_u64().unwrap() > 0);
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn export_documents_as_csv_and_parquet() {
+    CBPlayground::setup(
+        "export_documents_as_csv_and_parquet",
+        None,
+        None,
+        |dirs, sandbox| {
+            let key = new_doc_id();
+            sandbox.create_document(&dirs, &key, r#"{"address": {"city": "Bristol"}}"#);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline("doc export docs.csv --id-field key | first | to json"));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert!(json["exported"].as_i64().unwrap() >= 1);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("open docs.csv | where key == \"{}\" | first | to json", key)));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert_eq!("Bristol", json["address.city"]);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline("doc export docs.parquet | first | to json"));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert!(json["exported"].as_i64().unwrap() >= 1);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline("open docs.parquet --raw | bytes at 0..3 | decode"));
+            assert_eq!("", out.err);
+            assert_eq!("PAR1", out.out);
+        },
+    );
+}
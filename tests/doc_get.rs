@@ -28,3 +28,35 @@ fn get_a_document_not_found() {
         assert!(out.out.contains("Key not found"));
     });
 }
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn get_any_replica() {
+    CBPlayground::setup("get_any_replica", None, None, |dirs, sandbox| {
+        sandbox.create_document(&dirs, "get_any_replica", r#"{"testkey": "testvalue"}"#);
+
+        let out = cbsh!(cwd: dirs.test(), pipeline(r#"doc get "get_any_replica" --replica any | first | to json"#));
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+
+        assert_eq!("", out.err);
+        assert_eq!(r#"{"testkey":"testvalue"}"#, json["content"].to_string());
+        assert!(json["is_replica"].is_boolean());
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn get_a_document_with_meta() {
+    CBPlayground::setup("get_a_document_with_meta", None, None, |dirs, sandbox| {
+        sandbox.create_document(&dirs, "get_a_document_with_meta", r#"{"testkey": "testvalue"}"#);
+
+        let out = cbsh!(cwd: dirs.test(), pipeline(r#"doc get "get_a_document_with_meta" --with-meta | first | to json"#));
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+
+        assert_eq!("", out.err);
+        assert_eq!(false, json["meta"]["deleted"]);
+        assert!(json["meta"]["expiry"].is_null());
+        assert_eq!("json", json["meta"]["datatype"][0]);
+        assert_eq!(0, json["retries"]);
+    });
+}
@@ -43,3 +43,46 @@ fn import_missing_id() {
         assert_eq!("Missing doc id", json["failures"]);
     });
 }
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn import_with_id_template_and_errors_file() {
+    CBPlayground::setup(
+        "import_with_id_template_and_errors_file",
+        None,
+        None,
+        |dirs, sandbox| {
+            let content = r#"{id: import_with_id_template, foo: bar}"#;
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("{} | to json -r | save users.jsonl", content)));
+            assert_eq!("", out.err);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline("doc import users.jsonl --id-template user::%id% --mode insert | first | to json"));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert_eq!(1, json["success"]);
+            assert_eq!(0, json["failed"]);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline("doc get user::import_with_id_template | first | to json"));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert_eq!("bar", json["content"]["foo"]);
+
+            // Inserting the same document again fails, which is recorded in the errors file.
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline("doc import users.jsonl --id-template user::%id% --mode insert --errors-file failed.jsonl | first | to json"));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert_eq!(0, json["success"]);
+            assert_eq!(1, json["failed"]);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline("open failed.jsonl | from json --objects | first | to json"));
+            assert_eq!("", out.err);
+
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert_eq!("user::import_with_id_template", json["id"]);
+            assert_eq!("bar", json["document"]["foo"]);
+        },
+    );
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use crate::common::{new_doc_id, playground::CBPlayground, support};
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn lock_and_unlock_a_document() {
+    CBPlayground::setup("lock_and_unlock_a_document", None, None, |dirs, sandbox| {
+        let key = new_doc_id();
+        sandbox.create_document(&dirs, &key, r#"{"testkey": "testvalue"}"#);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("doc get-and-lock {} | doc unlock | first | to json", &key)));
+        assert_eq!("", out.err);
+
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+
+        assert_eq!(1, json["success"]);
+        assert_eq!(1, json["processed"]);
+        assert_eq!(0, json["failed"]);
+        assert_eq!("", json["failures"]);
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn replace_locked_document() {
+    CBPlayground::setup("replace_locked_document", None, None, |dirs, sandbox| {
+        let key = new_doc_id();
+        sandbox.create_document(&dirs, &key, r#"{"testkey": "testvalue"}"#);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("doc get-and-lock {} | first | to json", &key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!("testvalue", json["content"]["testkey"]);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("doc replace {} {{fizz: buzz}} | first | to json", &key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!(1, json["failed"]);
+        assert_eq!("Document locked", json["failures"]);
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn touch_a_document() {
+    CBPlayground::setup("touch_a_document", None, None, |dirs, sandbox| {
+        let key = new_doc_id();
+        sandbox.create_document(&dirs, &key, r#"{"testkey": "testvalue"}"#);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("doc touch {} --expiry 60 | first | to json", &key)));
+        assert_eq!("", out.err);
+
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+
+        assert_eq!(1, json["success"]);
+        assert_eq!(0, json["failed"]);
+    });
+}
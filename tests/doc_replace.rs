@@ -68,3 +68,21 @@ fn replace_missing_doc_id() {
         assert_eq!("Missing doc id", json["failures"]);
     });
 }
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn replace_with_stale_cas() {
+    CBPlayground::setup("replace_with_stale_cas", None, None, |dirs, sandbox| {
+        let key = new_doc_id();
+        sandbox.create_document(&dirs, key.clone(), r#"{"testkey": "testvalue"}"#);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("doc replace {} {{fizz: buzz}} --cas 1234 | first | to json", key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+
+        assert_eq!(0, json["success"]);
+        assert_eq!(1, json["processed"]);
+        assert_eq!(1, json["failed"]);
+        assert_eq!("CAS mismatch", json["failures"]);
+    });
+}
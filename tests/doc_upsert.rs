@@ -34,3 +34,19 @@ fn upsert_missing_id() {
         assert_eq!("Missing doc id", json["failures"]);
     });
 }
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn upsert_a_string_document() {
+    CBPlayground::setup("upsert_a_string_document", None, None, |dirs, sandbox| {
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(r#"doc upsert string_doc "not json" --format string | first | to json"#));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!(1, json["success"]);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(r#"doc get string_doc | first | to json"#));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!("not json", json["content"]);
+    });
+}
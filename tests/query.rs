@@ -1,6 +1,6 @@
 mod common;
 
-use crate::common::{playground, playground::PerTestOptions, utils, TestResult};
+use crate::common::{playground, playground::PerTestOptions, support, utils, TestResult};
 use serde_json::Value;
 use std::ops::Add;
 use std::path::Path;
@@ -153,3 +153,249 @@ fn fetch_meta() {
         },
     );
 }
+
+#[test]
+#[cfg_attr(not(feature = "query"), ignore)]
+fn query_is_consistent_with_last_write() {
+    let config = utils::test_config();
+
+    playground::CBPlayground::setup(
+        "query_is_consistent_with_last_write",
+        None,
+        PerTestOptions::default().set_no_default_collection(true),
+        |dirs, sandbox| {
+            create_primary_index("", config.bucket(), dirs.test(), sandbox).unwrap();
+            let key = format!("test-{}", Uuid::new_v4());
+
+            // The write and the query must run in the same shell for the write to be known.
+            let cmd = format!(
+                "doc upsert {1} {{testkey: testvalue}} --bucket {0} | ignore; query \"SELECT `{0}`.* FROM `{0}` WHERE meta().id=\"{1}\"\" --consistent-with-last-write | select testkey | first | to json",
+                config.bucket(),
+                key
+            );
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(cmd));
+
+            assert_eq!("", out.err);
+            let json: Value = serde_json::from_str(&out.out).unwrap();
+            assert_eq!("testvalue", json["testkey"]);
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "query"), ignore)]
+fn execute_a_prepared_query() {
+    let config = utils::test_config();
+
+    playground::CBPlayground::setup(
+        "execute_a_prepared_query",
+        None,
+        PerTestOptions::default().set_no_default_collection(true),
+        |dirs, _sandbox| {
+            let key = format!("test-{}", Uuid::new_v4());
+
+            // Prepared statements are cached by the shell, so must be executed in the same one.
+            let cmd = format!(
+                "doc upsert {1} {{testkey: testvalue}} --bucket {0} | ignore; query prepare by_key \"SELECT `{0}`.* FROM `{0}` USE KEYS $1\" | ignore; query execute by_key --params [{1}] | select testkey | first | to json",
+                config.bucket(),
+                key
+            );
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(cmd));
+
+            assert_eq!("", out.err);
+            let json: Value = serde_json::from_str(&out.out).unwrap();
+            assert_eq!("testvalue", json["testkey"]);
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "query"), ignore)]
+fn query_without_adhoc_reuses_prepared_statement() {
+    let config = utils::test_config();
+
+    playground::CBPlayground::setup(
+        "query_without_adhoc_reuses_prepared_statement",
+        None,
+        PerTestOptions::default().set_no_default_collection(true),
+        |dirs, _sandbox| {
+            let key = format!("test-{}", Uuid::new_v4());
+
+            // The key is selected too so that the statement, and so its prepared name, is unique
+            // to this run.
+            let cmd = format!(
+                "doc upsert {1} {{testkey: testvalue}} --bucket {0} | ignore; [1 2] | each {{|_| query \"SELECT `{0}`.*, '{1}' AS run FROM `{0}` USE KEYS $1\" --params [{1}] --adhoc false | first }} | select testkey | to json",
+                config.bucket(),
+                key
+            );
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(cmd));
+
+            assert_eq!("", out.err);
+            let json: Value = serde_json::from_str(&out.out).unwrap();
+            assert_eq!(
+                serde_json::json!([{"testkey": "testvalue"}, {"testkey": "testvalue"}]),
+                json
+            );
+
+            // Both runs used the one statement prepared by the first.
+            let cmd = format!(
+                "query \"SELECT DISTINCT RAW name FROM system:prepareds WHERE statement LIKE '%{}%'\" | to json",
+                key
+            );
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(cmd));
+
+            assert_eq!("", out.err);
+            let json: Value = serde_json::from_str(&out.out).unwrap();
+            let names = json.as_array().unwrap();
+            assert_eq!(1, names.len(), "{:?}", names);
+            assert!(names[0].as_str().unwrap().starts_with("cbsh_"));
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "query"), ignore)]
+fn query_vitals_reports_each_node() {
+    playground::CBPlayground::setup(
+        "query_vitals_reports_each_node",
+        None,
+        None,
+        |dirs, _sandbox| {
+            let out = cbsh!(
+                cwd: dirs.test(),
+                support::cb_pipeline("query vitals | first | to json")
+            );
+
+            assert_eq!("", out.err);
+            let json: Value = serde_json::from_str(&out.out).unwrap();
+            assert_ne!("", json["node"]);
+            assert!(json.get("uptime").is_some());
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "query"), ignore)]
+fn query_cancel_unknown_request() {
+    playground::CBPlayground::setup(
+        "query_cancel_unknown_request",
+        None,
+        None,
+        |dirs, _sandbox| {
+            let out = cbsh!(
+                cwd: dirs.test(),
+                support::cb_pipeline(format!("query cancel {}", Uuid::new_v4()))
+            );
+
+            assert!(out.err.contains("No active request"), "{}", out.err);
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "query"), ignore)]
+fn query_active_lists_running_requests() {
+    playground::CBPlayground::setup(
+        "query_active_lists_running_requests",
+        None,
+        None,
+        |dirs, _sandbox| {
+            // The request listing the active requests is itself running while it does so.
+            let out = cbsh!(
+                cwd: dirs.test(),
+                support::cb_pipeline("query active | where statement =~ active_requests | first | to json")
+            );
+
+            assert_eq!("", out.err);
+            let json: Value = serde_json::from_str(&out.out).unwrap();
+            assert_ne!("", json["request_id"]);
+            assert_ne!("", json["node"]);
+            assert_eq!("running", json["state"]);
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "query"), ignore)]
+fn query_completed_lists_slow_requests() {
+    playground::CBPlayground::setup(
+        "query_completed_lists_slow_requests",
+        None,
+        None,
+        |dirs, _sandbox| {
+            let context_id = Uuid::new_v4();
+
+            // Requests are only kept once completed if they run for over a second by default.
+            let cmd = format!(
+                "query \"SELECT RAW COUNT(*) FROM ARRAY_RANGE(0, 3000) AS a, ARRAY_RANGE(0, 3000) AS b\" --client-context-id {} | ignore; query completed | where client_context_id == \"{}\" | first | to json",
+                context_id, context_id
+            );
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(cmd));
+
+            assert_eq!("", out.err);
+            let json: Value = serde_json::from_str(&out.out).unwrap();
+            assert_ne!("", json["request_id"]);
+            assert_ne!("", json["node"]);
+            assert_eq!("completed", json["state"]);
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "query"), ignore)]
+fn query_cancel_a_running_request() {
+    playground::CBPlayground::setup(
+        "query_cancel_a_running_request",
+        None,
+        None,
+        |dirs, sandbox| {
+            let context_id = Uuid::new_v4();
+
+            // The request runs for far longer than the query timeout unless it is cancelled.
+            let cwd = dirs.test().to_path_buf();
+            let cmd = format!(
+                "query \"SELECT RAW COUNT(*) FROM ARRAY_RANGE(0, 100000) AS a, ARRAY_RANGE(0, 100000) AS b\" --client-context-id {}",
+                context_id
+            );
+            let running = std::thread::spawn(move || cbsh!(cwd: cwd, support::cb_pipeline(cmd)));
+
+            let mut request_id = Value::default();
+            sandbox.retry_until(
+                Instant::now().add(Duration::from_secs(30)),
+                Duration::from_millis(200),
+                format!(
+                    "query active | where client_context_id == \"{}\" | get request_id | to json",
+                    context_id
+                )
+                .as_str(),
+                dirs.test(),
+                playground::RetryExpectations::ExpectOut,
+                |json| -> TestResult<bool> {
+                    request_id = json[0].clone();
+                    Ok(request_id.is_string())
+                },
+            );
+
+            let out = cbsh!(
+                cwd: dirs.test(),
+                support::cb_pipeline(format!("query cancel {}", request_id.as_str().unwrap()))
+            );
+            assert_eq!("", out.err);
+
+            // The request stops well before the query timeout would have ended it.
+            sandbox.retry_until(
+                Instant::now().add(Duration::from_secs(10)),
+                Duration::from_millis(200),
+                format!(
+                    "query active | where client_context_id == \"{}\" | length | to json",
+                    context_id
+                )
+                .as_str(),
+                dirs.test(),
+                playground::RetryExpectations::ExpectOut,
+                |json| -> TestResult<bool> { Ok(json == 0) },
+            );
+            running.join().unwrap();
+        },
+    );
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use crate::common::{playground, playground::PerTestOptions, support, utils, TestResult};
+use serde_json::Value;
+use std::ops::Add;
+use std::time::{Duration, Instant};
+
+#[test]
+#[cfg_attr(not(feature = "query_index"), ignore)]
+#[cfg_attr(not(feature = "collections"), ignore)]
+fn explain_flags_uncovered_fetch() {
+    let config = utils::test_config();
+
+    playground::CBPlayground::setup(
+        "explain_flags_uncovered_fetch",
+        Some(config.clone()),
+        PerTestOptions::default().set_no_default_collection(true),
+        |dirs, sandbox| {
+            let scope = config.scope().unwrap();
+            let cmd = format!("cb-env scope \"{}\" |", scope.clone());
+            sandbox.set_scope(scope.clone());
+            let collection = config.collection().unwrap();
+            sandbox.set_collection(collection.clone());
+
+            let index_name =
+                utils::create_index(cmd.clone(), "`field1`", collection, dirs.test(), sandbox);
+
+            let mut explained = Value::default();
+            let cmd = format!(
+                "{} query explain \"SELECT * FROM `{}` WHERE field1 = 'a'\" | first | to json",
+                cmd,
+                config.collection().unwrap()
+            );
+            sandbox.retry_until(
+                Instant::now().add(Duration::from_secs(30)),
+                Duration::from_millis(200),
+                cmd.as_str(),
+                dirs.test(),
+                playground::RetryExpectations::ExpectOut,
+                |json| -> TestResult<bool> {
+                    // The index may still be building, in which case the plan will not use it.
+                    let uses_index = json["operators"]
+                        .as_array()
+                        .map(|ops| ops.iter().any(|o| o["index"] == index_name.as_str()))
+                        .unwrap_or_default();
+                    explained = json.clone();
+                    Ok(uses_index)
+                },
+            );
+
+            let operators = explained["operators"].as_array().unwrap();
+            let scan = operators
+                .iter()
+                .find(|o| o["index"] == index_name.as_str())
+                .unwrap();
+            assert_eq!("IndexScan3", scan["operator"]);
+            assert_eq!(1, scan["spans"].as_array().unwrap().len());
+
+            let summary = explained["summary"].as_array().unwrap();
+            assert!(summary
+                .iter()
+                .any(|s| s["operator"] == "Fetch" && s["index"] == index_name.as_str()));
+        },
+    );
+}
+
+#[test]
+#[cfg_attr(not(feature = "query"), ignore)]
+fn explain_reports_syntax_errors() {
+    playground::CBPlayground::setup(
+        "explain_reports_syntax_errors",
+        None,
+        None,
+        |dirs, _sandbox| {
+            let out = cbsh!(
+                cwd: dirs.test(),
+                support::cb_pipeline("query explain \"SELEC 1\"")
+            );
+
+            assert!(out.err.contains("syntax error"), "{}", out.err);
+            assert!(!out.err.contains("Unexpected status code"), "{}", out.err);
+        },
+    );
+}
@@ -0,0 +1,18 @@
+mod common;
+
+use crate::common::{new_doc_id, playground::CBPlayground, support};
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn get_path_and_virtual_xattr() {
+    CBPlayground::setup("get_path_and_virtual_xattr", None, None, |dirs, sandbox| {
+        let key = new_doc_id();
+        sandbox.create_document(&dirs, key.clone(), r#"{"testkey": "testvalue"}"#);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("subdoc get testkey {} --xattr ['$document.exptime'] | first | to json", &key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!("testvalue", json["content"]);
+        assert_eq!(0, json["xattrs"]["$document.exptime"]);
+    });
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use crate::common::{new_doc_id, playground::CBPlayground, support};
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn upsert_a_path() {
+    CBPlayground::setup("upsert_a_path", None, None, |dirs, sandbox| {
+        let key = new_doc_id();
+        sandbox.create_document(&dirs, key.clone(), r#"{"testkey": "testvalue"}"#);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("subdoc upsert {} nested.field fizz --create-path | first | to json", &key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!("nested.field", json["path"]);
+        assert_eq!("success", json["status"]);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("doc get {} | first | to json", &key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!(
+            r#"{"nested":{"field":"fizz"},"testkey":"testvalue"}"#,
+            json["content"].to_string()
+        );
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn insert_existing_path() {
+    CBPlayground::setup("insert_existing_path", None, None, |dirs, sandbox| {
+        let key = new_doc_id();
+        sandbox.create_document(&dirs, key.clone(), r#"{"testkey": "testvalue"}"#);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("subdoc insert {} testkey fizz | first | to json", &key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!("path already exists", json["status"]);
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn increment_a_counter() {
+    CBPlayground::setup("increment_a_counter", None, None, |dirs, sandbox| {
+        let key = new_doc_id();
+        sandbox.create_document(&dirs, key.clone(), r#"{"count": 5}"#);
+
+        let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("subdoc counter {} count 3 | first | to json", &key)));
+        assert_eq!("", out.err);
+        let json = sandbox.parse_out_to_json(out.out).unwrap();
+        assert_eq!("success", json["status"]);
+        assert_eq!(8, json["value"]);
+    });
+}
+
+#[test]
+#[cfg_attr(not(feature = "key_value"), ignore)]
+fn too_many_paths_for_a_document() {
+    CBPlayground::setup(
+        "too_many_paths_for_a_document",
+        None,
+        None,
+        |dirs, sandbox| {
+            let key = new_doc_id();
+            sandbox.create_document(&dirs, key.clone(), r#"{"testkey": "testvalue"}"#);
+
+            // Splitting the paths across two mutations would not be atomic, so it must be refused.
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("1..17 | each {{|i| {{id: {}, path: $'field($i)', value: $i}} }} | subdoc upsert", &key)));
+            assert!(out.err.contains("Too many paths"), "{}", out.err);
+
+            let out = cbsh!(cwd: dirs.test(), support::cb_pipeline(format!("doc get {} | first | to json", &key)));
+            assert_eq!("", out.err);
+            let json = sandbox.parse_out_to_json(out.out).unwrap();
+            assert_eq!(r#"{"testkey":"testvalue"}"#, json["content"].to_string());
+        },
+    );
+}